@@ -0,0 +1,330 @@
+// Operand expression, e.g. <(TABLE+2*$10) or *-3.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    Number(i32),
+    Symbol(String),
+    Location,
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>)
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    Low,
+    High
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr
+}
+
+// Binary operators from the lowest precedence.
+const PRECEDENCE: &[&[(&str, BinaryOp)]] = &[
+    &[("|", BinaryOp::Or)],
+    &[("^", BinaryOp::Xor)],
+    &[("&", BinaryOp::And)],
+    &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[("*", BinaryOp::Mul), ("/", BinaryOp::Div), ("%", BinaryOp::Mod)]
+];
+
+impl Expr {
+    pub fn parse(input: &str) -> Result<Expr, String> {
+        let mut parser = Parser {
+            chars: input.chars().collect(),
+            idx:   0
+        };
+
+        let expr = parser.binary(0)?;
+
+        parser.skip_whitespace();
+        match parser.peek() {
+            None    => Ok(expr),
+            Some(c) => Err(format!("Unexpected character in expression: '{}'", c))
+        }
+    }
+
+    // Location is the value of *, symbols are resolved
+    // with lookup (labels and variables).
+    pub fn eval<F>(&self, location: u16, lookup: &F) -> Result<i32, String>
+        where F: Fn(&str) -> Option<u16> {
+        match *self {
+            Expr::Number(value)   => Ok(value),
+            Expr::Location        => Ok(location as i32),
+            Expr::Symbol(ref name) => {
+                match lookup(name) {
+                    Some(value) => Ok(value as i32),
+                    None        => Err(format!("Label not defined: {}", name))
+                }
+            }
+
+            Expr::Unary(op, ref expr) => {
+                let value = expr.eval(location, lookup)?;
+                Ok(match op {
+                    UnaryOp::Neg  => value.wrapping_neg(),
+                    UnaryOp::Not  => !value,
+                    UnaryOp::Low  => value & 0xFF,
+                    UnaryOp::High => (value >> 8) & 0xFF
+                })
+            }
+
+            Expr::Binary(op, ref lhs, ref rhs) => {
+                let lhs = lhs.eval(location, lookup)?;
+                let rhs = rhs.eval(location, lookup)?;
+
+                if rhs == 0 && (op == BinaryOp::Div || op == BinaryOp::Mod) {
+                    return Err(String::from("Division by zero"));
+                }
+
+                Ok(match op {
+                    BinaryOp::Add => lhs.wrapping_add(rhs),
+                    BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                    BinaryOp::Mul => lhs.wrapping_mul(rhs),
+                    BinaryOp::Div => lhs.wrapping_div(rhs),
+                    BinaryOp::Mod => lhs.wrapping_rem(rhs),
+                    BinaryOp::And => lhs & rhs,
+                    BinaryOp::Or  => lhs | rhs,
+                    BinaryOp::Xor => lhs ^ rhs,
+                    BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+                    BinaryOp::Shr => lhs.wrapping_shr(rhs as u32)
+                })
+            }
+        }
+    }
+
+    // Expressions without symbols and * can be evaluated
+    // right away.
+    pub fn eval_const(&self) -> Result<i32, String> {
+        self.eval(0, &|_| None).and_then(|value| {
+            if self.uses_location() {
+                Err(String::from("Location counter not allowed here"))
+            } else {
+                Ok(value)
+            }
+        })
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(*self, Expr::Number(_))
+    }
+
+    fn uses_location(&self) -> bool {
+        match *self {
+            Expr::Location               => true,
+            Expr::Number(_)              |
+            Expr::Symbol(_)              => false,
+            Expr::Unary(_, ref expr)     => expr.uses_location(),
+            Expr::Binary(_, ref lhs, ref rhs) => {
+                lhs.uses_location() || rhs.uses_location()
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    idx:   usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.idx).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.idx += 1;
+        }
+    }
+
+    fn accept(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+
+        let len = token.chars().count();
+        let matches = self.idx + len <= self.chars.len()
+            && self.chars[self.idx..self.idx + len].iter().cloned().eq(token.chars());
+
+        if matches {
+            self.idx += len;
+        }
+        matches
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+
+        let mut lhs = self.binary(level + 1)?;
+        'outer: loop {
+            for &(token, op) in PRECEDENCE[level].iter() {
+                if self.accept(token) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let op = if self.accept("-") {
+            UnaryOp::Neg
+        } else if self.accept("~") {
+            UnaryOp::Not
+        } else if self.accept("<") {
+            UnaryOp::Low
+        } else if self.accept(">") {
+            UnaryOp::High
+        } else {
+            return self.primary();
+        };
+
+        let expr = self.unary()?;
+        Ok(Expr::Unary(op, Box::new(expr)))
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        self.skip_whitespace();
+
+        let c = match self.peek() {
+            Some(c) => c,
+            None    => return Err(String::from("Unexpected end of expression"))
+        };
+
+        match c {
+            '(' => {
+                self.idx += 1;
+                let expr = self.binary(0)?;
+                if !self.accept(")") {
+                    return Err(String::from("Missing ')' in expression"));
+                }
+                Ok(expr)
+            }
+            '*' => {
+                self.idx += 1;
+                Ok(Expr::Location)
+            }
+            '$' => {
+                self.idx += 1;
+                self.number(16)
+            }
+            '%' => {
+                self.idx += 1;
+                self.number(2)
+            }
+            '\'' => {
+                // Character literal, the closing quote is optional.
+                match self.chars.get(self.idx + 1).cloned() {
+                    Some(c) => {
+                        self.idx += 2;
+                        if self.peek() == Some('\'') {
+                            self.idx += 1;
+                        }
+                        Ok(Expr::Number(c as i32))
+                    }
+                    None    => Err(String::from("Missing character in literal"))
+                }
+            }
+            c if c.is_ascii_digit() => self.number(10),
+            c if c.is_alphabetic() || c == '_' => {
+                let start = self.idx;
+                while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                    self.idx += 1;
+                }
+
+                let name: String = self.chars[start..self.idx].iter().collect();
+                Ok(Expr::Symbol(name.to_uppercase()))
+            }
+            c => Err(format!("Unexpected character in expression: '{}'", c))
+        }
+    }
+
+    fn number(&mut self, radix: u32) -> Result<Expr, String> {
+        let start = self.idx;
+        while self.peek().is_some_and(|c| c.is_digit(radix)) {
+            self.idx += 1;
+        }
+
+        let digits: String = self.chars[start..self.idx].iter().collect();
+        match i32::from_str_radix(&digits, radix) {
+            Ok(value) => Ok(Expr::Number(value)),
+            Err(_)    => Err(format!("Invalid number in expression: '{}'", digits))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asm::expr::Expr;
+
+    fn lookup(name: &str) -> Option<u16> {
+        match name {
+            "TABLE" => Some(0x1234),
+            "LOOP"  => Some(0xC010),
+            _       => None
+        }
+    }
+
+    fn eval(input: &str) -> Result<i32, String> {
+        Expr::parse(input).and_then(|expr| expr.eval(0xC000, &lookup))
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(eval("$FF"), Ok(0xFF));
+        assert_eq!(eval("42"), Ok(42));
+        assert_eq!(eval("%1010"), Ok(0x0A));
+        assert_eq!(eval("'A'"), Ok(0x41));
+        assert_eq!(eval("' '"), Ok(0x20));
+        assert_eq!(eval("*"), Ok(0xC000));
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+        assert_eq!(eval("$F0 | $0F & $3C"), Ok(0xFC));
+        assert_eq!(eval("1 << 4 ^ $11"), Ok(0x01));
+        assert_eq!(eval("-1 & $FFFF"), Ok(0xFFFF));
+        assert_eq!(eval("~0 & $FF"), Ok(0xFF));
+        assert_eq!(eval("7 % 4 + 9 / 2"), Ok(7));
+        assert_eq!(eval("*-3"), Ok(0xBFFD));
+        assert_eq!(eval("**2"), Ok(0x18000));
+    }
+
+    #[test]
+    fn symbols() {
+        assert_eq!(eval("<TABLE"), Ok(0x34));
+        assert_eq!(eval(">TABLE"), Ok(0x12));
+        assert_eq!(eval("table+1"), Ok(0x1235));
+        assert_eq!(eval(">(LOOP+$F0)"), Ok(0xC1));
+        assert_eq!(eval("MISSING+1"), Err(String::from("Label not defined: MISSING")));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Expr::parse("").is_err());
+        assert!(Expr::parse("(1 + 2").is_err());
+        assert!(Expr::parse("1 +").is_err());
+        assert!(Expr::parse("$12,X").is_err());
+        assert!(Expr::parse("$").is_err());
+        assert_eq!(eval("1 / 0"), Err(String::from("Division by zero")));
+        assert!(Expr::parse("*+1").unwrap().eval_const().is_err());
+        assert_eq!(Expr::parse("$10 * 2").unwrap().eval_const(), Ok(0x20));
+    }
+}
@@ -1,16 +1,27 @@
 use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::mem;
 use std::path::{Path, PathBuf};
 
 use asm::Assembler;
+use asm::Diagnostic;
+use asm::expr::Expr;
+use asm::Span;
 use asm::VariableSize;
 use inst::mcs6502;
-use inst::mcs6502::ops;
 use inst::mcs6502::addr;
+use inst::mcs6502::cmos;
 use inst::mcs6502::AddressMode;
+use inst::mcs6502::Variant;
+use cpus::mcs6502::{INT_NOMASK_ADDRESS, INT_REQ_ADDRESS, PC_INIT_ADDRESS};
 use util;
 
+// Source line along with its location, lines generated
+// by macros share the location of the macro call.
+pub type Line = (Span, String);
+
 pub struct Assembler6502 {
     data:      Vec<u8>,
     files:     Vec<String>,
@@ -20,9 +31,80 @@ pub struct Assembler6502 {
     vars:      HashMap<String, u16>,
     var_uses:  HashMap<u16, String>,
     prep:      Preprocessor,
-    debug:     bool
+    variant:   Variant,
+    diags:     Vec<Diagnostic>,
+    span:      Option<Span>,
+    source:    String,
+    locations: HashMap<u16, Span>,
+    origin:    Option<u16>,
+    start_jmp: bool,
+    vectors:   Vec<(usize, Fixup)>,
+    fixups:    Vec<Fixup>,
+    listing:   Vec<(usize, usize, String)>
+}
+
+// Bytes shown per line of the listing.
+const LISTING_BYTES: usize = 8;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum FixupSize {
+    Byte,
+    Word,
+    Relative
+}
+
+impl FixupSize {
+    // Encodes the value, the diagnostic has no span.
+    fn encode(&self, value: i32, location: u16) -> (u16, Option<Diagnostic>) {
+        match *self {
+            FixupSize::Byte     => {
+                let operand = (value as u16) & 0xFF;
+                if (-128..=0xFF).contains(&value) {
+                    (operand, None)
+                } else {
+                    let message = format!("Operand ${:X} truncated to ${:02X}", value, operand);
+                    (operand, Some(Diagnostic::warning(message, None)))
+                }
+            }
+
+            FixupSize::Word     => {
+                let operand = value as u16;
+                if (-0x8000..=0xFFFF).contains(&value) {
+                    (operand, None)
+                } else {
+                    let message = format!("Operand ${:X} truncated to ${:04X}", value, operand);
+                    (operand, Some(Diagnostic::warning(message, None)))
+                }
+            }
+
+            FixupSize::Relative => {
+                // Relative to the end of the 2 byte branch.
+                let off = value - (location as i32 + 2);
+                if (-128..=127).contains(&off) {
+                    ((off as u16) & 0xFF, None)
+                } else {
+                    let message = format!("Branch target at 0x{:X} too far: ${:X}", location, value);
+                    (0, Some(Diagnostic::error(message, None)))
+                }
+            }
+        }
+    }
+}
+
+// Expression written at addr (index of the output) once
+// all labels are known.
+#[derive(Debug, Clone)]
+struct Fixup {
+    addr:     usize,
+    size:     FixupSize,
+    expr:     Expr,
+    location: u16,
+    span:     Option<Span>
 }
 
+// Size of the JMP START prefix of programs without .ORG.
+const START_JMP_SIZE: usize = 3;
+
 impl Assembler6502 {
     pub fn new() -> Assembler6502 {
         Assembler6502 {
@@ -34,53 +116,139 @@ impl Assembler6502 {
             vars:     HashMap::new(),
             var_uses: HashMap::new(),
             prep:     Preprocessor::new(),
-            debug:    false
+            variant:  Variant::Nmos,
+            diags:    Vec::new(),
+            span:     None,
+            source:   String::new(),
+            locations: HashMap::new(),
+            origin:   None,
+            start_jmp: false,
+            vectors:  Vec::new(),
+            fixups:   Vec::new(),
+            listing:  Vec::new()
+        }
+    }
+
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+    }
+
+    // Assembled image, it starts at the first .ORG address.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    // Address of a label or a variable after linking.
+    pub fn label(&self, name: &str) -> Option<u16> {
+        self.symbol(&name.to_uppercase())
+    }
+
+    // Address, bytes and source of every line, long
+    // data is cut after LISTING_BYTES bytes.
+    pub fn listing(&self) -> String {
+        let mut res = String::new();
+
+        for &(start, end, ref source) in self.listing.iter() {
+            let shown = &self.data[start..end.min(start + LISTING_BYTES)];
+            let mut bytes = shown.iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            if end - start > LISTING_BYTES {
+                bytes.push_str(" ...");
+            }
+
+            let addr = (self.base() + start) & 0xFFFF;
+            let line = format!("{:04X}  {:<27}  {}", addr, bytes, source);
+            res.push_str(line.trim_end());
+            res.push('\n');
+        }
+
+        res
+    }
+
+    // Labels and variables in the VICE label file format,
+    // e.g. al C:C000 .RESET
+    pub fn symbols(&self) -> String {
+        let mut symbols: Vec<(u16, &str)> = self.labels.iter()
+            .chain(self.vars.iter())
+            .map(|(name, &addr)| (addr, name.trim_end_matches(':')))
+            .collect();
+        symbols.sort();
+
+        let mut res = String::new();
+        for (addr, name) in symbols.iter() {
+            res.push_str(&format!("al C:{:04X} .{}\n", addr, name));
         }
+
+        res
     }
 }
 
 impl Assembler for Assembler6502 {
-    fn assemble(&mut self, input: &str) {
+    fn assemble(&mut self, input: &str) -> Vec<Diagnostic> {
         self.data.clear();
         self.labels.clear();
         self.jumps.clear();
-
+        self.diags.clear();
+        self.locations.clear();
+        self.vectors.clear();
+        self.fixups.clear();
+        self.listing.clear();
+        self.span = None;
+
+        // Dropped if the program starts with .ORG.
         self.translate("JMP START");
+        self.origin = None;
+        self.start_jmp = true;
 
         self.assemble_file(input);
+        self.span = None;
+
+        mem::take(&mut self.diags)
     }
 
-    fn link(&mut self) {
+    fn link(&mut self) -> Vec<Diagnostic> {
         // TODO: Implement shifting? Shift all bytes from
         //       address X and increment all labels by
         //       the shifted amount if they are after X?
         //       This would allow inserting code after
         //       assembling but before linking.
-        if !self.labels.contains_key("START:") {
-            panic!("Start label not defined.");
+        if self.start_jmp && !self.labels.contains_key("START:") {
+            self.error(String::from("Start label not defined"));
         }
 
         // Skip data section.
-        self.translate("JMP END");
+        if self.start_jmp {
+            self.translate("JMP END");
+        }
 
+        let mut diags = Vec::new();
         for (&addr, var) in self.var_uses.iter() {
             match self.vars.get(var) {
                 Some(&target) => {
                     self.data[(addr + 1) as usize] = util::lower(target);
 
                     // IndirectX and IndirectY only have 1 byte operand.
-                    let mode = addr::get_addr_mode(self.data[addr as usize]);
+                    let mode = self.addr_mode(self.data[addr as usize]);
                     if addr::pc_offset(&mode)  == 3 {
                         self.data[(addr + 2) as usize] = util::upper(target);
                     }
                 }
                 None => {
-                    panic!("Variable not defined: {}", var);
+                    let span = self.locations.get(&addr).cloned();
+                    let message = format!("Variable not defined: {}", var.trim_end_matches(':'));
+                    diags.push(Diagnostic::error(message, span));
                 }
             }
         }
 
-        self.translate("END:");
+        if self.start_jmp {
+            self.translate("END:");
+        }
+
+        self.fill_vectors();
 
         for (&addr, label) in self.jumps.iter() {
             match self.labels.get(label) {
@@ -90,26 +258,68 @@ impl Assembler for Assembler6502 {
                     self.data[(addr + 1) as usize] = lo;
                     self.data[(addr + 2) as usize] = hi;
                 }
-                None => panic!("Label not defined: {}", label)
+                None => {
+                    let span = self.locations.get(&addr).cloned();
+                    let message = format!("Label not defined: {}", label.trim_end_matches(':'));
+                    diags.push(Diagnostic::error(message, span));
+                }
             }
         }
 
         for (&addr, label) in self.branches.iter() {
+            let span = self.locations.get(&addr).cloned();
+
             match self.labels.get(label) {
                 Some(&target) => {
-                    let target = target as i16;
-                    let addr = addr as i16;
-                    let off = target - addr;
-
-                    if off > 127i16 {
-                        panic!("Branch label at 0x{:X} too far: {}", addr, label);
+                    // Offset is the last byte of the instruction and
+                    // is relative to the next one.
+                    let mode = self.addr_mode(self.data[addr as usize]);
+                    let size = addr::pc_offset(&mode);
+                    let idx = addr as usize + size - 1;
+
+                    let next = self.base() + addr as usize + size;
+                    let off = target as i32 - next as i32;
+
+                    if !(-128..=127).contains(&off) {
+                        let message = format!("Branch label at 0x{:X} too far: {}",
+                                              addr, label.trim_end_matches(':'));
+                        diags.push(Diagnostic::error(message, span));
                     } else {
-                        self.data[(addr + 1) as usize] = util::lower(off as u16);
+                        self.data[idx] = util::lower(off as u16);
                     }
                 }
-                None => panic!("Label not defined: {}", label)
+                None => {
+                    let message = format!("Label not defined: {}", label.trim_end_matches(':'));
+                    diags.push(Diagnostic::error(message, span));
+                }
             }
         }
+
+        for fixup in mem::take(&mut self.fixups).iter() {
+            let value = fixup.expr.eval(fixup.location, &|name| self.symbol(name));
+            match value {
+                Ok(value) => {
+                    let (operand, diag) = fixup.size.encode(value, fixup.location);
+
+                    self.data[fixup.addr] = util::lower(operand);
+                    if fixup.size == FixupSize::Word {
+                        self.data[fixup.addr + 1] = util::upper(operand);
+                    }
+
+                    if let Some(mut diag) = diag {
+                        diag.span = fixup.span.clone();
+                        diags.push(diag);
+                    }
+                }
+                Err(error) => diags.push(Diagnostic::error(error, fixup.span.clone()))
+            }
+        }
+
+        // Hash map order is random.
+        diags.sort_by_key(|d| d.span.as_ref().map(|s| (s.file.clone(), s.line, s.column)));
+        self.diags.append(&mut diags);
+
+        mem::take(&mut self.diags)
     }
 
     fn output(&mut self, output: &str) {
@@ -118,211 +328,530 @@ impl Assembler for Assembler6502 {
         writer.write_all(&self.data)
             .expect("Cannot write to output file.");
     }
-
-    fn debug(&mut self, debug: bool) {
-        self.debug = debug;
-    }
 }
 
 impl Assembler6502 {
     pub fn translate(&mut self, command: &str) {
-        let data_end = self.data.len() as u16;
         let mut command = command;
 
         // Label definition on its own line.
         if mcs6502::is_valid_identifier(command, true) {
-            let mut label = command.to_string();
-            if !label.ends_with(":") {
-                label.push_str(":");
+            self.define_label(command);
+            return;
+        }
+
+        command = strip_comment(command);
+
+        let mut tokens: Vec<&str> = command.split_whitespace().collect();
+        if tokens.is_empty() {
+            return;
+        }
+
+        if mcs6502::is_valid_identifier(tokens[0], true) {
+            self.define_label(tokens[0]);
+
+            tokens.remove(0);
+            if tokens.is_empty() {
+                return;
             }
+        }
+
+        let op = tokens[0];
 
-            match self.labels.insert(label, data_end as u16) {
-                Some(_) => panic!("Redefinition of label in {}", command),
-                None    => ()
+        // Expressions can contain spaces.
+        let arg = tokens[1..].join(" ");
+
+        if self.variant == Variant::Cmos {
+            if let Some(op) = cmos::bit_op_to_opcode(op) {
+                self.push_bit_instruction(op, &arg);
+                return;
             }
-            return;
         }
 
-        let tokens: Vec<&str> = command.split(";").collect();
-        if tokens.len() == 0 || tokens[0] == "" || tokens[0].starts_with(";") {
-            return; // Commented line.
-        } else {
-            command = tokens[0];
+        let (mut addr_mode, operand, expr) = match self.parse_expression(&arg) {
+            Some((mode, Ok(expr)))  => (mode, 0, Some(expr)),
+            Some((_, Err(error)))   => {
+                self.error_at(&arg, error);
+                return;
+            }
+            None                    => {
+                let (mode, operand) = mcs6502::parse_arguments(&arg);
+                (mode, operand, None)
+            }
+        };
+
+        // Relative and absolute don't differ in assembly.
+        if addr_mode == AddressMode::Absolute && self.is_branch(op) {
+            addr_mode = AddressMode::Relative;
         }
 
-        let mut tokens: Vec<&str> = command.split_whitespace().collect();
-        assert!(tokens.len() > 0);
+        let res = match self.variant {
+            Variant::Nmos => mcs6502::name_mode_to_opcode(op, &addr_mode),
+            Variant::Cmos => {
+                // Only JMP takes a 16bit pointer, the rest use (zp).
+                addr_mode = match (op, addr_mode) {
+                    ("JMP", AddressMode::IndirectX) => AddressMode::AbsoluteIndirectX,
+                    ("JMP", mode)                   => mode,
+                    (_, AddressMode::Indirect)      => AddressMode::ZeroPageIndirect,
+                    (_, mode)                       => mode
+                };
+
+                cmos::name_mode_to_opcode(op, &addr_mode)
+            }
+        };
 
-        if mcs6502::is_valid_identifier(tokens[0], true) {
-            let mut label = tokens[0].to_string();
-            if !label.ends_with(":") {
-                label.push_str(":");
+        match res {
+            Ok(op)     => {
+                match expr {
+                    Some(expr) => self.push_expression(op, expr, addr_mode, &arg),
+                    None       => self.push_instruction(op, operand, addr_mode, &arg)
+                }
             }
+            Err(error) => {
+                // Point at the operand if the instruction exists.
+                let token = if mcs6502::is_valid_instruction(op) && tokens.len() > 1 {
+                    tokens[1]
+                } else {
+                    op
+                };
 
-            match self.labels.insert(label, data_end) {
-                Some(_) => panic!("Redefinition of label in {}", command),
-                None    => ()
+                self.error_at(token, error);
             }
+        }
+    }
 
-            tokens.remove(0);
+    // Splits the operand into the address mode and expression,
+    // labels and the accumulator are left to parse_arguments.
+    fn parse_expression(&self, arg: &str) -> Option<(AddressMode, Result<Expr, String>)> {
+        let arg = arg.trim();
+        if arg.is_empty() || arg.eq_ignore_ascii_case("A") {
+            return None;
         }
 
-        let op = tokens[0];
+        let compact: String = arg.chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_uppercase();
+
+        let (mode, body) = if let Some(body) = arg.strip_prefix('#') {
+            (AddressMode::Immediate, body)
+        } else if arg.starts_with('(') && compact.ends_with(",X)") {
+            (AddressMode::IndirectX, &arg[1..arg.rfind(',').unwrap_or(1)])
+        } else if arg.starts_with('(') && compact.ends_with("),Y") {
+            (AddressMode::IndirectY, &arg[1..arg.rfind(')').unwrap_or(1)])
+        } else if arg.starts_with('(') && compact.ends_with(')') {
+            (AddressMode::Indirect, &arg[1..arg.len() - 1])
+        } else if compact.ends_with(",X") {
+            (AddressMode::AbsoluteX, &arg[..arg.rfind(',').unwrap_or(0)])
+        } else if compact.ends_with(",Y") {
+            (AddressMode::AbsoluteY, &arg[..arg.rfind(',').unwrap_or(0)])
+        } else {
+            (AddressMode::Absolute, arg)
+        };
+
+        let body = body.trim();
+        if mode != AddressMode::Immediate && mcs6502::is_valid_identifier(body, false) {
+            return None;
+        }
+
+        let expr = Expr::parse(body);
+        if expr.is_err() && mode != AddressMode::Immediate && body.starts_with('*') {
+            // Zero page, e.g. *$12 or *PTR+1,X.
+            let mode = match mode {
+                AddressMode::AbsoluteX => AddressMode::ZeroPageX,
+                AddressMode::AbsoluteY => AddressMode::ZeroPageY,
+                AddressMode::Absolute  => AddressMode::ZeroPage,
+                _                      => return Some((mode, expr))
+            };
+
+            return Some((mode, Expr::parse(&body[1..])));
+        }
+
+        Some((mode, expr))
+    }
+
+    fn is_branch(&self, op: &str) -> bool {
+        match self.variant {
+            Variant::Nmos => mcs6502::name_mode_to_opcode(op, &AddressMode::Relative).is_ok(),
+            Variant::Cmos => cmos::name_mode_to_opcode(op, &AddressMode::Relative).is_ok()
+        }
+    }
+
+    // Value of a label or a variable.
+    fn symbol(&self, name: &str) -> Option<u16> {
+        let mut key = String::from(name);
+        key.push(':');
 
-        let mut arg = if tokens.len() > 1 {
-            tokens[1].to_string()
+        self.labels.get(&key).or_else(|| self.vars.get(&key)).cloned()
+    }
+
+    // Instruction with an expression operand, it's resolved when
+    // linking if it contains labels that are not yet defined.
+    fn push_expression(&mut self, op: u8, expr: Expr, mode: AddressMode, arg: &str) {
+        let location = self.location();
+        let addr = self.data.len() + 1;
+
+        let size = if mode == AddressMode::Relative {
+            FixupSize::Relative
+        } else if addr::pc_offset(&mode) == 2 {
+            FixupSize::Byte
         } else {
-            "".to_string()
+            FixupSize::Word
+        };
+
+        // Numeric branch operands are offsets.
+        if size == FixupSize::Relative && expr.is_number() {
+            let value = expr.eval_const().unwrap_or(0);
+            let (operand, diag) = FixupSize::Byte.encode(value, location);
+            self.push_diagnostic(diag);
+            self.push_instruction(op, operand, mode, arg);
+            return;
+        }
+
+        match expr.eval(location, &|name| self.symbol(name)) {
+            Ok(value) => {
+                let (operand, diag) = size.encode(value, location);
+                self.push_diagnostic(diag);
+                self.push_instruction(op, operand, mode, arg);
+            }
+            Err(_)    => {
+                self.push_instruction(op, 0, mode, arg);
+                self.fixups.push(Fixup {
+                    addr,
+                    size,
+                    expr,
+                    location,
+                    span: self.span.clone()
+                });
+            }
+        }
+    }
+
+    fn push_diagnostic(&mut self, diag: Option<Diagnostic>) {
+        if let Some(mut diag) = diag {
+            diag.span = self.span.clone();
+            self.diags.push(diag);
+        }
+    }
+
+    fn define_label(&mut self, label: &str) {
+        let data_end = self.location();
+
+        let mut name = label.to_string();
+        if !name.ends_with(':') {
+            name.push(':');
+        }
+
+        let message = match self.labels.entry(name) {
+            Entry::Occupied(entry) => {
+                format!("Redefinition of label: {}", entry.key().trim_end_matches(':'))
+            }
+
+            Entry::Vacant(entry)   => {
+                entry.insert(data_end);
+                return;
+            }
         };
-        if tokens.len() > 2 && arg.ends_with(',') {
-            arg.push_str(tokens[2]);
+
+        self.error_at(label, message);
+    }
+
+    fn error(&mut self, message: String) {
+        let span = self.span.clone();
+        self.diags.push(Diagnostic::error(message, span));
+    }
+
+    fn warning(&mut self, message: String) {
+        let span = self.span.clone();
+        self.diags.push(Diagnostic::warning(message, span));
+    }
+
+    // Points the error at the token in the current line if possible.
+    fn error_at(&mut self, token: &str, message: String) {
+        let span = self.span.clone().map(|mut span| {
+            if let Some(idx) = self.source.find(token) {
+                span.column = idx + 1;
+            }
+            span
+        });
+
+        self.diags.push(Diagnostic::error(message, span));
+    }
+
+    fn addr_mode(&self, opcode: u8) -> AddressMode {
+        match self.variant {
+            Variant::Nmos => addr::get_addr_mode(opcode),
+            Variant::Cmos => cmos::get_addr_mode(opcode)
         }
+    }
+
+    // RMB and SMB take a zero page address, BBR and BBS
+    // also a branch target, e.g. BBR0 *$12, LOOP.
+    fn push_bit_instruction(&mut self, op: u8, arg: &str) {
+        let data_end = self.data.len() as u16;
+        let args: Vec<&str> = arg.splitn(2, ',').collect();
 
-        let (addr_mode, operand) = mcs6502::parse_arguments(&arg);
-        let mut addr_mode = addr_mode;
+        let (mode, operand) = mcs6502::parse_arguments(args[0]);
+        if mode != AddressMode::ZeroPage {
+            self.error_at(args[0], format!("Invalid zero page address: {}", args[0]));
+            return;
+        }
 
-        if op.starts_with("B") && addr_mode == AddressMode::Absolute {
-            // Relative and 2 digit absolute don't differ in assembly :/
-            addr_mode = AddressMode::Relative;
+        if self.addr_mode(op) == AddressMode::ZeroPage {
+            self.push_two_byte(op, util::lower(operand));
+            return;
         }
 
-        let op = mcs6502::name_mode_to_opcode(op, &addr_mode);
-        self.push_instruction(op, operand, addr_mode, &arg);
+        let target = args.get(1).map(|t| t.trim()).unwrap_or("");
+        if mcs6502::is_valid_identifier(target, false) {
+            let mut label = target.to_uppercase();
+            label.push(':');
+            self.branches.insert(data_end, label);
+            self.record_location(data_end);
+
+            self.push_three_byte(op, util::lower(operand) as u16);
+        } else {
+            let chars: Vec<char> = target.chars().collect();
+            match mcs6502::extract_operand(&chars) {
+                Some(offset) => {
+                    let operand = (util::lower(offset) as u16) << 8 | (operand & 0xFF);
+                    self.push_three_byte(op, operand);
+                }
+                None         => {
+                    self.error_at(target, format!("Invalid branch target: {}", target));
+                }
+            }
+        }
     }
 
     fn assemble_file(&mut self, input: &str) {
-        let file = File::open(input).
-            expect(&format!("Unable to open input file: {}", input));
+        let file = match File::open(input) {
+            Ok(file)   => file,
+            Err(error) => {
+                self.error(format!("Unable to open input file {}: {}", input, error));
+                return;
+            }
+        };
+
         let reader = BufReader::new(&file);
-        let mut lines: Vec<String> = reader.lines()
-                                        .map(|s| s.unwrap().to_string())
-                                        .collect();
+        let mut lines: Vec<Line> = Vec::new();
+        for (idx, line) in reader.lines().enumerate() {
+            match line {
+                Ok(line)   => lines.push((Span::new(input, idx + 1, 1), line)),
+                Err(error) => {
+                    let span = Span::new(input, idx + 1, 1);
+                    let message = format!("Unable to read input file: {}", error);
+                    self.diags.push(Diagnostic::error(message, Some(span)));
+                    return;
+                }
+            }
+        }
+
+        // The listing shows the lines as they were written.
+        let originals: Vec<String> = lines.iter().map(|(_, line)| line.clone()).collect();
+        let mut listed = 0;
 
         lines = self.prep.process(lines);
+        let mut diags = self.prep.diagnostics();
+        self.diags.append(&mut diags);
 
-        for line in lines.iter() {
-            let line = line.trim();
+        for (span, source) in lines.iter() {
+            let line = strip_comment(source).trim();
+
+            // Further lines of a macro expansion only show their bytes.
+            let original = if span.line != listed {
+                originals.get(span.line - 1).cloned().unwrap_or_default()
+            } else {
+                String::new()
+            };
+            listed = span.line;
+
+            let mut span = span.clone();
+            if let Some(idx) = source.find(line) {
+                span.column += idx;
+            }
+            self.span = Some(span);
+            self.source = source.clone();
+
+            let entry = self.listing.len();
+            let start = self.data.len();
+            self.listing.push((start, start, original));
 
             if line.starts_with(".INCLUDE ") {
-                // This is a problem, includes will always be changed to lowercase :/
-                let file = self.get_include_file(&line.to_lowercase(), input);
-                let file = String::from(file.to_str().unwrap());
+                let file = match self.get_include_file(line, input) {
+                    Some(file) => String::from(file.to_str().unwrap_or("")),
+                    None       => {
+                        self.error(format!("Invalid file include: {}", line));
+                        continue;
+                    }
+                };
 
                 if !self.files.contains(&file) {
                     self.files.push(file.clone());
                     self.assemble_file(&file);
                 }
+            } else if line.starts_with(".ORG ") {
+                if let Some(addr) = self.directive_arg(line, 1) {
+                    self.set_origin(addr);
+                }
+            } else if line.starts_with(".ALIGN ") {
+                if let Some(align) = self.directive_arg(line, 1) {
+                    self.align(align);
+                }
+            } else if line.starts_with(".RES ") {
+                self.reserve(line);
+            } else if line.starts_with(".NMI ") {
+                self.set_vector(line, INT_NOMASK_ADDRESS);
+            } else if line.starts_with(".RESET ") {
+                self.set_vector(line, PC_INIT_ADDRESS);
+            } else if line.starts_with(".IRQ ") {
+                self.set_vector(line, INT_REQ_ADDRESS);
             } else if line.starts_with(".BYTE ") {
                 self.declare_variable(&line, VariableSize::Byte);
             } else if line.starts_with(".WORD ") {
                 // TODO: 16bit instructions?
                 self.declare_variable(&line, VariableSize::Word);
-            } else if !line.is_empty() && !line.starts_with(";") {
+            } else if !line.is_empty() {
                 self.translate(&line);
             }
+
+            // Included lines have their own entries.
+            if !line.starts_with(".INCLUDE ") && self.data.len() > start {
+                self.listing[entry].1 = self.data.len();
+            }
         }
     }
 
-    fn get_include_file(&self, line: &str, input: &str) -> PathBuf {
+    fn get_include_file(&self, line: &str, input: &str) -> Option<PathBuf> {
+        if line.len() < 11 {
+            return None;
+        }
+
         let (_, rest) = line.split_at(8);
         let line = &rest[2..];
         let file_end = line.rfind("\"").unwrap_or(0);
 
         if file_end == 0 {
-            panic!("Invalid file include: {}", line);
+            return None;
         }
 
         let (file, _) = line.split_at(file_end);
 
         let input_path = Path::new(input);
         match input_path.parent() {
-            Some(parent) => parent.join(file),
-            None => Path::new(file).to_path_buf()
+            Some(parent) => Some(parent.join(file)),
+            None => Some(Path::new(file).to_path_buf())
         }
     }
 
     fn declare_variable(&mut self, line: &str, size: VariableSize) {
-        // TODO: If we declare an array and place ; in the
-        //       middle of the init values, the assembler will
-        //       flip out.
-        let words: Vec<&str> = line.split(" ").collect();
+        // Directive, name and the initializers separated by commas.
+        let rest = line.split_once(char::is_whitespace).map_or("", |(_, rest)| rest.trim());
+        let (name, inits) = match rest.split_once(char::is_whitespace) {
+            Some((name, inits)) => (name, inits.trim()),
+            None                => (rest, "")
+        };
 
-        let count = words.len();
         let mut values: Vec<u8> = Vec::new();
-
-        // Marker for dissasembler.
-        if self.debug {
-            values.push(ops::custom::VARIABLE);
-            values.push(((count - 2) as u8) * size.bytes());
-        }
+        let mut fixups: Vec<Fixup> = Vec::new();
+        let start = self.data.len();
+        let location = self.location();
 
         // Default value.
-        if count == 2 {
+        if inits.is_empty() {
             for _ in 0 .. size.bytes() {
                 values.push(0x00u8);
             }
         } else {
-            for i in 2..count {
-                let chars: Vec<char> = words[i].chars().collect();
-                match size {
-                    VariableSize::Byte => {
-                        let value = mcs6502::extract_operand(&chars);
-                        if let Some(value) = value {
-                            values.push(util::lower(value));
-                        } else {
-                            panic!("Invalid byte initializer: {}", words[i]);
-                        }
+            for init in split_items(inits) {
+                let init = init.trim();
+                let expr = match Expr::parse(init) {
+                    Ok(expr)   => expr,
+                    Err(error) => {
+                        self.error_at(init, format!("Invalid initializer {}: {}", init, error));
+                        continue;
                     }
-                    VariableSize::Word => {
-                        let value = mcs6502::extract_operand(&chars);
-                        if let Some(value) = value {
-                            values.push(util::lower(value));
+                };
+
+                let (size, bytes) = match size {
+                    VariableSize::Byte => (FixupSize::Byte, 1),
+                    VariableSize::Word => (FixupSize::Word, 2)
+                };
+
+                let value_location = location.wrapping_add(values.len() as u16);
+                match expr.eval(value_location, &|name| self.symbol(name)) {
+                    Ok(value) => {
+                        let (value, diag) = size.encode(value, value_location);
+                        self.push_diagnostic(diag);
+
+                        values.push(util::lower(value));
+                        if bytes == 2 {
                             values.push(util::upper(value));
-                        } else {
-                            panic!("Invalid word initializer: {}", words[i]);
                         }
                     }
+                    Err(_)    => {
+                        fixups.push(Fixup {
+                            addr:     start + values.len(),
+                            size,
+                            expr,
+                            location: value_location,
+                            span:     self.span.clone()
+                        });
+
+                        let len = values.len() + bytes;
+                        values.resize(len, 0x00);
+                    }
                 }
             }
         }
 
-        if !mcs6502::is_valid_identifier(words[1], false) {
-            panic!("Invalid variable name: '{}'", words[1]);
+        if !mcs6502::is_valid_identifier(name, false) {
+            self.error_at(name, format!("Invalid variable name: '{}'", name));
+            return;
         }
 
-        let mut name = String::from(words[1]);
+        let mut name = String::from(name);
         name.push_str(":");
 
         // Variables are where they were declared.
-        self.vars.insert(name.clone(), (self.data.len() + 2) as u16);
+        self.vars.insert(name.clone(), location);
         for value in values.iter() {
             self.data.push(*value);
         }
+        self.fixups.append(&mut fixups);
     }
 
     fn push_instruction(&mut self, op: u8, operand: u16, mode: AddressMode, arg: &str) {
         let data_end = self.data.len() as u16;
+        self.record_location(data_end);
 
         match mode {
-            AddressMode::Implied     => {
+            AddressMode::Implied     |
+            AddressMode::Accumulator => {
                 self.push_one_byte(op);
             }
 
-            AddressMode::Accumulator |
             AddressMode::Relative    |
             AddressMode::Immediate   |
             AddressMode::IndirectX   |
             AddressMode::IndirectY   |
             AddressMode::ZeroPageX   |
             AddressMode::ZeroPageY   |
-            AddressMode::ZeroPage    => {
+            AddressMode::ZeroPage    |
+            AddressMode::ZeroPageIndirect => {
+                if operand > 0xFF {
+                    self.warning(format!("Operand ${:X} truncated to ${:02X}",
+                                         operand, util::lower(operand)));
+                }
+
                 self.push_two_byte(op, util::lower(operand));
             }
 
             AddressMode::Absolute    |
             AddressMode::AbsoluteX   |
             AddressMode::AbsoluteY   |
-            AddressMode::Indirect    => {
+            AddressMode::Indirect    |
+            AddressMode::AbsoluteIndirectX => {
                 self.push_three_byte(op, operand);
             }
 
@@ -336,13 +865,22 @@ impl Assembler6502 {
                 label = self.strip_index(&label);
                 label.push_str(":");
 
-                if mcs6502::can_jump_to_label(op) {
+                let (jump, branch, variable) = match self.variant {
+                    Variant::Nmos => (mcs6502::can_jump_to_label(op),
+                                      mcs6502::can_branch_to_label(op),
+                                      mcs6502::can_use_variables(op)),
+                    Variant::Cmos => (cmos::can_jump_to_label(op),
+                                      cmos::can_branch_to_label(op),
+                                      cmos::can_use_variables(op))
+                };
+
+                if jump {
                     self.jumps.insert(data_end , label);
                     self.push_three_byte(op, 0x00u16);
-                } else if mcs6502::can_branch_to_label(op) {
+                } else if branch {
                     self.branches.insert(data_end, label);
                     self.push_two_byte(op, 0x00u8);
-                } else if mcs6502::can_use_variables(op) {
+                } else if variable {
                     self.var_uses.insert(data_end, label);
                     match mode {
                         AddressMode::ILabel      |
@@ -358,13 +896,168 @@ impl Assembler6502 {
                         _                        => ()
                     }
                 } else {
-                    panic!("Instruction cannot use label: 0x{:X} {} ({:?})", op, label, mode);
+                    let name = mcs6502::op_name(op);
+                    self.error_at(arg, format!("Instruction {} cannot use label: {}",
+                                               name, label.trim_end_matches(':')));
                 }
             }
 
             _                     => {
-                panic!("Invalid address mode {:?} for opcode: 0x{:X}", mode, op);
+                self.error_at(arg, format!("Invalid operand: {}", arg));
+            }
+        }
+    }
+
+    // Remembers the source of the instruction for errors found
+    // when linking.
+    fn record_location(&mut self, addr: u16) {
+        if let Some(ref span) = self.span {
+            self.locations.insert(addr, span.clone());
+        }
+    }
+
+    // Address of the first byte of the output.
+    fn base(&self) -> usize {
+        self.origin.unwrap_or(0) as usize
+    }
+
+    // Address of the next emitted byte.
+    fn location(&self) -> u16 {
+        (self.base() + self.data.len()) as u16
+    }
+
+    // Numeric argument of a directive, e.g. .ORG $C000.
+    fn directive_arg(&mut self, line: &str, idx: usize) -> Option<u16> {
+        let arg = line.split(';').next().unwrap_or("")
+                      .split_whitespace().nth(idx).unwrap_or("");
+        let location = self.location();
+
+        // Only labels defined before the directive can be used.
+        let value = Expr::parse(arg).and_then(|expr| {
+            expr.eval(location, &|name| self.symbol(name))
+        });
+
+        match value {
+            Ok(value)  => Some(value as u16),
+            Err(error) => {
+                let token = if arg.is_empty() { line } else { arg };
+                self.error_at(token, format!("Invalid directive argument {}: {}", arg, error));
+                None
+            }
+        }
+    }
+
+    fn set_origin(&mut self, addr: u16) {
+        // Program starting with .ORG, the reset vector is used
+        // instead of the jump to START.
+        if self.origin.is_none() && self.start_jmp && self.data.len() == START_JMP_SIZE {
+            self.data.clear();
+            self.jumps.remove(&0);
+            self.start_jmp = false;
+
+            for line in self.listing.iter_mut() {
+                line.0 = 0;
+                line.1 = 0;
+            }
+
+            // Labels preceding the directive.
+            for value in self.labels.values_mut() {
+                *value = addr;
             }
+            self.origin = Some(addr);
+            return;
+        }
+
+        let location = self.location();
+        if addr < location || self.base() + self.data.len() > 0xFFFF {
+            self.error(format!("Origin ${:04X} is below the current address ${:04X}",
+                               addr, location));
+            return;
+        }
+
+        self.pad((addr - location) as usize, 0x00);
+    }
+
+    fn align(&mut self, align: u16) {
+        if align == 0 {
+            self.error(String::from("Alignment must be positive"));
+            return;
+        }
+
+        let location = self.base() + self.data.len();
+        let align = align as usize;
+        self.pad((align - location % align) % align, 0x00);
+    }
+
+    // .RES count [fill]
+    fn reserve(&mut self, line: &str) {
+        let count = match self.directive_arg(line, 1) {
+            Some(count) => count,
+            None        => return
+        };
+
+        let has_fill = line.split(';').next().unwrap_or("").split_whitespace().count() > 2;
+        let fill = if has_fill {
+            match self.directive_arg(line, 2) {
+                Some(fill) => util::lower(fill),
+                None       => return
+            }
+        } else {
+            0x00
+        };
+
+        self.pad(count as usize, fill);
+    }
+
+    fn pad(&mut self, count: usize, fill: u8) {
+        let size = self.data.len() + count;
+        self.data.resize(size, fill);
+    }
+
+    // .NMI, .RESET and .IRQ take the address of the handler,
+    // usually its label.
+    fn set_vector(&mut self, line: &str, vector: usize) {
+        let arg = line.split(';').next().unwrap_or("")
+                      .split_whitespace().nth(1).unwrap_or("");
+
+        let expr = match Expr::parse(arg) {
+            Ok(expr)   => expr,
+            Err(error) => {
+                self.error_at(line, format!("Invalid vector {}: {}", arg, error));
+                return;
+            }
+        };
+
+        let fixup = Fixup {
+            addr:     0,
+            size:     FixupSize::Word,
+            expr,
+            location: self.location(),
+            span:     self.span.clone()
+        };
+        self.vectors.push((vector, fixup));
+    }
+
+    // Extends the output up to $FFFF and writes the vectors.
+    fn fill_vectors(&mut self) {
+        if self.vectors.is_empty() {
+            return;
+        }
+
+        let base = self.base();
+        if base > INT_NOMASK_ADDRESS {
+            self.error(format!("Origin ${:04X} is above the vectors", base));
+            return;
+        }
+
+        let size = 0x10000 - base;
+        if self.data.len() < size {
+            self.data.resize(size, 0x00);
+        }
+
+        for (vector, mut fixup) in mem::take(&mut self.vectors).into_iter() {
+            fixup.addr = vector - base;
+            self.fixups.push(fixup);
         }
     }
 
@@ -402,6 +1095,93 @@ impl Assembler6502 {
     }
 }
 
+// Whether each character is part of a character or string
+// literal, which keep their case and can contain ; and ,.
+fn literals(line: &str) -> Vec<bool> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut res = vec![false; chars.len()];
+
+    let mut idx = 0;
+    while idx < chars.len() {
+        match chars[idx] {
+            '"'  => {
+                res[idx] = true;
+                idx += 1;
+                while idx < chars.len() {
+                    res[idx] = true;
+                    idx += 1;
+                    if chars[idx - 1] == '"' {
+                        break;
+                    }
+                }
+            }
+            '\'' => {
+                // The closing quote is optional.
+                let mut end = (idx + 2).min(chars.len());
+                if chars.get(end) == Some(&'\'') {
+                    end += 1;
+                }
+                for lit in res[idx..end].iter_mut() {
+                    *lit = true;
+                }
+                idx = end;
+            }
+            _    => idx += 1
+        }
+    }
+
+    res
+}
+
+// Uppercases the line except for its literals.
+fn upper_code(line: &str) -> String {
+    let mut res = String::with_capacity(line.len());
+    for (c, lit) in line.chars().zip(literals(line)) {
+        if lit {
+            res.push(c);
+        } else {
+            res.extend(c.to_uppercase());
+        }
+    }
+
+    res
+}
+
+// Line without the comment, a ; in a literal doesn't start one.
+fn strip_comment(line: &str) -> &str {
+    let comment = line.char_indices()
+        .zip(literals(line))
+        .find(|&((_, c), lit)| c == ';' && !lit);
+
+    match comment {
+        Some(((idx, _), _)) => &line[..idx],
+        None                => line
+    }
+}
+
+// Splits the list at commas outside of parentheses and literals.
+fn split_items(list: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for ((idx, c), lit) in list.char_indices().zip(literals(list)) {
+        match c {
+            _ if lit          => {}
+            '('               => depth += 1,
+            ')'               => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&list[start..idx]);
+                start = idx + 1;
+            }
+            _                 => {}
+        }
+    }
+    items.push(&list[start..]);
+
+    items
+}
+
 #[derive(Debug)]
 struct Macro {
     pub name: String,
@@ -411,17 +1191,28 @@ struct Macro {
 
 pub struct Preprocessor {
     macros:      HashMap<String, Macro>,
-    aux_defined: bool
+    aux_defined: bool,
+    diags:       Vec<Diagnostic>
 }
 
 impl Preprocessor {
     pub fn new() -> Preprocessor {
         Preprocessor {
             macros:      HashMap::new(),
-            aux_defined: false
+            aux_defined: false,
+            diags:       Vec::new()
         }
     }
-    pub fn register_macro(&mut self, code: Vec<String>) {
+
+    pub fn diagnostics(&mut self) -> Vec<Diagnostic> {
+        mem::take(&mut self.diags)
+    }
+
+    fn error(&mut self, message: String, span: &Span) {
+        self.diags.push(Diagnostic::error(message, Some(span.clone())));
+    }
+
+    pub fn register_macro(&mut self, code: Vec<String>, span: &Span) {
         if code.len() > 1 {
             let mut iter = code.iter();
             let declaration = iter.next().unwrap();
@@ -445,19 +1236,23 @@ impl Preprocessor {
             }
 
             while let Some(line) = iter.next() {
-                code.push(line.trim().to_uppercase());
+                code.push(upper_code(line.trim()));
             }
 
             // Allow redefinitions.
-            self.macros.insert(name.clone(), Macro { name, args, code });
+            let prev = self.macros.insert(name.clone(), Macro { name: name.clone(), args, code });
+            if prev.is_some() {
+                let message = format!("Redefinition of macro: {}", &name[1..]);
+                self.diags.push(Diagnostic::warning(message, Some(span.clone())));
+            }
         }
     }
 
-    pub fn process(&mut self, code: Vec<String>) -> Vec<String> {
+    pub fn process(&mut self, code: Vec<Line>) -> Vec<Line> {
         let mut expanded = true;
         let mut iteration = 0u8;
-        let mut input: Vec<String> = code;
-        let mut output: Vec<String> = Vec::new();
+        let mut input: Vec<Line> = code;
+        let mut output: Vec<Line> = Vec::new();
 
         // Auxiliary variables.
         if !self.aux_defined {
@@ -472,14 +1267,14 @@ impl Preprocessor {
             // Auxiliary block that limits iter's lifetime.
             {
                 let mut iter = input.iter().peekable();
-                while let Some(line) = iter.next() {
-                    let line = line.to_uppercase();
+                while let Some((span, line)) = iter.next() {
+                    let line = upper_code(line);
 
                     if line.starts_with(".MACRO ") {
                         let mut macro_code: Vec<String> = Vec::new();
                         macro_code.push(line.clone());
 
-                        while let Some(&line) = iter.peek() {
+                        while let Some((_, line)) = iter.peek() {
                             if line.starts_with(" ") {
                                 macro_code.push(line.clone());
                             } else {
@@ -488,24 +1283,24 @@ impl Preprocessor {
                             iter.next();
                         }
 
-                        self.register_macro(macro_code);
+                        self.register_macro(macro_code, span);
                     } else if line.starts_with("$PUSH ") {
-                        self.macro_push(&line, &mut output);
+                        self.macro_push(&line, span, &mut output);
                         expanded = true;
                     } else if line.starts_with("$POP ") {
-                        self.macro_pop(&line, &mut output);
+                        self.macro_pop(&line, span, &mut output);
                         expanded = true;
                     } else if line.starts_with("$STRING ") {
-                        self.macro_string(&line, &mut output);
+                        self.macro_string(&line, span, &mut output);
                         expanded = true;
                     } else if line.starts_with("$MOV ") {
-                        self.macro_mov(&line, &mut output);
+                        self.macro_mov(&line, span, &mut output);
                         expanded = true;
                     } else if line.starts_with("$") {
-                        self.expand_macro(&line, &mut output);
+                        self.expand_macro(&line, span, &mut output);
                         expanded = true;
                     } else {
-                        output.push(line.clone());
+                        output.push((span.clone(), line.clone()));
                     }
                 }
             }
@@ -519,7 +1314,7 @@ impl Preprocessor {
         output
     }
 
-    fn expand_macro(&mut self, line: &str, output: &mut Vec<String>) {
+    fn expand_macro(&mut self, line: &str, span: &Span, output: &mut Vec<Line>) {
         let mut tokens = line.split_whitespace();
 
         if let Some(name) = tokens.next() {
@@ -530,7 +1325,10 @@ impl Preprocessor {
                         if let Some(arg) = tokens.next() {
                             mapping.insert(mac.args[i].clone(), arg.to_string());
                         } else {
-                            panic!("Not enough arguments for macro {}: {}", name, i);
+                            let message = format!("Not enough arguments for macro {}: {}, expected {}",
+                                                  &mac.name[1..], i, mac.args.len());
+                            self.diags.push(Diagnostic::error(message, Some(span.clone())));
+                            return;
                         }
                     }
 
@@ -556,56 +1354,63 @@ impl Preprocessor {
                             out_line.push_str(word);
                             out_line.push_str(" ");
                         }
-                        output.push(out_line.trim_right().to_string());
+                        output.push((span.clone(), out_line.trim_right().to_string()));
                     }
                 } else {
                     for i in 0 .. mac.code.len() {
-                        output.push(mac.code[i].clone());
+                        output.push((span.clone(), mac.code[i].clone()));
                     }
                 }
 
             } else {
-                panic!("Unknown macro: {}", name);
+                self.error(format!("Unknown macro: {}", &name[1..]), span);
             }
         }
     }
 
-    fn macro_push(&mut self, line: &str, output: &mut Vec<String>) {
+    fn macro_push(&mut self, line: &str, span: &Span, output: &mut Vec<Line>) {
         let mut tokens = line.split_whitespace();
         tokens.next();
 
         let arguments: Vec<&str> = tokens.collect();
 
         if arguments.len() != 1 {
-            panic!("The $push macro supports only one argument, {} given {:?}",
-                   arguments.len(), arguments);
+            self.error(format!("The $push macro supports only one argument, {} given {:?}",
+                               arguments.len(), arguments), span);
+            return;
         }
 
-        output.push(format!("LDA {}", arguments[0]));
-        output.push(String::from("PHA"));
+        output.push((span.clone(), format!("LDA {}", arguments[0])));
+        output.push((span.clone(), String::from("PHA")));
     }
 
-    fn macro_pop(&mut self, line: &str, output: &mut Vec<String>) {
+    fn macro_pop(&mut self, line: &str, span: &Span, output: &mut Vec<Line>) {
         let mut tokens = line.split_whitespace();
         tokens.next();
 
         let arguments: Vec<&str> = tokens.collect();
 
         if arguments.len() != 1 {
-            panic!("The $pop macro supports only one argument, {} given {:?}",
-                   arguments.len(), arguments);
+            self.error(format!("The $pop macro supports only one argument, {} given {:?}",
+                               arguments.len(), arguments), span);
+            return;
         }
 
-        output.push(String::from("PLA"));
-        output.push(format!("STA {}", arguments[0]));
+        output.push((span.clone(), String::from("PLA")));
+        output.push((span.clone(), format!("STA {}", arguments[0])));
     }
 
-    fn macro_string(&mut self, line: &str, output: &mut Vec<String>) {
+    fn macro_string(&mut self, line: &str, span: &Span, output: &mut Vec<Line>) {
         let mut tokens = line.split_whitespace();
         tokens.next();
 
         let arguments: Vec<&str> = tokens.collect();
 
+        if arguments.len() < 2 {
+            self.error(String::from("The $string macro requires a name and a string"), span);
+            return;
+        }
+
         let mut string = String::new();
         string.push_str(arguments[1]);
 
@@ -622,33 +1427,253 @@ impl Preprocessor {
         }
 
         if string.find('"') == string.rfind('"') {
-            panic!("String not misses quotes: {}", string);
+            self.error(format!("String misses quotes: {}", string), span);
+            return;
         }
 
         let mut out_string = String::from(".BYTE ");
         out_string.push_str(arguments[0]);
+        out_string.push(' ');
         for c in string.chars() {
             if c != '"' {
-                out_string.push_str(" $");
-                out_string.push_str(&format!("{:X}", c as u8));
+                out_string.push_str(&format!("${:X}, ", c as u8));
             }
         }
-        out_string.push_str(" $00");
+        out_string.push_str("$00");
 
-        output.push(out_string);
+        output.push((span.clone(), out_string));
     }
 
-    fn macro_mov(&mut self, line: &str, output: &mut Vec<String>) {
+    fn macro_mov(&mut self, line: &str, span: &Span, output: &mut Vec<Line>) {
         let mut tokens = line.split_whitespace();
         tokens.next();
 
         let arguments: Vec<&str> = tokens.collect();
 
         if arguments.len() != 2 {
-            panic!("The $mov macro requires two arguments.")
+            self.error(String::from("The $mov macro requires two arguments"), span);
+            return;
         }
 
-        output.push(format!("LDA {}", arguments[1]));
-        output.push(format!("STA {}", arguments[0]));
+        output.push((span.clone(), format!("LDA {}", arguments[1])));
+        output.push((span.clone(), format!("STA {}", arguments[0])));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::File;
+    use std::io::Write;
+
+    use asm::Assembler;
+    use asm::Severity;
+    use asm::Span;
+    use asm::mcs6502::Assembler6502;
+    use asm::mcs6502::Preprocessor;
+
+    fn write_source(name: &str, code: &str) -> String {
+        let path = env::temp_dir().join(name);
+        let mut file = File::create(&path).unwrap();
+        file.write_all(code.as_bytes()).unwrap();
+
+        String::from(path.to_str().unwrap())
+    }
+
+    #[test]
+    fn preprocessor_diagnostics() {
+        let span = Span::new("test.a65", 3, 1);
+        let lines = vec![
+            (span.clone(), String::from("$mov a")),
+            (span.clone(), String::from("$unknown"))
+        ];
+
+        let mut prep = Preprocessor::new();
+        let output = prep.process(lines);
+        let diags = prep.diagnostics();
+
+        assert!(output.is_empty());
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].span, Some(span.clone()));
+        assert_eq!(diags[1].message, "Unknown macro: UNKNOWN");
+        assert!(prep.diagnostics().is_empty());
+
+        let lines = vec![
+            (span.clone(), String::from(".macro pair __a __b")),
+            (span.clone(), String::from("  LDA __a")),
+            (span.clone(), String::from("$pair #$01"))
+        ];
+
+        prep.process(lines);
+        let diags = prep.diagnostics();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].message, "Not enough arguments for macro PAIR: 1, expected 2");
+    }
+
+    #[test]
+    fn assembler_diagnostics() {
+        let input = write_source("dzemu_asm_diagnostics.a65",
+                                 "START:\n  LDX #$1234\n  LDA ($12), Z\nSTART:\n  BNE MISSING\n");
+
+        let mut asm = Assembler6502::new();
+        let diags = asm.assemble(&input);
+
+        assert_eq!(diags.len(), 3);
+        assert_eq!(diags[0].severity, Severity::Warning);
+        assert_eq!(diags[0].span, Some(Span::new(&input, 2, 3)));
+        assert_eq!(diags[1].span, Some(Span::new(&input, 3, 7)));
+        assert_eq!(diags[2].message, "Redefinition of label: START");
+
+        let diags = asm.link();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].span, Some(Span::new(&input, 5, 3)));
+        assert_eq!(format!("{}", diags[0]),
+                   format!("{}:5:3: error: Label not defined: MISSING", input));
+    }
+
+    #[test]
+    fn origin_and_vectors() {
+        let input = write_source("dzemu_asm_origin.a65",
+                                 ".ORG $C000\nRESET:\n  JMP LOOP\n.ALIGN 4\nLOOP:\n  BNE LOOP\n\
+                                  .RES 2 $EA\n.RESET RESET\n.NMI LOOP\n.IRQ LOOP\n");
+
+        let mut asm = Assembler6502::new();
+        assert!(asm.assemble(&input).is_empty());
+        assert!(asm.link().is_empty());
+
+        // No JMP START prefix, code is at $C000.
+        assert_eq!(asm.data.len(), 0x4000);
+        assert_eq!(&asm.data[0..3], &[0x4C, 0x04, 0xC0]);
+        assert_eq!(asm.data[3], 0x00);
+        assert_eq!(&asm.data[4..8], &[0xD0, 0xFE, 0xEA, 0xEA]);
+
+        // NMI, RESET and IRQ.
+        assert_eq!(&asm.data[0x3FFA..], &[0x04, 0xC0, 0x00, 0xC0, 0x04, 0xC0]);
+    }
+
+    #[test]
+    fn origin_diagnostics() {
+        let input = write_source("dzemu_asm_origin_diagnostics.a65",
+                                 "START:\n  NOP\n.ORG $0010\n  NOP\n.ORG $0002\n.ALIGN 0\n.RESET MISSING\n");
+
+        let mut asm = Assembler6502::new();
+        let diags = asm.assemble(&input);
+
+        // Code before .ORG keeps the jump to START.
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].message, "Origin $0002 is below the current address $0011");
+        assert_eq!(diags[1].message, "Alignment must be positive");
+        assert_eq!(asm.data[0x10], 0xEA);
+
+        let diags = asm.link();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].span, Some(Span::new(&input, 7, 1)));
+        assert_eq!(asm.data.len(), 0x10000);
+    }
+
+    #[test]
+    fn expressions() {
+        let input = write_source("dzemu_asm_expressions.a65",
+                                 ".ORG $C000\nRESET:\n  LDA #<TABLE\n  LDX #>TABLE\n  STA TABLE + 1\n\
+                                  \x20 LDA *$10+1,X\n  BNE *-2\n  BIT $1234\n  JMP FORWARD+3\n\
+                                  \x20 AND #%1010\n  CMP #'A'\nFORWARD:\nTABLE:\n\
+                                  .BYTE DATA <FORWARD, >FORWARD, 'Z', <*\n.WORD PTRS FORWARD, TABLE+2\n\
+                                  .RESET RESET+0\n");
+
+        let mut asm = Assembler6502::new();
+        assert!(asm.assemble(&input).is_empty());
+        assert!(asm.link().is_empty());
+
+        let code = vec![
+            0xA9, 0x15, 0xA2, 0xC0, 0x8D, 0x16, 0xC0,
+            0xB5, 0x11, 0xD0, 0xFC, 0x2C, 0x34, 0x12,
+            0x4C, 0x18, 0xC0, 0x29, 0x0A, 0xC9, 0x41,
+            0x15, 0xC0, 0x5A, 0x18, 0x15, 0xC0, 0x17, 0xC0
+        ];
+        assert_eq!(&asm.data[..code.len()], &code[..]);
+        assert_eq!(&asm.data[0x3FFC..0x3FFE], &[0x00, 0xC0]);
+    }
+
+    #[test]
+    fn accumulator() {
+        let input = write_source("dzemu_asm_accumulator.a65",
+                                 ".ORG $C000\nRESET:\n  ASL A\n  ROR A\nNEXT:\n  JMP NEXT\n");
+
+        let mut asm = Assembler6502::new();
+        assert!(asm.assemble(&input).is_empty());
+        assert!(asm.link().is_empty());
+
+        // Only the opcode, NEXT is at $C002.
+        assert_eq!(&asm.data[..5], &[0x0A, 0x6A, 0x4C, 0x02, 0xC0]);
+    }
+
+    #[test]
+    fn literals() {
+        let input = write_source("dzemu_asm_literals.a65",
+                                 "  lda #'a' ; load 'a'\n  LDX #';'\n  CMP #\"x\" ; ok\n\
+                                  .BYTE DATA 1 + 2, ',', (1, 2)\n.WORD PTRS 1 + 2 * 3, 'b'\n\
+                                  $string hello \"Hi, you\"\n");
+
+        let mut asm = Assembler6502::new();
+        let diags = asm.assemble(&input);
+
+        // Parentheses keep their comma.
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].span, Some(Span::new(&input, 3, 7)));
+        assert_eq!(diags[1].span, Some(Span::new(&input, 4, 24)));
+
+        let data = vec![
+            0xA9, 0x61, 0xA2, 0x3B,
+            0x03, 0x2C,
+            0x07, 0x00, 0x62, 0x00,
+            0x48, 0x69, 0x2C, 0x20, 0x79, 0x6F, 0x75, 0x00
+        ];
+        assert_eq!(&asm.data[3..], &data[..]);
+    }
+
+    #[test]
+    fn expression_diagnostics() {
+        let input = write_source("dzemu_asm_expression_diagnostics.a65",
+                                 "START:\n  LDA #MISSING+1\n  BNE *+200\n  JMP (1+2\n\
+                                  .BYTE DATA $\n  LDA #$100+1\n");
+
+        let mut asm = Assembler6502::new();
+        let diags = asm.assemble(&input);
+
+        assert_eq!(diags.len(), 4);
+        assert_eq!(diags[0].message, "Branch target at 0x5 too far: $CD");
+        assert_eq!(diags[1].span, Some(Span::new(&input, 4, 7)));
+        assert_eq!(diags[2].span, Some(Span::new(&input, 5, 12)));
+        assert_eq!(diags[3].severity, Severity::Warning);
+
+        let diags = asm.link();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].span, Some(Span::new(&input, 2, 3)));
+        assert_eq!(diags[0].message, "Label not defined: MISSING");
+    }
+
+    #[test]
+    fn listing_and_symbols() {
+        let input = write_source("dzemu_asm_listing.a65",
+                                 ".ORG $C000\nRESET:\n  LDX #$00\n\n  BNE RESET\n\
+                                  .BYTE DATA 1, 2, 3, 4, 5, 6, 7, 8, 9\n  lda #'a' ; Load\n$push #$01\n");
+
+        let mut asm = Assembler6502::new();
+        assert!(asm.assemble(&input).is_empty());
+        assert!(asm.link().is_empty());
+
+        let listing = asm.listing();
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], "C000                               .ORG $C000");
+        assert_eq!(lines[2], "C000  A2 00                          LDX #$00");
+        assert_eq!(lines[3], "C002");
+        assert_eq!(lines[4], "C002  D0 FC                          BNE RESET");
+        assert_eq!(lines[5], "C004  01 02 03 04 05 06 07 08 ...  .BYTE DATA 1, 2, 3, 4, 5, 6, 7, 8, 9");
+        assert_eq!(lines[6], "C00D  A9 61                          lda #'a' ; Load");
+        assert_eq!(lines[7], "C00F  A9 01                        $push #$01");
+        assert_eq!(lines[8], "C011  48");
+
+        assert_eq!(asm.symbols(), "al C:C000 .RESET\nal C:C004 .DATA\n");
     }
 }
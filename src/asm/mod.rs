@@ -1,10 +1,12 @@
+use std::fmt;
+
+pub mod expr;
 pub mod mcs6502;
 
 pub trait Assembler {
-    fn assemble(&mut self, &str);
-    fn link(&mut self);
+    fn assemble(&mut self, input: &str) -> Vec<Diagnostic>;
+    fn link(&mut self) -> Vec<Diagnostic>;
     fn output(&mut self, &str);
-    fn debug(&mut self, bool);
 }
 
 pub enum VariableSize {
@@ -20,3 +22,82 @@ impl VariableSize {
         }
     }
 }
+
+// Location in a source file, line and column start at 1.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Span {
+    pub file: String,
+    pub line: usize,
+    pub column: usize
+}
+
+impl Span {
+    pub fn new(file: &str, line: usize, column: usize) -> Span {
+        Span {
+            file: String::from(file),
+            line,
+            column
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning
+}
+
+// Problem found in the source, the span is missing for
+// code generated by the assembler itself.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>
+}
+
+impl Diagnostic {
+    pub fn error(message: String, span: Option<Span>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            message,
+            span
+        }
+    }
+
+    pub fn warning(message: String, span: Option<Span>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            message,
+            span
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error   => "error",
+            Severity::Warning => "warning"
+        };
+
+        match self.span {
+            Some(ref span) => write!(f, "{}: {}: {}", span, severity, self.message),
+            None           => write!(f, "{}: {}", severity, self.message)
+        }
+    }
+}
+
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.is_error())
+}
@@ -0,0 +1,106 @@
+use std::error::Error;
+use std::fmt;
+
+use cpus::Cpu;
+use cpus::CpuError;
+use cpus::mcs6502::Mcs6502;
+use mems::Memory;
+
+// Test images (e.g. the Klaus Dormann functional tests) report
+// their result by jumping to themselves, the address of the
+// trap tells which test failed or that all of them passed.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Trap {
+    pub pc: u16,
+    pub instructions: u64,
+    pub cycles: u64
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "trapped at ${:04X} after {} instructions ({} cycles)",
+               self.pc, self.instructions, self.cycles)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HarnessError {
+    Cpu(CpuError),
+
+    // No trap within the instruction limit, pc is where
+    // the cpu stopped.
+    Timeout(u16)
+}
+
+impl fmt::Display for HarnessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HarnessError::Cpu(ref error) => write!(f, "{}", error),
+            HarnessError::Timeout(pc)    => write!(f, "no trap before the limit (PC: ${:04X})", pc)
+        }
+    }
+}
+
+impl Error for HarnessError {}
+
+// Copies a flat binary to memory starting at addr, the
+// image wraps around the end of the address space.
+pub fn load_flat<M: Memory>(cpu: &mut Mcs6502<M>, image: &[u8], addr: usize) {
+    for (i, &byte) in image.iter().enumerate() {
+        cpu.memory().write_u8((addr + i) & 0xFFFF, byte);
+    }
+}
+
+// Runs from start until an instruction leaves the pc where it
+// was, i.e. JMP * or a taken branch to itself.
+pub fn run_until_trap<M: Memory>(cpu: &mut Mcs6502<M>, start: u16,
+                                 limit: u64) -> Result<Trap, HarnessError> {
+    let mut regs = cpu.registers();
+    regs.pc = start;
+    cpu.set_registers(regs);
+
+    let first = cpu.cycles();
+    for instructions in 1..=limit {
+        let pc = cpu.pc();
+        cpu.execute().map_err(HarnessError::Cpu)?;
+
+        if cpu.pc() == pc {
+            return Ok(Trap {
+                pc: pc as u16,
+                instructions,
+                cycles: cpu.cycles() - first
+            });
+        }
+    }
+
+    Err(HarnessError::Timeout(cpu.pc() as u16))
+}
+
+#[cfg(test)]
+mod tests {
+    use cpus::harness;
+    use cpus::harness::HarnessError;
+    use cpus::mcs6502::Mcs6502;
+    use inst::mcs6502::ops;
+    use mems::ram::Ram8b;
+
+    #[test]
+    fn traps() {
+        let image = vec![
+            ops::LDX_IMMEDIATE, 0x03,
+            ops::DEX_IMPLIED,
+            ops::BNE_RELATIVE, 0xFD,
+            ops::JMP_ABSOLUTE, 0x05, 0x02
+        ];
+
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+        harness::load_flat(&mut cpu, &image, 0x0200);
+
+        let trap = harness::run_until_trap(&mut cpu, 0x0200, 100).unwrap();
+        assert_eq!(trap.pc, 0x0205);
+        assert_eq!(trap.instructions, 8);
+
+        let error = harness::run_until_trap(&mut cpu, 0x0200, 4).unwrap_err();
+        assert_eq!(error, HarnessError::Timeout(0x0203));
+    }
+}
@@ -1,9 +1,23 @@
+use std::io::Write;
+
 use cpus::Cpu;
+use cpus::CpuError;
+use cpus::Fault;
 use cpus::Stack;
+use cpus::StopReason;
 use mems::Memory;
+use inst::mcs6502;
 use inst::mcs6502::ops;
 use inst::mcs6502::addr;
+use inst::mcs6502::cycles;
+use inst::mcs6502::cmos;
 use inst::mcs6502::AddressMode;
+use inst::mcs6502::ADDRESS_MODES;
+use inst::mcs6502::Variant;
+use cpus::state;
+use cpus::state::StateError;
+use cpus::state::StateReader;
+use cpus::state::StateWriter;
 
 // Start of the interrupt vector.
 pub const INT_VECTOR_START:   usize = 0xFFFA;
@@ -29,16 +43,71 @@ pub const STACK_BASE_ADDRESS: usize = 0x0100;
 // Starting address of the stack (lower byte).
 pub const STACK_START_VALUE:  u8 = 0xFF;
 
+// Stack pointer after power on, the reset sequence
+// leaves it at $FD.
+pub const STACK_POWER_ON_VALUE: u8 = 0x00;
+
+// Cycles taken by the reset sequence, counted before
+// the first instruction.
+pub const RESET_CYCLES: u64 = 7;
+
 // Processor status register fields.
-// 5 is expansion bit.
+// B and the expansion bit only exist in
+// the copies pushed to the stack.
 pub const STS_CAR_MASK:    u8 = 1 << 0;
 pub const STS_ZER_MASK:    u8 = 1 << 1;
 pub const STS_INT_MASK:    u8 = 1 << 2;
 pub const STS_DEC_MASK:    u8 = 1 << 3;
 pub const STS_BRK_MASK:    u8 = 1 << 4;
+pub const STS_EXP_MASK:    u8 = 1 << 5;
 pub const STS_OVF_MASK:    u8 = 1 << 6;
 pub const STS_NEG_MASK:    u8 = 1 << 7;
 
+// Flag semantics of ADC and SBC in decimal mode.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DecimalMode {
+    // Original NMOS 6502, N, V and Z are based on
+    // intermediate or binary results.
+    Nmos,
+
+    // 65C02, N and Z reflect the decimal result at
+    // the cost of an extra cycle.
+    Cmos,
+
+    // Ricoh 2A03 of the NES, D can be set but the
+    // arithmetic stays binary.
+    Disabled
+}
+
+// Handling of the undocumented instructions whose
+// results differ between individual chips.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UnstableOpcodes {
+    // Use the most common behaviour.
+    Emulate,
+
+    // Skip the instruction.
+    Nop,
+
+    // Report a fault.
+    Trap
+}
+
+// Value ORed with the accumulator by the unstable ANE and
+// immediate LAX instructions, depends on the chip and temperature.
+pub const UNSTABLE_MAGIC: u8 = 0xEE;
+
+// Programmer visible registers, used by debuggers.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Registers {
+    pub pc: u16,
+    pub sp: u8,
+    pub accu: u8,
+    pub idx_x: u8,
+    pub idx_y: u8,
+    pub status: u8
+}
+
 pub struct Mcs6502<M: Memory> {
     ram: M,
     pc: usize,
@@ -48,7 +117,37 @@ pub struct Mcs6502<M: Memory> {
     accu: u8,
     addr_mode: AddressMode,
     status: u8,
-    rom_end: usize
+    rom_start: usize,
+    cycles: u64,
+    instructions: u64,
+    decimal: DecimalMode,
+    unstable: UnstableOpcodes,
+    variant: Variant,
+    waiting: bool,
+    halt: Option<StopReason>,
+    fault: Option<Fault>,
+    stack_checks: bool,
+
+    // IRQ is level triggered and active while any of the
+    // sources asserts it, NMI is triggered by the edge.
+    irq_lines: u32,
+    nmi_line: bool,
+    nmi_pending: bool,
+
+    // Interrupts sampled at the end of the last instruction,
+    // serviced before the next one.
+    irq_poll: bool,
+    nmi_poll: bool,
+
+    // Halt conditions configured by the host.
+    jam_halts: bool,
+    brk_trap: bool,
+    self_loop_halts: bool,
+    cycle_limit: Option<u64>,
+    instruction_limit: Option<u64>,
+
+    // Receives a line per executed instruction.
+    trace: Option<Box<dyn Write>>
 }
 
 impl<M: Memory> Cpu<M> for Mcs6502<M> {
@@ -57,31 +156,302 @@ impl<M: Memory> Cpu<M> for Mcs6502<M> {
     }
 
     fn boot(&mut self, cart : &Memory) {
-        self.restart();
+        self.ram.map(self.rom_start, cart);
 
-        self.ram.map(ROM_MAP_ADDRESS, cart);
-        self.rom_end = ROM_MAP_ADDRESS + cart.size();
+        // The cart can contain the reset vector.
+        self.restart();
     }
 
     fn restart(&mut self) {
-        self.accu = 0;
-        self.idx_x = 0;
-        self.idx_y = 0;
-
+        // Reset keeps A, X and Y, the sequence performs the
+        // pushes of an interrupt without writing to memory.
         self.pc = self.ram.read_u16(PC_INIT_ADDRESS) as usize;
-        self.sp = STACK_START_VALUE - 2;
+        self.sp = self.sp.wrapping_sub(3);
+        self.cycles = RESET_CYCLES;
+        self.instructions = 0;
+        self.waiting = false;
+        self.halt = None;
+        self.fault = None;
+        self.nmi_pending = false;
+        self.irq_poll = false;
+        self.nmi_poll = false;
 
         // Last instruction of the init sequence of a rom
         // should be CLI.
         self.set_flag(true, STS_INT_MASK);
+        if self.variant == Variant::Cmos {
+            self.set_flag(false, STS_DEC_MASK);
+        }
+    }
+
+    fn execute(&mut self) -> Result<(), CpuError> {
+        // A halted cpu does nothing until resumed or reset.
+        if self.halt.is_some() {
+            return Ok(());
+        }
+
+        if self.cycle_limit.is_some_and(|limit| self.cycles >= limit) {
+            self.halt = Some(StopReason::CycleLimit);
+            return Ok(());
+        }
+
+        if self.instruction_limit.is_some_and(|limit| self.instructions >= limit) {
+            self.halt = Some(StopReason::InstructionLimit);
+            return Ok(());
+        }
+
+        // Ignore faults caused by the host between instructions.
+        self.ram.fault();
+
+        // WAI resumes on an interrupt even if it's masked.
+        if self.waiting && (self.irq_lines != 0 || self.nmi_pending) {
+            self.waiting = false;
+            self.sample_interrupts(self.get_flag(STS_INT_MASK));
+        }
+
+        let pc = self.pc;
+        let masked = self.get_flag(STS_INT_MASK);
+
+        let opcode = if self.nmi_poll || self.irq_poll {
+            // The first instruction of the handler always runs.
+            if self.nmi_poll {
+                self.nmi_pending = false;
+                self.interrupt_sequence(INT_NOMASK_ADDRESS, false);
+            } else {
+                self.interrupt_sequence(INT_REQ_ADDRESS, false);
+            }
+
+            self.cycles += 7;
+            self.irq_poll = false;
+            self.nmi_poll = false;
+            ops::BRK_IMPLIED
+        } else {
+            self.ram.set_pc(pc);
+            self.write_trace();
+
+            let opcode = self.ram.read_u8(pc);
+            if let Some(reason) = self.halts_on(opcode) {
+                self.halt = Some(reason);
+                return Ok(());
+            }
+
+            self.execute_opcode(opcode);
+            self.instructions += 1;
+
+            // WAI legitimately keeps the pc in place.
+            if self.self_loop_halts && self.pc == pc && !self.waiting && self.halt.is_none() {
+                self.halt = Some(StopReason::SelfLoop(pc));
+            }
+
+            // CLI, SEI and PLP change I after the interrupts are
+            // sampled, RTI before it.
+            if opcode == ops::RTI_IMPLIED {
+                self.sample_interrupts(self.get_flag(STS_INT_MASK));
+            } else {
+                self.sample_interrupts(masked);
+            }
+            opcode
+        };
+
+        // Faults leave the pc at the faulting instruction.
+        let memory = self.ram.fault();
+        match self.fault.take().or(memory.map(Fault::Memory)) {
+            Some(fault) => {
+                self.pc = pc;
+                Err(CpuError { pc, opcode, fault })
+            }
+
+            None        => Ok(())
+        }
+    }
+
+    fn run(&mut self, count: usize) -> Result<(), CpuError> {
+        for _ in 0..count {
+            self.execute()?;
+        }
+
+        Ok(())
+    }
+
+    fn run_cycles(&mut self, count: u64) -> Result<(), CpuError> {
+        // Instructions are atomic, so the last one can
+        // overshoot the requested amount of cycles. A halted
+        // cpu doesn't advance, so stop there.
+        let target = self.cycles + count;
+        while self.cycles < target && self.halt.is_none() {
+            self.execute()?;
+        }
+
+        Ok(())
+    }
+
+    fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    fn dump(&self) {
+        let top = STACK_BASE_ADDRESS + STACK_START_VALUE as usize;
+        println!("CPU STATE:");
+        println!("|         PC: 0x{:X}", self.pc());
+        println!("|         SP: 0x{:X}", self.sp());
+        println!("|          X: 0x{:X}", self.idx_x);
+        println!("|          Y: 0x{:X}", self.idx_y);
+        println!("|       ACCU: 0x{:X}", self.accu);
+        println!("|     STATUS: 0b{:b}", self.status);
+        println!("|       ADDR: {:?}", self.addr_mode);
+        println!("|     CYCLES: {}", self.cycles);
+        println!("| STACK BASE: 0x{:X}", STACK_BASE_ADDRESS);
+        println!("| STACK  TOP: 0x{:X}", top);
+
+        println!("CPU STACK:");
+        if top == self.sp() {
+            println!("| EMPTY");
+        } else {
+            let mut i = top;
+            while i > self.sp() {
+                println!("| [{:X}]: {:X}", i, self.ram.read_u8(i));
+                i = i.wrapping_sub(1);
+            }
+        }
+    }
+
+    fn pc(&self) -> usize {
+        // Returns the addr relative to the start of
+        // the rom mapping block.
+        self.pc - ROM_MAP_ADDRESS
+    }
+
+    fn running(&self) -> bool {
+        self.halt.is_none()
+    }
+
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.halt
+    }
+}
+
+impl<M: Memory> Stack for Mcs6502<M> {
+    fn sp_dec(&mut self) {
+        if self.stack_checks && self.sp == 0x00 {
+            self.set_fault(Fault::StackOverflow);
+        }
+
+        self.sp = self.sp.wrapping_sub(1);
+    }
+
+    fn sp_inc(&mut self) {
+        if self.stack_checks && self.sp == 0xFF {
+            self.set_fault(Fault::StackUnderflow);
+        }
+
+        self.sp = self.sp.wrapping_add(1);
+    }
+
+    fn push_u8(&mut self, data: u8) {
+        let sp = self.sp();
+        self.ram.write_u8(sp, data);
+        self.sp_dec();
+    }
+
+    // The high byte goes first, so the word is little endian
+    // in memory and wraps within the stack page.
+    fn push_u16(&mut self, data: u16) {
+        self.push_u8((data >> 8) as u8);
+        self.push_u8(data as u8);
+    }
+
+    fn pop_u8(&mut self) -> u8 {
+        self.sp_inc();
+        self.ram.read_u8(self.sp())
+    }
+
+    fn pop_u16(&mut self) -> u16 {
+        let lo = self.pop_u8() as u16;
+        let hi = self.pop_u8() as u16;
+        (hi << 8) | lo
+    }
+
+    fn sp(&self) -> usize {
+        (self.sp as usize) + STACK_BASE_ADDRESS
+    }
+}
+
+impl<M: Memory> Mcs6502<M> {
+    pub fn new(ram: M) -> Mcs6502<M> {
+        Mcs6502 {
+            ram,
+            pc: 0,
+            sp: STACK_POWER_ON_VALUE,
+            idx_x: 0u8,
+            idx_y: 0u8,
+            accu: 0u8,
+            addr_mode: AddressMode::None,
+            status: 0u8,
+            rom_start: ROM_MAP_ADDRESS,
+            cycles: 0,
+            instructions: 0,
+            decimal: DecimalMode::Nmos,
+            unstable: UnstableOpcodes::Emulate,
+            variant: Variant::Nmos,
+            waiting: false,
+            halt: None,
+            fault: None,
+            stack_checks: false,
+            irq_lines: 0,
+            nmi_line: false,
+            nmi_pending: false,
+            irq_poll: false,
+            nmi_poll: false,
+            jam_halts: false,
+            brk_trap: false,
+            self_loop_halts: false,
+            cycle_limit: None,
+            instruction_limit: None,
+            trace: None
+        }
     }
 
-    fn execute(&mut self) {
-        let opcode = self.ram.read_u8(self.pc as usize);
-        self.addr_mode = addr::get_addr_mode(opcode);
-        let operand = self.get_operand();
+    fn execute_opcode(&mut self, opcode: u8) {
+        // WAI halts the cpu until an interrupt, the
+        // clock keeps running.
+        if self.waiting {
+            self.cycles += 1;
+            return;
+        }
+
+        self.addr_mode = match self.variant {
+            Variant::Nmos => addr::get_addr_mode(opcode),
+            Variant::Cmos => cmos::get_addr_mode(opcode)
+        };
+
+        // Stores and jumps don't read their target, which
+        // matters for I/O registers.
+        let operand = if self.reads_operand(opcode) {
+            self.get_operand()
+        } else {
+            0
+        };
+
+        let (count, penalty) = match self.variant {
+            Variant::Nmos => {
+                (cycles::base_count(opcode), cycles::has_page_penalty(opcode))
+            }
+
+            Variant::Cmos => {
+                (cmos::base_count(opcode), cmos::has_page_penalty(opcode))
+            }
+        };
+
+        self.cycles += count as u64;
+        if penalty && self.page_crossed() {
+            self.cycles += 1;
+        }
 
         match opcode {
+            op if self.variant == Variant::Cmos && cmos::redefines(op) => {
+                self.execute_cmos(op, operand)
+            }
+
             ops::ADC_IMMEDIATE   |
             ops::ADC_ZERO_PAGE   |
             ops::ADC_ZERO_PAGE_X |
@@ -211,7 +581,34 @@ impl<M: Memory> Cpu<M> for Mcs6502<M> {
             ops::LSR_ABSOLUTE    |
             ops::LSR_ABSOLUTE_X  => self.op_lsr(operand),
 
-            ops::NOP_IMPLIED     => self.op_nop(),
+            ops::NOP_IMPLIED     |
+            ops::NOP_IMPLIED_1A  |
+            ops::NOP_IMPLIED_3A  |
+            ops::NOP_IMPLIED_5A  |
+            ops::NOP_IMPLIED_7A  |
+            ops::NOP_IMPLIED_DA  |
+            ops::NOP_IMPLIED_FA  |
+            ops::NOP_IMMEDIATE_80 |
+            ops::NOP_IMMEDIATE_82 |
+            ops::NOP_IMMEDIATE_89 |
+            ops::NOP_IMMEDIATE_C2 |
+            ops::NOP_IMMEDIATE_E2 |
+            ops::NOP_ZERO_PAGE_04 |
+            ops::NOP_ZERO_PAGE_44 |
+            ops::NOP_ZERO_PAGE_64 |
+            ops::NOP_ZERO_PAGE_X_14 |
+            ops::NOP_ZERO_PAGE_X_34 |
+            ops::NOP_ZERO_PAGE_X_54 |
+            ops::NOP_ZERO_PAGE_X_74 |
+            ops::NOP_ZERO_PAGE_X_D4 |
+            ops::NOP_ZERO_PAGE_X_F4 |
+            ops::NOP_ABSOLUTE_0C |
+            ops::NOP_ABSOLUTE_X_1C |
+            ops::NOP_ABSOLUTE_X_3C |
+            ops::NOP_ABSOLUTE_X_5C |
+            ops::NOP_ABSOLUTE_X_7C |
+            ops::NOP_ABSOLUTE_X_DC |
+            ops::NOP_ABSOLUTE_X_FC => self.op_nop(),
 
             ops::ORA_IMMEDIATE   |
             ops::ORA_ZERO_PAGE   |
@@ -253,7 +650,8 @@ impl<M: Memory> Cpu<M> for Mcs6502<M> {
             ops::SBC_ABSOLUTE_X  |
             ops::SBC_ABSOLUTE_Y  |
             ops::SBC_INDIRECT_X  |
-            ops::SBC_INDIRECT_Y  => self.op_sbc(operand),
+            ops::SBC_INDIRECT_Y  |
+            ops::SBC_IMMEDIATE_EB => self.op_sbc(operand),
 
             ops::SEC_IMPLIED     => self.op_sec(),
 
@@ -289,109 +687,299 @@ impl<M: Memory> Cpu<M> for Mcs6502<M> {
 
             ops::TXS_IMPLIED     => self.op_txs(),
 
-            ops::custom::TOS_ABSOLUTE => self.op_tos(),
-
-            ops::custom::PRT_ABSOLUTE => self.op_prt(),
-
-            op => panic!("Unknown opcode: 0x{:X}", op)
+            ops::SLO_ZERO_PAGE   |
+            ops::SLO_ZERO_PAGE_X |
+            ops::SLO_ABSOLUTE    |
+            ops::SLO_ABSOLUTE_X  |
+            ops::SLO_ABSOLUTE_Y  |
+            ops::SLO_INDIRECT_X  |
+            ops::SLO_INDIRECT_Y  => self.op_slo(operand),
+
+            ops::RLA_ZERO_PAGE   |
+            ops::RLA_ZERO_PAGE_X |
+            ops::RLA_ABSOLUTE    |
+            ops::RLA_ABSOLUTE_X  |
+            ops::RLA_ABSOLUTE_Y  |
+            ops::RLA_INDIRECT_X  |
+            ops::RLA_INDIRECT_Y  => self.op_rla(operand),
+
+            ops::SRE_ZERO_PAGE   |
+            ops::SRE_ZERO_PAGE_X |
+            ops::SRE_ABSOLUTE    |
+            ops::SRE_ABSOLUTE_X  |
+            ops::SRE_ABSOLUTE_Y  |
+            ops::SRE_INDIRECT_X  |
+            ops::SRE_INDIRECT_Y  => self.op_sre(operand),
+
+            ops::RRA_ZERO_PAGE   |
+            ops::RRA_ZERO_PAGE_X |
+            ops::RRA_ABSOLUTE    |
+            ops::RRA_ABSOLUTE_X  |
+            ops::RRA_ABSOLUTE_Y  |
+            ops::RRA_INDIRECT_X  |
+            ops::RRA_INDIRECT_Y  => self.op_rra(operand),
+
+            ops::SAX_ZERO_PAGE   |
+            ops::SAX_ZERO_PAGE_Y |
+            ops::SAX_ABSOLUTE    |
+            ops::SAX_INDIRECT_X  => self.op_sax(),
+
+            ops::LAX_ZERO_PAGE   |
+            ops::LAX_ZERO_PAGE_Y |
+            ops::LAX_ABSOLUTE    |
+            ops::LAX_ABSOLUTE_Y  |
+            ops::LAX_INDIRECT_X  |
+            ops::LAX_INDIRECT_Y  => self.op_lax(operand),
+
+            ops::DCP_ZERO_PAGE   |
+            ops::DCP_ZERO_PAGE_X |
+            ops::DCP_ABSOLUTE    |
+            ops::DCP_ABSOLUTE_X  |
+            ops::DCP_ABSOLUTE_Y  |
+            ops::DCP_INDIRECT_X  |
+            ops::DCP_INDIRECT_Y  => self.op_dcp(operand),
+
+            ops::ISC_ZERO_PAGE   |
+            ops::ISC_ZERO_PAGE_X |
+            ops::ISC_ABSOLUTE    |
+            ops::ISC_ABSOLUTE_X  |
+            ops::ISC_ABSOLUTE_Y  |
+            ops::ISC_INDIRECT_X  |
+            ops::ISC_INDIRECT_Y  => self.op_isc(operand),
+
+            ops::ANC_IMMEDIATE_0B |
+            ops::ANC_IMMEDIATE_2B => self.op_anc(operand),
+
+            ops::ALR_IMMEDIATE   => self.op_alr(operand),
+
+            ops::ARR_IMMEDIATE   => self.op_arr(operand),
+
+            ops::SBX_IMMEDIATE   => self.op_sbx(operand),
+
+            ops::LAS_ABSOLUTE_Y  => self.op_las(operand),
+
+            ops::LAX_IMMEDIATE   => if self.unstable() {
+                self.op_lax((self.accu | UNSTABLE_MAGIC) & operand)
+            },
+
+            ops::ANE_IMMEDIATE   => if self.unstable() {
+                self.op_ane(operand)
+            },
+
+            ops::SHA_ABSOLUTE_Y  |
+            ops::SHA_INDIRECT_Y  => if self.unstable() {
+                self.op_sha()
+            },
+
+            ops::SHX_ABSOLUTE_Y  => if self.unstable() {
+                self.op_shx()
+            },
+
+            ops::SHY_ABSOLUTE_X  => if self.unstable() {
+                self.op_shy()
+            },
+
+            ops::TAS_ABSOLUTE_Y  => if self.unstable() {
+                self.op_tas()
+            },
+
+            _ => self.set_fault(Fault::IllegalOpcode)
         }
 
         self.pc = self.pc.wrapping_add(addr::pc_offset(&self.addr_mode));
     }
 
-    fn run(&mut self, count: usize) {
-        for _ in 0..count {
-            self.execute();
+    pub fn registers(&self) -> Registers {
+        Registers {
+            pc: self.pc as u16,
+            sp: self.sp,
+            accu: self.accu,
+            idx_x: self.idx_x,
+            idx_y: self.idx_y,
+            status: self.status
         }
     }
 
-    fn dump(&self) {
-        let top = STACK_BASE_ADDRESS + STACK_START_VALUE as usize;
-        println!("CPU STATE:");
-        println!("|         PC: 0x{:X}", self.pc());
-        println!("|         SP: 0x{:X}", self.sp());
-        println!("|          X: 0x{:X}", self.idx_x);
-        println!("|          Y: 0x{:X}", self.idx_y);
-        println!("|       ACCU: 0x{:X}", self.accu);
-        println!("|     STATUS: 0b{:b}", self.status);
-        println!("|       ADDR: {:?}", self.addr_mode);
-        println!("| STACK BASE: 0x{:X}", STACK_BASE_ADDRESS);
-        println!("| STACK  TOP: 0x{:X}", top);
-
-        println!("CPU STACK:");
-        if top == self.sp() {
-            println!("| EMPTY");
-        } else {
-            let mut i = top;
-            while i > self.sp() {
-                println!("| [{:X}]: {:X}", i, self.ram.read_u8(i));
-                i = i.wrapping_sub(1);
-            }
-        }
+    pub fn set_registers(&mut self, regs: Registers) {
+        self.pc = regs.pc as usize;
+        self.sp = regs.sp;
+        self.accu = regs.accu;
+        self.idx_x = regs.idx_x;
+        self.idx_y = regs.idx_y;
+        self.status = regs.status;
     }
 
-    fn pc(&self) -> usize {
-        // Returns the addr relative to the start of
-        // the rom mapping block.
-        self.pc - ROM_MAP_ADDRESS
+    pub fn variant(&self) -> Variant {
+        self.variant
     }
 
-    fn running(&self) -> bool {
-        self.pc_valid()
+    // Traces are written before each instruction in the
+    // nestest.log layout, None turns tracing off.
+    pub fn set_trace(&mut self, trace: Option<Box<dyn Write>>) {
+        self.trace = trace;
     }
-}
 
-impl<M: Memory> Stack for Mcs6502<M> {
-    fn sp_dec(&mut self) {
-        self.sp = self.sp.wrapping_sub(1);
-    }
+    // E.g. C000  A9 42     LDA #$42  ...  A:00 X:00 Y:00 P:24 SP:FD CYC:7
+    // P shows the expansion bit set and B clear, as the
+    // reference logs do.
+    pub fn trace_line(&self) -> String {
+        let mut idx = self.pc;
+        let inst = mcs6502::op_to_str_variant(&self.ram, &mut idx, self.variant);
 
-    fn sp_inc(&mut self) {
-        self.sp = self.sp.wrapping_add(1);
-    }
+        let bytes: Vec<String> = (self.pc..idx)
+            .map(|addr| format!("{:02X}", self.ram.peek(addr & 0xFFFF)))
+            .collect();
 
-    fn push_u8(&mut self, data: u8) {
-        let sp = self.sp();
-        self.ram.write_u8(sp, data);
-        self.sp_dec();
+        format!("{:04X}  {:<8}  {:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+                self.pc, bytes.join(" "), inst, self.accu, self.idx_x, self.idx_y,
+                (self.status | STS_EXP_MASK) & !STS_BRK_MASK, self.sp, self.cycles)
     }
 
-    fn push_u16(&mut self, data: u16) {
-        let sp = self.sp();
-        self.ram.write_u16(sp, data);
-        self.sp_dec();
-        self.sp_dec();
-    }
+    fn write_trace(&mut self) {
+        if self.trace.is_none() {
+            return;
+        }
 
-    fn pop_u8(&mut self) -> u8 {
-        self.sp_inc();
-        self.ram.read_u8(self.sp())
+        // A failing trace is turned off rather than
+        // stopping the cpu.
+        let line = self.trace_line();
+        if let Some(ref mut trace) = self.trace {
+            if writeln!(trace, "{}", line).is_err() {
+                self.trace = None;
+            }
+        }
     }
 
-    fn pop_u16(&mut self) -> u16 {
-        self.sp_inc();
-        self.sp_inc();
-        self.ram.read_u16(self.sp())
-    }
+    // Registers, configuration and the whole memory, see
+    // cpus::state for the header. Memory is peeked and only
+    // RAM is restored, the state of devices is not included.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new(state::MACHINE_MCS6502);
+
+        writer.u16(self.pc as u16);
+        writer.u8(self.sp);
+        writer.u8(self.accu);
+        writer.u8(self.idx_x);
+        writer.u8(self.idx_y);
+        writer.u8(self.status);
+        writer.u8(self.addr_mode as u8);
+        writer.u32(self.rom_start as u32);
+        writer.u64(self.cycles);
+        writer.u64(self.instructions);
+        writer.u8(self.variant as u8);
+        writer.u8(self.decimal as u8);
+        writer.u8(self.unstable as u8);
+        writer.bool(self.waiting);
+        write_halt(&mut writer, self.halt);
+        writer.bool(self.stack_checks);
+        writer.u32(self.irq_lines);
+        writer.bool(self.nmi_line);
+        writer.bool(self.nmi_pending);
+        writer.bool(self.irq_poll);
+        writer.bool(self.nmi_poll);
+        writer.bool(self.jam_halts);
+        writer.bool(self.brk_trap);
+        writer.bool(self.self_loop_halts);
+        write_limit(&mut writer, self.cycle_limit);
+        write_limit(&mut writer, self.instruction_limit);
+
+        let size = self.ram.size();
+        writer.u32(size as u32);
+        for addr in 0..size {
+            writer.u8(self.ram.peek(addr));
+        }
 
-    fn sp(&self) -> usize {
-        (self.sp as usize) + STACK_BASE_ADDRESS
-    }
-}
+        writer.finish()
+    }
+
+    // The cpu is left untouched if the state is invalid.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut reader = StateReader::new(data, state::MACHINE_MCS6502)?;
+
+        let pc = reader.u16()? as usize;
+        let sp = reader.u8()?;
+        let accu = reader.u8()?;
+        let idx_x = reader.u8()?;
+        let idx_y = reader.u8()?;
+        let status = reader.u8()?;
+        let addr_mode = match ADDRESS_MODES.get(reader.u8()? as usize) {
+            Some(&mode) => mode,
+            None        => return Err(StateError::Invalid("address mode"))
+        };
+        let rom_start = reader.u32()? as usize;
+        let cycles = reader.u64()?;
+        let instructions = reader.u64()?;
+        let variant = match reader.u8()? {
+            0 => Variant::Nmos,
+            1 => Variant::Cmos,
+            _ => return Err(StateError::Invalid("variant"))
+        };
+        let decimal = match reader.u8()? {
+            0 => DecimalMode::Nmos,
+            1 => DecimalMode::Cmos,
+            2 => DecimalMode::Disabled,
+            _ => return Err(StateError::Invalid("decimal mode"))
+        };
+        let unstable = match reader.u8()? {
+            0 => UnstableOpcodes::Emulate,
+            1 => UnstableOpcodes::Nop,
+            2 => UnstableOpcodes::Trap,
+            _ => return Err(StateError::Invalid("unstable opcodes"))
+        };
+        let waiting = reader.bool()?;
+        let halt = read_halt(&mut reader)?;
+        let stack_checks = reader.bool()?;
+        let irq_lines = reader.u32()?;
+        let nmi_line = reader.bool()?;
+        let nmi_pending = reader.bool()?;
+        let irq_poll = reader.bool()?;
+        let nmi_poll = reader.bool()?;
+        let jam_halts = reader.bool()?;
+        let brk_trap = reader.bool()?;
+        let self_loop_halts = reader.bool()?;
+        let cycle_limit = read_limit(&mut reader)?;
+        let instruction_limit = read_limit(&mut reader)?;
+
+        let size = reader.u32()? as usize;
+        if size != self.ram.size() {
+            return Err(StateError::Invalid("memory size"));
+        }
+        let memory = reader.bytes(size)?;
+        reader.finish()?;
 
-impl<M: Memory> Mcs6502<M> {
-    pub fn new(ram: M) -> Mcs6502<M> {
-        Mcs6502 {
-            ram,
-            pc: 0,
-            sp: STACK_START_VALUE,
-            idx_x: 0u8,
-            idx_y: 0u8,
-            accu: 0u8,
-            addr_mode: AddressMode::None,
-            status: 0u8,
-            rom_end: 0
+        for (addr, &value) in memory.iter().enumerate() {
+            self.ram.poke(addr, value);
         }
+
+        self.pc = pc;
+        self.sp = sp;
+        self.accu = accu;
+        self.idx_x = idx_x;
+        self.idx_y = idx_y;
+        self.status = status;
+        self.addr_mode = addr_mode;
+        self.rom_start = rom_start;
+        self.cycles = cycles;
+        self.instructions = instructions;
+        self.variant = variant;
+        self.decimal = decimal;
+        self.unstable = unstable;
+        self.waiting = waiting;
+        self.halt = halt;
+        self.stack_checks = stack_checks;
+        self.irq_lines = irq_lines;
+        self.nmi_line = nmi_line;
+        self.nmi_pending = nmi_pending;
+        self.irq_poll = irq_poll;
+        self.nmi_poll = nmi_poll;
+        self.jam_halts = jam_halts;
+        self.brk_trap = brk_trap;
+        self.self_loop_halts = self_loop_halts;
+        self.cycle_limit = cycle_limit;
+        self.instruction_limit = instruction_limit;
+        self.fault = None;
+
+        Ok(())
     }
 
     pub fn set_init_pc_value(&mut self, addr: u16) {
@@ -406,58 +994,202 @@ impl<M: Memory> Mcs6502<M> {
         self.ram.write_u16(INT_NOMASK_ADDRESS, addr);
     }
 
-    fn pc_valid(&self) -> bool {
-        self.pc >= ROM_MAP_ADDRESS && self.pc < self.rom_end
+    // Where boot maps the rom, e.g. $C000 for images assembled
+    // with .ORG $C000.
+    pub fn set_rom_map_address(&mut self, addr: usize) {
+        self.rom_start = addr;
     }
 
-    pub fn interrupt(&mut self) {
-        if !self.get_flag(STS_INT_MASK) {
-            let pc = self.pc as u16;
-            let status = self.status;
-
-            self.push_u16(pc);
-            self.push_u8(status);
-            self.set_flag(false, STS_BRK_MASK);
-            self.set_flag(true, STS_INT_MASK);
-            self.pc = self.ram.read_u16(INT_REQ_ADDRESS) as usize;
-        }
+    pub fn set_decimal_mode(&mut self, mode: DecimalMode) {
+        self.decimal = mode;
     }
 
-    pub fn non_maskable_interrupt(&mut self) {
-        let pc = self.pc as u16;
-        let status = self.status;
+    pub fn set_unstable_opcodes(&mut self, policy: UnstableOpcodes) {
+        self.unstable = policy;
+    }
 
-        self.push_u16(pc);
-        self.push_u8(status);
-        self.set_flag(false, STS_BRK_MASK);
-        self.set_flag(true, STS_INT_MASK);
-        self.pc = self.ram.read_u16(INT_NOMASK_ADDRESS) as usize;
+    // Report stack pointer wrap arounds as faults.
+    pub fn set_stack_checks(&mut self, enabled: bool) {
+        self.stack_checks = enabled;
     }
 
-    fn get_operand(&self) -> u8 {
-        match self.addr_mode {
-            AddressMode::Relative    |
-            AddressMode::Immediate   => {
-                self.ram.read_u8(self.pc + 1)
-            }
+    // Also selects the decimal mode flag semantics of the variant.
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+        self.decimal = match variant {
+            Variant::Nmos => DecimalMode::Nmos,
+            Variant::Cmos => DecimalMode::Cmos
+        };
+    }
 
-            AddressMode::ZeroPage    => {
-                let addr = self.ram.read_u8(self.pc + 1) as usize;
-                self.ram.read_u8(addr)
-            }
+    // JAM opcodes halt the NMOS cpu instead of faulting.
+    pub fn set_jam_halts(&mut self, enabled: bool) {
+        self.jam_halts = enabled;
+    }
 
-            AddressMode::ZeroPageX   => {
-                let mut addr = self.ram.read_u8(self.pc + 1) as usize;
-                addr = addr.wrapping_add(self.idx_x as usize);
+    // BRK returns to the host instead of entering the handler.
+    pub fn set_brk_trap(&mut self, enabled: bool) {
+        self.brk_trap = enabled;
+    }
 
-                self.ram.read_u8(addr)
-            }
+    // Halt on an instruction that jumps or branches to itself.
+    pub fn set_self_loop_halts(&mut self, enabled: bool) {
+        self.self_loop_halts = enabled;
+    }
 
-            AddressMode::ZeroPageY   => {
-                let mut addr = self.ram.read_u8(self.pc + 1) as usize;
-                addr = addr.wrapping_add(self.idx_y as usize);
+    // Both limits count from the last reset.
+    pub fn set_cycle_limit(&mut self, limit: Option<u64>) {
+        self.cycle_limit = limit;
+    }
 
-                self.ram.read_u8(addr)
+    pub fn set_instruction_limit(&mut self, limit: Option<u64>) {
+        self.instruction_limit = limit;
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    // Continues after a halt requested by the host, the cpu
+    // needs a reset after JAM and STP.
+    pub fn resume(&mut self) {
+        match self.halt {
+            Some(StopReason::Jam(_)) | Some(StopReason::Stop(_)) => {}
+            _                                                    => self.halt = None
+        }
+    }
+
+    // Host configured halts checked before the instruction runs.
+    fn halts_on(&self, opcode: u8) -> Option<StopReason> {
+        if self.brk_trap && opcode == ops::BRK_IMPLIED {
+            Some(StopReason::Break(self.pc))
+        } else if self.jam_halts && self.variant == Variant::Nmos && mcs6502::is_jam(opcode) {
+            Some(StopReason::Jam(self.pc))
+        } else {
+            None
+        }
+    }
+
+    // Enters the IRQ handler right away unless it's masked,
+    // devices should use set_irq instead.
+    pub fn interrupt(&mut self) {
+        // WAI resumes even when the interrupt is masked.
+        self.waiting = false;
+
+        if !self.get_flag(STS_INT_MASK) {
+            self.interrupt_sequence(INT_REQ_ADDRESS, false);
+            self.cycles += 7;
+        }
+    }
+
+    // Enters the NMI handler right away, devices should
+    // use set_nmi instead.
+    pub fn non_maskable_interrupt(&mut self) {
+        self.waiting = false;
+        self.nmi_pending = false;
+
+        self.interrupt_sequence(INT_NOMASK_ADDRESS, false);
+        self.cycles += 7;
+    }
+
+    // Keeps the cpu off the bus for the cycles, e.g. while
+    // a DMA controller copies memory.
+    pub fn stall(&mut self, cycles: u64) {
+        self.cycles += cycles;
+    }
+
+    // Asserts or releases the IRQ line for one of 32 sources.
+    pub fn set_irq(&mut self, source: u8, active: bool) {
+        let mask = 1u32 << source;
+
+        if active {
+            self.irq_lines |= mask;
+        } else {
+            self.irq_lines &= !mask;
+        }
+    }
+
+    pub fn irq_active(&self) -> bool {
+        self.irq_lines != 0
+    }
+
+    // The NMI is taken once when the line becomes active.
+    pub fn set_nmi(&mut self, active: bool) {
+        if active && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+
+        self.nmi_line = active;
+    }
+
+    // Interrupts are polled at the end of an instruction, so
+    // a line asserted between instructions is serviced after
+    // the next one.
+    fn sample_interrupts(&mut self, masked: bool) {
+        self.nmi_poll = self.nmi_pending;
+        self.irq_poll = self.irq_lines != 0 && !masked;
+    }
+
+    // Pushes the pc and the status and jumps to the handler,
+    // B is only set in the status pushed by BRK.
+    fn interrupt_sequence(&mut self, vector: usize, brk: bool) {
+        let pc = self.pc as u16;
+        self.push_u16(pc);
+
+        let mut status = self.status | STS_EXP_MASK;
+        if brk {
+            status |= STS_BRK_MASK;
+        } else {
+            status &= !STS_BRK_MASK;
+        }
+        self.push_u8(status);
+
+        self.set_flag(true, STS_INT_MASK);
+        self.clear_decimal_on_interrupt();
+
+        // An NMI raised during the sequence hijacks the vector
+        // fetch, on NMOS even the one of BRK, which is lost.
+        let hijack = self.nmi_pending && vector == INT_REQ_ADDRESS
+            && (!brk || self.variant == Variant::Nmos);
+
+        let vector = if hijack {
+            self.nmi_pending = false;
+            INT_NOMASK_ADDRESS
+        } else {
+            vector
+        };
+
+        self.pc = self.ram.read_u16(vector) as usize;
+    }
+
+    // The 65C02 clears decimal mode when entering an interrupt handler.
+    fn clear_decimal_on_interrupt(&mut self) {
+        if self.variant == Variant::Cmos {
+            self.set_flag(false, STS_DEC_MASK);
+        }
+    }
+
+    fn get_operand(&self) -> u8 {
+        match self.addr_mode {
+            AddressMode::Relative    |
+            AddressMode::Immediate   => {
+                self.ram.read_u8(self.pc + 1)
+            }
+
+            AddressMode::ZeroPage    |
+            AddressMode::ZeroPageRelative => {
+                let addr = self.ram.read_u8(self.pc + 1) as usize;
+                self.ram.read_u8(addr)
+            }
+
+            AddressMode::ZeroPageX   => {
+                let addr = self.ram.read_u8(self.pc + 1).wrapping_add(self.idx_x);
+                self.ram.read_u8(addr as usize)
+            }
+
+            AddressMode::ZeroPageY   => {
+                let addr = self.ram.read_u8(self.pc + 1).wrapping_add(self.idx_y);
+                self.ram.read_u8(addr as usize)
             }
 
             AddressMode::Absolute    => {
@@ -467,31 +1199,32 @@ impl<M: Memory> Mcs6502<M> {
 
             AddressMode::AbsoluteX   => {
                 let mut addr = self.ram.read_u16(self.pc + 1) as usize;
-                addr = addr.wrapping_add(self.idx_x as usize);
+                addr = (addr + self.idx_x as usize) & 0xFFFF;
 
                 self.ram.read_u8(addr)
             }
 
             AddressMode::AbsoluteY   => {
                 let mut addr = self.ram.read_u16(self.pc + 1) as usize;
-                addr = addr.wrapping_add(self.idx_y as usize);
+                addr = (addr + self.idx_y as usize) & 0xFFFF;
 
                 self.ram.read_u8(addr)
             }
 
             AddressMode::IndirectX   => {
-                let mut ptr = (self.ram.read_u8(self.pc + 1) + self.idx_x) as usize;
-                ptr = ptr % 0xFF;
-
-                let addr = self.ram.read_u16(ptr) as usize;
+                let ptr = self.ram.read_u8(self.pc + 1).wrapping_add(self.idx_x);
+                let addr = self.zero_page_u16(ptr);
                 self.ram.read_u8(addr)
             }
 
             AddressMode::IndirectY   => {
-                let ptr = self.ram.read_u8(self.pc + 1) as usize;
-                let mut addr = self.ram.read_u16(ptr) as usize;
-                addr += self.idx_y as usize;
+                let ptr = self.ram.read_u8(self.pc + 1);
+                let addr = (self.zero_page_u16(ptr) + self.idx_y as usize) & 0xFFFF;
+                self.ram.read_u8(addr)
+            }
 
+            AddressMode::ZeroPageIndirect => {
+                let addr = self.zero_page_pointer();
                 self.ram.read_u8(addr)
             }
 
@@ -501,6 +1234,73 @@ impl<M: Memory> Mcs6502<M> {
         }
     }
 
+    fn reads_operand(&self, opcode: u8) -> bool {
+        if self.variant == Variant::Cmos && cmos::redefines(opcode) {
+            return !matches!(opcode,
+                ops::cmos::STZ_ZERO_PAGE           |
+                ops::cmos::STZ_ZERO_PAGE_X         |
+                ops::cmos::STZ_ABSOLUTE            |
+                ops::cmos::STZ_ABSOLUTE_X          |
+                ops::cmos::STA_ZERO_PAGE_INDIRECT  |
+                ops::cmos::JMP_ABSOLUTE_INDIRECT_X);
+        }
+
+        !matches!(opcode,
+            ops::STA_ZERO_PAGE   |
+            ops::STA_ZERO_PAGE_X |
+            ops::STA_ABSOLUTE    |
+            ops::STA_ABSOLUTE_X  |
+            ops::STA_ABSOLUTE_Y  |
+            ops::STA_INDIRECT_X  |
+            ops::STA_INDIRECT_Y  |
+            ops::STX_ZERO_PAGE   |
+            ops::STX_ZERO_PAGE_Y |
+            ops::STX_ABSOLUTE    |
+            ops::STY_ZERO_PAGE   |
+            ops::STY_ZERO_PAGE_X |
+            ops::STY_ABSOLUTE    |
+            ops::SAX_ZERO_PAGE   |
+            ops::SAX_ZERO_PAGE_Y |
+            ops::SAX_ABSOLUTE    |
+            ops::SAX_INDIRECT_X  |
+            ops::SHA_ABSOLUTE_Y  |
+            ops::SHA_INDIRECT_Y  |
+            ops::SHX_ABSOLUTE_Y  |
+            ops::SHY_ABSOLUTE_X  |
+            ops::TAS_ABSOLUTE_Y  |
+            ops::JMP_ABSOLUTE    |
+            ops::JMP_INDIRECT    |
+            ops::JSR_ABSOLUTE)
+    }
+
+    // The operand was already read, peeking again isn't
+    // seen by watchpoints.
+    fn page_crossed(&self) -> bool {
+        let (base, offset) = match self.addr_mode {
+            AddressMode::AbsoluteX => (self.peek_u16(self.pc + 1), self.idx_x),
+            AddressMode::AbsoluteY => (self.peek_u16(self.pc + 1), self.idx_y),
+
+            AddressMode::IndirectY => {
+                let ptr = self.ram.peek(self.pc + 1);
+                let low = self.ram.peek(ptr as usize) as usize;
+                let high = self.ram.peek(ptr.wrapping_add(1) as usize) as usize;
+                ((high << 8) | low, self.idx_y)
+            }
+
+            _                      => return false
+        };
+
+        let addr = base.wrapping_add(offset as usize);
+        (base & 0xFF00) != (addr & 0xFF00)
+    }
+
+    fn peek_u16(&self, addr: usize) -> usize {
+        let low = self.ram.peek(addr) as usize;
+        let high = self.ram.peek(addr + 1) as usize;
+
+        (high << 8) | low
+    }
+
     fn set_flag(&mut self, cond: bool, mask: u8) {
         if cond {
             self.status |= mask;
@@ -513,27 +1313,37 @@ impl<M: Memory> Mcs6502<M> {
         self.status & mask > 0
     }
 
+    // Pointer stored in the zero page, wraps around
+    // the end of the page.
+    fn zero_page_pointer(&self) -> usize {
+        let ptr = self.ram.read_u8(self.pc + 1);
+        self.zero_page_u16(ptr)
+    }
+
+    // Word at ptr, the high byte of $FF is read from $00.
+    fn zero_page_u16(&self, ptr: u8) -> usize {
+        let low = self.ram.read_u8(ptr as usize) as usize;
+        let high = self.ram.read_u8(ptr.wrapping_add(1) as usize) as usize;
+
+        (high << 8) | low
+    }
+
     fn set_operand(&mut self, operand: u8) {
         match self.addr_mode {
-            AddressMode::ZeroPage    => {
+            AddressMode::ZeroPage    |
+            AddressMode::ZeroPageRelative => {
                 let addr = self.ram.read_u8(self.pc + 1) as usize;
                 self.ram.write_u8(addr, operand);
             }
 
             AddressMode::ZeroPageX   => {
-                let mut addr = self.ram.read_u8(self.pc + 1) as usize;
-                addr = addr.wrapping_add(self.idx_x as usize);
-                addr &= 0xFF;
-
-                self.ram.write_u8(addr, operand);
+                let addr = self.ram.read_u8(self.pc + 1).wrapping_add(self.idx_x);
+                self.ram.write_u8(addr as usize, operand);
             }
 
             AddressMode::ZeroPageY   => {
-                let mut addr = self.ram.read_u8(self.pc + 1) as usize;
-                addr = addr.wrapping_add(self.idx_y as usize);
-                addr &= 0xFF;
-
-                self.ram.write_u8(addr, operand);
+                let addr = self.ram.read_u8(self.pc + 1).wrapping_add(self.idx_y);
+                self.ram.write_u8(addr as usize, operand);
             }
 
             AddressMode::Absolute    => {
@@ -543,31 +1353,32 @@ impl<M: Memory> Mcs6502<M> {
 
             AddressMode::AbsoluteX   => {
                 let mut addr = self.ram.read_u16(self.pc + 1) as usize;
-                addr = addr.wrapping_add(self.idx_x as usize);
+                addr = (addr + self.idx_x as usize) & 0xFFFF;
 
                 self.ram.write_u8(addr, operand);
             }
 
             AddressMode::AbsoluteY   => {
                 let mut addr = self.ram.read_u16(self.pc + 1) as usize;
-                addr = addr.wrapping_add(self.idx_y as usize);
+                addr = (addr + self.idx_y as usize) & 0xFFFF;
 
                 self.ram.write_u8(addr, operand);
             }
 
             AddressMode::IndirectX   => {
-                let mut ptr = (self.ram.read_u8(self.pc + 1) + self.idx_x) as usize;
-                ptr = ptr % 0xFF;
-
-                let addr = self.ram.read_u16(ptr) as usize;
+                let ptr = self.ram.read_u8(self.pc + 1).wrapping_add(self.idx_x);
+                let addr = self.zero_page_u16(ptr);
                 self.ram.write_u8(addr, operand);
             }
 
             AddressMode::IndirectY   => {
-                let ptr = self.ram.read_u8(self.pc + 1) as usize;
-                let mut addr = self.ram.read_u16(ptr) as usize;
-                addr += self.idx_y as usize;
+                let ptr = self.ram.read_u8(self.pc + 1);
+                let addr = (self.zero_page_u16(ptr) + self.idx_y as usize) & 0xFFFF;
+                self.ram.write_u8(addr, operand);
+            }
 
+            AddressMode::ZeroPageIndirect => {
+                let addr = self.zero_page_pointer();
                 self.ram.write_u8(addr, operand);
             }
 
@@ -579,44 +1390,249 @@ impl<M: Memory> Mcs6502<M> {
 
     fn branch(&mut self, cond: bool, offset: u8) {
         if cond {
-            let soff = offset as i8;
-            if soff < 0 {
-                let offset = soff.abs() as usize;
-                self.pc = self.pc.wrapping_sub(offset);
-            } else {
-                self.pc = self.pc.wrapping_add(offset as usize);
+            // Taken branch costs one cycle, another one
+            // if it lands on a different page. The offset is
+            // relative to the next instruction.
+            let next = self.pc.wrapping_add(addr::pc_offset(&self.addr_mode));
+            self.cycles += 1;
+
+            self.pc = (next as u16).wrapping_add(offset as i8 as u16) as usize;
+            if (self.pc & 0xFF00) != (next & 0xFF00) {
+                self.cycles += 1;
             }
 
             self.pc = self.pc.wrapping_sub(addr::pc_offset(&self.addr_mode));
         }
     }
 
+    fn compare(&mut self, register: u8, operand: u8) {
+        let res = register.wrapping_sub(operand);
+
+        self.set_flag(register >= operand, STS_CAR_MASK);
+        self.set_flag((res & STS_NEG_MASK) > 0, STS_NEG_MASK);
+        self.set_flag(res == 0, STS_ZER_MASK);
+    }
+
+    fn unstable(&mut self) -> bool {
+        match self.unstable {
+            UnstableOpcodes::Emulate => true,
+            UnstableOpcodes::Nop     => false,
+            UnstableOpcodes::Trap    => {
+                self.set_fault(Fault::UnstableOpcode);
+                false
+            }
+        }
+    }
+
+    // Keeps the first fault of the instruction.
+    fn set_fault(&mut self, fault: Fault) {
+        if self.fault.is_none() {
+            self.fault = Some(fault);
+        }
+    }
+
+    // Stores value "And" high byte of the target address plus one,
+    // on page crossing the result also replaces the high byte of
+    // the address.
+    fn store_high_and(&mut self, value: u8) {
+        let (base, offset) = match self.addr_mode {
+            AddressMode::AbsoluteX => {
+                (self.ram.read_u16(self.pc + 1) as usize, self.idx_x)
+            }
+
+            AddressMode::AbsoluteY => {
+                (self.ram.read_u16(self.pc + 1) as usize, self.idx_y)
+            }
+
+            AddressMode::IndirectY => (self.zero_page_pointer(), self.idx_y),
+
+            _                      => return
+        };
+
+        let mut addr = (base + offset as usize) & 0xFFFF;
+        let res = value & ((base >> 8) as u8).wrapping_add(1);
+
+        if (base & 0xFF00) != (addr & 0xFF00) {
+            addr = ((res as usize) << 8) | (addr & 0xFF);
+        }
+
+        self.ram.write_u8(addr, res);
+    }
+
+    // Instructions added or changed by the 65C02.
+    fn execute_cmos(&mut self, opcode: u8, operand: u8) {
+        match opcode {
+            ops::cmos::BRA_RELATIVE            => self.branch(true, operand),
+
+            ops::cmos::BIT_IMMEDIATE           => self.op_bit_immediate(operand),
+
+            ops::cmos::BIT_ZERO_PAGE_X         |
+            ops::cmos::BIT_ABSOLUTE_X          => self.op_bit(operand),
+
+            ops::cmos::INC_ACCUMULATOR         => self.op_inc(operand),
+
+            ops::cmos::DEC_ACCUMULATOR         => self.op_dec(operand),
+
+            ops::cmos::JMP_ABSOLUTE_INDIRECT_X => self.op_jmp(),
+
+            ops::cmos::PHX_IMPLIED             => self.op_phx(),
+
+            ops::cmos::PHY_IMPLIED             => self.op_phy(),
+
+            ops::cmos::PLX_IMPLIED             => self.op_plx(),
+
+            ops::cmos::PLY_IMPLIED             => self.op_ply(),
+
+            ops::cmos::STZ_ZERO_PAGE           |
+            ops::cmos::STZ_ZERO_PAGE_X         |
+            ops::cmos::STZ_ABSOLUTE            |
+            ops::cmos::STZ_ABSOLUTE_X          => self.op_stz(),
+
+            ops::cmos::TRB_ZERO_PAGE           |
+            ops::cmos::TRB_ABSOLUTE            => self.op_trb(operand),
+
+            ops::cmos::TSB_ZERO_PAGE           |
+            ops::cmos::TSB_ABSOLUTE            => self.op_tsb(operand),
+
+            ops::cmos::ORA_ZERO_PAGE_INDIRECT  => self.op_ora(operand),
+
+            ops::cmos::AND_ZERO_PAGE_INDIRECT  => self.op_and(operand),
+
+            ops::cmos::EOR_ZERO_PAGE_INDIRECT  => self.op_eor(operand),
+
+            ops::cmos::ADC_ZERO_PAGE_INDIRECT  => self.op_adc(operand),
+
+            ops::cmos::STA_ZERO_PAGE_INDIRECT  => self.op_sta(),
+
+            ops::cmos::LDA_ZERO_PAGE_INDIRECT  => self.op_lda(operand),
+
+            ops::cmos::CMP_ZERO_PAGE_INDIRECT  => self.op_cmp(operand),
+
+            ops::cmos::SBC_ZERO_PAGE_INDIRECT  => self.op_sbc(operand),
+
+            ops::cmos::RMB0_ZERO_PAGE          |
+            ops::cmos::RMB1_ZERO_PAGE          |
+            ops::cmos::RMB2_ZERO_PAGE          |
+            ops::cmos::RMB3_ZERO_PAGE          |
+            ops::cmos::RMB4_ZERO_PAGE          |
+            ops::cmos::RMB5_ZERO_PAGE          |
+            ops::cmos::RMB6_ZERO_PAGE          |
+            ops::cmos::RMB7_ZERO_PAGE          => self.op_rmb(opcode, operand),
+
+            ops::cmos::SMB0_ZERO_PAGE          |
+            ops::cmos::SMB1_ZERO_PAGE          |
+            ops::cmos::SMB2_ZERO_PAGE          |
+            ops::cmos::SMB3_ZERO_PAGE          |
+            ops::cmos::SMB4_ZERO_PAGE          |
+            ops::cmos::SMB5_ZERO_PAGE          |
+            ops::cmos::SMB6_ZERO_PAGE          |
+            ops::cmos::SMB7_ZERO_PAGE          => self.op_smb(opcode, operand),
+
+            ops::cmos::BBR0_ZERO_PAGE_RELATIVE |
+            ops::cmos::BBR1_ZERO_PAGE_RELATIVE |
+            ops::cmos::BBR2_ZERO_PAGE_RELATIVE |
+            ops::cmos::BBR3_ZERO_PAGE_RELATIVE |
+            ops::cmos::BBR4_ZERO_PAGE_RELATIVE |
+            ops::cmos::BBR5_ZERO_PAGE_RELATIVE |
+            ops::cmos::BBR6_ZERO_PAGE_RELATIVE |
+            ops::cmos::BBR7_ZERO_PAGE_RELATIVE => self.op_bbr(opcode, operand),
+
+            ops::cmos::BBS0_ZERO_PAGE_RELATIVE |
+            ops::cmos::BBS1_ZERO_PAGE_RELATIVE |
+            ops::cmos::BBS2_ZERO_PAGE_RELATIVE |
+            ops::cmos::BBS3_ZERO_PAGE_RELATIVE |
+            ops::cmos::BBS4_ZERO_PAGE_RELATIVE |
+            ops::cmos::BBS5_ZERO_PAGE_RELATIVE |
+            ops::cmos::BBS6_ZERO_PAGE_RELATIVE |
+            ops::cmos::BBS7_ZERO_PAGE_RELATIVE => self.op_bbs(opcode, operand),
+
+            ops::cmos::WAI_IMPLIED             => self.op_wai(),
+
+            ops::cmos::STP_IMPLIED             => self.op_stp(),
+
+            // Undefined opcodes are NOPs on the 65C02.
+            _                                  => self.op_nop()
+        }
+    }
+
     fn jump(&mut self, addr: usize) {
         self.pc = addr + ROM_MAP_ADDRESS;
     }
 
+    fn decimal_active(&self) -> bool {
+        self.get_flag(STS_DEC_MASK) && self.decimal != DecimalMode::Disabled
+    }
+
     fn op_adc(&mut self, operand: u8) {
-        if self.get_flag(STS_DEC_MASK) {
-            panic!("Decimal addition not implemented yet!");
+        if self.decimal_active() {
+            self.adc_decimal(operand);
         } else {
-            let mut result = self.accu.wrapping_add(operand);
-            let carry = self.get_flag(STS_CAR_MASK);
-            result = result.wrapping_add(carry as u8);
+            self.adc_binary(operand);
+        }
+    }
+
+    fn adc_binary(&mut self, operand: u8) {
+        let accu = self.accu;
+        let carry = self.get_flag(STS_CAR_MASK) as u16;
+        let sum = (accu as u16) + (operand as u16) + carry;
+        let result = (sum & 0xFF) as u8;
 
-            let mut check = (self.accu as u16) + (carry as u16);
-            check +=  operand as u16;
+        // Overflow happens when both operands have the same sign
+        // and the sign of the result differs.
+        let overflow = (!(accu ^ operand) & (accu ^ result) & 0x80) > 0;
 
-            self.set_flag(check > 255, STS_CAR_MASK);
+        self.set_flag(sum > 0xFF, STS_CAR_MASK);
+        self.set_flag(overflow, STS_OVF_MASK);
+        self.set_flag(result == 0, STS_ZER_MASK);
+        self.set_flag((result & STS_NEG_MASK) > 0, STS_NEG_MASK);
+
+        self.accu = result;
+    }
 
-            let signed = result as i16;
-            self.set_flag(signed > 127 || signed < -128, STS_OVF_MASK);
+    fn adc_decimal(&mut self, operand: u8) {
+        let accu = self.accu as i16;
+        let value = operand as i16;
+        let carry = self.get_flag(STS_CAR_MASK) as i16;
 
-            self.accu = result;
+        let mut lo = (accu & 0x0F) + (value & 0x0F) + carry;
+        if lo >= 0x0A {
+            lo = ((lo + 0x06) & 0x0F) + 0x10;
         }
 
-        let result = self.accu;
-        self.set_flag(result == 0, STS_ZER_MASK);
-        self.set_flag((result as i8) < 0, STS_NEG_MASK);
+        // N and V are taken from the intermediate result before
+        // the high nibble is adjusted, with the high nibbles treated
+        // as signed values for the overflow.
+        let mut res = (accu & 0xF0) + (value & 0xF0) + lo;
+        let signed = ((accu & 0xF0) as i8 as i16) + ((value & 0xF0) as i8 as i16) + lo;
+        let overflow = !(-128..=127).contains(&signed);
+        let negative = (res & 0x80) > 0;
+
+        if res >= 0xA0 {
+            res += 0x60;
+        }
+
+        let result = (res & 0xFF) as u8;
+        self.set_flag(res >= 0x100, STS_CAR_MASK);
+        self.set_flag(overflow, STS_OVF_MASK);
+
+        match self.decimal {
+            DecimalMode::Nmos => {
+                // Zero flag reflects the binary sum.
+                let binary = accu + value + carry;
+                self.set_flag((binary & 0xFF) == 0, STS_ZER_MASK);
+                self.set_flag(negative, STS_NEG_MASK);
+            }
+
+            DecimalMode::Cmos => {
+                self.set_flag(result == 0, STS_ZER_MASK);
+                self.set_flag((result & STS_NEG_MASK) > 0, STS_NEG_MASK);
+                self.cycles += 1;
+            }
+
+            DecimalMode::Disabled => unreachable!("binary arithmetic")
+        }
+
+        self.accu = result;
     }
 
     fn op_and(&mut self, operand: u8) {
@@ -677,13 +1693,9 @@ impl<M: Memory> Mcs6502<M> {
     }
 
     fn op_brk(&mut self) {
-        let pc = self.pc + 2;
-        let status = self.status;
-        self.push_u16(pc as u16);
-        self.push_u8(status);
-
-        self.set_flag(true, STS_BRK_MASK);
-        self.pc = self.ram.read_u16(INT_REQ_ADDRESS) as usize;
+        // The byte after BRK is padding, RTI returns past it.
+        self.pc = self.pc.wrapping_add(2);
+        self.interrupt_sequence(INT_REQ_ADDRESS, true);
         self.pc = self.pc.wrapping_sub(addr::pc_offset(&self.addr_mode));
     }
 
@@ -714,29 +1726,22 @@ impl<M: Memory> Mcs6502<M> {
     }
 
     fn op_cmp(&mut self, operand: u8) {
-        let res = (self.accu as i8) - (operand as i8);
-
-        self.set_flag(res >= 0, STS_CAR_MASK);
-        self.set_flag(res < 0, STS_NEG_MASK);
-        self.set_flag(res == 0, STS_ZER_MASK);
+        let accu = self.accu;
+        self.compare(accu, operand);
     }
 
     fn op_cpx(&mut self, operand: u8) {
         let idx_x = self.idx_x;
-        self.set_flag(idx_x >= operand, STS_CAR_MASK);
-        self.set_flag(idx_x < operand, STS_NEG_MASK);
-        self.set_flag(idx_x == operand, STS_ZER_MASK);
+        self.compare(idx_x, operand);
     }
 
     fn op_cpy(&mut self, operand: u8) {
         let idx_y = self.idx_y;
-        self.set_flag(idx_y >= operand, STS_CAR_MASK);
-        self.set_flag(idx_y < operand, STS_NEG_MASK);
-        self.set_flag(idx_y == operand, STS_ZER_MASK);
+        self.compare(idx_y, operand);
     }
 
     fn op_dec(&mut self, mut operand: u8) {
-        operand -= 1;
+        operand = operand.wrapping_sub(1);
         self.set_operand(operand);
 
         self.set_flag((operand & STS_NEG_MASK) > 0, STS_NEG_MASK);
@@ -744,7 +1749,7 @@ impl<M: Memory> Mcs6502<M> {
     }
 
     fn op_dex(&mut self) {
-        self.idx_x = (self.idx_x as i8 - 1) as u8;
+        self.idx_x = self.idx_x.wrapping_sub(1);
 
         let idx_x = self.idx_x;
         self.set_flag((idx_x & STS_NEG_MASK) > 0, STS_NEG_MASK);
@@ -752,7 +1757,7 @@ impl<M: Memory> Mcs6502<M> {
     }
 
     fn op_dey(&mut self) {
-        self.idx_y = (self.idx_y as i8 - 1) as u8;
+        self.idx_y = self.idx_y.wrapping_sub(1);
 
         let idx_y = self.idx_y;
         self.set_flag((idx_y & STS_NEG_MASK) > 0, STS_NEG_MASK);
@@ -776,7 +1781,7 @@ impl<M: Memory> Mcs6502<M> {
     }
 
     fn op_inx(&mut self) {
-        self.idx_x += 1;
+        self.idx_x = self.idx_x.wrapping_add(1);
 
         let idx_x = self.idx_x;
         self.set_flag((idx_x & STS_NEG_MASK) > 0, STS_NEG_MASK);
@@ -784,7 +1789,7 @@ impl<M: Memory> Mcs6502<M> {
     }
 
     fn op_iny(&mut self) {
-        self.idx_y += 1;
+        self.idx_y = self.idx_y.wrapping_add(1);
 
         let idx_y = self.idx_y;
         self.set_flag((idx_y & STS_NEG_MASK) > 0, STS_NEG_MASK);
@@ -801,7 +1806,22 @@ impl<M: Memory> Mcs6502<M> {
             }
 
             AddressMode::Indirect => {
-                let actual_addr = self.ram.read_u16(addr) as usize;
+                // NMOS 6502 does not carry into the high byte
+                // of the pointer, e.g. JMP ($10FF) reads $10FF and $1000.
+                let high = match self.variant {
+                    Variant::Nmos => (addr & 0xFF00) | ((addr + 1) & 0xFF),
+                    Variant::Cmos => (addr + 1) & 0xFFFF
+                };
+
+                let low = self.ram.read_u8(addr) as usize;
+                let high = self.ram.read_u8(high) as usize;
+                let actual_addr = (high << 8) | low;
+                self.jump(actual_addr.wrapping_sub(offs));
+            }
+
+            AddressMode::AbsoluteIndirectX => {
+                let ptr = (addr + self.idx_x as usize) & 0xFFFF;
+                let actual_addr = self.ram.read_u16(ptr) as usize;
                 self.jump(actual_addr.wrapping_sub(offs));
             }
 
@@ -873,16 +1893,20 @@ impl<M: Memory> Mcs6502<M> {
     }
 
     fn op_php(&mut self) {
-        let status = self.status;
+        let status = self.status | STS_BRK_MASK | STS_EXP_MASK;
         self.push_u8(status);
     }
 
     fn op_pla(&mut self) {
         self.accu = self.pop_u8();
+
+        let accu = self.accu;
+        self.set_flag((accu & STS_NEG_MASK) > 0, STS_NEG_MASK);
+        self.set_flag(accu == 0, STS_ZER_MASK);
     }
 
     fn op_plp(&mut self) {
-        self.status = self.pop_u8();
+        self.status = self.pop_u8() & !(STS_BRK_MASK | STS_EXP_MASK);
     }
 
     fn op_rol(&mut self, mut operand: u8) {
@@ -913,7 +1937,10 @@ impl<M: Memory> Mcs6502<M> {
 
     fn op_rti(&mut self) {
         self.op_plp();
-        self.op_rts();
+
+        // Unlike RTS, the pulled address is the next instruction.
+        let pc = self.pop_u16() as usize;
+        self.pc = pc.wrapping_sub(addr::pc_offset(&self.addr_mode));
     }
 
     fn op_rts(&mut self) {
@@ -923,21 +1950,59 @@ impl<M: Memory> Mcs6502<M> {
     }
 
     fn op_sbc(&mut self, operand: u8) {
-        if self.get_flag(STS_DEC_MASK) {
-            panic!("Decimal sbc mode not implemented!");
+        if self.decimal_active() {
+            self.sbc_decimal(operand);
         } else {
-            let mut res = self.accu as i16;
-            res -= operand as i16;
-            if !self.get_flag(STS_CAR_MASK) {
-                res -= 1i16;
+            // A - M - !C is the same as A + !M + C.
+            self.adc_binary(!operand);
+        }
+    }
+
+    fn sbc_decimal(&mut self, operand: u8) {
+        let accu = self.accu as i16;
+        let value = operand as i16;
+        let borrow = 1 - (self.get_flag(STS_CAR_MASK) as i16);
+
+        let mut lo = (accu & 0x0F) - (value & 0x0F) - borrow;
+        let result = match self.decimal {
+            DecimalMode::Nmos => {
+                if lo < 0 {
+                    lo = ((lo - 0x06) & 0x0F) - 0x10;
+                }
+
+                let mut res = (accu & 0xF0) - (value & 0xF0) + lo;
+                if res < 0 {
+                    res -= 0x60;
+                }
+
+                (res & 0xFF) as u8
+            }
+
+            DecimalMode::Cmos => {
+                let mut res = accu - value - borrow;
+                if res < 0 {
+                    res -= 0x60;
+                }
+                if lo < 0 {
+                    res -= 0x06;
+                }
+
+                self.cycles += 1;
+                (res & 0xFF) as u8
             }
 
-            self.set_flag(res >= 0, STS_CAR_MASK);
-            self.set_flag(res < -127i16 || res > 127i16, STS_OVF_MASK);
-            self.set_flag(res < 0, STS_NEG_MASK);
+            DecimalMode::Disabled => unreachable!("binary arithmetic")
+        };
 
-            self.accu = (res & 0xFF) as u8;
+        // All flags match the binary subtraction on NMOS, the 65C02
+        // only fixes N and Z to reflect the decimal result.
+        self.adc_binary(!operand);
+        if self.decimal == DecimalMode::Cmos {
+            self.set_flag(result == 0, STS_ZER_MASK);
+            self.set_flag((result & STS_NEG_MASK) > 0, STS_NEG_MASK);
         }
+
+        self.accu = result;
     }
 
     fn op_sec(&mut self) {
@@ -1011,93 +2076,339 @@ impl<M: Memory> Mcs6502<M> {
         self.sp = self.idx_x;
     }
 
-    fn op_prt(&mut self) {
-        let pc = self.pc;
-        let mut addr = self.ram.read_u16(pc + 1) as usize;
-        let mut byte = 0x01u8;
-        let mut buf: Vec<u8> = Vec::new();
-
-        while byte != 0x00 {
-            byte = self.ram.read_u8(addr);
-            addr += 1;
-            buf.push(byte);
-        }
-
-        let str = String::from_utf8_lossy(&buf);
-        println!("{}", str);
+    fn op_slo(&mut self, operand: u8) {
+        self.op_asl(operand);
+        self.op_ora(operand << 1);
     }
 
-    fn op_tos(&mut self) {
-        let res = self.accu.to_string();
-        let buf: Vec<u8> = res.bytes().collect();
-
-        let pc = self.pc;
-        let addr = self.ram.read_u16(pc + 1) as usize;
+    fn op_rla(&mut self, operand: u8) {
+        let input_carry = self.get_flag(STS_CAR_MASK) as u8;
+        self.op_rol(operand);
+        self.op_and((operand << 1) | input_carry);
+    }
 
-        for i in 0..buf.len() {
-            self.ram.write_u8(addr + i, buf[i]);
-        }
-        self.ram.write_u8(addr + buf.len(), 0x00);
+    fn op_sre(&mut self, operand: u8) {
+        self.op_lsr(operand);
+        self.op_eor(operand >> 1);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use mems::Memory;
-    use mems::rom::Rom8b;
-    use mems::ram::Ram8b;
-    use cpus::Cpu;
-    use cpus::Stack;
-    use cpus::mcs6502::Mcs6502;
-    use cpus::mcs6502;
-    use inst::mcs6502::ops;
-    use inst::mcs6502::AddressMode;
+    fn op_rra(&mut self, operand: u8) {
+        let input_carry = self.get_flag(STS_CAR_MASK) as u8;
+        self.op_ror(operand);
+        self.op_adc((operand >> 1) | (input_carry << 7));
+    }
 
-    #[test]
-    fn op_adc() {
-        // TODO: Test decimal addition when it's implemented.
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::ADC_ZERO_PAGE);
-        instructions.push(0x0A);
+    fn op_sax(&mut self) {
+        let res = self.accu & self.idx_x;
+        self.set_operand(res);
+    }
 
-        let cart = Rom8b::from_vec(instructions);
-        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+    fn op_lax(&mut self, operand: u8) {
+        self.op_lda(operand);
+        self.idx_x = operand;
+    }
 
-        cpu.boot(&cart);
-        cpu.set_flag(true, mcs6502::STS_NEG_MASK);
-        cpu.accu = 0x05;
-        cpu.memory().write_u8(0x0A, 0x02);
+    fn op_dcp(&mut self, operand: u8) {
+        let operand = operand.wrapping_sub(1);
+        self.set_operand(operand);
 
-        let target = 0x05 + 0x02;
-        cpu.execute();
-        assert_eq!(cpu.accu, target);
-        assert!(!cpu.get_flag(mcs6502::STS_NEG_MASK));
+        let accu = self.accu;
+        self.compare(accu, operand);
     }
 
-    #[test]
-    fn op_and() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::AND_ZERO_PAGE);
-        instructions.push(0x0A);
-
-        let cart = Rom8b::from_vec(instructions);
-        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+    fn op_isc(&mut self, operand: u8) {
+        let operand = operand.wrapping_add(1);
+        self.set_operand(operand);
+        self.op_sbc(operand);
+    }
 
-        cpu.boot(&cart);
-        cpu.accu = 0xD5;
-        cpu.memory().write_u8(0x0A, 0xAC);
+    fn op_anc(&mut self, operand: u8) {
+        self.op_and(operand);
 
-        let target = 0xD5 & 0xAC;
-        cpu.execute();
-        assert_eq!(cpu.accu, target);
+        let negative = self.get_flag(STS_NEG_MASK);
+        self.set_flag(negative, STS_CAR_MASK);
     }
 
-    #[test]
-    fn op_asl() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::ASL_ZERO_PAGE);
-        instructions.push(0x0A);
-        instructions.push(ops::ASL_ACCUMULATOR);
+    fn op_alr(&mut self, operand: u8) {
+        let res = self.accu & operand;
+        self.set_flag((res & 1) == 1, STS_CAR_MASK);
+
+        self.accu = res >> 1;
+
+        let accu = self.accu;
+        self.set_flag((accu & STS_NEG_MASK) > 0, STS_NEG_MASK);
+        self.set_flag(accu == 0, STS_ZER_MASK);
+    }
+
+    fn op_arr(&mut self, operand: u8) {
+        let res = self.accu & operand;
+        let input_carry = self.get_flag(STS_CAR_MASK) as u8;
+        let mut accu = (res >> 1) | (input_carry << 7);
+
+        self.set_flag((accu & STS_NEG_MASK) > 0, STS_NEG_MASK);
+        self.set_flag(accu == 0, STS_ZER_MASK);
+
+        if self.decimal_active() {
+            // Decimal mode fixes up the nibbles of the rotated value
+            // based on the unrotated one.
+            self.set_flag(((res ^ accu) & STS_OVF_MASK) > 0, STS_OVF_MASK);
+
+            if (res & 0x0F) + (res & 0x01) > 0x05 {
+                accu = (accu & 0xF0) | (accu.wrapping_add(0x06) & 0x0F);
+            }
+
+            let high_fix = (res as u16 & 0xF0) + (res as u16 & 0x10) > 0x50;
+            self.set_flag(high_fix, STS_CAR_MASK);
+            if high_fix {
+                accu = accu.wrapping_add(0x60);
+            }
+        } else {
+            let bit6 = (accu >> 6) & 1;
+            let bit5 = (accu >> 5) & 1;
+            self.set_flag(bit6 == 1, STS_CAR_MASK);
+            self.set_flag((bit6 ^ bit5) == 1, STS_OVF_MASK);
+        }
+
+        self.accu = accu;
+    }
+
+    fn op_sbx(&mut self, operand: u8) {
+        let res = self.accu & self.idx_x;
+        self.set_flag(res >= operand, STS_CAR_MASK);
+
+        self.idx_x = res.wrapping_sub(operand);
+
+        let idx_x = self.idx_x;
+        self.set_flag((idx_x & STS_NEG_MASK) > 0, STS_NEG_MASK);
+        self.set_flag(idx_x == 0, STS_ZER_MASK);
+    }
+
+    fn op_las(&mut self, operand: u8) {
+        let res = operand & self.sp;
+        self.sp = res;
+        self.op_lax(res);
+    }
+
+    fn op_ane(&mut self, operand: u8) {
+        let res = (self.accu | UNSTABLE_MAGIC) & self.idx_x & operand;
+        self.op_lda(res);
+    }
+
+    fn op_sha(&mut self) {
+        let res = self.accu & self.idx_x;
+        self.store_high_and(res);
+    }
+
+    fn op_shx(&mut self) {
+        let res = self.idx_x;
+        self.store_high_and(res);
+    }
+
+    fn op_shy(&mut self) {
+        let res = self.idx_y;
+        self.store_high_and(res);
+    }
+
+    fn op_tas(&mut self) {
+        self.sp = self.accu & self.idx_x;
+
+        let sp = self.sp;
+        self.store_high_and(sp);
+    }
+
+    fn op_bit_immediate(&mut self, operand: u8) {
+        // Immediate BIT only affects the zero flag.
+        let res = self.accu & operand;
+        self.set_flag(res == 0, STS_ZER_MASK);
+    }
+
+    fn op_phx(&mut self) {
+        let idx_x = self.idx_x;
+        self.push_u8(idx_x);
+    }
+
+    fn op_phy(&mut self) {
+        let idx_y = self.idx_y;
+        self.push_u8(idx_y);
+    }
+
+    fn op_plx(&mut self) {
+        self.idx_x = self.pop_u8();
+
+        let idx_x = self.idx_x;
+        self.set_flag((idx_x & STS_NEG_MASK) > 0, STS_NEG_MASK);
+        self.set_flag(idx_x == 0, STS_ZER_MASK);
+    }
+
+    fn op_ply(&mut self) {
+        self.idx_y = self.pop_u8();
+
+        let idx_y = self.idx_y;
+        self.set_flag((idx_y & STS_NEG_MASK) > 0, STS_NEG_MASK);
+        self.set_flag(idx_y == 0, STS_ZER_MASK);
+    }
+
+    fn op_stz(&mut self) {
+        self.set_operand(0);
+    }
+
+    fn op_trb(&mut self, operand: u8) {
+        let accu = self.accu;
+        self.set_flag((accu & operand) == 0, STS_ZER_MASK);
+        self.set_operand(operand & !accu);
+    }
+
+    fn op_tsb(&mut self, operand: u8) {
+        let accu = self.accu;
+        self.set_flag((accu & operand) == 0, STS_ZER_MASK);
+        self.set_operand(operand | accu);
+    }
+
+    fn op_rmb(&mut self, opcode: u8, operand: u8) {
+        let mask = 1 << cmos::bit_index(opcode);
+        self.set_operand(operand & !mask);
+    }
+
+    fn op_smb(&mut self, opcode: u8, operand: u8) {
+        let mask = 1 << cmos::bit_index(opcode);
+        self.set_operand(operand | mask);
+    }
+
+    fn op_bbr(&mut self, opcode: u8, operand: u8) {
+        let mask = 1 << cmos::bit_index(opcode);
+        let offset = self.ram.read_u8(self.pc + 2);
+        self.branch((operand & mask) == 0, offset);
+    }
+
+    fn op_bbs(&mut self, opcode: u8, operand: u8) {
+        let mask = 1 << cmos::bit_index(opcode);
+        let offset = self.ram.read_u8(self.pc + 2);
+        self.branch((operand & mask) != 0, offset);
+    }
+
+    fn op_wai(&mut self) {
+        self.waiting = true;
+    }
+
+    fn op_stp(&mut self) {
+        self.halt = Some(StopReason::Stop(self.pc));
+    }
+}
+
+// Halts are stored as a tag followed by the pc, which is
+// zero for the limits.
+fn write_halt(writer: &mut StateWriter, halt: Option<StopReason>) {
+    let (tag, pc) = match halt {
+        None                               => (0, 0),
+        Some(StopReason::Jam(pc))          => (1, pc),
+        Some(StopReason::Stop(pc))         => (2, pc),
+        Some(StopReason::Break(pc))        => (3, pc),
+        Some(StopReason::SelfLoop(pc))     => (4, pc),
+        Some(StopReason::CycleLimit)       => (5, 0),
+        Some(StopReason::InstructionLimit) => (6, 0)
+    };
+
+    writer.u8(tag);
+    writer.u16(pc as u16);
+}
+
+fn read_halt(reader: &mut StateReader) -> Result<Option<StopReason>, StateError> {
+    let tag = reader.u8()?;
+    let pc = reader.u16()? as usize;
+
+    match tag {
+        0 => Ok(None),
+        1 => Ok(Some(StopReason::Jam(pc))),
+        2 => Ok(Some(StopReason::Stop(pc))),
+        3 => Ok(Some(StopReason::Break(pc))),
+        4 => Ok(Some(StopReason::SelfLoop(pc))),
+        5 => Ok(Some(StopReason::CycleLimit)),
+        6 => Ok(Some(StopReason::InstructionLimit)),
+        _ => Err(StateError::Invalid("halt"))
+    }
+}
+
+fn write_limit(writer: &mut StateWriter, limit: Option<u64>) {
+    writer.bool(limit.is_some());
+    writer.u64(limit.unwrap_or(0));
+}
+
+fn read_limit(reader: &mut StateReader) -> Result<Option<u64>, StateError> {
+    let set = reader.bool()?;
+    let limit = reader.u64()?;
+
+    Ok(if set { Some(limit) } else { None })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io;
+    use std::io::Write;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    use devs::console;
+    use devs::console::Console;
+    use mems::Memory;
+    use mems::MemoryError;
+    use mems::rom::Rom8b;
+    use mems::ram::Ram8b;
+    use mems::bus::Bus8b;
+    use cpus::Cpu;
+    use cpus::CpuError;
+    use cpus::Fault;
+    use cpus::Stack;
+    use cpus::StopReason;
+    use cpus::mcs6502::Mcs6502;
+    use cpus::mcs6502::DecimalMode;
+    use cpus::mcs6502::UnstableOpcodes;
+    use cpus::mcs6502;
+    use inst::mcs6502::ops;
+    use inst::mcs6502::AddressMode;
+    use inst::mcs6502::Variant;
+    use inst::mcs6502::ADDRESS_MODES;
+
+    #[test]
+    fn op_adc() {
+        let instructions = vec![ops::ADC_ZERO_PAGE, 0x0A];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_flag(true, mcs6502::STS_NEG_MASK);
+        cpu.accu = 0x05;
+        cpu.memory().write_u8(0x0A, 0x02);
+
+        let target = 0x05 + 0x02;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.accu, target);
+        assert!(!cpu.get_flag(mcs6502::STS_NEG_MASK));
+    }
+
+    #[test]
+    fn op_and() {
+        let instructions = vec![ops::AND_ZERO_PAGE, 0x0A];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.accu = 0xD5;
+        cpu.memory().write_u8(0x0A, 0xAC);
+
+        let target = 0xD5 & 0xAC;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.accu, target);
+    }
+
+    #[test]
+    fn op_asl() {
+        let instructions = vec![
+            ops::ASL_ZERO_PAGE, 0x0A,
+            ops::ASL_ACCUMULATOR
+        ];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1107,24 +2418,26 @@ mod tests {
         let mut orig = 0xFF;
         cpu.memory().write_u8(0x0A, orig);
         let mut target = orig << 1;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory().read_u8(0x0A), target);
         assert_eq!((orig & (1 << 7)) > 0, cpu.get_flag(mcs6502::STS_CAR_MASK));
 
         orig = 0x07;
         cpu.accu = orig;
         target = cpu.accu << 1;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.accu, target);
         assert_eq!((orig & (1 << 7)) > 0, cpu.get_flag(mcs6502::STS_CAR_MASK));
+
+        // The accumulator is not an operand byte.
+        assert_eq!(cpu.pc, 0x03);
     }
 
     fn aux_branch(opcode: u8, flag: u8, cond: bool) {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(opcode);
-        instructions.push(0x0A);
-        instructions.push(opcode);
-        instructions.push(0x0A);
+        let instructions = vec![
+            opcode, 0x0A,
+            opcode, 0x0A
+        ];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1134,13 +2447,13 @@ mod tests {
         // Fail to branch.
         let mut target = 0x02;
         cpu.set_flag(!cond, flag);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.pc(), target);
 
-        // Succeed at branching.
-        target += 0x0A;
+        // Succeed at branching, relative to the next instruction.
+        target += 0x02 + 0x0A;
         cpu.set_flag(cond, flag);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.pc(), target);
     }
 
@@ -1162,11 +2475,10 @@ mod tests {
     #[test]
     fn op_bit() {
         let addr: u8 = 0x7F;
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::BIT_ZERO_PAGE);
-        instructions.push(addr);
-        instructions.push(ops::BIT_ZERO_PAGE);
-        instructions.push(addr);
+        let instructions = vec![
+            ops::BIT_ZERO_PAGE, addr,
+            ops::BIT_ZERO_PAGE, addr
+        ];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1175,13 +2487,13 @@ mod tests {
         cpu.memory().write_u8(addr as usize, 0b11001010);
         cpu.accu = 0b11101100;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert!(cpu.get_flag(mcs6502::STS_NEG_MASK));
         assert!(cpu.get_flag(mcs6502::STS_OVF_MASK));
         assert!(!cpu.get_flag(mcs6502::STS_ZER_MASK));
 
         cpu.memory().write_u8(addr as usize, 0b00000011);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert!(cpu.get_flag(mcs6502::STS_ZER_MASK));
     }
 
@@ -1202,8 +2514,7 @@ mod tests {
 
     #[test]
     fn op_brk() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::BRK_IMPLIED);
+        let instructions = vec![ops::BRK_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1216,11 +2527,17 @@ mod tests {
         let pc = cpu.pc();
         let status = cpu.status;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.pc(), 0xBEEF);
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 7);
+
+        // B and the expansion bit are set in the pushed copy only.
         let stack_status = cpu.pop_u8();
-        assert_eq!(stack_status, status);
-        let stack_pc = cpu.pop_u16() as usize;
+        assert_eq!(stack_status, status | mcs6502::STS_BRK_MASK | mcs6502::STS_EXP_MASK);
+        assert!(!cpu.get_flag(mcs6502::STS_BRK_MASK));
+
+        // Return address skips the padding byte, high byte first.
+        let stack_pc = cpu.pop_u8() as usize | (cpu.pop_u8() as usize) << 8;
         assert_eq!(stack_pc, pc + 2)
     }
 
@@ -1235,15 +2552,14 @@ mod tests {
     }
 
     fn aux_clear(opcode: u8, flag: u8) {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(opcode);
+        let instructions = vec![opcode];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
 
         cpu.boot(&cart);
         cpu.set_flag(true, flag);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert!(!cpu.get_flag(flag));
     }
 
@@ -1269,13 +2585,11 @@ mod tests {
 
     #[test]
     fn op_cmp() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::CMP_IMMEDIATE);
-        instructions.push(0x05);
-        instructions.push(ops::CMP_IMMEDIATE);
-        instructions.push(0x06);
-        instructions.push(ops::CMP_IMMEDIATE);
-        instructions.push(0x07);
+        let instructions = vec![
+            ops::CMP_IMMEDIATE, 0x05,
+            ops::CMP_IMMEDIATE, 0x06,
+            ops::CMP_IMMEDIATE, 0x07
+        ];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1283,26 +2597,24 @@ mod tests {
         cpu.boot(&cart);
         cpu.accu = 0x06;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert!(cpu.get_flag(mcs6502::STS_ZER_MASK));
         assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert!(cpu.get_flag(mcs6502::STS_NEG_MASK));
     }
 
     #[test]
     fn op_cpx() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::CPX_IMMEDIATE);
-        instructions.push(0x05);
-        instructions.push(ops::CPX_IMMEDIATE);
-        instructions.push(0x06);
-        instructions.push(ops::CPX_IMMEDIATE);
-        instructions.push(0x07);
+        let instructions = vec![
+            ops::CPX_IMMEDIATE, 0x05,
+            ops::CPX_IMMEDIATE, 0x06,
+            ops::CPX_IMMEDIATE, 0x07
+        ];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1310,26 +2622,24 @@ mod tests {
         cpu.boot(&cart);
         cpu.idx_x = 0x06;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert!(cpu.get_flag(mcs6502::STS_ZER_MASK));
         assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert!(cpu.get_flag(mcs6502::STS_NEG_MASK));
     }
 
     #[test]
     fn op_cpy() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::CPY_IMMEDIATE);
-        instructions.push(0x05);
-        instructions.push(ops::CPY_IMMEDIATE);
-        instructions.push(0x06);
-        instructions.push(ops::CPY_IMMEDIATE);
-        instructions.push(0x07);
+        let instructions = vec![
+            ops::CPY_IMMEDIATE, 0x05,
+            ops::CPY_IMMEDIATE, 0x06,
+            ops::CPY_IMMEDIATE, 0x07
+        ];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1337,66 +2647,64 @@ mod tests {
         cpu.boot(&cart);
         cpu.idx_y = 0x06;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert!(cpu.get_flag(mcs6502::STS_ZER_MASK));
         assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert!(cpu.get_flag(mcs6502::STS_NEG_MASK));
     }
 
     #[test]
     fn op_dec() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::DEC_ABSOLUTE);
-        instructions.push(0x0A);
-        instructions.push(0x00);
+        let instructions = vec![ops::DEC_ABSOLUTE, 0x0A, 0x00];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
 
         cpu.boot(&cart);
         cpu.memory().write_u8(0x000A, 5);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory().read_u8(0x000A), 4);
     }
 
     #[test]
     fn op_dex() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::DEX_IMPLIED);
+        let instructions = vec![ops::DEX_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
 
         cpu.boot(&cart);
         cpu.idx_x = 0x0B;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.idx_x, 0x0A);
+
+        cpu.boot(&cart);
+        cpu.idx_x = 0x80;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.idx_x, 0x7F);
     }
 
     #[test]
     fn op_dey() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::DEY_IMPLIED);
+        let instructions = vec![ops::DEY_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
 
         cpu.boot(&cart);
         cpu.idx_y = 0x0B;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.idx_y, 0x0A);
     }
 
     #[test]
     fn op_eor() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::EOR_ZERO_PAGE);
-        instructions.push(0x0A);
+        let instructions = vec![ops::EOR_ZERO_PAGE, 0x0A];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1406,60 +2714,57 @@ mod tests {
         cpu.memory().write_u8(0x0A, 0xF3);
 
         let target = 0x31 ^ 0xF3;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.accu, target);
     }
 
     #[test]
     fn op_inc() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::INC_ABSOLUTE);
-        instructions.push(0x0A);
-        instructions.push(0x00);
+        let instructions = vec![ops::INC_ABSOLUTE, 0x0A, 0x00];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
 
         cpu.boot(&cart);
         cpu.memory().write_u8(0x000A, 5);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory().read_u8(0x000A), 6);
     }
 
     #[test]
     fn op_inx() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::INX_IMPLIED);
+        let instructions = vec![ops::INX_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
 
         cpu.boot(&cart);
         cpu.idx_x = 0x0A;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.idx_x, 0x0B);
+
+        cpu.boot(&cart);
+        cpu.idx_x = 0xFF;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.idx_x, 0x00);
     }
 
     #[test]
     fn op_iny() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::INY_IMPLIED);
+        let instructions = vec![ops::INY_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
 
         cpu.boot(&cart);
         cpu.idx_y = 0x0A;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.idx_y, 0x0B);
     }
 
     #[test]
     fn op_jmp() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::JMP_ABSOLUTE);
-        instructions.push(0xA0);
-        instructions.push(0x01);
+        let instructions = vec![ops::JMP_ABSOLUTE, 0xA0, 0x01];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1467,7 +2772,7 @@ mod tests {
         cpu.boot(&cart);
         let mut target = 0x01A0;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.addr_mode, AddressMode::Absolute);
         assert_eq!(cpu.pc(), target);
 
@@ -1476,18 +2781,17 @@ mod tests {
         cpu.memory().write_u16(0xABBA, 0xBAAB);
         target = 0xBAAB;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.addr_mode, AddressMode::Indirect);
         assert_eq!(cpu.pc(), target);
     }
 
     #[test]
     fn op_jsr() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(0x00);
-        instructions.push(ops::JSR_ABSOLUTE);
-        instructions.push(0xA0);
-        instructions.push(0x01);
+        let instructions = vec![
+            0x00,
+            ops::JSR_ABSOLUTE, 0xA0, 0x01
+        ];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1495,20 +2799,68 @@ mod tests {
         cpu.boot(&cart);
         cpu.pc += 1;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         let stored_pc = cpu.pop_u16() as usize;
         assert_eq!(stored_pc, 0x03);
         assert_eq!(cpu.pc(), 0x01A0);
     }
 
+    #[test]
+    fn stack_layout() {
+        // JSR to a routine that returns through an address
+        // pushed by hand.
+        let mut rom = vec![0x00; 0x20];
+        rom[0x00] = ops::JSR_ABSOLUTE;
+        rom[0x01] = 0x10;
+        rom[0x02] = 0x00;
+        rom[0x10] = ops::LDA_IMMEDIATE;
+        rom[0x11] = 0x00;
+        rom[0x12] = ops::PHA_IMPLIED;
+        rom[0x13] = ops::LDA_IMMEDIATE;
+        rom[0x14] = 0x1F;
+        rom[0x15] = ops::PHA_IMPLIED;
+        rom[0x16] = ops::RTS_IMPLIED;
+
+        let cart = Rom8b::from_vec(rom);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        assert_eq!(cpu.sp, 0xFD);
+
+        // High byte first, the address is the last byte of JSR.
+        cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0xFB);
+        assert_eq!(cpu.memory().read_u8(0x01FD), 0x00);
+        assert_eq!(cpu.memory().read_u8(0x01FC), 0x02);
+        assert_eq!(cpu.memory().read_u16(0x01FC), 0x0002);
+
+        cpu.run(5).unwrap();
+        assert_eq!(cpu.pc(), 0x0020);
+        assert_eq!(cpu.sp, 0xFB);
+    }
+
+    #[test]
+    fn stack_wrap() {
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        // Words wrap within the stack page.
+        cpu.sp = 0x00;
+        cpu.push_u16(0x1234);
+        assert_eq!(cpu.sp, 0xFE);
+        assert_eq!(cpu.memory().read_u8(0x0100), 0x12);
+        assert_eq!(cpu.memory().read_u8(0x01FF), 0x34);
+        assert_eq!(cpu.memory().read_u8(0x0200), 0x00);
+
+        assert_eq!(cpu.pop_u16(), 0x1234);
+        assert_eq!(cpu.sp, 0x00);
+    }
+
     #[test]
     fn op_lda() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::LDA_IMMEDIATE);
-        instructions.push(0xAB);
-        instructions.push(ops::LDA_ABSOLUTE);
-        instructions.push(0x34);
-        instructions.push(0x12);
+        let instructions = vec![
+            ops::LDA_IMMEDIATE, 0xAB,
+            ops::LDA_ABSOLUTE, 0x34, 0x12
+        ];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1516,23 +2868,21 @@ mod tests {
         cpu.boot(&cart);
         let mut target = 0xAB;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.accu, target);
 
         target = 0xFC;
         cpu.memory().write_u8(0x1234, target);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.accu, target);
     }
 
     #[test]
     fn op_ldx() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::LDX_IMMEDIATE);
-        instructions.push(0xAB);
-        instructions.push(ops::LDX_ABSOLUTE);
-        instructions.push(0x34);
-        instructions.push(0x12);
+        let instructions = vec![
+            ops::LDX_IMMEDIATE, 0xAB,
+            ops::LDX_ABSOLUTE, 0x34, 0x12
+        ];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1540,23 +2890,21 @@ mod tests {
         cpu.boot(&cart);
         let mut target = 0xAB;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.idx_x, target);
 
         target = 0xFC;
         cpu.memory().write_u8(0x1234, target);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.idx_x, target);
     }
 
     #[test]
     fn op_ldy() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::LDY_IMMEDIATE);
-        instructions.push(0xAB);
-        instructions.push(ops::LDY_ABSOLUTE);
-        instructions.push(0x34);
-        instructions.push(0x12);
+        let instructions = vec![
+            ops::LDY_IMMEDIATE, 0xAB,
+            ops::LDY_ABSOLUTE, 0x34, 0x12
+        ];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1564,21 +2912,21 @@ mod tests {
         cpu.boot(&cart);
         let mut target = 0xAB;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.idx_y, target);
 
         target = 0xFC;
         cpu.memory().write_u8(0x1234, target);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.idx_y, target);
     }
 
     #[test]
     fn op_lsr() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::LSR_ZERO_PAGE);
-        instructions.push(0x0A);
-        instructions.push(ops::LSR_ACCUMULATOR);
+        let instructions = vec![
+            ops::LSR_ZERO_PAGE, 0x0A,
+            ops::LSR_ACCUMULATOR
+        ];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1588,28 +2936,38 @@ mod tests {
         let mut orig = 0xF3;
         cpu.memory().write_u8(0x0A, orig);
         let mut target = orig >> 1;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory().read_u8(0x0A), target);
         assert_eq!((orig & 1) == 1, cpu.get_flag(mcs6502::STS_CAR_MASK));
 
         orig = 0x0A;
         cpu.accu = orig;
         target = cpu.accu >> 1;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.accu, target);
         assert_eq!((orig & 1) == 1, cpu.get_flag(mcs6502::STS_CAR_MASK));
     }
 
     #[test]
     fn op_nop() {
-        assert!(true);
+        let instructions = vec![ops::NOP_IMPLIED];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        let mut regs = cpu.registers();
+        regs.pc = 0x01;
+
+        // Only the pc and the cycles change.
+        cpu.execute().unwrap();
+        assert_eq!(cpu.registers(), regs);
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 2);
     }
 
     #[test]
     fn op_ora() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::ORA_ZERO_PAGE);
-        instructions.push(0x0A);
+        let instructions = vec![ops::ORA_ZERO_PAGE, 0x0A];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1619,14 +2977,13 @@ mod tests {
         cpu.memory().write_u8(0x0A, 0xF3);
 
         let target = 0x31 | 0xF3;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.accu, target);
     }
 
     #[test]
     fn op_pha() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::PHA_IMPLIED);
+        let instructions = vec![ops::PHA_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1634,7 +2991,7 @@ mod tests {
         cpu.boot(&cart);
 
         cpu.accu = 0xAF;
-        cpu.execute();
+        cpu.execute().unwrap();
 
         let top = cpu.pop_u8();
         assert_eq!(top, cpu.accu);
@@ -1642,8 +2999,7 @@ mod tests {
 
     #[test]
     fn op_php() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::PHP_IMPLIED);
+        let instructions = vec![ops::PHP_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1656,7 +3012,7 @@ mod tests {
         cpu.set_flag(true, mcs6502::STS_DEC_MASK);
         cpu.set_flag(false, mcs6502::STS_INT_MASK);
         cpu.set_flag(true, mcs6502::STS_OVF_MASK);
-        cpu.execute();
+        cpu.execute().unwrap();
 
         let top = cpu.pop_u8();
 
@@ -1670,8 +3026,7 @@ mod tests {
 
     #[test]
     fn op_pla() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::PLA_IMPLIED);
+        let instructions = vec![ops::PLA_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1679,15 +3034,23 @@ mod tests {
         cpu.boot(&cart);
         cpu.push_u8(0xFA);
 
-        cpu.execute();
+        cpu.execute().unwrap();
 
         assert_eq!(cpu.accu, 0xFA);
+        assert!(cpu.get_flag(mcs6502::STS_NEG_MASK));
+        assert!(!cpu.get_flag(mcs6502::STS_ZER_MASK));
+
+        cpu.boot(&cart);
+        cpu.push_u8(0x00);
+        cpu.execute().unwrap();
+
+        assert!(!cpu.get_flag(mcs6502::STS_NEG_MASK));
+        assert!(cpu.get_flag(mcs6502::STS_ZER_MASK));
     }
 
     #[test]
     fn op_plp() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::PLP_IMPLIED);
+        let instructions = vec![ops::PLP_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1700,7 +3063,7 @@ mod tests {
         state |= mcs6502::STS_NEG_MASK;
         cpu.push_u8(state);
 
-        cpu.execute();
+        cpu.execute().unwrap();
 
         assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
         assert!(cpu.get_flag(mcs6502::STS_NEG_MASK));
@@ -1713,9 +3076,7 @@ mod tests {
     #[test]
     fn op_rol() {
         // TODO: Test variant with carry clear.
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::ROL_ZERO_PAGE);
-        instructions.push(0x0A);
+        let instructions = vec![ops::ROL_ZERO_PAGE, 0x0A];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1730,7 +3091,7 @@ mod tests {
             target |= 1;
         }
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory().read_u8(0x0A), target);
         assert_eq!((orig & (1 << 7)) > 0, cpu.get_flag(mcs6502::STS_CAR_MASK));
     }
@@ -1738,9 +3099,7 @@ mod tests {
     #[test]
     fn op_ror() {
         // TODO: Test variant with carry clear.
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::ROR_ZERO_PAGE);
-        instructions.push(0x0A);
+        let instructions = vec![ops::ROR_ZERO_PAGE, 0x0A];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1755,22 +3114,30 @@ mod tests {
             target |= 1 << 7;
         }
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory().read_u8(0x0A), target);
         assert_eq!((orig & 1) == 1, cpu.get_flag(mcs6502::STS_CAR_MASK));
     }
 
     #[test]
     fn op_rti() {
-        // Composite instruction.
-        op_plp();
-        op_rts();
+        let cart = Rom8b::from_vec(vec![ops::RTI_IMPLIED]);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.push_u8(0x12);
+        cpu.push_u8(0x34);
+        cpu.push_u8(mcs6502::STS_CAR_MASK | mcs6502::STS_BRK_MASK | mcs6502::STS_EXP_MASK);
+
+        // Returns to the pulled address, B is not restored.
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x1234);
+        assert_eq!(cpu.status, mcs6502::STS_CAR_MASK);
     }
 
     #[test]
     fn op_rts() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::RTS_IMPLIED);
+        let instructions = vec![ops::RTS_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1781,16 +3148,13 @@ mod tests {
         cpu.push_u16(target);
         target += 1; // JSR stores address of its last byte.
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.pc(), target as usize);
     }
 
     #[test]
     fn op_sbc() {
-        // TODO: Test decimal subtraction when it's implemented.
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::SBC_ZERO_PAGE);
-        instructions.push(0x0A);
+        let instructions = vec![ops::SBC_ZERO_PAGE, 0x0A];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1801,20 +3165,156 @@ mod tests {
         cpu.memory().write_u8(0x0A, 0x02);
 
         let target = 0x05 - 0x02;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.accu, target);
     }
 
+    #[test]
+    fn op_adc_overflow() {
+        let instructions = vec![
+            ops::ADC_IMMEDIATE, 0x50,
+            ops::ADC_IMMEDIATE, 0x90
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.accu = 0x50;
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.accu, 0xA0);
+        assert!(cpu.get_flag(mcs6502::STS_OVF_MASK));
+        assert!(cpu.get_flag(mcs6502::STS_NEG_MASK));
+        assert!(!cpu.get_flag(mcs6502::STS_CAR_MASK));
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.accu, 0x30);
+        assert!(cpu.get_flag(mcs6502::STS_OVF_MASK));
+        assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
+    }
+
+    fn to_bcd(value: u8) -> u8 {
+        ((value / 10) << 4) | (value % 10)
+    }
+
+    fn aux_decimal(cpu: &mut Mcs6502<Ram8b>, opcode: u8,
+                   accu: u8, operand: u8, carry: bool) {
+        cpu.pc = 0;
+        cpu.memory().write_u8(0, opcode);
+        cpu.memory().write_u8(1, operand);
+        cpu.accu = accu;
+        cpu.set_flag(true, mcs6502::STS_DEC_MASK);
+        cpu.set_flag(carry, mcs6502::STS_CAR_MASK);
+        cpu.execute().unwrap();
+    }
+
+    #[test]
+    fn op_adc_decimal() {
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        for mode in [DecimalMode::Nmos, DecimalMode::Cmos].iter() {
+            cpu.set_decimal_mode(*mode);
+
+            for a in 0..100u8 {
+                for b in 0..100u8 {
+                    for carry in 0..2u8 {
+                        aux_decimal(&mut cpu, ops::ADC_IMMEDIATE,
+                                    to_bcd(a), to_bcd(b), carry == 1);
+
+                        let sum = a + b + carry;
+                        assert_eq!(cpu.accu, to_bcd(sum % 100));
+                        assert_eq!(cpu.get_flag(mcs6502::STS_CAR_MASK), sum > 99);
+
+                        if *mode == DecimalMode::Nmos {
+                            let binary = to_bcd(a).wrapping_add(to_bcd(b))
+                                                  .wrapping_add(carry);
+                            assert_eq!(cpu.get_flag(mcs6502::STS_ZER_MASK), binary == 0);
+                        } else {
+                            assert_eq!(cpu.get_flag(mcs6502::STS_ZER_MASK), cpu.accu == 0);
+                            assert_eq!(cpu.get_flag(mcs6502::STS_NEG_MASK), cpu.accu >= 0x80);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn op_sbc_decimal() {
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        for mode in [DecimalMode::Nmos, DecimalMode::Cmos].iter() {
+            cpu.set_decimal_mode(*mode);
+
+            for a in 0..100i16 {
+                for b in 0..100i16 {
+                    for carry in 0..2i16 {
+                        aux_decimal(&mut cpu, ops::SBC_IMMEDIATE,
+                                    to_bcd(a as u8), to_bcd(b as u8), carry == 1);
+
+                        let diff = a - b - (1 - carry);
+                        assert_eq!(cpu.accu, to_bcd(((diff + 100) % 100) as u8));
+                        assert_eq!(cpu.get_flag(mcs6502::STS_CAR_MASK), diff >= 0);
+
+                        if *mode == DecimalMode::Nmos {
+                            let binary = (to_bcd(a as u8) as i16) - (to_bcd(b as u8) as i16)
+                                         - (1 - carry);
+                            assert_eq!(cpu.get_flag(mcs6502::STS_ZER_MASK), (binary & 0xFF) == 0);
+                            assert_eq!(cpu.get_flag(mcs6502::STS_NEG_MASK), (binary & 0x80) > 0);
+                        } else {
+                            assert_eq!(cpu.get_flag(mcs6502::STS_ZER_MASK), cpu.accu == 0);
+                            assert_eq!(cpu.get_flag(mcs6502::STS_NEG_MASK), cpu.accu >= 0x80);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn decimal_undocumented_flags() {
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        // 99 + 1 wraps to 00, NMOS sets N from the intermediate
+        // result and Z from the binary sum.
+        aux_decimal(&mut cpu, ops::ADC_IMMEDIATE, 0x99, 0x01, false);
+        assert_eq!(cpu.accu, 0x00);
+        assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
+        assert!(cpu.get_flag(mcs6502::STS_NEG_MASK));
+        assert!(!cpu.get_flag(mcs6502::STS_ZER_MASK));
+
+        // 79 + 00 + 1 overflows into a negative number.
+        aux_decimal(&mut cpu, ops::ADC_IMMEDIATE, 0x79, 0x00, true);
+        assert_eq!(cpu.accu, 0x80);
+        assert!(cpu.get_flag(mcs6502::STS_OVF_MASK));
+
+        // Invalid BCD input.
+        aux_decimal(&mut cpu, ops::ADC_IMMEDIATE, 0x0F, 0x0F, false);
+        assert_eq!(cpu.accu, 0x14);
+
+        cpu.set_decimal_mode(DecimalMode::Cmos);
+        aux_decimal(&mut cpu, ops::ADC_IMMEDIATE, 0x99, 0x01, false);
+        assert_eq!(cpu.accu, 0x00);
+        assert!(!cpu.get_flag(mcs6502::STS_NEG_MASK));
+        assert!(cpu.get_flag(mcs6502::STS_ZER_MASK));
+
+        // The 2A03 ignores D.
+        cpu.set_decimal_mode(DecimalMode::Disabled);
+        aux_decimal(&mut cpu, ops::ADC_IMMEDIATE, 0x99, 0x01, false);
+        assert_eq!(cpu.accu, 0x9A);
+        assert!(cpu.get_flag(mcs6502::STS_DEC_MASK));
+    }
+
     fn aux_set(opcode: u8, flag: u8) {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(opcode);
+        let instructions = vec![opcode];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
 
         cpu.boot(&cart);
         cpu.set_flag(false, flag);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert!(cpu.get_flag(flag));
     }
 
@@ -1835,9 +3335,7 @@ mod tests {
 
     #[test]
     fn op_sta() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::STA_ZERO_PAGE);
-        instructions.push(0x35);
+        let instructions = vec![ops::STA_ZERO_PAGE, 0x35];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1845,16 +3343,14 @@ mod tests {
         cpu.boot(&cart);
         cpu.accu = 0xAC;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         let res = cpu.memory().read_u8(0x35);
         assert_eq!(cpu.accu, res);
     }
 
     #[test]
     fn op_stx() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::STX_ZERO_PAGE_Y);
-        instructions.push(0x35);
+        let instructions = vec![ops::STX_ZERO_PAGE_Y, 0x35];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1863,7 +3359,7 @@ mod tests {
         cpu.idx_x = 0xAC;
         cpu.idx_y = 0x05;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         let addr: usize = 0x35 + 0x05;
         let res = cpu.memory().read_u8(addr);
         assert_eq!(cpu.idx_x, res);
@@ -1871,9 +3367,7 @@ mod tests {
 
     #[test]
     fn op_sty() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::STY_ZERO_PAGE_X);
-        instructions.push(0x35);
+        let instructions = vec![ops::STY_ZERO_PAGE_X, 0x35];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1882,7 +3376,7 @@ mod tests {
         cpu.idx_y = 0xAC;
         cpu.idx_x = 0xFB; // -5
 
-        cpu.execute();
+        cpu.execute().unwrap();
         let addr: usize = 0x30;
         let res = cpu.memory().read_u8(addr);
         assert_eq!(cpu.idx_y, res);
@@ -1890,8 +3384,7 @@ mod tests {
 
     #[test]
     fn op_tax() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::TAX_IMPLIED);
+        let instructions = vec![ops::TAX_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1899,14 +3392,13 @@ mod tests {
         cpu.boot(&cart);
         cpu.accu = 0xC5;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.idx_x, cpu.accu);
     }
 
     #[test]
     fn op_tay() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::TAY_IMPLIED);
+        let instructions = vec![ops::TAY_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1914,14 +3406,13 @@ mod tests {
         cpu.boot(&cart);
         cpu.accu = 0xC5;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.idx_y, cpu.accu);
     }
 
     #[test]
     fn op_tya() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::TYA_IMPLIED);
+        let instructions = vec![ops::TYA_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1929,28 +3420,26 @@ mod tests {
         cpu.boot(&cart);
         cpu.idx_y = 0xC5;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.accu, cpu.idx_y);
     }
 
     #[test]
     fn op_tsx() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::TSX_IMPLIED);
+        let instructions = vec![ops::TSX_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
 
         cpu.boot(&cart);
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.idx_x, cpu.sp);
     }
 
     #[test]
     fn op_txa() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::TXA_IMPLIED);
+        let instructions = vec![ops::TXA_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1958,14 +3447,13 @@ mod tests {
         cpu.boot(&cart);
         cpu.idx_x = 0xC5;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.accu, cpu.idx_x);
     }
 
     #[test]
     fn op_txs() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::TXS_IMPLIED);
+        let instructions = vec![ops::TXS_IMPLIED];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
@@ -1973,68 +3461,1150 @@ mod tests {
         cpu.boot(&cart);
         cpu.idx_x = 0xC5;
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.sp, cpu.idx_x);
     }
 
     #[test]
-    fn op_prt() {
-        let addr: usize = 0x04FF;
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::custom::PRT_ABSOLUTE);
-        instructions.push((addr & 0xFF) as u8);
-        instructions.push((addr >> 8) as u8);
+    fn former_pseudo_opcodes() {
+        let instructions = vec![
+            ops::ISC_ABSOLUTE_X, 0x00, 0x20,
+            ops::ISC_ABSOLUTE_Y, 0x00, 0x20,
+            ops::NOP_ABSOLUTE_X_FC, 0xFF, 0x20
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.idx_x = 0x01;
+        cpu.idx_y = 0x02;
+        cpu.accu = 0x05;
+        cpu.set_flag(true, mcs6502::STS_CAR_MASK);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x2001), 0x01);
+        assert_eq!(cpu.accu, 0x04);
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 7);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x2002), 0x01);
+        assert_eq!(cpu.accu, 0x03);
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 14);
+
+        // Reads across the page.
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x09);
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 19);
+
+        // BBS7 and NOPs on the 65C02.
+        let instructions = vec![
+            ops::cmos::BBS7_ZERO_PAGE_RELATIVE, 0x10, 0x03,
+            0xFC, 0x00, 0x20,
+            0xFC, 0x00, 0x20,
+            0xFB
+        ];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
 
         cpu.boot(&cart);
-        cpu.memory().write_u8(addr, 0x48);
-        cpu.memory().write_u8(addr + 1, 0x45);
-        cpu.memory().write_u8(addr + 2, 0x4C);
-        cpu.memory().write_u8(addr + 3, 0x4C);
-        cpu.memory().write_u8(addr + 4, 0x4F);
-        cpu.memory().write_u8(addr + 5, 0x00);
+        cpu.set_variant(Variant::Cmos);
+        cpu.memory().write_u8(0x0010, 0x80);
 
-        cpu.execute();
-        // TODO: Make PRT use a display device (by default some
-        //       stdout wrapper), so we can test this properly.
-        //assert!(false);
+        // Taken, ISC $0310,X would have changed the memory.
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x06);
+        assert_eq!(cpu.memory().read_u8(0x0310), 0x00);
+        assert_eq!(cpu.memory().read_u8(0x0010), 0x80);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x09);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x0A);
     }
 
     #[test]
-    fn prg_function_call() {
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::LDX_IMMEDIATE);
-        instructions.push(0xAB);
-        instructions.push(ops::JSR_ABSOLUTE);
-        instructions.push(0x34);
-        instructions.push(0x12);
-        instructions.push(ops::LDX_IMMEDIATE);
-        instructions.push(0xBC);
+    fn op_lax_sax() {
+        let instructions = vec![
+            ops::LAX_ZERO_PAGE, 0x0A,
+            ops::SAX_ZERO_PAGE, 0x0B
+        ];
 
         let cart = Rom8b::from_vec(instructions);
         let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
 
         cpu.boot(&cart);
+        cpu.memory().write_u8(0x0A, 0xF3);
 
-        // Create the function.
-        let mut instructions: Vec<u8> = Vec::new();
-        instructions.push(ops::LDY_IMMEDIATE);
-        instructions.push(0x3D);
-        instructions.push(ops::RTS_IMPLIED);
-        let func = Rom8b::from_vec(instructions);
-        cpu.memory().map(0x1234, &func);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.accu, 0xF3);
+        assert_eq!(cpu.idx_x, 0xF3);
+        assert!(cpu.get_flag(mcs6502::STS_NEG_MASK));
 
-        // Note: After it executes everything, mem[pc] is equal
-        //       to 0, which will execute BRK, which loads the
-        //       value at mem[INT_REQ_ADDRESS], resetting the
-        //       sequence.
-        for _ in 0..5 {
-            cpu.execute();
-        }
+        cpu.idx_x = 0x3C;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x0B), 0xF3 & 0x3C);
+    }
 
-        assert_eq!(cpu.idx_x, 0xBC);
-        assert_eq!(cpu.idx_y, 0x3D);
+    #[test]
+    fn op_dcp_isc() {
+        let instructions = vec![
+            ops::DCP_ZERO_PAGE, 0x0A,
+            ops::ISC_ZERO_PAGE, 0x0B
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.memory().write_u8(0x0A, 0x06);
+        cpu.memory().write_u8(0x0B, 0x01);
+        cpu.accu = 0x05;
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x0A), 0x05);
+        assert!(cpu.get_flag(mcs6502::STS_ZER_MASK));
+        assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x0B), 0x02);
+        assert_eq!(cpu.accu, 0x03);
+    }
+
+    #[test]
+    fn op_slo_rla_sre_rra() {
+        let instructions = vec![
+            ops::SLO_ZERO_PAGE, 0x0A,
+            ops::RLA_ZERO_PAGE, 0x0A,
+            ops::SRE_ZERO_PAGE, 0x0A,
+            ops::RRA_ZERO_PAGE, 0x0A
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.memory().write_u8(0x0A, 0x81);
+        cpu.accu = 0x10;
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x0A), 0x02);
+        assert_eq!(cpu.accu, 0x12);
+        assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x0A), 0x05);
+        assert_eq!(cpu.accu, 0x12 & 0x05);
+        assert!(!cpu.get_flag(mcs6502::STS_CAR_MASK));
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x0A), 0x02);
+        assert_eq!(cpu.accu, 0x02);
+        assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x0A), 0x81);
+        assert_eq!(cpu.accu, 0x02 + 0x81);
+    }
+
+    #[test]
+    fn op_immediate_undocumented() {
+        let instructions = vec![
+            ops::ANC_IMMEDIATE_0B, 0xF0,
+            ops::ALR_IMMEDIATE, 0x33,
+            ops::ARR_IMMEDIATE, 0xFF,
+            ops::SBX_IMMEDIATE, 0x02
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.accu = 0x8F;
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.accu, 0x80);
+        assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
+
+        cpu.accu = 0x0F;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.accu, 0x01);
+        assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
+
+        cpu.accu = 0xC0;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.accu, 0xE0);
+        assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
+        assert!(!cpu.get_flag(mcs6502::STS_OVF_MASK));
+
+        cpu.accu = 0x0F;
+        cpu.idx_x = 0x3C;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.idx_x, 0x0A);
+        assert!(cpu.get_flag(mcs6502::STS_CAR_MASK));
+    }
+
+    #[test]
+    fn op_nop_multi_byte() {
+        let instructions = vec![
+            ops::NOP_IMPLIED_1A,
+            ops::NOP_IMMEDIATE_80, 0xFF,
+            ops::NOP_ZERO_PAGE_X_14, 0xFF,
+            ops::NOP_ABSOLUTE_X_1C, 0xFF, 0x00
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.idx_x = 0x01;
+        cpu.run(4).unwrap();
+
+        assert_eq!(cpu.pc(), 8);
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 2 + 2 + 4 + 5);
+    }
+
+    #[test]
+    fn unstable_opcodes() {
+        let instructions = vec![
+            ops::LAX_IMMEDIATE, 0x0F,
+            ops::LAX_IMMEDIATE, 0x0F
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.accu = 0x01;
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.accu, (0x01 | mcs6502::UNSTABLE_MAGIC) & 0x0F);
+        assert_eq!(cpu.idx_x, cpu.accu);
+
+        cpu.set_unstable_opcodes(UnstableOpcodes::Nop);
+        cpu.accu = 0x01;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.accu, 0x01);
+        assert_eq!(cpu.pc(), 4);
+    }
+
+    #[test]
+    fn sha_pointer_wrap() {
+        let instructions = vec![ops::SHA_INDIRECT_Y, 0xFF];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        // High byte of the pointer is the opcode at $00.
+        cpu.boot(&cart);
+        cpu.memory().write_u8(0x00FF, 0x10);
+        cpu.memory().write_u8(0x0100, 0x20);
+        cpu.accu = 0xFF;
+        cpu.idx_x = 0xFF;
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x9310), 0x94);
+        assert_eq!(cpu.memory().read_u8(0x2010), 0x00);
+    }
+
+    #[test]
+    fn unstable_opcodes_trap() {
+        let instructions = vec![ops::ANE_IMMEDIATE, 0x0F];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_unstable_opcodes(UnstableOpcodes::Trap);
+
+        let error = cpu.execute().unwrap_err();
+        assert_eq!(error.fault, Fault::UnstableOpcode);
+        assert_eq!(error.opcode, ops::ANE_IMMEDIATE);
+        assert_eq!(cpu.pc(), 0);
+    }
+
+    #[test]
+    fn illegal_opcode() {
+        let instructions = vec![ops::NOP_IMPLIED, 0x02];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+
+        let error = cpu.run(2).unwrap_err();
+        assert_eq!(error, CpuError { pc: 1, opcode: 0x02, fault: Fault::IllegalOpcode });
+        assert_eq!(cpu.pc(), 1);
+    }
+
+    #[test]
+    fn memory_faults() {
+        let instructions = vec![
+            ops::STA_ABSOLUTE, 0x00, 0x10,
+            ops::LDA_ABSOLUTE, 0x00, 0x30
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(0x2000));
+
+        cpu.boot(&cart);
+
+        cpu.execute().unwrap();
+
+        let error = cpu.execute().unwrap_err();
+        assert_eq!(error.fault, Fault::Memory(MemoryError::BusError(0x3000)));
+        assert_eq!(error.pc, 3);
+
+        let mut rom = Rom8b::from_vec(vec![0x00]);
+        rom.write_u8(0x00, 0x42);
+        assert_eq!(rom.fault(), Some(MemoryError::ReadOnly(0x00)));
+        assert_eq!(rom.read_u8(0x00), 0x00);
+    }
+
+    #[test]
+    fn stack_checks() {
+        let instructions = vec![
+            ops::PLA_IMPLIED,
+            ops::PHA_IMPLIED,
+            ops::PHA_IMPLIED
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_stack_checks(true);
+
+        cpu.sp = 0xFF;
+        let error = cpu.execute().unwrap_err();
+        assert_eq!(error.fault, Fault::StackUnderflow);
+
+        cpu.pc = 1;
+        cpu.sp = 0x00;
+        let error = cpu.execute().unwrap_err();
+        assert_eq!(error.fault, Fault::StackOverflow);
+
+        cpu.set_stack_checks(false);
+        cpu.execute().unwrap();
+    }
+
+    #[test]
+    fn cycles_page_crossing() {
+        let instructions = vec![
+            ops::LDA_IMMEDIATE, 0x01,
+            ops::LDA_ABSOLUTE_X, 0x10, 0x12,
+            ops::LDA_ABSOLUTE_X, 0xFF, 0x12,
+            ops::STA_ABSOLUTE_X, 0xFF, 0x12
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.idx_x = 0x01;
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 2);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 2 + 4);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 2 + 4 + 5);
+
+        // Stores always take the extra cycle.
+        cpu.execute().unwrap();
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 2 + 4 + 5 + 5);
+    }
+
+    #[test]
+    fn cycles_branch() {
+        let instructions = vec![
+            ops::BEQ_RELATIVE, 0x0A,
+            ops::BEQ_RELATIVE, 0x0A
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+
+        // Not taken.
+        cpu.set_flag(false, mcs6502::STS_ZER_MASK);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 2);
+
+        // Taken, same page.
+        cpu.set_flag(true, mcs6502::STS_ZER_MASK);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 2 + 3);
+
+        // Taken, crossing to the previous page.
+        cpu.pc = 0x0200;
+        cpu.memory().write_u8(0x0200, ops::BEQ_RELATIVE);
+        cpu.memory().write_u8(0x0201, 0xF0);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 2 + 3 + 4);
+    }
+
+    #[test]
+    fn run_cycles() {
+        let instructions = vec![ops::INX_IMPLIED; 10];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.run_cycles(7).unwrap();
+
+        // Last instruction is not interrupted.
+        assert_eq!(cpu.idx_x, 4);
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 8);
+    }
+
+    #[test]
+    fn run_cycles_halts() {
+        let instructions = vec![
+            ops::INX_IMPLIED,
+            ops::BRK_IMPLIED,
+            ops::JAM_02
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_instruction_limit(Some(1));
+        cpu.run_cycles(100).unwrap();
+        assert_eq!(cpu.stop_reason(), Some(StopReason::InstructionLimit));
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 2);
+
+        cpu.set_instruction_limit(None);
+        cpu.set_brk_trap(true);
+        cpu.resume();
+        cpu.run_cycles(100).unwrap();
+        assert_eq!(cpu.stop_reason(), Some(StopReason::Break(1)));
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 2);
+
+        cpu.pc = 2;
+        cpu.set_jam_halts(true);
+        cpu.resume();
+        cpu.run_cycles(100).unwrap();
+        assert_eq!(cpu.stop_reason(), Some(StopReason::Jam(2)));
+        assert_eq!(cpu.pc(), 2);
+    }
+
+    #[test]
+    fn prg_function_call() {
+        let instructions = vec![
+            ops::LDX_IMMEDIATE, 0xAB,
+            ops::JSR_ABSOLUTE, 0x34, 0x12,
+            ops::LDX_IMMEDIATE, 0xBC
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+
+        // Create the function.
+        let instructions = vec![
+            ops::LDY_IMMEDIATE, 0x3D,
+            ops::RTS_IMPLIED
+        ];
+        let func = Rom8b::from_vec(instructions);
+        cpu.memory().map(0x1234, &func);
+
+        // Note: After it executes everything, mem[pc] is equal
+        //       to 0, which will execute BRK, which loads the
+        //       value at mem[INT_REQ_ADDRESS], resetting the
+        //       sequence.
+        for _ in 0..5 {
+            cpu.execute().unwrap();
+        }
+
+        assert_eq!(cpu.idx_x, 0xBC);
+        assert_eq!(cpu.idx_y, 0x3D);
+    }
+
+    #[test]
+    fn cmos_bra_stz() {
+        let instructions = vec![
+            ops::cmos::BRA_RELATIVE, 0x02,
+            ops::NOP_IMPLIED,
+            ops::NOP_IMPLIED,
+            ops::cmos::STZ_ZERO_PAGE, 0x20,
+            ops::cmos::STZ_ABSOLUTE_X, 0x00, 0x03
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_variant(Variant::Cmos);
+        cpu.idx_x = 0x05;
+        cpu.memory().write_u8(0x20, 0xAB);
+        cpu.memory().write_u8(0x0305, 0xCD);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 4);
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 3);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x20), 0x00);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x0305), 0x00);
+    }
+
+    #[test]
+    fn cmos_stack_index() {
+        let instructions = vec![
+            ops::cmos::PHX_IMPLIED,
+            ops::cmos::PHY_IMPLIED,
+            ops::cmos::PLX_IMPLIED,
+            ops::cmos::PLY_IMPLIED
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_variant(Variant::Cmos);
+        cpu.idx_x = 0x80;
+        cpu.idx_y = 0x00;
+
+        cpu.run(4).unwrap();
+        assert_eq!(cpu.idx_x, 0x00);
+        assert_eq!(cpu.idx_y, 0x80);
+        assert!(cpu.get_flag(mcs6502::STS_NEG_MASK));
+        assert!(!cpu.get_flag(mcs6502::STS_ZER_MASK));
+    }
+
+    #[test]
+    fn cmos_tsb_trb() {
+        let instructions = vec![
+            ops::cmos::TSB_ZERO_PAGE, 0x20,
+            ops::cmos::TRB_ABSOLUTE, 0x00, 0x03
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_variant(Variant::Cmos);
+        cpu.accu = 0x0F;
+        cpu.memory().write_u8(0x20, 0xF0);
+        cpu.memory().write_u8(0x0300, 0x3C);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x20), 0xFF);
+        assert!(cpu.get_flag(mcs6502::STS_ZER_MASK));
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x0300), 0x30);
+        assert!(!cpu.get_flag(mcs6502::STS_ZER_MASK));
+    }
+
+    #[test]
+    fn cmos_zero_page_indirect() {
+        let instructions = vec![
+            ops::cmos::LDA_ZERO_PAGE_INDIRECT, 0x10,
+            ops::cmos::STA_ZERO_PAGE_INDIRECT, 0x20
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_variant(Variant::Cmos);
+
+        cpu.memory().write_u16(0x10, 0x1234);
+        cpu.memory().write_u8(0x1234, 0x42);
+        cpu.memory().write_u16(0x20, 0x0400);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.accu, 0x42);
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 5);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x0400), 0x42);
+    }
+
+    #[test]
+    fn cmos_bit_instructions() {
+        let instructions = vec![
+            ops::cmos::SMB3_ZERO_PAGE, 0x20,
+            ops::cmos::BBS3_ZERO_PAGE_RELATIVE, 0x20, 0x02,
+            ops::NOP_IMPLIED,
+            ops::NOP_IMPLIED,
+            ops::cmos::RMB3_ZERO_PAGE, 0x20,
+            ops::cmos::BBR3_ZERO_PAGE_RELATIVE, 0x20, 0x02
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_variant(Variant::Cmos);
+        cpu.memory().write_u8(0x20, 0x81);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x20), 0x89);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 7);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory().read_u8(0x20), 0x81);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 14);
+    }
+
+    #[test]
+    fn jmp_indirect_page_wrap() {
+        let instructions = vec![ops::JMP_INDIRECT, 0xFF, 0x02];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.memory().write_u8(0x02FF, 0x34);
+        cpu.memory().write_u8(0x0300, 0x12);
+        cpu.memory().write_u8(0x0200, 0x56);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x5634);
+
+        cpu.restart();
+        cpu.set_variant(Variant::Cmos);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x1234);
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 6);
+    }
+
+    #[test]
+    fn cmos_jmp_indexed_indirect() {
+        let instructions = vec![ops::cmos::JMP_ABSOLUTE_INDIRECT_X, 0x00, 0x03];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_variant(Variant::Cmos);
+        cpu.idx_x = 0x02;
+        cpu.memory().write_u16(0x0302, 0x1234);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x1234);
+    }
+
+    #[test]
+    fn cmos_wai_stp() {
+        let instructions = vec![
+            ops::cmos::WAI_IMPLIED,
+            ops::cmos::STP_IMPLIED
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_variant(Variant::Cmos);
+
+        cpu.execute().unwrap();
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 1);
+
+        // Masked interrupt only resumes execution.
+        cpu.interrupt();
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 2);
+        assert!(!cpu.running());
+        assert_eq!(cpu.stop_reason(), Some(StopReason::Stop(1)));
+
+        // Only a reset resumes the cpu.
+        cpu.resume();
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 2);
+
+        cpu.restart();
+        assert!(cpu.running());
+    }
+
+    #[test]
+    fn halts() {
+        let instructions = vec![
+            ops::INX_IMPLIED,
+            ops::BRK_IMPLIED,
+            ops::NOP_IMPLIED,
+            ops::JAM_02
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_brk_trap(true);
+        cpu.set_jam_halts(true);
+
+        // BRK is not executed.
+        cpu.run(3).unwrap();
+        assert_eq!(cpu.stop_reason(), Some(StopReason::Break(1)));
+        assert_eq!(cpu.pc(), 1);
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 2);
+        assert_eq!(cpu.instructions(), 1);
+
+        cpu.pc = 2;
+        cpu.resume();
+        cpu.run(3).unwrap();
+        assert_eq!(cpu.stop_reason(), Some(StopReason::Jam(3)));
+        assert_eq!(format!("{}", cpu.stop_reason().unwrap()), "jammed at 0x0003");
+
+        // JAM needs a reset.
+        cpu.resume();
+        assert!(!cpu.running());
+        cpu.restart();
+        assert_eq!(cpu.stop_reason(), None);
+
+        // Without the halt JAM stays an illegal opcode.
+        cpu.set_jam_halts(false);
+        cpu.pc = 3;
+        assert!(cpu.execute().is_err());
+    }
+
+    #[test]
+    fn limits() {
+        let instructions = vec![
+            ops::INX_IMPLIED,
+            ops::JMP_ABSOLUTE, 0x00, 0x00
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_instruction_limit(Some(5));
+        while cpu.running() {
+            cpu.execute().unwrap();
+        }
+        assert_eq!(cpu.stop_reason(), Some(StopReason::InstructionLimit));
+        assert_eq!(cpu.instructions(), 5);
+        assert_eq!(cpu.idx_x, 3);
+
+        // The limit counts from the reset.
+        cpu.restart();
+        cpu.set_instruction_limit(None);
+        cpu.set_cycle_limit(Some(mcs6502::RESET_CYCLES + 10));
+        while cpu.running() {
+            cpu.execute().unwrap();
+        }
+        assert_eq!(cpu.stop_reason(), Some(StopReason::CycleLimit));
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 10);
+
+        // Host halts can be resumed once the limit is raised.
+        cpu.set_cycle_limit(Some(mcs6502::RESET_CYCLES + 20));
+        cpu.resume();
+        assert!(cpu.running());
+    }
+
+    #[test]
+    fn self_loop() {
+        let instructions = vec![
+            ops::LDX_IMMEDIATE, 0x02,
+            ops::DEX_IMPLIED,
+            ops::BNE_RELATIVE, 0xFD,
+            ops::JMP_ABSOLUTE, 0x05, 0x00
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_self_loop_halts(true);
+        while cpu.running() {
+            cpu.execute().unwrap();
+        }
+        assert_eq!(cpu.stop_reason(), Some(StopReason::SelfLoop(5)));
+        assert_eq!(cpu.instructions(), 6);
+    }
+
+    #[test]
+    fn reset() {
+        let instructions = vec![
+            ops::JMP_ABSOLUTE, 0x00, 0x20
+        ];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_variant(Variant::Cmos);
+        assert_eq!(cpu.sp, 0xFD);
+
+        // Programs can leave the rom.
+        cpu.memory().write_u8(0x2000, ops::LDA_IMMEDIATE);
+        cpu.memory().write_u8(0x2001, 0x42);
+        cpu.run(2).unwrap();
+        assert_eq!(cpu.pc, 0x2002);
+        assert!(cpu.running());
+
+        // Registers are kept, D is cleared on the 65C02.
+        cpu.set_flag(true, mcs6502::STS_DEC_MASK);
+        cpu.restart();
+        assert_eq!(cpu.pc, 0x0000);
+        assert_eq!(cpu.accu, 0x42);
+        assert!(!cpu.get_flag(mcs6502::STS_DEC_MASK));
+        assert!(cpu.get_flag(mcs6502::STS_INT_MASK));
+        assert_eq!(cpu.instructions(), 0);
+
+        // Each reset moves the stack pointer.
+        assert_eq!(cpu.sp, 0xFA);
+    }
+
+    #[test]
+    fn cmos_interrupt_clears_decimal() {
+        let instructions = vec![ops::BRK_IMPLIED];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_flag(true, mcs6502::STS_DEC_MASK);
+        cpu.execute().unwrap();
+        assert!(cpu.get_flag(mcs6502::STS_DEC_MASK));
+
+        cpu.restart();
+        cpu.set_variant(Variant::Cmos);
+        cpu.execute().unwrap();
+        assert!(!cpu.get_flag(mcs6502::STS_DEC_MASK));
+    }
+
+    #[test]
+    fn cmos_undefined_nops() {
+        let instructions = vec![0x03, 0x02, 0xFF, 0x5C, 0x00, 0x00];
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+
+        cpu.boot(&cart);
+        cpu.set_variant(Variant::Cmos);
+
+        cpu.run(3).unwrap();
+        assert_eq!(cpu.pc(), 6);
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 1 + 2 + 8);
+    }
+
+    #[test]
+    fn bus_read_only() {
+        let instructions = vec![
+            ops::LDA_IMMEDIATE, 0x42,
+            ops::STA_ABSOLUTE, 0x00, 0xF0,
+            ops::LDA_ABSOLUTE, 0x00, 0xF0
+        ];
+
+        // Reset vector points to the start of ram.
+        let mut rom = vec![0x24; 0x1000];
+        rom[0x0FFC] = 0x00;
+        rom[0x0FFD] = 0x00;
+
+        let mut bus = Bus8b::new(64 * 1024);
+        bus.attach(0x0000, Box::new(Ram8b::new(0x8000)));
+        bus.attach(0xF000, Box::new(Rom8b::from_vec(rom)));
+
+        let cart = Rom8b::from_vec(instructions);
+        let mut cpu = Mcs6502::new(bus);
+
+        cpu.boot(&cart);
+        cpu.execute().unwrap();
+
+        let error = cpu.execute().unwrap_err();
+        assert_eq!(error.fault, Fault::Memory(MemoryError::ReadOnly(0xF000)));
+        assert_eq!(error.pc, 0x02);
+
+        // The write was dropped.
+        cpu.pc = 0x05;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.accu, 0x24);
+    }
+
+    #[test]
+    fn rom_map_address() {
+        // LDA #$42 at $C000, reset vector at the end of the rom.
+        let mut rom = vec![0x00; 0x4000];
+        rom[0x0000] = ops::LDA_IMMEDIATE;
+        rom[0x0001] = 0x42;
+        rom[0x3FFC] = 0x00;
+        rom[0x3FFD] = 0xC0;
+
+        let cart = Rom8b::from_vec(rom);
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+        cpu.set_rom_map_address(0xC000);
+
+        cpu.boot(&cart);
+        assert_eq!(cpu.pc, 0xC000);
+        assert!(cpu.running());
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.accu, 0x42);
+        assert_eq!(cpu.pc, 0xC002);
+    }
+
+    #[test]
+    fn save_state() {
+        let program = vec![
+            ops::LDA_IMMEDIATE, 0x42,
+            ops::STA_ZERO_PAGE, 0x80,
+            ops::INX_IMPLIED,
+            ops::INY_IMPLIED,
+            ops::ADC_IMMEDIATE, 0x01
+        ];
+
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+        cpu.boot(&Rom8b::from_vec(program));
+        cpu.set_unstable_opcodes(UnstableOpcodes::Trap);
+        cpu.run(3).unwrap();
+
+        let state = cpu.save_state();
+        assert_eq!(&state[0..4], b"DZST");
+
+        let mut other = Mcs6502::new(Ram8b::new(64 * 1024));
+        other.load_state(&state).unwrap();
+        assert_eq!(other.save_state(), state);
+        assert_eq!(other.registers(), cpu.registers());
+        assert_eq!(other.cycles(), cpu.cycles());
+        assert_eq!(other.addr_mode, AddressMode::Implied);
+        assert_eq!(other.memory().read_u8(0x80), 0x42);
+
+        // Both continue the same way.
+        cpu.run(2).unwrap();
+        other.run(2).unwrap();
+        assert_eq!(other.save_state(), cpu.save_state());
+
+        let mut small = Mcs6502::new(Ram8b::new(0x100));
+        assert!(small.load_state(&state).is_err());
+        assert!(other.load_state(&state[..state.len() - 1]).is_err());
+        assert!(other.load_state(b"NOPE").is_err());
+
+        for (i, &mode) in ADDRESS_MODES.iter().enumerate() {
+            assert_eq!(mode as usize, i);
+        }
+    }
+
+    #[test]
+    fn save_state_devices() {
+        struct Shared(Rc<RefCell<Vec<u8>>>);
+
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // RAM, the console and a rom at $FF00.
+        fn console_cpu(output: Rc<RefCell<Vec<u8>>>, input: mpsc::Receiver<u8>,
+                       rom: u8) -> Mcs6502<Bus8b> {
+            let console = Console::new(Box::new(Shared(output)), input);
+            let end = console::CONSOLE_ADDRESS + console::CONSOLE_SIZE;
+
+            let mut bus = Bus8b::new(64 * 1024);
+            bus.attach(0x0000, Box::new(Ram8b::new(console::CONSOLE_ADDRESS)));
+            bus.attach(console::CONSOLE_ADDRESS, Box::new(console));
+            bus.attach(end, Box::new(Ram8b::new(0xFF00 - end)));
+            bus.attach(0xFF00, Box::new(Rom8b::from_vec(vec![rom; 0x100])));
+            Mcs6502::new(bus)
+        }
+
+        let program = vec![
+            ops::LDA_IMMEDIATE, 0x42,
+            ops::STA_ZERO_PAGE, 0x80
+        ];
+
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let (sender, receiver) = mpsc::channel();
+        let mut cpu = console_cpu(output.clone(), receiver, 0x00);
+        cpu.boot(&Rom8b::from_vec(program));
+        cpu.run(2).unwrap();
+
+        // Saving leaves the input to the program.
+        sender.send(b'A').unwrap();
+        let state = cpu.save_state();
+        assert_eq!(cpu.memory().read_u8(console::CONSOLE_ADDRESS + console::GETCHAR), b'A');
+
+        // Loading doesn't print or fault and keeps the rom.
+        let restored = Rc::new(RefCell::new(Vec::new()));
+        let mut other = console_cpu(restored.clone(), mpsc::channel().1, 0xEA);
+        other.load_state(&state).unwrap();
+        assert!(restored.borrow().is_empty());
+        assert_eq!(other.memory().fault(), None);
+        assert_eq!(other.memory().read_u8(0x80), 0x42);
+        assert_eq!(other.memory().read_u8(0xFF00), 0xEA);
+        assert_eq!(other.registers(), cpu.registers());
+        assert!(output.borrow().is_empty());
+    }
+
+    #[test]
+    fn trace() {
+        // Keeps the written trace readable by the test.
+        struct Shared(Rc<RefCell<Vec<u8>>>);
+
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let program = vec![
+            ops::LDA_IMMEDIATE, 0x42,
+            ops::STA_ABSOLUTE, 0x00, 0x02,
+            ops::TAX_IMPLIED
+        ];
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+        cpu.boot(&Rom8b::from_vec(program));
+        cpu.set_trace(Some(Box::new(Shared(log.clone()))));
+        cpu.run(3).unwrap();
+
+        cpu.set_trace(None);
+        cpu.execute().unwrap();
+
+        let trace = String::from_utf8(log.borrow().clone()).unwrap();
+        assert_eq!(trace, "\
+0000  A9 42     LDA #$42                        A:00 X:00 Y:00 P:24 SP:FD CYC:7
+0002  8D 00 02  STA $0200                       A:42 X:00 Y:00 P:24 SP:FD CYC:9
+0005  AA        TAX                             A:42 X:00 Y:00 P:24 SP:FD CYC:13
+");
+    }
+
+    // NOPs at $0200, IRQ handler at $0300 and NMI handler at
+    // $0400 made of NOPs followed by RTI.
+    fn interrupt_cpu(variant: Variant) -> Mcs6502<Ram8b> {
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+        cpu.set_variant(variant);
+
+        for addr in 0x0200..0x0210 {
+            cpu.memory().write_u8(addr, ops::NOP_IMPLIED);
+        }
+
+        for &handler in [0x0300, 0x0400].iter() {
+            cpu.memory().write_u8(handler, ops::NOP_IMPLIED);
+            cpu.memory().write_u8(handler + 1, ops::RTI_IMPLIED);
+        }
+
+        cpu.memory().write_u16(mcs6502::INT_REQ_ADDRESS, 0x0300);
+        cpu.memory().write_u16(mcs6502::INT_NOMASK_ADDRESS, 0x0400);
+        cpu.pc = 0x0200;
+        cpu.sp = 0xFF;
+        cpu
+    }
+
+    #[test]
+    fn irq_line() {
+        let mut cpu = interrupt_cpu(Variant::Nmos);
+
+        // Masked line is ignored.
+        cpu.set_flag(true, mcs6502::STS_INT_MASK);
+        cpu.set_irq(3, true);
+        cpu.run(2).unwrap();
+        assert_eq!(cpu.pc(), 0x0202);
+
+        // CLI takes effect after the next instruction.
+        cpu.memory().write_u8(0x0202, ops::CLI_IMPLIED);
+        cpu.run(2).unwrap();
+        assert_eq!(cpu.pc(), 0x0204);
+
+        let cycles = cpu.cycles();
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x0300);
+        assert_eq!(cpu.cycles(), cycles + 7);
+        assert!(cpu.get_flag(mcs6502::STS_INT_MASK));
+        assert_eq!(cpu.memory().read_u8(0x01FF), 0x02);
+        assert_eq!(cpu.memory().read_u8(0x01FE), 0x04);
+        assert_eq!(cpu.memory().read_u8(0x01FD), mcs6502::STS_EXP_MASK);
+
+        // Level triggered, RTI unmasks it right away.
+        cpu.run(2).unwrap();
+        assert_eq!(cpu.pc(), 0x0204);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x0300);
+
+        cpu.set_irq(3, false);
+        assert!(!cpu.irq_active());
+        cpu.run(3).unwrap();
+        assert_eq!(cpu.pc(), 0x0205);
+    }
+
+    #[test]
+    fn nmi_edge() {
+        let mut cpu = interrupt_cpu(Variant::Nmos);
+        cpu.set_flag(true, mcs6502::STS_INT_MASK);
+
+        // Serviced after the next instruction even if masked.
+        cpu.set_nmi(true);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x0201);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x0400);
+        assert_eq!(cpu.memory().read_u8(0x01FD),
+                   mcs6502::STS_EXP_MASK | mcs6502::STS_INT_MASK);
+
+        // Holding the line does not retrigger it.
+        cpu.run(2).unwrap();
+        assert_eq!(cpu.pc(), 0x0201);
+        cpu.run(2).unwrap();
+        assert_eq!(cpu.pc(), 0x0203);
+
+        cpu.set_nmi(false);
+        cpu.set_nmi(true);
+        cpu.run(2).unwrap();
+        assert_eq!(cpu.pc(), 0x0400);
+    }
+
+    #[test]
+    fn brk_hijack() {
+        // NMI arriving during BRK takes its vector, the pushed
+        // status still has B set.
+        let mut cpu = interrupt_cpu(Variant::Nmos);
+        cpu.memory().write_u8(0x0200, ops::BRK_IMPLIED);
+        cpu.set_nmi(true);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x0400);
+        assert_eq!(cpu.memory().read_u8(0x01FE), 0x02);
+        assert_ne!(cpu.memory().read_u8(0x01FD) & mcs6502::STS_BRK_MASK, 0);
+
+        cpu.run(2).unwrap();
+        assert_eq!(cpu.pc(), 0x0202);
+
+        // The 65C02 finishes BRK and takes the NMI afterwards.
+        let mut cpu = interrupt_cpu(Variant::Cmos);
+        cpu.memory().write_u8(0x0200, ops::BRK_IMPLIED);
+        cpu.set_nmi(true);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x0300);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x0400);
+    }
+
+    #[test]
+    fn irq_hijack() {
+        let mut cpu = interrupt_cpu(Variant::Cmos);
+        cpu.set_irq(0, true);
+        cpu.execute().unwrap();
+
+        // NMI raised before the IRQ sequence fetches the vector.
+        cpu.set_nmi(true);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x0400);
+        assert_eq!(cpu.memory().read_u8(0x01FD) & mcs6502::STS_BRK_MASK, 0);
     }
 }
@@ -1,16 +1,100 @@
+use std::error::Error;
+use std::fmt;
+
 use mems::Memory;
+use mems::MemoryError;
 
 pub mod mcs6502;
+pub mod harness;
+pub mod state;
+
+// Reason the cpu could not execute an instruction.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Fault {
+    IllegalOpcode,
+
+    // Unstable opcode with trapping enabled.
+    UnstableOpcode,
+
+    Memory(MemoryError),
+
+    // Only reported if stack checks are enabled.
+    StackOverflow,
+    StackUnderflow
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Fault::IllegalOpcode     => write!(f, "illegal opcode"),
+            Fault::UnstableOpcode    => write!(f, "unstable opcode"),
+            Fault::Memory(ref error) => write!(f, "{}", error),
+            Fault::StackOverflow     => write!(f, "stack overflow"),
+            Fault::StackUnderflow    => write!(f, "stack underflow")
+        }
+    }
+}
+
+// Fault along with the instruction that caused it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CpuError {
+    pub pc: usize,
+    pub opcode: u8,
+    pub fault: Fault
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (PC: 0x{:04X}, opcode: 0x{:02X})", self.fault, self.pc, self.opcode)
+    }
+}
+
+impl Error for CpuError {}
+
+// Why the cpu stopped running, pc is the address of the
+// instruction that halted it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StopReason {
+    // JAM opcode of the NMOS 6502 or STP of the 65C02,
+    // only a reset resumes the cpu.
+    Jam(usize),
+    Stop(usize),
+
+    // Host trap on BRK, the instruction is not executed.
+    Break(usize),
+
+    // Jump or branch to itself.
+    SelfLoop(usize),
+
+    CycleLimit,
+    InstructionLimit
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StopReason::Jam(pc)          => write!(f, "jammed at 0x{:04X}", pc),
+            StopReason::Stop(pc)         => write!(f, "stopped at 0x{:04X}", pc),
+            StopReason::Break(pc)        => write!(f, "break at 0x{:04X}", pc),
+            StopReason::SelfLoop(pc)     => write!(f, "endless loop at 0x{:04X}", pc),
+            StopReason::CycleLimit       => write!(f, "cycle limit reached"),
+            StopReason::InstructionLimit => write!(f, "instruction limit reached")
+        }
+    }
+}
 
 pub trait Cpu<M: Memory> {
     fn memory(&mut self) -> &mut M;
     fn boot(&mut self, &Memory);
     fn restart(&mut self);
-    fn execute(&mut self);
-    fn run(&mut self, usize);
+    fn execute(&mut self) -> Result<(), CpuError>;
+    fn run(&mut self, count: usize) -> Result<(), CpuError>;
+    fn run_cycles(&mut self, count: u64) -> Result<(), CpuError>;
+    fn cycles(&self) -> u64;
     fn dump(&self);
     fn pc(&self) -> usize;
     fn running(&self) -> bool;
+    fn stop_reason(&self) -> Option<StopReason>;
 }
 
 pub trait Stack {
@@ -0,0 +1,189 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+
+// Save state layout, values are little endian:
+//   magic  4 bytes "DZST"
+//   version u16
+//   machine u8
+//   machine specific payload
+pub const MAGIC: &[u8; 4] = b"DZST";
+pub const VERSION: u16 = 3;
+
+// Machine ids stored in the header.
+pub const MACHINE_MCS6502: u8 = 0x01;
+
+#[derive(Debug)]
+pub enum StateError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u16),
+    WrongMachine(u8),
+
+    // Data ended before the whole state was read.
+    Truncated,
+
+    // Field with a value that cannot be restored.
+    Invalid(&'static str)
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::Io(ref error)              => write!(f, "{}", error),
+            StateError::BadMagic                   => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version {}", version)
+            }
+            StateError::WrongMachine(machine)      => {
+                write!(f, "save state of another machine (id {})", machine)
+            }
+            StateError::Truncated                  => write!(f, "truncated save state"),
+            StateError::Invalid(field)             => write!(f, "invalid value of {}", field)
+        }
+    }
+}
+
+impl Error for StateError {}
+
+impl From<io::Error> for StateError {
+    fn from(error: io::Error) -> StateError {
+        StateError::Io(error)
+    }
+}
+
+pub struct StateWriter {
+    data: Vec<u8>
+}
+
+impl StateWriter {
+    pub fn new(machine: u8) -> StateWriter {
+        let mut writer = StateWriter { data: Vec::new() };
+        writer.bytes(MAGIC);
+        writer.u16(VERSION);
+        writer.u8(machine);
+
+        writer
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    idx: usize
+}
+
+impl<'a> StateReader<'a> {
+    // Checks the header.
+    pub fn new(data: &'a [u8], machine: u8) -> Result<StateReader<'a>, StateError> {
+        let mut reader = StateReader { data, idx: 0 };
+
+        if reader.bytes(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(StateError::BadMagic);
+        }
+
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let id = reader.u8()?;
+        if id != machine {
+            return Err(StateError::WrongMachine(id));
+        }
+
+        Ok(reader)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Invalid("flag"))
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.bytes(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, StateError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, StateError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn bytes(&mut self, count: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() - self.idx < count {
+            return Err(StateError::Truncated);
+        }
+
+        let bytes = &self.data[self.idx..self.idx + count];
+        self.idx += count;
+        Ok(bytes)
+    }
+
+    // Trailing data means the state was not written by us.
+    pub fn finish(self) -> Result<(), StateError> {
+        if self.idx == self.data.len() {
+            Ok(())
+        } else {
+            Err(StateError::Invalid("state size"))
+        }
+    }
+}
+
+pub fn write_file(name: &str, state: &[u8]) -> Result<(), StateError> {
+    let mut file = File::create(name)?;
+    file.write_all(state)?;
+
+    Ok(())
+}
+
+pub fn read_file(name: &str) -> Result<Vec<u8>, StateError> {
+    let mut state = Vec::new();
+    File::open(name)?.read_to_end(&mut state)?;
+
+    Ok(state)
+}
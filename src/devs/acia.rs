@@ -0,0 +1,359 @@
+use std::cell::Cell;
+use std::io::Write;
+use std::sync::mpsc::Receiver;
+
+use mems::Memory;
+
+// Registers relative to the start of the device.
+pub const DATA:    usize = 0x00;
+pub const STATUS:  usize = 0x01;
+pub const COMMAND: usize = 0x02;
+pub const CONTROL: usize = 0x03;
+
+pub const ACIA_SIZE: usize = 0x04;
+
+// Status register bits.
+pub const STS_OVERRUN: u8 = 1 << 2;
+pub const STS_RDRF:    u8 = 1 << 3;
+pub const STS_TDRE:    u8 = 1 << 4;
+pub const STS_IRQ:     u8 = 1 << 7;
+
+// Command register fields.
+const CMD_DTR:       u8 = 1 << 0;
+const CMD_NO_RX_IRQ: u8 = 1 << 1;
+const CMD_TX_SHIFT:  u8 = 2;
+const CMD_TX_MASK:   u8 = 0x03;
+const CMD_ECHO:      u8 = 1 << 4;
+
+const CMD_PARITY:    u8 = 1 << 5;
+
+// Transmitter control with the TX interrupt enabled.
+const TX_IRQ:        u8 = 1;
+
+// Control register fields.
+const CTL_BAUD_MASK:  u8 = 0x0F;
+const CTL_WORD_SHIFT: u8 = 5;
+const CTL_WORD_MASK:  u8 = 0x03;
+const CTL_STOP_BITS:  u8 = 1 << 7;
+
+// Baud rates selected by the control register, the external
+// clock is taken as the fastest common rate.
+const BAUD_RATES: [u32; 16] = [
+    115_200, 50, 75, 110, 135, 150, 300, 600,
+    1_200, 1_800, 2_400, 3_600, 4_800, 7_200, 9_600, 19_200
+];
+
+// 6551 Asynchronous Communications Interface Adapter. Bytes
+// are received one at a time from the input and sent as soon
+// as they are written, there are no framing or parity errors
+// and the modem lines are always active. Transfers can be
+// paced to take as long as on a real serial line.
+pub struct Acia {
+    output: Box<dyn Write>,
+    input: Receiver<u8>,
+    rx_data: u8,
+    command: u8,
+    control: u8,
+
+    // Pacing, the clock is that of the cpu.
+    clock_hz: Option<u64>,
+    baud: Option<u32>,
+    tx_wait: u64,
+    rx_wait: u64,
+
+    // Reads change these.
+    status: Cell<u8>,
+    irq: Cell<bool>
+}
+
+impl Memory for Acia {
+    fn write_u8(&mut self, addr: usize, data: u8) {
+        match addr {
+            DATA    => self.transmit(data),
+
+            // Programmed reset, the control register is kept.
+            STATUS  => {
+                let mask = CMD_DTR | CMD_NO_RX_IRQ | CMD_TX_MASK << CMD_TX_SHIFT | CMD_ECHO;
+                self.command &= !mask;
+                self.clear_status(STS_OVERRUN);
+                self.irq.set(false);
+            }
+
+            COMMAND => self.command = data,
+            CONTROL => self.control = data,
+            _       => ()
+        }
+    }
+
+    fn read_u8(&self, addr: usize) -> u8 {
+        match addr {
+            DATA    => {
+                self.clear_status(STS_RDRF | STS_OVERRUN);
+                self.rx_data
+            }
+
+            // Reading the status acknowledges the interrupt.
+            STATUS  => {
+                let status = if self.irq.get() {
+                    self.status.get() | STS_IRQ
+                } else {
+                    self.status.get()
+                };
+                self.irq.set(false);
+                status
+            }
+
+            COMMAND => self.command,
+            CONTROL => self.control,
+            _       => 0
+        }
+    }
+
+    fn write_u16(&mut self, addr: usize, data: u16) {
+        self.write_u8(addr, (data & 0xFF) as u8);
+        self.write_u8(addr + 1, (data >> 8) as u8);
+    }
+
+    fn read_u16(&self, addr: usize) -> u16 {
+        let lo = self.read_u8(addr) as u16;
+        let hi = self.read_u8(addr + 1) as u16;
+
+        (hi << 8) | lo
+    }
+
+    fn size(&self) -> usize {
+        ACIA_SIZE
+    }
+}
+
+impl Acia {
+    pub fn new(output: Box<dyn Write>, input: Receiver<u8>) -> Acia {
+        Acia {
+            output,
+            input,
+            rx_data: 0,
+            command: 0,
+            control: 0,
+            clock_hz: None,
+            baud: None,
+            tx_wait: 0,
+            rx_wait: 0,
+            status: Cell::new(STS_TDRE),
+            irq: Cell::new(false)
+        }
+    }
+
+    // State of the IRQ output.
+    pub fn irq(&self) -> bool {
+        self.irq.get()
+    }
+
+    // Paces transfers to the baud rate in the control register
+    // for a cpu running at the given clock, without one bytes
+    // are transferred right away.
+    pub fn set_pacing(&mut self, clock_hz: Option<u64>) {
+        self.clock_hz = clock_hz;
+    }
+
+    // Overrides the baud rate in the control register, e.g. for
+    // the WDC 65C51 whose programs wait out the transmission.
+    pub fn set_baud(&mut self, baud: Option<u32>) {
+        self.baud = baud;
+    }
+
+    // Receives the next byte once the previous one was read,
+    // the receiver is only enabled with DTR.
+    pub fn tick(&mut self, cycles: u64) {
+        if self.tx_wait > 0 {
+            self.tx_wait = self.tx_wait.saturating_sub(cycles);
+            if self.tx_wait == 0 {
+                self.set_status(STS_TDRE);
+                self.tx_interrupt();
+            }
+        }
+
+        self.rx_wait = self.rx_wait.saturating_sub(cycles);
+        if self.rx_wait > 0 || self.command & CMD_DTR == 0 || self.status.get() & STS_RDRF != 0 {
+            return;
+        }
+
+        let data = match self.input.try_recv() {
+            Ok(data) => data,
+            Err(_)   => return
+        };
+
+        self.rx_data = data;
+        self.rx_wait = self.frame_cycles();
+        self.set_status(STS_RDRF);
+        if self.command & CMD_NO_RX_IRQ == 0 {
+            self.irq.set(true);
+        }
+
+        if self.command & CMD_ECHO != 0 {
+            self.send(data);
+        }
+    }
+
+    // The transmitter is busy for the duration of the frame.
+    fn transmit(&mut self, data: u8) {
+        self.send(data);
+
+        self.tx_wait = self.frame_cycles();
+        if self.tx_wait > 0 {
+            self.clear_status(STS_TDRE);
+        } else {
+            self.tx_interrupt();
+        }
+    }
+
+    fn tx_interrupt(&mut self) {
+        let tx = (self.command >> CMD_TX_SHIFT) & CMD_TX_MASK;
+        if self.command & CMD_DTR != 0 && tx == TX_IRQ {
+            self.irq.set(true);
+        }
+    }
+
+    // Cycles it takes to transfer a byte with the start bit,
+    // parity and stop bits.
+    fn frame_cycles(&self) -> u64 {
+        let clock_hz = match self.clock_hz {
+            Some(clock_hz) => clock_hz,
+            None           => return 0
+        };
+
+        let baud = self.baud.unwrap_or(BAUD_RATES[(self.control & CTL_BAUD_MASK) as usize]);
+        let data = 8 - ((self.control >> CTL_WORD_SHIFT) & CTL_WORD_MASK) as u64;
+        let parity = if self.command & CMD_PARITY != 0 { 1 } else { 0 };
+        let stop = if self.control & CTL_STOP_BITS != 0 { 2 } else { 1 };
+
+        clock_hz * (1 + data + parity + stop) / baud as u64
+    }
+
+    fn send(&mut self, data: u8) {
+        // Programs are not told about failed output.
+        let _ = self.output.write_all(&[data]);
+        let _ = self.output.flush();
+    }
+
+    fn set_status(&self, flags: u8) {
+        self.status.set(self.status.get() | flags);
+    }
+
+    fn clear_status(&self, flags: u8) {
+        self.status.set(self.status.get() & !flags);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io;
+    use std::io::Write;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    use devs::acia;
+    use devs::acia::Acia;
+    use mems::Memory;
+
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn receive() {
+        let (sender, receiver) = mpsc::channel();
+        let mut acia = Acia::new(Box::new(io::sink()), receiver);
+        sender.send(b'A').unwrap();
+        sender.send(b'B').unwrap();
+
+        // The receiver needs DTR.
+        acia.tick(1);
+        assert_eq!(acia.read_u8(acia::STATUS), acia::STS_TDRE);
+
+        acia.write_u8(acia::COMMAND, 0x09);
+        acia.tick(1);
+        assert!(acia.irq());
+        assert_eq!(acia.read_u8(acia::STATUS),
+                   acia::STS_IRQ | acia::STS_RDRF | acia::STS_TDRE);
+        assert!(!acia.irq());
+
+        // The next byte waits until the first one is read.
+        acia.tick(1);
+        assert_eq!(acia.read_u8(acia::DATA), b'A');
+        assert_eq!(acia.read_u8(acia::STATUS), acia::STS_TDRE);
+        acia.tick(1);
+        assert_eq!(acia.read_u8(acia::DATA), b'B');
+
+        // Programmed reset disables the receiver.
+        sender.send(b'C').unwrap();
+        acia.write_u8(acia::STATUS, 0x00);
+        acia.tick(1);
+        assert_eq!(acia.read_u8(acia::COMMAND), 0x00);
+        assert_eq!(acia.read_u8(acia::STATUS), acia::STS_TDRE);
+    }
+
+    #[test]
+    fn transmit() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let (_, receiver) = mpsc::channel();
+        let mut acia = Acia::new(Box::new(Output(output.clone())), receiver);
+
+        acia.write_u8(acia::CONTROL, 0x1F);
+        acia.write_u8(acia::COMMAND, 0x0B);
+        acia.write_u8(acia::DATA, b'O');
+        assert!(!acia.irq());
+
+        // Transmit interrupts.
+        acia.write_u8(acia::COMMAND, 0x07);
+        acia.write_u8(acia::DATA, b'K');
+        assert!(acia.irq());
+
+        assert_eq!(acia.read_u8(acia::CONTROL), 0x1F);
+        assert_eq!(*output.borrow(), b"OK".to_vec());
+    }
+
+    #[test]
+    fn pacing() {
+        let (sender, receiver) = mpsc::channel();
+        let mut acia = Acia::new(Box::new(io::sink()), receiver);
+        sender.send(b'A').unwrap();
+        sender.send(b'B').unwrap();
+
+        // 10 bits at 9600 baud on a 1 MHz cpu.
+        acia.set_pacing(Some(1_000_000));
+        acia.write_u8(acia::CONTROL, 0x1E);
+        acia.write_u8(acia::COMMAND, 0x07);
+
+        acia.write_u8(acia::DATA, b'O');
+        assert_eq!(acia.read_u8(acia::STATUS), 0x00);
+        acia.tick(1040);
+        assert_eq!(acia.read_u8(acia::STATUS), acia::STS_RDRF);
+        acia.tick(1);
+        assert_eq!(acia.read_u8(acia::STATUS), acia::STS_IRQ | acia::STS_RDRF | acia::STS_TDRE);
+
+        // The next byte arrives a frame after the first one.
+        assert_eq!(acia.read_u8(acia::DATA), b'A');
+        acia.tick(1);
+        assert_eq!(acia.read_u8(acia::STATUS), acia::STS_TDRE);
+
+        // 11 bits with parity.
+        acia.set_baud(Some(110));
+        acia.write_u8(acia::COMMAND, 0x2B);
+        acia.tick(1039);
+        assert_eq!(acia.read_u8(acia::DATA), b'B');
+        acia.write_u8(acia::DATA, b'K');
+        acia.tick(99_999);
+        assert_eq!(acia.read_u8(acia::STATUS), 0x00);
+        acia.tick(1);
+        assert_eq!(acia.read_u8(acia::STATUS), acia::STS_TDRE);
+    }
+}
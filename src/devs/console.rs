@@ -0,0 +1,164 @@
+use std::io;
+use std::io::Write;
+use std::sync::mpsc::Receiver;
+
+use devs;
+use mems::Memory;
+
+// Usual location of the console, e.g. in py65 and the
+// EhBASIC and Wozmon ports for it.
+pub const CONSOLE_ADDRESS: usize = 0xF000;
+
+// Registers relative to the start of the device.
+pub const PUTCHAR: usize = 0x01;
+pub const GETCHAR: usize = 0x04;
+
+pub const CONSOLE_SIZE: usize = 0x10;
+
+// Character I/O device, writes to PUTCHAR print a character
+// and reads of GETCHAR return the next input character or 0
+// if there is none.
+pub struct Console {
+    output: Box<dyn Write>,
+    input: Receiver<u8>
+}
+
+impl Memory for Console {
+    fn write_u8(&mut self, addr: usize, data: u8) {
+        if addr == PUTCHAR {
+            // Programs are not told about failed output.
+            let _ = self.output.write_all(&[data]);
+            let _ = self.output.flush();
+        }
+    }
+
+    fn read_u8(&self, addr: usize) -> u8 {
+        if addr != GETCHAR {
+            return 0;
+        }
+
+        // Line based programs expect CR at the end of a line.
+        match self.input.try_recv() {
+            Ok(b'\n') => b'\r',
+            Ok(data)  => data,
+            Err(_)    => 0
+        }
+    }
+
+    // Leaves the input for the program.
+    fn peek(&self, _addr: usize) -> u8 {
+        0
+    }
+
+    fn write_u16(&mut self, addr: usize, data: u16) {
+        self.write_u8(addr, (data & 0xFF) as u8);
+        self.write_u8(addr + 1, (data >> 8) as u8);
+    }
+
+    fn read_u16(&self, addr: usize) -> u16 {
+        let lo = self.read_u8(addr) as u16;
+        let hi = self.read_u8(addr + 1) as u16;
+
+        (hi << 8) | lo
+    }
+
+    fn size(&self) -> usize {
+        CONSOLE_SIZE
+    }
+}
+
+impl Console {
+    pub fn new(output: Box<dyn Write>, input: Receiver<u8>) -> Console {
+        Console { output, input }
+    }
+
+    // Console of the terminal the emulator runs in.
+    pub fn stdio() -> Console {
+        Console::new(Box::new(io::stdout()), devs::stdin())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io;
+    use std::io::Write;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    use cpus::Cpu;
+    use cpus::mcs6502::Mcs6502;
+    use devs::console;
+    use devs::console::Console;
+    use inst::mcs6502::ops;
+    use mems::Memory;
+    use mems::bus::Bus8b;
+    use mems::ram::Ram8b;
+    use mems::rom::Rom8b;
+
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn registers() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let (sender, receiver) = mpsc::channel();
+        let mut console = Console::new(Box::new(Output(output.clone())), receiver);
+
+        console.write_u8(console::PUTCHAR, b'H');
+        console.write_u8(console::PUTCHAR - 1, b'X');
+        console.write_u8(console::PUTCHAR, b'I');
+        assert_eq!(*output.borrow(), b"HI".to_vec());
+
+        assert_eq!(console.read_u8(console::GETCHAR), 0x00);
+        sender.send(b'A').unwrap();
+        sender.send(b'\n').unwrap();
+        assert_eq!(console.peek(console::GETCHAR), 0x00);
+        assert_eq!(console.read_u8(console::GETCHAR), b'A');
+        assert_eq!(console.read_u8(console::GETCHAR), b'\r');
+        assert_eq!(console.read_u8(console::GETCHAR), 0x00);
+    }
+
+    #[test]
+    fn echo() {
+        // Echoes the input until it reads a CR.
+        let rom = vec![
+            ops::LDA_ABSOLUTE, 0x04, 0xF0,
+            ops::BEQ_RELATIVE, 0xFB,
+            ops::STA_ABSOLUTE, 0x01, 0xF0,
+            ops::CMP_IMMEDIATE, 0x0D,
+            ops::BNE_RELATIVE, 0xF4,
+            ops::BRK_IMPLIED
+        ];
+
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let (sender, receiver) = mpsc::channel();
+        let console = Console::new(Box::new(Output(output.clone())), receiver);
+
+        let mut bus = Bus8b::new(64 * 1024);
+        bus.attach(0x0000, Box::new(Ram8b::new(console::CONSOLE_ADDRESS)));
+        bus.attach(console::CONSOLE_ADDRESS, Box::new(console));
+
+        let mut cpu = Mcs6502::new(bus);
+        cpu.boot(&Rom8b::from_vec(rom));
+        cpu.set_brk_trap(true);
+
+        for &byte in b"hi\n" {
+            sender.send(byte).unwrap();
+        }
+        while cpu.running() {
+            cpu.execute().unwrap();
+        }
+
+        assert_eq!(*output.borrow(), b"hi\r".to_vec());
+    }
+}
@@ -0,0 +1,309 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc::Receiver;
+
+use devs::Port;
+
+// Keys are held down and then released for this long,
+// enough for the monitor to debounce them at 1 MHz.
+pub const HOLD_CYCLES:    u64 = 50_000;
+pub const RELEASE_CYCLES: u64 = 50_000;
+
+// Values of PB1-PB4 from 0 select the rows of the keypad,
+// from 4 the digits of the display.
+const FIRST_DIGIT: u8 = 4;
+
+pub const DIGITS: usize = 6;
+
+// Keys of the matrix, seven in each row.
+const KEY_AD:   u8 = 16;
+const KEY_DA:   u8 = 17;
+const KEY_PLUS: u8 = 18;
+const KEY_GO:   u8 = 19;
+const KEY_PC:   u8 = 20;
+
+const KEYS_PER_ROW: u8 = 7;
+
+// Segment patterns of the hex digits, bit 0 is the top
+// segment and then clockwise with the middle one last.
+const HEX_DIGITS: [u8; 16] = [
+    0x3F, 0x06, 0x5B, 0x4F, 0x66, 0x6D, 0x7D, 0x07,
+    0x7F, 0x6F, 0x77, 0x7C, 0x39, 0x5E, 0x79, 0x71
+];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Key {
+    Matrix(u8),
+
+    // ST triggers the NMI and RS resets the cpu.
+    Stop,
+    Reset
+}
+
+// Hex keypad and six digit LED display of the KIM-1, on the
+// ports of its 6530-002. PA0-PA6 drive the segments and read
+// the keys, PB1-PB4 select a row of keys or a digit.
+//
+// Host keys: 0-9 and a-f are the hex keys, m is AD, n is DA,
+// + is +, g is GO, p is PC, s is ST, r is RS and t toggles
+// the SST switch.
+pub struct Keypad {
+    keys: Receiver<u8>,
+    pressed: Option<Key>,
+
+    // Cycles until the key is released or the next one
+    // can be pressed.
+    wait: u64,
+
+    select: u8,
+    digits: [u8; DIGITS],
+    single_step: bool,
+    reset: bool,
+    changed: bool
+}
+
+struct SegmentPort(Rc<RefCell<Keypad>>);
+struct SelectPort(Rc<RefCell<Keypad>>);
+
+impl Port for SegmentPort {
+    // A digit shows the segments written while it's selected.
+    fn write(&mut self, data: u8, ddr: u8) {
+        let mut keypad = self.0.borrow_mut();
+        let segments = data & ddr & 0x7F;
+
+        if let Some(digit) = keypad.digit() {
+            if keypad.digits[digit] != segments {
+                keypad.digits[digit] = segments;
+                keypad.changed = true;
+            }
+        }
+    }
+
+    // Pressed keys of the selected row read as low.
+    fn read(&self) -> u8 {
+        let keypad = self.0.borrow();
+        match keypad.pressed {
+            Some(Key::Matrix(key)) if key / KEYS_PER_ROW == keypad.select => {
+                !(1 << (key % KEYS_PER_ROW))
+            }
+            _ => 0xFF
+        }
+    }
+}
+
+impl Port for SelectPort {
+    // Inputs are pulled high and select nothing.
+    fn write(&mut self, data: u8, ddr: u8) {
+        let pins = (data & ddr) | !ddr;
+        self.0.borrow_mut().select = (pins >> 1) & 0x0F;
+    }
+
+    fn read(&self) -> u8 {
+        0xFF
+    }
+}
+
+impl Keypad {
+    pub fn new(keys: Receiver<u8>) -> Keypad {
+        Keypad {
+            keys,
+            pressed: None,
+            wait: 0,
+            select: 0,
+            digits: [0; DIGITS],
+            single_step: false,
+            reset: false,
+            changed: false
+        }
+    }
+
+    // Ports A and B to connect to the 6530.
+    pub fn ports(keypad: &Rc<RefCell<Keypad>>) -> (Box<dyn Port>, Box<dyn Port>) {
+        (Box::new(SegmentPort(keypad.clone())), Box::new(SelectPort(keypad.clone())))
+    }
+
+    pub fn tick(&mut self, cycles: u64) {
+        if self.wait > cycles {
+            self.wait -= cycles;
+            return;
+        }
+
+        self.wait = 0;
+        if self.pressed.take().is_some() {
+            self.wait = RELEASE_CYCLES;
+            return;
+        }
+
+        while let Ok(ch) = self.keys.try_recv() {
+            if ch == b't' {
+                self.single_step = !self.single_step;
+                continue;
+            }
+
+            if let Some(key) = Keypad::key(ch) {
+                self.reset |= key == Key::Reset;
+                self.pressed = Some(key);
+                self.wait = HOLD_CYCLES;
+                return;
+            }
+        }
+    }
+
+    // State of the NMI line driven by the ST key.
+    pub fn nmi(&self) -> bool {
+        self.pressed == Some(Key::Stop)
+    }
+
+    // Returns whether RS was pressed since the last call.
+    pub fn take_reset(&mut self) -> bool {
+        let reset = self.reset;
+        self.reset = false;
+        reset
+    }
+
+    // Position of the SST switch.
+    pub fn single_step(&self) -> bool {
+        self.single_step
+    }
+
+    // Returns whether the display may have changed since
+    // the last call.
+    pub fn take_changed(&mut self) -> bool {
+        let changed = self.changed;
+        self.changed = false;
+        changed
+    }
+
+    // Digits as hex characters with a space between the address
+    // and the data, ? for other patterns.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for (i, &segments) in self.digits.iter().enumerate() {
+            if i == 4 {
+                text.push(' ');
+            }
+
+            text.push(match HEX_DIGITS.iter().position(|&hex| hex == segments) {
+                Some(value)            => b"0123456789ABCDEF"[value] as char,
+                None if segments == 0  => ' ',
+                None                   => '?'
+            });
+        }
+
+        text
+    }
+
+    // Seven segment rendering of the display in three lines.
+    pub fn lines(&self) -> Vec<String> {
+        let lit = |segments: u8, bit: u8, ch: char| {
+            if segments & (1 << bit) != 0 { ch } else { ' ' }
+        };
+
+        let mut lines = vec![String::new(), String::new(), String::new()];
+        for (i, &segs) in self.digits.iter().enumerate() {
+            let gap = if i == 4 { "   " } else if i > 0 { " " } else { "" };
+            for line in lines.iter_mut() {
+                line.push_str(gap);
+            }
+
+            lines[0].extend(&[' ', lit(segs, 0, '_'), ' ']);
+            lines[1].extend(&[lit(segs, 5, '|'), lit(segs, 6, '_'), lit(segs, 1, '|')]);
+            lines[2].extend(&[lit(segs, 4, '|'), lit(segs, 3, '_'), lit(segs, 2, '|')]);
+        }
+
+        lines
+    }
+
+    fn digit(&self) -> Option<usize> {
+        match self.select {
+            select @ FIRST_DIGIT..=9 => Some((select - FIRST_DIGIT) as usize),
+            _                        => None
+        }
+    }
+
+    fn key(ch: u8) -> Option<Key> {
+        let key = match ch {
+            b'0'..=b'9' => ch - b'0',
+            b'a'..=b'f' => ch - b'a' + 10,
+            b'A'..=b'F' => ch - b'A' + 10,
+            b'm'        => KEY_AD,
+            b'n'        => KEY_DA,
+            b'+'        => KEY_PLUS,
+            b'g'        => KEY_GO,
+            b'p'        => KEY_PC,
+            b's'        => return Some(Key::Stop),
+            b'r'        => return Some(Key::Reset),
+            _           => return None
+        };
+
+        Some(Key::Matrix(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    use devs::keypad;
+    use devs::keypad::Keypad;
+
+    #[test]
+    fn display() {
+        let (_, receiver) = mpsc::channel();
+        let keypad = Rc::new(RefCell::new(Keypad::new(receiver)));
+        let (mut segments, mut select) = Keypad::ports(&keypad);
+
+        // Digits are selected by 4-9 on PB1-PB4.
+        for (digit, &pattern) in [0x06, 0x39, 0x3F, 0x3F, 0x77, 0x6F].iter().enumerate() {
+            select.write((digit as u8 + 4) << 1, 0x1E);
+            segments.write(pattern, 0x7F);
+        }
+
+        // Writes to the keypad rows don't change the digits.
+        select.write(0x00, 0x1E);
+        segments.write(0xFF, 0x7F);
+
+        assert!(keypad.borrow_mut().take_changed());
+        assert_eq!(keypad.borrow().text(), "1C00 A9");
+        assert_eq!(keypad.borrow().lines(), vec![
+            "     _   _   _     _   _ ",
+            "  | |   | | | |   |_| |_|",
+            "  | |_  |_| |_|   | |  _|"
+        ]);
+    }
+
+    #[test]
+    fn keys() {
+        let (sender, receiver) = mpsc::channel();
+        let keypad = Rc::new(RefCell::new(Keypad::new(receiver)));
+        let (segments, mut select) = Keypad::ports(&keypad);
+
+        for &key in b"tgsr" {
+            sender.send(key).unwrap();
+        }
+
+        // GO is the sixth key of the third row.
+        keypad.borrow_mut().tick(1);
+        assert!(keypad.borrow().single_step());
+        select.write(0x05, 0x1E);
+        assert_eq!(segments.read(), 0xDF);
+        select.write(0x03, 0x1E);
+        assert_eq!(segments.read(), 0xFF);
+
+        keypad.borrow_mut().tick(keypad::HOLD_CYCLES);
+        select.write(0x05, 0x1E);
+        assert_eq!(segments.read(), 0xFF);
+
+        keypad.borrow_mut().tick(keypad::RELEASE_CYCLES);
+        assert!(keypad.borrow().nmi());
+        assert!(!keypad.borrow_mut().take_reset());
+
+        keypad.borrow_mut().tick(keypad::HOLD_CYCLES);
+        assert!(!keypad.borrow().nmi());
+        keypad.borrow_mut().tick(keypad::RELEASE_CYCLES);
+        assert!(keypad.borrow_mut().take_reset());
+        assert!(!keypad.borrow_mut().take_reset());
+    }
+}
@@ -0,0 +1,381 @@
+use devs::Port;
+
+// Pins of the port the display is connected to, data bits
+// D4-D7 are on the lowest four pins.
+pub const PIN_RS: u8 = 1 << 4;
+pub const PIN_RW: u8 = 1 << 5;
+pub const PIN_E:  u8 = 1 << 6;
+
+const DATA_MASK: u8 = 0x0F;
+
+pub const COLUMNS: usize = 16;
+pub const ROWS:    usize = 2;
+
+// Instructions, identified by their highest set bit.
+const CMD_CLEAR:        u8 = 0x01;
+const CMD_HOME:         u8 = 0x02;
+const CMD_ENTRY_MODE:   u8 = 0x04;
+const CMD_DISPLAY:      u8 = 0x08;
+const CMD_SHIFT:        u8 = 0x10;
+const CMD_FUNCTION:     u8 = 0x20;
+const CMD_CGRAM_ADDR:   u8 = 0x40;
+const CMD_DDRAM_ADDR:   u8 = 0x80;
+
+// Instruction arguments.
+const ENTRY_INCREMENT:  u8 = 1 << 1;
+const ENTRY_SHIFT:      u8 = 1 << 0;
+const DISPLAY_ON:       u8 = 1 << 2;
+const SHIFT_DISPLAY:    u8 = 1 << 3;
+const SHIFT_RIGHT:      u8 = 1 << 2;
+const FUNCTION_8BIT:    u8 = 1 << 4;
+const FUNCTION_2LINES:  u8 = 1 << 3;
+
+const DDRAM_SIZE:       usize = 0x80;
+const CGRAM_SIZE:       usize = 0x40;
+
+// Length of a line in the display memory, the second one
+// starts at 0x40 in the two line mode.
+const LINE_SIZE:        u8 = 0x28;
+const LINE2_ADDRESS:    u8 = 0x40;
+
+// HD44780 character LCD wired in the 4-bit mode, as in the
+// breadboard computer. Instructions complete right away so
+// the busy flag is never set.
+pub struct Lcd {
+    pins: u8,
+
+    // High nibble waiting for the low one in the 4-bit mode.
+    nibble: Option<u8>,
+
+    // Low nibble of a read is returned by the second pulse.
+    read_low: bool,
+
+    eight_bit: bool,
+    two_lines: bool,
+    display_on: bool,
+    increment: bool,
+    entry_shift: bool,
+    cgram: bool,
+    address: u8,
+    shift: u8,
+    ddram: [u8; DDRAM_SIZE],
+    cgram_data: [u8; CGRAM_SIZE],
+    changed: bool
+}
+
+impl Port for Lcd {
+    // Transfers happen on the falling edge of E.
+    fn write(&mut self, data: u8, ddr: u8) {
+        // Pins the port does not drive are low.
+        let pins = data & ddr;
+        let falling = self.pins & PIN_E != 0 && pins & PIN_E == 0;
+        self.pins = pins;
+
+        if !falling {
+            return;
+        }
+
+        if pins & PIN_RW != 0 {
+            self.end_read();
+        } else {
+            self.latch(pins & DATA_MASK);
+        }
+    }
+
+    fn read(&self) -> u8 {
+        let reading = PIN_E | PIN_RW;
+        if self.pins & reading != reading {
+            return 0xFF;
+        }
+
+        let byte = self.output();
+        let nibble = if self.eight_bit || !self.read_low {
+            byte >> 4
+        } else {
+            byte & DATA_MASK
+        };
+
+        !DATA_MASK | nibble
+    }
+}
+
+impl Default for Lcd {
+    fn default() -> Lcd {
+        Lcd::new()
+    }
+}
+
+impl Lcd {
+    pub fn new() -> Lcd {
+        Lcd {
+            pins: 0,
+            nibble: None,
+            read_low: false,
+            eight_bit: true,
+            two_lines: false,
+            display_on: false,
+            increment: true,
+            entry_shift: false,
+            cgram: false,
+            address: 0,
+            shift: 0,
+            ddram: [b' '; DDRAM_SIZE],
+            cgram_data: [0; CGRAM_SIZE],
+            changed: false
+        }
+    }
+
+    // Visible characters, blank if the display is off. Custom
+    // characters show as ? and so do the ones that differ
+    // from ASCII.
+    pub fn lines(&self) -> Vec<String> {
+        let rows = if self.two_lines { ROWS } else { 1 };
+
+        (0..ROWS).map(|row| {
+            (0..COLUMNS).map(|col| {
+                if !self.display_on || row >= rows {
+                    return ' ';
+                }
+
+                let offset = (col as u8 + self.shift) % LINE_SIZE;
+                match self.ddram[(row as u8 * LINE2_ADDRESS + offset) as usize] {
+                    ch @ 0x20..=0x5B |
+                    ch @ 0x5D..=0x7D => ch as char,
+                    _                => '?'
+                }
+            }).collect()
+        }).collect()
+    }
+
+    // Returns whether the visible contents may have changed
+    // since the last call.
+    pub fn take_changed(&mut self) -> bool {
+        let changed = self.changed;
+        self.changed = false;
+        changed
+    }
+
+    // The data lines D0-D3 are not connected and read as low
+    // in the 8-bit mode, which is only used to switch to 4 bits.
+    fn latch(&mut self, nibble: u8) {
+        let byte = match self.nibble.take() {
+            _ if self.eight_bit => nibble << 4,
+            Some(high)          => high << 4 | nibble,
+            None                => {
+                self.nibble = Some(nibble);
+                return;
+            }
+        };
+
+        if self.pins & PIN_RS != 0 {
+            self.write_data(byte);
+        } else {
+            self.instruction(byte);
+        }
+    }
+
+    fn end_read(&mut self) {
+        if !self.eight_bit && !self.read_low {
+            self.read_low = true;
+            return;
+        }
+
+        self.read_low = false;
+        if self.pins & PIN_RS != 0 {
+            self.move_address(self.increment);
+        }
+    }
+
+    // Busy flag and the address or the data at the address.
+    fn output(&self) -> u8 {
+        if self.pins & PIN_RS == 0 {
+            self.address & 0x7F
+        } else if self.cgram {
+            self.cgram_data[self.address as usize % CGRAM_SIZE]
+        } else {
+            self.ddram[self.address as usize]
+        }
+    }
+
+    fn instruction(&mut self, byte: u8) {
+        self.changed = true;
+
+        if byte & CMD_DDRAM_ADDR != 0 {
+            self.cgram = false;
+            self.address = byte & !CMD_DDRAM_ADDR;
+        } else if byte & CMD_CGRAM_ADDR != 0 {
+            self.cgram = true;
+            self.address = byte & !CMD_CGRAM_ADDR;
+        } else if byte & CMD_FUNCTION != 0 {
+            self.eight_bit = byte & FUNCTION_8BIT != 0;
+            self.two_lines = byte & FUNCTION_2LINES != 0;
+            self.nibble = None;
+        } else if byte & CMD_SHIFT != 0 {
+            let right = byte & SHIFT_RIGHT != 0;
+            if byte & SHIFT_DISPLAY != 0 {
+                self.shift_display(right);
+            } else {
+                self.move_address(right);
+            }
+        } else if byte & CMD_DISPLAY != 0 {
+            self.display_on = byte & DISPLAY_ON != 0;
+        } else if byte & CMD_ENTRY_MODE != 0 {
+            self.increment = byte & ENTRY_INCREMENT != 0;
+            self.entry_shift = byte & ENTRY_SHIFT != 0;
+        } else if byte & CMD_HOME != 0 {
+            self.cgram = false;
+            self.address = 0;
+            self.shift = 0;
+        } else if byte & CMD_CLEAR != 0 {
+            self.ddram = [b' '; DDRAM_SIZE];
+            self.cgram = false;
+            self.address = 0;
+            self.shift = 0;
+            self.increment = true;
+        }
+    }
+
+    fn write_data(&mut self, byte: u8) {
+        if self.cgram {
+            self.cgram_data[self.address as usize % CGRAM_SIZE] = byte;
+        } else {
+            self.ddram[self.address as usize] = byte;
+            self.changed = true;
+        }
+
+        self.move_address(self.increment);
+        if self.entry_shift && !self.cgram {
+            // The cursor stays in place and the text moves.
+            self.shift_display(!self.increment);
+        }
+    }
+
+    // Moving past the end of a line continues on the other.
+    fn move_address(&mut self, increment: bool) {
+        if self.cgram {
+            self.address = if increment {
+                (self.address + 1) % CGRAM_SIZE as u8
+            } else {
+                (self.address + CGRAM_SIZE as u8 - 1) % CGRAM_SIZE as u8
+            };
+            return;
+        }
+
+        let (first, last) = if self.two_lines {
+            (LINE2_ADDRESS, LINE2_ADDRESS + LINE_SIZE - 1)
+        } else {
+            (0, 2 * LINE_SIZE - 1)
+        };
+
+        self.address = match (increment, self.address) {
+            (true, addr) if addr == LINE_SIZE - 1 && self.two_lines => first,
+            (true, addr) if addr >= last                            => 0,
+            (true, addr)                                            => addr + 1,
+            (false, addr) if addr == first && self.two_lines        => LINE_SIZE - 1,
+            (false, 0)                                              => last,
+            (false, addr)                                           => addr - 1
+        };
+    }
+
+    fn shift_display(&mut self, right: bool) {
+        // Shifting right moves the text, so the window moves left.
+        self.shift = if right {
+            (self.shift + LINE_SIZE - 1) % LINE_SIZE
+        } else {
+            (self.shift + 1) % LINE_SIZE
+        };
+        self.changed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use devs::Port;
+    use devs::lcd;
+    use devs::lcd::Lcd;
+
+    // Sends a byte as two nibbles like the breadboard examples.
+    fn send(lcd: &mut Lcd, byte: u8, rs: u8) {
+        for &nibble in &[byte >> 4, byte & 0x0F] {
+            lcd.write(nibble | rs, 0xFF);
+            lcd.write(nibble | rs | lcd::PIN_E, 0xFF);
+            lcd.write(nibble | rs, 0xFF);
+        }
+    }
+
+    fn read(lcd: &mut Lcd, rs: u8) -> u8 {
+        let mut byte = 0;
+        for _ in 0..2 {
+            lcd.write(rs | lcd::PIN_RW | lcd::PIN_E, 0xF0);
+            byte = byte << 4 | (lcd.read() & 0x0F);
+            lcd.write(rs | lcd::PIN_RW, 0xF0);
+        }
+
+        byte
+    }
+
+    fn init() -> Lcd {
+        let mut lcd = Lcd::new();
+
+        // Switch to 4 bits with a single nibble.
+        lcd.write(0x02, 0xFF);
+        lcd.write(0x02 | lcd::PIN_E, 0xFF);
+        lcd.write(0x02, 0xFF);
+
+        send(&mut lcd, 0x28, 0);
+        send(&mut lcd, 0x0E, 0);
+        send(&mut lcd, 0x06, 0);
+        send(&mut lcd, 0x01, 0);
+        lcd
+    }
+
+    #[test]
+    fn text() {
+        let mut lcd = init();
+        assert!(lcd.take_changed());
+        assert!(!lcd.take_changed());
+
+        for &ch in b"Hello" {
+            send(&mut lcd, ch, lcd::PIN_RS);
+        }
+        send(&mut lcd, 0xC0, 0);
+        for &ch in b"world!" {
+            send(&mut lcd, ch, lcd::PIN_RS);
+        }
+
+        assert!(lcd.take_changed());
+        assert_eq!(lcd.lines(), vec!["Hello           ", "world!          "]);
+
+        // Display shifts.
+        send(&mut lcd, 0x18, 0);
+        assert_eq!(lcd.lines(), vec!["ello            ", "orld!           "]);
+        send(&mut lcd, 0x1C, 0);
+        send(&mut lcd, 0x1C, 0);
+        assert_eq!(lcd.lines(), vec![" Hello          ", " world!         "]);
+
+        // Display off.
+        send(&mut lcd, 0x08, 0);
+        assert_eq!(lcd.lines(), vec![" ".repeat(16), " ".repeat(16)]);
+    }
+
+    #[test]
+    fn reads() {
+        let mut lcd = init();
+        assert_eq!(read(&mut lcd, 0), 0x00);
+
+        send(&mut lcd, b'A', lcd::PIN_RS);
+        send(&mut lcd, b'B', lcd::PIN_RS);
+        assert_eq!(read(&mut lcd, 0), 0x02);
+
+        // Reading the data moves the address too.
+        send(&mut lcd, 0x80, 0);
+        assert_eq!(read(&mut lcd, lcd::PIN_RS), b'A');
+        assert_eq!(read(&mut lcd, lcd::PIN_RS), b'B');
+        assert_eq!(read(&mut lcd, 0), 0x02);
+
+        // End of the first line wraps to the second.
+        send(&mut lcd, 0xA7, 0);
+        send(&mut lcd, b'C', lcd::PIN_RS);
+        assert_eq!(read(&mut lcd, 0), 0x40);
+    }
+}
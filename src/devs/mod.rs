@@ -0,0 +1,62 @@
+use std::cell::RefCell;
+use std::io;
+use std::io::Read;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::thread;
+
+pub mod acia;
+pub mod console;
+pub mod keypad;
+pub mod lcd;
+pub mod pia;
+pub mod rriot;
+pub mod serial;
+pub mod via;
+
+// Peripheral connected to the pins of an I/O port.
+pub trait Port {
+    // Levels driven by the port, pins with their ddr bit
+    // cleared are inputs and should be ignored.
+    fn write(&mut self, data: u8, ddr: u8);
+
+    // Levels the peripheral drives on the pins.
+    fn read(&self) -> u8;
+}
+
+// Keeps a handle to a peripheral, e.g. to show the LCD.
+impl<P: Port> Port for Rc<RefCell<P>> {
+    fn write(&mut self, data: u8, ddr: u8) {
+        self.borrow_mut().write(data, ddr);
+    }
+
+    fn read(&self) -> u8 {
+        self.borrow().read()
+    }
+}
+
+// Reads stdin on a separate thread, so that devices can poll
+// for input without blocking the cpu. The channel closes when
+// stdin does.
+pub fn stdin() -> Receiver<u8> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut buf = [0; 256];
+        loop {
+            let count = match io::stdin().read(&mut buf) {
+                Ok(0) | Err(_) => return,
+                Ok(count)      => count
+            };
+
+            for &byte in &buf[..count] {
+                if sender.send(byte).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    receiver
+}
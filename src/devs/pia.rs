@@ -0,0 +1,141 @@
+use std::cell::Cell;
+use std::io::Write;
+use std::sync::mpsc::Receiver;
+
+use mems::Memory;
+
+// Registers relative to the start of the device.
+pub const KBD:   usize = 0x00;
+pub const KBDCR: usize = 0x01;
+pub const DSP:   usize = 0x02;
+pub const DSPCR: usize = 0x03;
+
+pub const PIA_SIZE: usize = 0x04;
+
+// Control register bits.
+const CR_DATA_SELECT: u8 = 1 << 2;
+const CR_IRQ1:        u8 = 1 << 7;
+
+// Set in the keys read from the keyboard.
+const KEY_STROBE:     u8 = 1 << 7;
+
+// 6821 PIA wired as in the Apple I, the keyboard is on port A
+// and the terminal on port B. With bit 2 of a control register
+// cleared, its data register address selects the data direction
+// register instead.
+pub struct Pia {
+    display: Box<dyn Write>,
+    keyboard: Receiver<u8>,
+    key: Cell<Option<u8>>,
+    kbd_ddr: u8,
+    kbd_cr: u8,
+    dsp_ddr: u8,
+    dsp_cr: u8
+}
+
+impl Memory for Pia {
+    fn write_u8(&mut self, addr: usize, data: u8) {
+        let kbd_ddr = self.kbd_cr & CR_DATA_SELECT == 0;
+        let dsp_ddr = self.dsp_cr & CR_DATA_SELECT == 0;
+
+        // The keyboard port is an input.
+        match addr {
+            KBD if kbd_ddr => self.kbd_ddr = data,
+            KBDCR          => self.kbd_cr = data & !CR_IRQ1,
+            DSP if dsp_ddr => self.dsp_ddr = data,
+            DSP            => self.print(data),
+            DSPCR          => self.dsp_cr = data & !CR_IRQ1,
+            _              => ()
+        }
+    }
+
+    fn read_u8(&self, addr: usize) -> u8 {
+        let kbd_ddr = self.kbd_cr & CR_DATA_SELECT == 0;
+        let dsp_ddr = self.dsp_cr & CR_DATA_SELECT == 0;
+
+        match addr {
+            KBD if kbd_ddr => self.kbd_ddr,
+
+            // Reading the key clears the strobe.
+            KBD            => {
+                self.poll_key();
+                self.key.take().map_or(0, |key| key | KEY_STROBE)
+            }
+
+            KBDCR          => {
+                self.poll_key();
+                match self.key.get() {
+                    Some(_) => self.kbd_cr | CR_IRQ1,
+                    None    => self.kbd_cr
+                }
+            }
+
+            // The terminal is never busy.
+            DSP if dsp_ddr => self.dsp_ddr,
+            DSP            => 0,
+            DSPCR          => self.dsp_cr,
+            _              => 0
+        }
+    }
+
+    fn write_u16(&mut self, addr: usize, data: u16) {
+        self.write_u8(addr, (data & 0xFF) as u8);
+        self.write_u8(addr + 1, (data >> 8) as u8);
+    }
+
+    fn read_u16(&self, addr: usize) -> u16 {
+        let lo = self.read_u8(addr) as u16;
+        let hi = self.read_u8(addr + 1) as u16;
+
+        (hi << 8) | lo
+    }
+
+    fn size(&self) -> usize {
+        PIA_SIZE
+    }
+}
+
+impl Pia {
+    pub fn new(display: Box<dyn Write>, keyboard: Receiver<u8>) -> Pia {
+        Pia {
+            display,
+            keyboard,
+            key: Cell::new(None),
+            kbd_ddr: 0,
+            kbd_cr: 0,
+            dsp_ddr: 0,
+            dsp_cr: 0
+        }
+    }
+
+    // The keyboard only has upper case letters, CR ends
+    // a line and _ is the rubout.
+    fn poll_key(&self) {
+        if self.key.get().is_some() {
+            return;
+        }
+
+        let key = match self.keyboard.try_recv() {
+            Ok(b'\n')           => b'\r',
+            Ok(0x08) | Ok(0x7F) => b'_',
+            Ok(key)             => key.to_ascii_uppercase() & 0x7F,
+            Err(_)              => return
+        };
+        self.key.set(Some(key));
+    }
+
+    // The terminal shows upper case characters and ignores
+    // control characters other than CR.
+    fn print(&mut self, data: u8) {
+        let data = match data & 0x7F {
+            0x0D             => b'\n',
+            ch @ 0x20..=0x5F => ch,
+            ch @ 0x60..=0x7E => ch.to_ascii_uppercase(),
+            _                => return
+        };
+
+        // Programs are not told about failed output.
+        let _ = self.display.write_all(&[data]);
+        let _ = self.display.flush();
+    }
+}
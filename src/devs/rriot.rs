@@ -0,0 +1,243 @@
+use std::cell::Cell;
+
+use devs::Port;
+use mems::Memory;
+
+// Registers relative to the start of the I/O section, the
+// RAM and the ROM of the chip are separate devices.
+pub const PAD:  usize = 0x00;
+pub const PADD: usize = 0x01;
+pub const PBD:  usize = 0x02;
+pub const PBDD: usize = 0x03;
+
+// Writes start the timer, the lowest two bits select the
+// divider and bit 3 enables the interrupt. Reads with bit 0
+// set return the interrupt flag, others the timer.
+pub const TIMER: usize = 0x04;
+
+pub const RRIOT_SIZE: usize = 0x10;
+
+const TIMER_DIVIDER: usize = 0x03;
+const TIMER_IRQ:     usize = 0x08;
+const TIMER_FLAG:    usize = 0x01;
+
+const DIVIDERS: [u32; 4] = [1, 8, 64, 1024];
+
+// Bit 7 of the flag register.
+pub const IRQ_FLAG: u8 = 1 << 7;
+
+// 6530 ROM-RAM-I/O-Timer, the I/O and timer part of it. After
+// reaching zero the timer sets its flag and keeps counting
+// down every cycle.
+pub struct Rriot {
+    port_a: Option<Box<dyn Port>>,
+    port_b: Option<Box<dyn Port>>,
+    pad: u8,
+    padd: u8,
+    pbd: u8,
+    pbdd: u8,
+    timer: u8,
+    divider: u32,
+    prescaler: u32,
+    irq_enabled: bool,
+
+    // Reading the timer clears it.
+    flag: Cell<bool>
+}
+
+impl Memory for Rriot {
+    fn write_u8(&mut self, addr: usize, data: u8) {
+        match addr {
+            PAD  => {
+                self.pad = data;
+                self.drive_a();
+            }
+            PADD => {
+                self.padd = data;
+                self.drive_a();
+            }
+            PBD  => {
+                self.pbd = data;
+                self.drive_b();
+            }
+            PBDD => {
+                self.pbdd = data;
+                self.drive_b();
+            }
+            _    => {
+                self.timer = data;
+                self.divider = DIVIDERS[addr & TIMER_DIVIDER];
+                self.prescaler = self.divider;
+                self.irq_enabled = addr & TIMER_IRQ != 0;
+                self.flag.set(false);
+            }
+        }
+    }
+
+    fn read_u8(&self, addr: usize) -> u8 {
+        match addr {
+            PAD  => Rriot::input(self.pad, self.padd, &self.port_a),
+            PADD => self.padd,
+            PBD  => Rriot::input(self.pbd, self.pbdd, &self.port_b),
+            PBDD => self.pbdd,
+            _    => if addr & TIMER_FLAG != 0 {
+                if self.flag.get() { IRQ_FLAG } else { 0 }
+            } else {
+                self.flag.set(false);
+                self.timer
+            }
+        }
+    }
+
+    fn write_u16(&mut self, addr: usize, data: u16) {
+        self.write_u8(addr, (data & 0xFF) as u8);
+        self.write_u8(addr + 1, (data >> 8) as u8);
+    }
+
+    fn read_u16(&self, addr: usize) -> u16 {
+        let lo = self.read_u8(addr) as u16;
+        let hi = self.read_u8(addr + 1) as u16;
+
+        (hi << 8) | lo
+    }
+
+    fn size(&self) -> usize {
+        RRIOT_SIZE
+    }
+}
+
+impl Default for Rriot {
+    fn default() -> Rriot {
+        Rriot::new()
+    }
+}
+
+impl Rriot {
+    pub fn new() -> Rriot {
+        Rriot {
+            port_a: None,
+            port_b: None,
+            pad: 0,
+            padd: 0,
+            pbd: 0,
+            pbdd: 0,
+            timer: 0,
+            divider: 1,
+            prescaler: 1,
+            irq_enabled: false,
+            flag: Cell::new(false)
+        }
+    }
+
+    // Unconnected pins are pulled high.
+    pub fn connect_a(&mut self, port: Box<dyn Port>) {
+        self.port_a = Some(port);
+        self.drive_a();
+    }
+
+    pub fn connect_b(&mut self, port: Box<dyn Port>) {
+        self.port_b = Some(port);
+        self.drive_b();
+    }
+
+    // The RES line turns the ports into inputs and disables
+    // the interrupt.
+    pub fn reset(&mut self) {
+        self.pad = 0;
+        self.padd = 0;
+        self.pbd = 0;
+        self.pbdd = 0;
+        self.irq_enabled = false;
+        self.drive_a();
+        self.drive_b();
+    }
+
+    // State of the IRQ output, PB7 on the real chip.
+    pub fn irq(&self) -> bool {
+        self.irq_enabled && self.flag.get()
+    }
+
+    pub fn tick(&mut self, cycles: u64) {
+        for _ in 0..cycles {
+            self.prescaler -= 1;
+            if self.prescaler > 0 {
+                continue;
+            }
+
+            let (timer, underflow) = self.timer.overflowing_sub(1);
+            self.timer = timer;
+            if underflow {
+                self.flag.set(true);
+                self.divider = 1;
+            }
+            self.prescaler = self.divider;
+        }
+    }
+
+    fn drive_a(&mut self) {
+        if let Some(ref mut port) = self.port_a {
+            port.write(self.pad, self.padd);
+        }
+    }
+
+    fn drive_b(&mut self) {
+        if let Some(ref mut port) = self.port_b {
+            port.write(self.pbd, self.pbdd);
+        }
+    }
+
+    fn input(output: u8, ddr: u8, port: &Option<Box<dyn Port>>) -> u8 {
+        let pins = match *port {
+            Some(ref port) => port.read(),
+            None           => 0xFF
+        };
+
+        (output & ddr) | (pins & !ddr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use devs::rriot;
+    use devs::rriot::Rriot;
+    use mems::Memory;
+
+    #[test]
+    fn ports() {
+        let mut rriot = Rriot::new();
+        rriot.write_u8(rriot::PADD, 0x0F);
+        rriot.write_u8(rriot::PAD, 0x05);
+        assert_eq!(rriot.read_u8(rriot::PAD), 0xF5);
+
+        rriot.reset();
+        assert_eq!(rriot.read_u8(rriot::PADD), 0x00);
+        assert_eq!(rriot.read_u8(rriot::PAD), 0xFF);
+    }
+
+    #[test]
+    fn timer() {
+        let mut rriot = Rriot::new();
+
+        // Divide by 8 with the interrupt enabled.
+        rriot.write_u8(rriot::TIMER | 0x09, 0x02);
+        rriot.tick(8);
+        assert_eq!(rriot.read_u8(rriot::TIMER), 0x01);
+        rriot.tick(8);
+        assert_eq!(rriot.read_u8(rriot::TIMER), 0x00);
+        assert!(!rriot.irq());
+        rriot.tick(8);
+        assert!(rriot.irq());
+        assert_eq!(rriot.read_u8(rriot::TIMER | 0x01), rriot::IRQ_FLAG);
+
+        // Then counts every cycle.
+        rriot.tick(1);
+        assert_eq!(rriot.read_u8(rriot::TIMER), 0xFE);
+        assert!(!rriot.irq());
+
+        // Divide by 1024 without the interrupt.
+        rriot.write_u8(rriot::TIMER | 0x03, 0x00);
+        rriot.tick(1024);
+        assert!(!rriot.irq());
+        assert_eq!(rriot.read_u8(rriot::TIMER | 0x01), rriot::IRQ_FLAG);
+    }
+}
@@ -0,0 +1,259 @@
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::net::Shutdown;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+// Host side of a serial port, e.g. for devs::acia.
+pub struct Link {
+    // Where terminals connect, a device or an address.
+    pub name: String,
+    pub output: Box<dyn Write>,
+    pub input: Receiver<u8>
+}
+
+// Sends everything read from the source to the channel
+// until either of them closes.
+fn forward<R: Read + Send + 'static>(mut source: R, sender: Sender<u8>) {
+    thread::spawn(move || {
+        let mut buf = [0; 256];
+        loop {
+            let count = match source.read(&mut buf) {
+                Ok(0) | Err(_) => return,
+                Ok(count)      => count
+            };
+
+            for &byte in &buf[..count] {
+                if sender.send(byte).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+}
+
+// Output to the last connected client, dropped while there
+// is none.
+struct TcpOutput {
+    client: Arc<Mutex<Option<TcpStream>>>
+}
+
+impl Write for TcpOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut client = self.client.lock().unwrap();
+        let failed = match *client {
+            Some(ref mut stream) => stream.write_all(buf).is_err(),
+            None                 => false
+        };
+
+        if failed {
+            *client = None;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Listens on the port of localhost, e.g. for nc or telnet.
+// A new connection replaces the previous one.
+pub fn tcp(port: u16) -> io::Result<Link> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let name = listener.local_addr()?.to_string();
+    let client: Arc<Mutex<Option<TcpStream>>> = Arc::new(Mutex::new(None));
+    let (sender, receiver) = mpsc::channel();
+
+    let accepted = client.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_)     => continue
+            };
+
+            let reader = match stream.try_clone() {
+                Ok(reader) => reader,
+                Err(_)     => continue
+            };
+
+            let _ = stream.set_nodelay(true);
+            if let Some(old) = accepted.lock().unwrap().replace(stream) {
+                let _ = old.shutdown(Shutdown::Both);
+            }
+            forward(reader, sender.clone());
+        }
+    });
+
+    Ok(Link {
+        name,
+        output: Box::new(TcpOutput { client }),
+        input: receiver
+    })
+}
+
+#[cfg(unix)]
+mod pty {
+    use std::ffi::CStr;
+    use std::fs::File;
+    use std::fs::OpenOptions;
+    use std::io;
+    use std::io::Write;
+    use std::mem;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::io::FromRawFd;
+    use std::sync::mpsc;
+
+    use libc;
+
+    use devs::serial::Link;
+    use devs::serial::forward;
+
+    // Output dropped while the terminal is not reading, so
+    // that the cpu doesn't block on a full buffer.
+    struct PtyOutput {
+        master: File,
+
+        // Keeps the master readable when no terminal is attached.
+        _slave: File
+    }
+
+    impl Write for PtyOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut poll = libc::pollfd {
+                fd: self.master.as_raw_fd(),
+                events: libc::POLLOUT,
+                revents: 0
+            };
+
+            let ready = unsafe { libc::poll(&mut poll, 1, 0) };
+            if ready > 0 && poll.revents & libc::POLLOUT != 0 {
+                self.master.write(buf)
+            } else {
+                Ok(buf.len())
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result)
+        }
+    }
+
+    pub fn open() -> io::Result<Link> {
+        let (master, name) = unsafe {
+            let fd = check(libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY))?;
+            let master = File::from_raw_fd(fd);
+            check(libc::grantpt(fd))?;
+            check(libc::unlockpt(fd))?;
+
+            let name = libc::ptsname(fd);
+            if name.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            (master, CStr::from_ptr(name).to_string_lossy().into_owned())
+        };
+
+        // Terminals get the bytes as they are, without
+        // echo or line editing.
+        let slave = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(&name)?;
+        unsafe {
+            let mut termios: libc::termios = mem::zeroed();
+            check(libc::tcgetattr(slave.as_raw_fd(), &mut termios))?;
+            libc::cfmakeraw(&mut termios);
+            check(libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios))?;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        forward(master.try_clone()?, sender);
+
+        Ok(Link {
+            name,
+            output: Box::new(PtyOutput { master, _slave: slave }),
+            input: receiver
+        })
+    }
+}
+
+// Creates a pseudo-terminal, e.g. for screen or minicom.
+#[cfg(unix)]
+pub fn pty() -> io::Result<Link> {
+    pty::open()
+}
+
+#[cfg(not(unix))]
+pub fn pty() -> io::Result<Link> {
+    Err(io::Error::new(io::ErrorKind::Other, "pseudo-terminals need a unix host"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    use devs::serial;
+
+    #[test]
+    fn tcp() {
+        let mut link = serial::tcp(0).unwrap();
+
+        // Nobody is listening yet.
+        link.output.write_all(b"lost").unwrap();
+
+        let mut client = TcpStream::connect(&link.name).unwrap();
+        client.write_all(b"hi").unwrap();
+        assert_eq!(link.input.recv_timeout(Duration::from_secs(5)), Ok(b'h'));
+        assert_eq!(link.input.recv_timeout(Duration::from_secs(5)), Ok(b'i'));
+
+        link.output.write_all(b"ok").unwrap();
+        let mut buf = [0; 2];
+        client.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ok");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pty() {
+        let mut link = match serial::pty() {
+            Ok(link) => link,
+
+            // Some sandboxes have no pseudo-terminals.
+            Err(_)   => return
+        };
+
+        let mut terminal = OpenOptions::new().read(true).write(true)
+            .open(&link.name).unwrap();
+        terminal.write_all(b"hi\n").unwrap();
+        for &byte in b"hi\n" {
+            assert_eq!(link.input.recv_timeout(Duration::from_secs(5)), Ok(byte));
+        }
+
+        link.output.write_all(b"ok\n").unwrap();
+        let mut buf = [0; 3];
+        terminal.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ok\n");
+    }
+}
@@ -0,0 +1,513 @@
+use std::cell::Cell;
+
+use devs::Port;
+use mems::Memory;
+
+// Registers relative to the start of the device.
+pub const ORB:    usize = 0x00;
+pub const ORA:    usize = 0x01;
+pub const DDRB:   usize = 0x02;
+pub const DDRA:   usize = 0x03;
+pub const T1C_L:  usize = 0x04;
+pub const T1C_H:  usize = 0x05;
+pub const T1L_L:  usize = 0x06;
+pub const T1L_H:  usize = 0x07;
+pub const T2C_L:  usize = 0x08;
+pub const T2C_H:  usize = 0x09;
+pub const SR:     usize = 0x0A;
+pub const ACR:    usize = 0x0B;
+pub const PCR:    usize = 0x0C;
+pub const IFR:    usize = 0x0D;
+pub const IER:    usize = 0x0E;
+pub const ORA_NH: usize = 0x0F;
+
+pub const VIA_SIZE: usize = 0x10;
+
+// Interrupt flag and enable register bits.
+pub const INT_CA2: u8 = 1 << 0;
+pub const INT_CA1: u8 = 1 << 1;
+pub const INT_SR:  u8 = 1 << 2;
+pub const INT_CB2: u8 = 1 << 3;
+pub const INT_CB1: u8 = 1 << 4;
+pub const INT_T2:  u8 = 1 << 5;
+pub const INT_T1:  u8 = 1 << 6;
+pub const INT_ANY: u8 = 1 << 7;
+
+// Auxiliary control register fields.
+const ACR_T1_CONTINUOUS: u8 = 1 << 6;
+const ACR_T2_PULSES:     u8 = 1 << 5;
+const ACR_SR_SHIFT:      u8 = 2;
+const ACR_SR_MASK:       u8 = 0x07;
+
+// Shift register modes.
+const SR_DISABLED:      u8 = 0;
+const SR_IN_T2:         u8 = 1;
+const SR_IN_CLOCK:      u8 = 2;
+const SR_OUT_FREE:      u8 = 4;
+const SR_OUT_T2:        u8 = 5;
+const SR_OUT_CLOCK:     u8 = 6;
+
+// 6522 Versatile Interface Adapter. Both timers count the
+// cpu clock, pulse counting of T2 and the modes clocked by
+// CB1 are not emulated, CB2 reads as low.
+pub struct Via {
+    port_a: Option<Box<dyn Port>>,
+    port_b: Option<Box<dyn Port>>,
+    ora: u8,
+    orb: u8,
+    ddra: u8,
+    ddrb: u8,
+    t1_counter: u16,
+    t1_latch: u16,
+    t1_armed: bool,
+    t1_reload: bool,
+    t2_counter: u16,
+    t2_latch_lo: u8,
+    t2_armed: bool,
+    sr: u8,
+    acr: u8,
+    pcr: u8,
+    ier: u8,
+
+    // Reads change these.
+    ifr: Cell<u8>,
+    sr_bits: Cell<u8>,
+    sr_timer: Cell<u32>
+}
+
+impl Memory for Via {
+    fn write_u8(&mut self, addr: usize, data: u8) {
+        match addr {
+            ORB    => {
+                self.orb = data;
+                self.clear_flags(INT_CB1 | INT_CB2);
+                self.drive_b();
+            }
+            ORA    => {
+                self.ora = data;
+                self.clear_flags(INT_CA1 | INT_CA2);
+                self.drive_a();
+            }
+            ORA_NH => {
+                self.ora = data;
+                self.drive_a();
+            }
+            DDRB   => {
+                self.ddrb = data;
+                self.drive_b();
+            }
+            DDRA   => {
+                self.ddra = data;
+                self.drive_a();
+            }
+
+            // Writing the high byte of the counter starts T1.
+            T1C_L  |
+            T1L_L  => self.t1_latch = (self.t1_latch & 0xFF00) | data as u16,
+            T1C_H  => {
+                self.t1_latch = (self.t1_latch & 0x00FF) | (data as u16) << 8;
+                self.t1_counter = self.t1_latch;
+                self.t1_armed = true;
+                self.t1_reload = false;
+                self.clear_flags(INT_T1);
+            }
+            T1L_H  => {
+                self.t1_latch = (self.t1_latch & 0x00FF) | (data as u16) << 8;
+                self.clear_flags(INT_T1);
+            }
+
+            T2C_L  => self.t2_latch_lo = data,
+            T2C_H  => {
+                self.t2_counter = (data as u16) << 8 | self.t2_latch_lo as u16;
+                self.t2_armed = true;
+                self.clear_flags(INT_T2);
+            }
+
+            SR     => {
+                self.sr = data;
+                self.start_shift();
+            }
+            ACR    => self.acr = data,
+            PCR    => self.pcr = data,
+            IFR    => self.clear_flags(data),
+            IER    => if data & INT_ANY != 0 {
+                self.ier |= data & !INT_ANY;
+            } else {
+                self.ier &= !data;
+            },
+            _      => ()
+        }
+    }
+
+    fn read_u8(&self, addr: usize) -> u8 {
+        match addr {
+            ORB    => {
+                self.clear_flags(INT_CB1 | INT_CB2);
+                Via::input(self.orb, self.ddrb, &self.port_b)
+            }
+            ORA    => {
+                self.clear_flags(INT_CA1 | INT_CA2);
+                Via::input(self.ora, self.ddra, &self.port_a)
+            }
+            ORA_NH => Via::input(self.ora, self.ddra, &self.port_a),
+            DDRB   => self.ddrb,
+            DDRA   => self.ddra,
+            T1C_L  => {
+                self.clear_flags(INT_T1);
+                self.t1_counter as u8
+            }
+            T1C_H  => (self.t1_counter >> 8) as u8,
+            T1L_L  => self.t1_latch as u8,
+            T1L_H  => (self.t1_latch >> 8) as u8,
+            T2C_L  => {
+                self.clear_flags(INT_T2);
+                self.t2_counter as u8
+            }
+            T2C_H  => (self.t2_counter >> 8) as u8,
+            SR     => {
+                self.start_shift();
+                self.sr
+            }
+            ACR    => self.acr,
+            PCR    => self.pcr,
+            IFR    => if self.irq() {
+                self.ifr.get() | INT_ANY
+            } else {
+                self.ifr.get()
+            },
+            IER    => self.ier | INT_ANY,
+            _      => 0
+        }
+    }
+
+    fn write_u16(&mut self, addr: usize, data: u16) {
+        self.write_u8(addr, (data & 0xFF) as u8);
+        self.write_u8(addr + 1, (data >> 8) as u8);
+    }
+
+    fn read_u16(&self, addr: usize) -> u16 {
+        let lo = self.read_u8(addr) as u16;
+        let hi = self.read_u8(addr + 1) as u16;
+
+        (hi << 8) | lo
+    }
+
+    fn size(&self) -> usize {
+        VIA_SIZE
+    }
+}
+
+impl Default for Via {
+    fn default() -> Via {
+        Via::new()
+    }
+}
+
+impl Via {
+    pub fn new() -> Via {
+        Via {
+            port_a: None,
+            port_b: None,
+            ora: 0,
+            orb: 0,
+            ddra: 0,
+            ddrb: 0,
+            t1_counter: 0,
+            t1_latch: 0,
+            t1_armed: false,
+            t1_reload: false,
+            t2_counter: 0,
+            t2_latch_lo: 0,
+            t2_armed: false,
+            sr: 0,
+            acr: 0,
+            pcr: 0,
+            ier: 0,
+            ifr: Cell::new(0),
+            sr_bits: Cell::new(0),
+            sr_timer: Cell::new(0)
+        }
+    }
+
+    // Unconnected pins are pulled high.
+    pub fn connect_a(&mut self, port: Box<dyn Port>) {
+        self.port_a = Some(port);
+        self.drive_a();
+    }
+
+    pub fn connect_b(&mut self, port: Box<dyn Port>) {
+        self.port_b = Some(port);
+        self.drive_b();
+    }
+
+    // State of the IRQ output.
+    pub fn irq(&self) -> bool {
+        self.ifr.get() & self.ier != 0
+    }
+
+    pub fn tick(&mut self, cycles: u64) {
+        for _ in 0..cycles {
+            self.tick_t1();
+            self.tick_t2();
+            self.tick_sr();
+        }
+    }
+
+    // T1 reaches zero after N + 1 cycles and is reloaded from
+    // the latch a cycle later in the continuous mode.
+    fn tick_t1(&mut self) {
+        if self.t1_reload {
+            self.t1_counter = self.t1_latch;
+            self.t1_reload = false;
+            return;
+        }
+
+        let (counter, underflow) = self.t1_counter.overflowing_sub(1);
+        self.t1_counter = counter;
+        if !underflow {
+            return;
+        }
+
+        let continuous = self.acr & ACR_T1_CONTINUOUS != 0;
+        if self.t1_armed {
+            self.set_flags(INT_T1);
+            self.t1_armed = continuous;
+        }
+        self.t1_reload = continuous;
+    }
+
+    fn tick_t2(&mut self) {
+        if self.acr & ACR_T2_PULSES != 0 {
+            return;
+        }
+
+        let (counter, underflow) = self.t2_counter.overflowing_sub(1);
+        self.t2_counter = counter;
+        if underflow && self.t2_armed {
+            self.set_flags(INT_T2);
+            self.t2_armed = false;
+        }
+    }
+
+    fn tick_sr(&mut self) {
+        if self.sr_bits.get() == 0 || self.sr_period() == 0 {
+            return;
+        }
+
+        // The timer doesn't run in modes under CB1, so it starts
+        // over when the mode changes to one under the cpu clock.
+        let timer = match self.sr_timer.get() {
+            0     => self.sr_period() - 1,
+            timer => timer - 1
+        };
+        if timer > 0 {
+            self.sr_timer.set(timer);
+            return;
+        }
+        self.sr_timer.set(self.sr_period());
+
+        // Shifting out recirculates the data.
+        let mode = self.sr_mode();
+        if mode >= SR_OUT_FREE {
+            self.sr = self.sr.rotate_left(1);
+        } else {
+            self.sr <<= 1;
+        }
+
+        let bits = self.sr_bits.get() - 1;
+        if bits > 0 {
+            self.sr_bits.set(bits);
+        } else if mode == SR_OUT_FREE {
+            self.sr_bits.set(8);
+        } else {
+            self.sr_bits.set(0);
+            self.set_flags(INT_SR);
+        }
+    }
+
+    fn sr_mode(&self) -> u8 {
+        (self.acr >> ACR_SR_SHIFT) & ACR_SR_MASK
+    }
+
+    // Cycles per bit, 0 if the mode is not clocked by the cpu.
+    fn sr_period(&self) -> u32 {
+        match self.sr_mode() {
+            SR_IN_T2     |
+            SR_OUT_FREE  |
+            SR_OUT_T2    => 2 * (self.t2_latch_lo as u32 + 2),
+            SR_IN_CLOCK  |
+            SR_OUT_CLOCK => 2,
+            _            => 0
+        }
+    }
+
+    // Accessing the shift register starts a transfer of 8 bits.
+    fn start_shift(&self) {
+        if self.sr_mode() == SR_DISABLED {
+            return;
+        }
+
+        self.clear_flags(INT_SR);
+        self.sr_bits.set(8);
+        self.sr_timer.set(self.sr_period());
+    }
+
+    fn set_flags(&self, flags: u8) {
+        self.ifr.set(self.ifr.get() | flags);
+    }
+
+    fn clear_flags(&self, flags: u8) {
+        self.ifr.set(self.ifr.get() & !(flags & !INT_ANY));
+    }
+
+    fn drive_a(&mut self) {
+        if let Some(ref mut port) = self.port_a {
+            port.write(self.ora, self.ddra);
+        }
+    }
+
+    fn drive_b(&mut self) {
+        if let Some(ref mut port) = self.port_b {
+            port.write(self.orb, self.ddrb);
+        }
+    }
+
+    fn input(output: u8, ddr: u8, port: &Option<Box<dyn Port>>) -> u8 {
+        let pins = match *port {
+            Some(ref port) => port.read(),
+            None           => 0xFF
+        };
+
+        (output & ddr) | (pins & !ddr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use devs::Port;
+    use devs::via;
+    use devs::via::Via;
+    use mems::Memory;
+
+    struct Pins {
+        output: u8,
+        input: u8
+    }
+
+    impl Port for Pins {
+        fn write(&mut self, data: u8, ddr: u8) {
+            self.output = data & ddr;
+        }
+
+        fn read(&self) -> u8 {
+            self.input
+        }
+    }
+
+    #[test]
+    fn ports() {
+        let pins = Rc::new(RefCell::new(Pins { output: 0, input: 0x5A }));
+        let mut via = Via::new();
+        via.connect_b(Box::new(pins.clone()));
+
+        via.write_u8(via::DDRB, 0xF0);
+        via.write_u8(via::ORB, 0xFF);
+        assert_eq!(pins.borrow().output, 0xF0);
+        assert_eq!(via.read_u8(via::ORB), 0xFA);
+
+        // Nothing connected to port A.
+        via.write_u8(via::DDRA, 0x0F);
+        assert_eq!(via.read_u8(via::ORA), 0xF0);
+    }
+
+    #[test]
+    fn timer1() {
+        let mut via = Via::new();
+        via.write_u8(via::IER, via::INT_ANY | via::INT_T1);
+        via.write_u8(via::T1C_L, 0x10);
+        via.write_u8(via::T1C_H, 0x00);
+
+        via.tick(0x10);
+        assert!(!via.irq());
+        via.tick(1);
+        assert!(via.irq());
+        assert_eq!(via.read_u8(via::IFR), via::INT_ANY | via::INT_T1);
+
+        // One shot.
+        via.read_u8(via::T1C_L);
+        assert!(!via.irq());
+        via.tick(0x20000);
+        assert!(!via.irq());
+
+        // Continuous, the period is N + 2.
+        via.write_u8(via::ACR, 0x40);
+        via.write_u8(via::T1C_H, 0x00);
+        via.tick(0x11);
+        assert!(via.irq());
+        via.write_u8(via::IFR, via::INT_T1);
+        via.tick(0x11);
+        assert!(!via.irq());
+        via.tick(1);
+        assert!(via.irq());
+
+        // Disabled interrupts only set the flag.
+        via.write_u8(via::IER, via::INT_T1);
+        assert!(!via.irq());
+        assert_eq!(via.read_u8(via::IFR), via::INT_T1);
+        assert_eq!(via.read_u8(via::IER), via::INT_ANY);
+    }
+
+    #[test]
+    fn timer2() {
+        let mut via = Via::new();
+        via.write_u8(via::IER, via::INT_ANY | via::INT_T2);
+        via.write_u8(via::T2C_L, 0x00);
+        via.write_u8(via::T2C_H, 0x01);
+
+        via.tick(0x100);
+        assert!(!via.irq());
+        assert_eq!(via.read_u8(via::T2C_H), 0x00);
+        via.tick(1);
+        assert!(via.irq());
+
+        // Fires once until the counter is written.
+        via.read_u8(via::T2C_L);
+        via.tick(0x10000);
+        assert!(!via.irq());
+    }
+
+    #[test]
+    fn shift_register() {
+        let mut via = Via::new();
+        via.write_u8(via::IER, via::INT_ANY | via::INT_SR);
+
+        // Shift out under the system clock.
+        via.write_u8(via::ACR, 0x18);
+        via.write_u8(via::SR, 0x81);
+        via.tick(15);
+        assert!(!via.irq());
+        via.tick(1);
+        assert!(via.irq());
+        assert_eq!(via.read_u8(via::SR), 0x81);
+
+        // Shift in under T2, CB2 is low.
+        via.write_u8(via::ACR, 0x04);
+        via.write_u8(via::T2C_L, 0x02);
+        via.write_u8(via::SR, 0xFF);
+        via.tick(8 * 8);
+        assert!(via.irq());
+        assert_eq!(via.read_u8(via::SR), 0x00);
+
+        // Switching from a mode under CB1 starts the timer.
+        via.write_u8(via::ACR, 0x0C);
+        via.write_u8(via::SR, 0x81);
+        via.write_u8(via::ACR, 0x18);
+        via.tick(15);
+        assert!(!via.irq());
+        via.tick(1);
+        assert!(via.irq());
+    }
+}
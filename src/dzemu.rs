@@ -1,25 +1,277 @@
 extern crate dzemu;
 
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::sync::mpsc;
+
 use dzemu::util;
 use dzemu::cpus::Cpu;
+use dzemu::cpus::CpuError;
 use dzemu::cpus::mcs6502::Mcs6502;
+use dzemu::devs;
+use dzemu::devs::console;
+use dzemu::devs::console::Console;
+use dzemu::devs::serial;
+use dzemu::devs::serial::Link;
+use dzemu::inst::mcs6502;
+use dzemu::machines;
+use dzemu::machines::Machine;
+use dzemu::machines::apple1;
+use dzemu::machines::apple1::Apple1;
+use dzemu::machines::breadboard;
+use dzemu::machines::breadboard::Breadboard;
+use dzemu::machines::kim1;
+use dzemu::machines::kim1::Kim1;
+use dzemu::machines::nes::Nes;
+use dzemu::machines::nes::cart::Cartridge;
+use dzemu::machines::nes::ppu;
+use dzemu::mems::Memory;
+use dzemu::mems::bus::Bus8b;
 use dzemu::mems::ram::Ram8b;
 use dzemu::mems::rom::Rom8b;
+use dzemu::monitor::Monitor;
+use dzemu::monitor::mcs6502::Monitor6502;
+use dzemu::util::image;
+
+const USAGE: &str = "usage: dzemu [-m] [-t trace] rom [load address]\n       \
+                     dzemu --machine apple1 [--ram 4|8] [-t trace] wozmon\n       \
+                     dzemu --machine breadboard [--serial pty|tcp:port] \
+                     [--baud auto|rate] [-t trace] rom\n       \
+                     dzemu --machine kim1 [-t trace] rom\n       \
+                     dzemu --machine nes [--frames n] [--dump file.png|file.ppm] \
+                     [-t trace] rom.nes";
+
+// Removes the option and its value from the arguments.
+fn option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let idx = args.iter().position(|arg| arg == name)?;
+    if idx + 1 >= args.len() {
+        panic!("value of {} not provided\n{}", name, USAGE);
+    }
+
+    let value = args.remove(idx + 1);
+    args.remove(idx);
+    Some(value)
+}
+
+fn set_trace<M: Memory>(cpu: &mut Mcs6502<M>, trace: Option<&str>) {
+    match trace {
+        Some("-")  => cpu.set_trace(Some(Box::new(io::stdout()))),
+        Some(name) => {
+            let file = File::create(name).expect("Cannot create the trace file.");
+            cpu.set_trace(Some(Box::new(BufWriter::new(file))));
+        }
+        None       => {}
+    }
+}
+
+// Host side of the serial port of a machine, stdio by default.
+fn serial_link(spec: Option<&str>) -> Link {
+    let link = match spec {
+        None        => return Link {
+            name: String::from("stdio"),
+            output: Box::new(io::stdout()),
+            input: devs::stdin()
+        },
+        Some("pty") => serial::pty(),
+        Some(spec)  => match spec.strip_prefix("tcp:").map(str::parse) {
+            Some(Ok(port)) => serial::tcp(port),
+            _              => panic!("invalid serial port: {}\n{}", spec, USAGE)
+        }
+    };
+
+    let link = link.expect("Cannot open the serial port.");
+    eprintln!("serial port on {}", link.name);
+    link
+}
+
+fn save_frame(nes: &Nes, path: &str) {
+    let rgb = nes.ppu().frame_rgb();
+    image::save(path, ppu::WIDTH, ppu::HEIGHT, &rgb).expect("Cannot write the frame.");
+}
+
+fn report<M: Memory>(cpu: &Mcs6502<M>, result: Result<(), CpuError>) {
+    if let Err(error) = result {
+        println!("CPU FAULT: {}", error);
+        cpu.dump();
+    } else if let Some(reason) = cpu.stop_reason() {
+        println!("CPU halted: {}", reason);
+    }
+}
+
+// Runs a complete machine at its clock rate.
+fn run_machine(name: &str, args: &mut Vec<String>, trace: Option<&str>) {
+    match name {
+        "apple1"     => {
+            let ram = match option(args, "--ram").as_deref() {
+                Some("4") => 4,
+                Some("8") | None => 8,
+                Some(ram) => panic!("invalid RAM size: {}\n{}", ram, USAGE)
+            };
+
+            let rom = util::read_rom(&args[0]);
+            let mut apple = Apple1::new(&rom, ram, Box::new(io::stdout()), devs::stdin());
+            set_trace(apple.cpu(), trace);
+
+            let result = machines::run_realtime(&mut apple, apple1::CLOCK_HZ);
+            report(apple.cpu(), result);
+        }
+
+        // The LCD is shown on stderr. Serial transfers are instant
+        // unless paced to the programmed or the given baud rate.
+        "breadboard" => {
+            let link = serial_link(option(args, "--serial").as_deref());
+            let baud = match option(args, "--baud").as_deref() {
+                None         => None,
+                Some("auto") => Some(None),
+                Some(rate)   => match rate.parse() {
+                    Ok(rate) => Some(Some(rate)),
+                    Err(_)   => panic!("invalid baud rate: {}\n{}", rate, USAGE)
+                }
+            };
+
+            let rom = util::read_rom(&args[0]);
+            let mut board = Breadboard::new(&rom, link.output, link.input);
+            board.set_lcd_output(Some(Box::new(io::stderr())));
+            if let Some(baud) = baud {
+                board.acia().set_pacing(Some(breadboard::CLOCK_HZ));
+                board.acia().set_baud(baud);
+            }
+            set_trace(board.cpu(), trace);
+
+            let result = machines::run_realtime(&mut board, breadboard::CLOCK_HZ);
+            report(board.cpu(), result);
+        }
+
+        // Keys are read from stdin, see devs::keypad.
+        "kim1"       => {
+            let rom = util::read_rom(&args[0]);
+            let mut kim = Kim1::new(&rom, Box::new(io::stdout()), devs::stdin());
+            set_trace(kim.cpu(), trace);
+
+            let result = machines::run_realtime(&mut kim, kim1::CLOCK_HZ);
+            report(kim.cpu(), result);
+        }
+
+        // Headless and as fast as possible. The last frame is
+        // dumped at the end or, with {} in the name, every frame
+        // to a numbered file.
+        "nes"        => {
+            let frames = option(args, "--frames").map(|frames| match frames.parse::<u64>() {
+                Ok(frames) => frames,
+                Err(_)     => panic!("invalid frame count: {}\n{}", frames, USAGE)
+            });
+            let dump = option(args, "--dump");
+
+            let cart = match Cartridge::load(&util::read_rom(&args[0])) {
+                Ok(cart)   => cart,
+                Err(error) => panic!("cannot load {}: {}", args[0], error)
+            };
+
+            let mut nes = Nes::new(cart);
+            set_trace(nes.cpu(), trace);
+
+            let mut result = Ok(());
+            while nes.cpu().running() && result.is_ok()
+                && frames.is_none_or(|frames| nes.ppu().frames() < frames) {
+                result = nes.run_frame();
+
+                if let Some(ref path) = dump {
+                    if path.contains("{}") {
+                        let number = format!("{:05}", nes.ppu().frames());
+                        save_frame(&nes, &path.replace("{}", &number));
+                    }
+                }
+            }
+
+            if let Some(ref path) = dump {
+                if !path.contains("{}") {
+                    save_frame(&nes, path);
+                }
+            }
+            report(nes.cpu(), result);
+        }
+
+        _            => panic!("unknown machine: {}\n{}", name, USAGE)
+    }
+}
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    // -m starts the monitor instead of running the rom.
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let monitor = args.iter().any(|arg| arg == "-m");
+    args.retain(|arg| arg != "-m");
+
+    // -t writes a trace of executed instructions, - is stdout.
+    let trace = option(&mut args, "-t");
+    let machine = option(&mut args, "--machine");
+
+    if args.is_empty() {
+        panic!("rom file not provided\n{}", USAGE);
+    }
+
+    // Machines read the keyboard from stdin, so they
+    // cannot share it with the monitor.
+    if let Some(name) = machine {
+        if monitor {
+            panic!("the monitor cannot be used with a machine\n{}", USAGE);
+        }
+
+        run_machine(&name, &mut args, trace.as_deref());
+        return;
+    }
 
-    if args.len() < 2 {
-        panic!("rom file not provided");
+    let rom = Rom8b::from_vec(util::read_rom(&args[0]));
+    if !monitor {
+        util::dump_rom(&rom);
     }
 
-    let rom = Rom8b::from_vec(util::read_rom(&args[1]));
-    util::dump_rom(&rom);
+    // RAM everywhere except for the console. The monitor reads
+    // stdin itself, so the console gets no input under it.
+    let console = if monitor {
+        Console::new(Box::new(io::stdout()), mpsc::channel().1)
+    } else {
+        Console::stdio()
+    };
+
+    let end = console::CONSOLE_ADDRESS + console::CONSOLE_SIZE;
+    let mut bus = Bus8b::new(64 * 1024);
+    bus.attach(0x0000, Box::new(Ram8b::new(console::CONSOLE_ADDRESS)));
+    bus.attach(console::CONSOLE_ADDRESS, Box::new(console));
+    bus.attach(end, Box::new(Ram8b::new(64 * 1024 - end)));
+
+    let mut cpu = Mcs6502::new(bus);
+
+    // Optional load address, e.g. $C000 for a rom with vectors.
+    if args.len() > 1 {
+        let chars: Vec<char> = args[1].chars().collect();
+        match mcs6502::extract_operand(&chars) {
+            Some(addr) => cpu.set_rom_map_address(addr as usize),
+            None       => panic!("invalid load address: {}", args[1])
+        }
+    }
 
-    let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
     cpu.boot(&rom);
 
-    while cpu.running() {
-        cpu.execute();
+    // Programs run until they jam, break to the host or
+    // end in a loop to themselves.
+    cpu.set_jam_halts(true);
+    cpu.set_brk_trap(true);
+    cpu.set_self_loop_halts(true);
+
+    set_trace(&mut cpu, trace.as_deref());
+
+    if monitor {
+        let stdin = io::stdin();
+        let mut monitor = Monitor6502::new(cpu);
+        monitor.run(&mut stdin.lock(), &mut io::stdout())
+            .expect("Monitor I/O error.");
+        return;
+    }
+
+    let mut result = Ok(());
+    while cpu.running() && result.is_ok() {
+        result = cpu.execute();
     }
+    report(&cpu, result);
 }
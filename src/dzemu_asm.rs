@@ -1,18 +1,88 @@
 extern crate dzemu;
 
+use std::fs::File;
+use std::io::Write;
+use std::process;
+
+use dzemu::asm;
 use dzemu::asm::Assembler;
+use dzemu::asm::Diagnostic;
 use dzemu::asm::mcs6502::Assembler6502;
+use dzemu::inst::mcs6502::Variant;
+
+const USAGE: &str = "usage: dzemu_asm [-o output] [-l listing] [-s symbols] \
+                     [--cpu 6502|65c02] input";
+
+fn report(diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics.iter() {
+        eprintln!("{}", diagnostic);
+    }
+
+    if asm::has_errors(diagnostics) {
+        process::exit(1);
+    }
+}
+
+fn write_file(name: &str, contents: &str) {
+    let written = File::create(name)
+        .and_then(|mut file| file.write_all(contents.as_bytes()));
+
+    if let Err(error) = written {
+        eprintln!("Cannot write {}: {}", name, error);
+        process::exit(1);
+    }
+}
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let mut input = None;
+    let mut output = String::from("test.out");
+    let mut listing = None;
+    let mut symbols = None;
+    let mut variant = Variant::Nmos;
 
-    if args.len() < 2 {
-        panic!("rom file not provided");
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "-l" | "-s" | "--cpu" => {
+                let value = match args.next() {
+                    Some(value) => value,
+                    None        => panic!("{}", USAGE)
+                };
+
+                match arg.as_str() {
+                    "-o" => output = value,
+                    "-l" => listing = Some(value),
+                    "-s" => symbols = Some(value),
+                    _    => variant = match value.as_str() {
+                        "6502"  => Variant::Nmos,
+                        "65c02" => Variant::Cmos,
+                        _       => panic!("unknown cpu: {}\n{}", value, USAGE)
+                    }
+                }
+            }
+            _ if input.is_none() => input = Some(arg),
+            _                    => panic!("{}", USAGE)
+        }
     }
 
+    let input = match input {
+        Some(input) => input,
+        None        => panic!("rom file not provided\n{}", USAGE)
+    };
+
     let mut asm = Assembler6502::new();
-    asm.debug(true); // Without debug has problems atm.
-    asm.assemble(&args[1]);
-    asm.link();
-    asm.output("test.out");
+    asm.set_variant(variant);
+    report(&asm.assemble(&input));
+    report(&asm.link());
+    asm.output(&output);
+
+    if let Some(listing) = listing {
+        write_file(&listing, &asm.listing());
+    }
+
+    if let Some(symbols) = symbols {
+        write_file(&symbols, &asm.symbols());
+    }
 }
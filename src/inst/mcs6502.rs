@@ -1,12 +1,19 @@
 use mems::Memory;
 use util;
-use lang::lexer::Lexer;
-use lang::token::Token;
-use lang::token::TokenRule;
-use lang::token::rules::UInt;
+use asm::expr::Expr;
+
+// Supported members of the 6502 family.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Variant {
+    // Original NMOS 6502.
+    Nmos,
+
+    // WDC 65C02.
+    Cmos
+}
 
 // TODO: Add Implied to Mcs6502 emulator.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum AddressMode {
     Implied,
     Immediate,
@@ -22,6 +29,11 @@ pub enum AddressMode {
     Relative,
     Accumulator,
 
+    // Modes added by the 65C02.
+    ZeroPageIndirect,
+    AbsoluteIndirectX,
+    ZeroPageRelative,
+
     // Custom modes for the assembler.
     Label,
     LabelX,
@@ -33,6 +45,34 @@ pub enum AddressMode {
     None
 }
 
+// All modes in declaration order, the index is the value
+// of the mode in save states.
+pub const ADDRESS_MODES: &[AddressMode] = &[
+    AddressMode::Implied,
+    AddressMode::Immediate,
+    AddressMode::ZeroPage,
+    AddressMode::ZeroPageX,
+    AddressMode::ZeroPageY,
+    AddressMode::Absolute,
+    AddressMode::AbsoluteX,
+    AddressMode::AbsoluteY,
+    AddressMode::Indirect,
+    AddressMode::IndirectX,
+    AddressMode::IndirectY,
+    AddressMode::Relative,
+    AddressMode::Accumulator,
+    AddressMode::ZeroPageIndirect,
+    AddressMode::AbsoluteIndirectX,
+    AddressMode::ZeroPageRelative,
+    AddressMode::Label,
+    AddressMode::LabelX,
+    AddressMode::LabelY,
+    AddressMode::ILabel,
+    AddressMode::ILabelX,
+    AddressMode::ILabelY,
+    AddressMode::None
+];
+
 pub mod addr {
     use inst::mcs6502::ops;
     use inst::mcs6502::AddressMode;
@@ -46,8 +86,11 @@ pub mod addr {
             AddressMode::Absolute  |
             AddressMode::AbsoluteX |
             AddressMode::AbsoluteY |
-            AddressMode::Indirect  => 3,
-            AddressMode::Implied   => 1,
+            AddressMode::Indirect  |
+            AddressMode::AbsoluteIndirectX |
+            AddressMode::ZeroPageRelative  => 3,
+            AddressMode::Implied   |
+            AddressMode::Accumulator => 1,
             AddressMode::None      => 0,
             _                      => 2,
         }
@@ -65,7 +108,20 @@ pub mod addr {
             ops::LDX_IMMEDIATE   |
             ops::LDY_IMMEDIATE   |
             ops::ORA_IMMEDIATE   |
-            ops::SBC_IMMEDIATE   => AddressMode::Immediate,
+            ops::SBC_IMMEDIATE   |
+            ops::LAX_IMMEDIATE   |
+            ops::ANC_IMMEDIATE_0B |
+            ops::ANC_IMMEDIATE_2B |
+            ops::ALR_IMMEDIATE   |
+            ops::ARR_IMMEDIATE   |
+            ops::SBX_IMMEDIATE   |
+            ops::SBC_IMMEDIATE_EB |
+            ops::ANE_IMMEDIATE   |
+            ops::NOP_IMMEDIATE_80 |
+            ops::NOP_IMMEDIATE_82 |
+            ops::NOP_IMMEDIATE_89 |
+            ops::NOP_IMMEDIATE_C2 |
+            ops::NOP_IMMEDIATE_E2 => AddressMode::Immediate,
 
             ops::ADC_ZERO_PAGE   |
             ops::AND_ZERO_PAGE   |
@@ -87,7 +143,18 @@ pub mod addr {
             ops::SBC_ZERO_PAGE   |
             ops::STA_ZERO_PAGE   |
             ops::STX_ZERO_PAGE   |
-            ops::STY_ZERO_PAGE   => AddressMode::ZeroPage,
+            ops::STY_ZERO_PAGE   |
+            ops::SLO_ZERO_PAGE   |
+            ops::RLA_ZERO_PAGE   |
+            ops::SRE_ZERO_PAGE   |
+            ops::RRA_ZERO_PAGE   |
+            ops::SAX_ZERO_PAGE   |
+            ops::LAX_ZERO_PAGE   |
+            ops::DCP_ZERO_PAGE   |
+            ops::ISC_ZERO_PAGE   |
+            ops::NOP_ZERO_PAGE_04 |
+            ops::NOP_ZERO_PAGE_44 |
+            ops::NOP_ZERO_PAGE_64 => AddressMode::ZeroPage,
 
             ops::ADC_ZERO_PAGE_X |
             ops::AND_ZERO_PAGE_X |
@@ -104,10 +171,24 @@ pub mod addr {
             ops::ROR_ZERO_PAGE_X |
             ops::SBC_ZERO_PAGE_X |
             ops::STA_ZERO_PAGE_X |
-            ops::STY_ZERO_PAGE_X => AddressMode::ZeroPageX,
+            ops::STY_ZERO_PAGE_X |
+            ops::SLO_ZERO_PAGE_X |
+            ops::RLA_ZERO_PAGE_X |
+            ops::SRE_ZERO_PAGE_X |
+            ops::RRA_ZERO_PAGE_X |
+            ops::DCP_ZERO_PAGE_X |
+            ops::ISC_ZERO_PAGE_X |
+            ops::NOP_ZERO_PAGE_X_14 |
+            ops::NOP_ZERO_PAGE_X_34 |
+            ops::NOP_ZERO_PAGE_X_54 |
+            ops::NOP_ZERO_PAGE_X_74 |
+            ops::NOP_ZERO_PAGE_X_D4 |
+            ops::NOP_ZERO_PAGE_X_F4 => AddressMode::ZeroPageX,
 
             ops::LDX_ZERO_PAGE_Y |
-            ops::STX_ZERO_PAGE_Y => AddressMode::ZeroPageY,
+            ops::STX_ZERO_PAGE_Y |
+            ops::SAX_ZERO_PAGE_Y |
+            ops::LAX_ZERO_PAGE_Y => AddressMode::ZeroPageY,
 
             ops::ADC_ABSOLUTE    |
             ops::AND_ABSOLUTE    |
@@ -131,7 +212,16 @@ pub mod addr {
             ops::SBC_ABSOLUTE    |
             ops::STA_ABSOLUTE    |
             ops::STX_ABSOLUTE    |
-            ops::STY_ABSOLUTE    => AddressMode::Absolute,
+            ops::STY_ABSOLUTE    |
+            ops::SLO_ABSOLUTE    |
+            ops::RLA_ABSOLUTE    |
+            ops::SRE_ABSOLUTE    |
+            ops::RRA_ABSOLUTE    |
+            ops::SAX_ABSOLUTE    |
+            ops::LAX_ABSOLUTE    |
+            ops::DCP_ABSOLUTE    |
+            ops::ISC_ABSOLUTE    |
+            ops::NOP_ABSOLUTE_0C => AddressMode::Absolute,
 
             ops::ADC_ABSOLUTE_X  |
             ops::AND_ABSOLUTE_X  |
@@ -147,7 +237,20 @@ pub mod addr {
             ops::ROL_ABSOLUTE_X  |
             ops::ROR_ABSOLUTE_X  |
             ops::SBC_ABSOLUTE_X  |
-            ops::STA_ABSOLUTE_X  => AddressMode::AbsoluteX,
+            ops::STA_ABSOLUTE_X  |
+            ops::SLO_ABSOLUTE_X  |
+            ops::RLA_ABSOLUTE_X  |
+            ops::SRE_ABSOLUTE_X  |
+            ops::RRA_ABSOLUTE_X  |
+            ops::DCP_ABSOLUTE_X  |
+            ops::ISC_ABSOLUTE_X  |
+            ops::SHY_ABSOLUTE_X  |
+            ops::NOP_ABSOLUTE_X_1C |
+            ops::NOP_ABSOLUTE_X_3C |
+            ops::NOP_ABSOLUTE_X_5C |
+            ops::NOP_ABSOLUTE_X_7C |
+            ops::NOP_ABSOLUTE_X_DC |
+            ops::NOP_ABSOLUTE_X_FC => AddressMode::AbsoluteX,
 
             ops::ADC_ABSOLUTE_Y  |
             ops::AND_ABSOLUTE_Y  |
@@ -157,7 +260,18 @@ pub mod addr {
             ops::LDX_ABSOLUTE_Y  |
             ops::ORA_ABSOLUTE_Y  |
             ops::SBC_ABSOLUTE_Y  |
-            ops::STA_ABSOLUTE_Y  => AddressMode::AbsoluteY,
+            ops::STA_ABSOLUTE_Y  |
+            ops::SLO_ABSOLUTE_Y  |
+            ops::RLA_ABSOLUTE_Y  |
+            ops::SRE_ABSOLUTE_Y  |
+            ops::RRA_ABSOLUTE_Y  |
+            ops::LAX_ABSOLUTE_Y  |
+            ops::DCP_ABSOLUTE_Y  |
+            ops::ISC_ABSOLUTE_Y  |
+            ops::SHA_ABSOLUTE_Y  |
+            ops::SHX_ABSOLUTE_Y  |
+            ops::TAS_ABSOLUTE_Y  |
+            ops::LAS_ABSOLUTE_Y  => AddressMode::AbsoluteY,
 
             ops::JMP_INDIRECT    => AddressMode::Indirect,
 
@@ -168,7 +282,15 @@ pub mod addr {
             ops::LDA_INDIRECT_X  |
             ops::ORA_INDIRECT_X  |
             ops::SBC_INDIRECT_X  |
-            ops::STA_INDIRECT_X  => AddressMode::IndirectX,
+            ops::STA_INDIRECT_X  |
+            ops::SLO_INDIRECT_X  |
+            ops::RLA_INDIRECT_X  |
+            ops::SRE_INDIRECT_X  |
+            ops::RRA_INDIRECT_X  |
+            ops::SAX_INDIRECT_X  |
+            ops::LAX_INDIRECT_X  |
+            ops::DCP_INDIRECT_X  |
+            ops::ISC_INDIRECT_X  => AddressMode::IndirectX,
 
             ops::ADC_INDIRECT_Y  |
             ops::AND_INDIRECT_Y  |
@@ -177,7 +299,15 @@ pub mod addr {
             ops::LDA_INDIRECT_Y  |
             ops::ORA_INDIRECT_Y  |
             ops::SBC_INDIRECT_Y  |
-            ops::STA_INDIRECT_Y  => AddressMode::IndirectY,
+            ops::STA_INDIRECT_Y  |
+            ops::SLO_INDIRECT_Y  |
+            ops::RLA_INDIRECT_Y  |
+            ops::SRE_INDIRECT_Y  |
+            ops::RRA_INDIRECT_Y  |
+            ops::LAX_INDIRECT_Y  |
+            ops::DCP_INDIRECT_Y  |
+            ops::ISC_INDIRECT_Y  |
+            ops::SHA_INDIRECT_Y  => AddressMode::IndirectY,
 
             ops::BCC_RELATIVE    |
             ops::BCS_RELATIVE    |
@@ -217,14 +347,742 @@ pub mod addr {
             ops::TYA_IMPLIED     |
             ops::TSX_IMPLIED     |
             ops::TXA_IMPLIED     |
-            ops::TXS_IMPLIED     => AddressMode::Implied,
+            ops::TXS_IMPLIED     |
+            ops::NOP_IMPLIED_1A  |
+            ops::NOP_IMPLIED_3A  |
+            ops::NOP_IMPLIED_5A  |
+            ops::NOP_IMPLIED_7A  |
+            ops::NOP_IMPLIED_DA  |
+            ops::NOP_IMPLIED_FA  => AddressMode::Implied,
 
-            ops::custom::TOS_ABSOLUTE |
-            ops::custom::PRT_ABSOLUTE => AddressMode::Absolute,
+            _ => AddressMode::None,
+        }
+    }
+}
 
-            ops::custom::VARIABLE     => AddressMode::Immediate,
+pub mod cycles {
+    use inst::mcs6502::ops;
 
-            _ => AddressMode::None,
+    // Number of cycles an instruction takes, not counting
+    // page crossing and taken branch penalties.
+    pub fn base_count(opcode: u8) -> u8 {
+        match opcode {
+            ops::ADC_IMMEDIATE   |
+            ops::AND_IMMEDIATE   |
+            ops::CMP_IMMEDIATE   |
+            ops::CPX_IMMEDIATE   |
+            ops::CPY_IMMEDIATE   |
+            ops::EOR_IMMEDIATE   |
+            ops::LDA_IMMEDIATE   |
+            ops::LDX_IMMEDIATE   |
+            ops::LDY_IMMEDIATE   |
+            ops::ORA_IMMEDIATE   |
+            ops::SBC_IMMEDIATE   |
+            ops::ASL_ACCUMULATOR |
+            ops::LSR_ACCUMULATOR |
+            ops::ROL_ACCUMULATOR |
+            ops::ROR_ACCUMULATOR |
+            ops::BCC_RELATIVE    |
+            ops::BCS_RELATIVE    |
+            ops::BEQ_RELATIVE    |
+            ops::BMI_RELATIVE    |
+            ops::BNE_RELATIVE    |
+            ops::BPL_RELATIVE    |
+            ops::BVC_RELATIVE    |
+            ops::BVS_RELATIVE    |
+            ops::CLC_IMPLIED     |
+            ops::CLD_IMPLIED     |
+            ops::CLI_IMPLIED     |
+            ops::CLV_IMPLIED     |
+            ops::DEX_IMPLIED     |
+            ops::DEY_IMPLIED     |
+            ops::INX_IMPLIED     |
+            ops::INY_IMPLIED     |
+            ops::NOP_IMPLIED     |
+            ops::SEC_IMPLIED     |
+            ops::SED_IMPLIED     |
+            ops::SEI_IMPLIED     |
+            ops::TAX_IMPLIED     |
+            ops::TAY_IMPLIED     |
+            ops::TYA_IMPLIED     |
+            ops::TSX_IMPLIED     |
+            ops::TXA_IMPLIED     |
+            ops::TXS_IMPLIED     |
+            ops::LAX_IMMEDIATE   |
+            ops::ANC_IMMEDIATE_0B |
+            ops::ANC_IMMEDIATE_2B |
+            ops::ALR_IMMEDIATE   |
+            ops::ARR_IMMEDIATE   |
+            ops::SBX_IMMEDIATE   |
+            ops::SBC_IMMEDIATE_EB |
+            ops::ANE_IMMEDIATE   |
+            ops::NOP_IMMEDIATE_80 |
+            ops::NOP_IMMEDIATE_82 |
+            ops::NOP_IMMEDIATE_89 |
+            ops::NOP_IMMEDIATE_C2 |
+            ops::NOP_IMMEDIATE_E2 |
+            ops::NOP_IMPLIED_1A  |
+            ops::NOP_IMPLIED_3A  |
+            ops::NOP_IMPLIED_5A  |
+            ops::NOP_IMPLIED_7A  |
+            ops::NOP_IMPLIED_DA  |
+            ops::NOP_IMPLIED_FA  => 2,
+
+            ops::ADC_ZERO_PAGE   |
+            ops::AND_ZERO_PAGE   |
+            ops::BIT_ZERO_PAGE   |
+            ops::CMP_ZERO_PAGE   |
+            ops::CPX_ZERO_PAGE   |
+            ops::CPY_ZERO_PAGE   |
+            ops::EOR_ZERO_PAGE   |
+            ops::LDA_ZERO_PAGE   |
+            ops::LDX_ZERO_PAGE   |
+            ops::LDY_ZERO_PAGE   |
+            ops::ORA_ZERO_PAGE   |
+            ops::SBC_ZERO_PAGE   |
+            ops::STA_ZERO_PAGE   |
+            ops::STX_ZERO_PAGE   |
+            ops::STY_ZERO_PAGE   |
+            ops::JMP_ABSOLUTE    |
+            ops::PHA_IMPLIED     |
+            ops::PHP_IMPLIED     |
+            ops::SAX_ZERO_PAGE   |
+            ops::LAX_ZERO_PAGE   |
+            ops::NOP_ZERO_PAGE_04 |
+            ops::NOP_ZERO_PAGE_44 |
+            ops::NOP_ZERO_PAGE_64 => 3,
+
+            ops::ADC_ZERO_PAGE_X |
+            ops::AND_ZERO_PAGE_X |
+            ops::CMP_ZERO_PAGE_X |
+            ops::EOR_ZERO_PAGE_X |
+            ops::LDA_ZERO_PAGE_X |
+            ops::LDY_ZERO_PAGE_X |
+            ops::ORA_ZERO_PAGE_X |
+            ops::SBC_ZERO_PAGE_X |
+            ops::STA_ZERO_PAGE_X |
+            ops::STY_ZERO_PAGE_X |
+            ops::LDX_ZERO_PAGE_Y |
+            ops::STX_ZERO_PAGE_Y |
+            ops::ADC_ABSOLUTE    |
+            ops::AND_ABSOLUTE    |
+            ops::BIT_ABSOLUTE    |
+            ops::CMP_ABSOLUTE    |
+            ops::CPX_ABSOLUTE    |
+            ops::CPY_ABSOLUTE    |
+            ops::EOR_ABSOLUTE    |
+            ops::LDA_ABSOLUTE    |
+            ops::LDX_ABSOLUTE    |
+            ops::LDY_ABSOLUTE    |
+            ops::ORA_ABSOLUTE    |
+            ops::SBC_ABSOLUTE    |
+            ops::STA_ABSOLUTE    |
+            ops::STX_ABSOLUTE    |
+            ops::STY_ABSOLUTE    |
+            ops::ADC_ABSOLUTE_X  |
+            ops::AND_ABSOLUTE_X  |
+            ops::CMP_ABSOLUTE_X  |
+            ops::EOR_ABSOLUTE_X  |
+            ops::LDA_ABSOLUTE_X  |
+            ops::LDY_ABSOLUTE_X  |
+            ops::ORA_ABSOLUTE_X  |
+            ops::SBC_ABSOLUTE_X  |
+            ops::ADC_ABSOLUTE_Y  |
+            ops::AND_ABSOLUTE_Y  |
+            ops::CMP_ABSOLUTE_Y  |
+            ops::EOR_ABSOLUTE_Y  |
+            ops::LDA_ABSOLUTE_Y  |
+            ops::LDX_ABSOLUTE_Y  |
+            ops::ORA_ABSOLUTE_Y  |
+            ops::SBC_ABSOLUTE_Y  |
+            ops::PLA_IMPLIED     |
+            ops::PLP_IMPLIED     |
+            ops::SAX_ZERO_PAGE_Y |
+            ops::SAX_ABSOLUTE    |
+            ops::LAX_ZERO_PAGE_Y |
+            ops::LAX_ABSOLUTE    |
+            ops::LAX_ABSOLUTE_Y  |
+            ops::LAS_ABSOLUTE_Y  |
+            ops::NOP_ZERO_PAGE_X_14 |
+            ops::NOP_ZERO_PAGE_X_34 |
+            ops::NOP_ZERO_PAGE_X_54 |
+            ops::NOP_ZERO_PAGE_X_74 |
+            ops::NOP_ZERO_PAGE_X_D4 |
+            ops::NOP_ZERO_PAGE_X_F4 |
+            ops::NOP_ABSOLUTE_0C |
+            ops::NOP_ABSOLUTE_X_1C |
+            ops::NOP_ABSOLUTE_X_3C |
+            ops::NOP_ABSOLUTE_X_5C |
+            ops::NOP_ABSOLUTE_X_7C |
+            ops::NOP_ABSOLUTE_X_DC |
+            ops::NOP_ABSOLUTE_X_FC => 4,
+
+            ops::ASL_ZERO_PAGE   |
+            ops::DEC_ZERO_PAGE   |
+            ops::INC_ZERO_PAGE   |
+            ops::LSR_ZERO_PAGE   |
+            ops::ROL_ZERO_PAGE   |
+            ops::ROR_ZERO_PAGE   |
+            ops::STA_ABSOLUTE_X  |
+            ops::STA_ABSOLUTE_Y  |
+            ops::ADC_INDIRECT_Y  |
+            ops::AND_INDIRECT_Y  |
+            ops::CMP_INDIRECT_Y  |
+            ops::EOR_INDIRECT_Y  |
+            ops::LDA_INDIRECT_Y  |
+            ops::ORA_INDIRECT_Y  |
+            ops::SBC_INDIRECT_Y  |
+            ops::JMP_INDIRECT    |
+            ops::SLO_ZERO_PAGE   |
+            ops::RLA_ZERO_PAGE   |
+            ops::SRE_ZERO_PAGE   |
+            ops::RRA_ZERO_PAGE   |
+            ops::DCP_ZERO_PAGE   |
+            ops::ISC_ZERO_PAGE   |
+            ops::LAX_INDIRECT_Y  |
+            ops::SHA_ABSOLUTE_Y  |
+            ops::SHX_ABSOLUTE_Y  |
+            ops::SHY_ABSOLUTE_X  |
+            ops::TAS_ABSOLUTE_Y  => 5,
+
+            ops::ASL_ZERO_PAGE_X |
+            ops::DEC_ZERO_PAGE_X |
+            ops::INC_ZERO_PAGE_X |
+            ops::LSR_ZERO_PAGE_X |
+            ops::ROL_ZERO_PAGE_X |
+            ops::ROR_ZERO_PAGE_X |
+            ops::ASL_ABSOLUTE    |
+            ops::DEC_ABSOLUTE    |
+            ops::INC_ABSOLUTE    |
+            ops::LSR_ABSOLUTE    |
+            ops::ROL_ABSOLUTE    |
+            ops::ROR_ABSOLUTE    |
+            ops::ADC_INDIRECT_X  |
+            ops::AND_INDIRECT_X  |
+            ops::CMP_INDIRECT_X  |
+            ops::EOR_INDIRECT_X  |
+            ops::LDA_INDIRECT_X  |
+            ops::ORA_INDIRECT_X  |
+            ops::SBC_INDIRECT_X  |
+            ops::STA_INDIRECT_X  |
+            ops::STA_INDIRECT_Y  |
+            ops::JSR_ABSOLUTE    |
+            ops::RTI_IMPLIED     |
+            ops::RTS_IMPLIED     |
+            ops::SLO_ZERO_PAGE_X |
+            ops::RLA_ZERO_PAGE_X |
+            ops::SRE_ZERO_PAGE_X |
+            ops::RRA_ZERO_PAGE_X |
+            ops::DCP_ZERO_PAGE_X |
+            ops::ISC_ZERO_PAGE_X |
+            ops::SLO_ABSOLUTE    |
+            ops::RLA_ABSOLUTE    |
+            ops::SRE_ABSOLUTE    |
+            ops::RRA_ABSOLUTE    |
+            ops::DCP_ABSOLUTE    |
+            ops::ISC_ABSOLUTE    |
+            ops::SAX_INDIRECT_X  |
+            ops::LAX_INDIRECT_X  |
+            ops::SHA_INDIRECT_Y  => 6,
+
+            ops::ASL_ABSOLUTE_X  |
+            ops::DEC_ABSOLUTE_X  |
+            ops::INC_ABSOLUTE_X  |
+            ops::LSR_ABSOLUTE_X  |
+            ops::ROL_ABSOLUTE_X  |
+            ops::ROR_ABSOLUTE_X  |
+            ops::BRK_IMPLIED     |
+            ops::SLO_ABSOLUTE_X  |
+            ops::RLA_ABSOLUTE_X  |
+            ops::SRE_ABSOLUTE_X  |
+            ops::RRA_ABSOLUTE_X  |
+            ops::DCP_ABSOLUTE_X  |
+            ops::ISC_ABSOLUTE_X  |
+            ops::SLO_ABSOLUTE_Y  |
+            ops::RLA_ABSOLUTE_Y  |
+            ops::SRE_ABSOLUTE_Y  |
+            ops::RRA_ABSOLUTE_Y  |
+            ops::DCP_ABSOLUTE_Y  |
+            ops::ISC_ABSOLUTE_Y  => 7,
+
+            ops::SLO_INDIRECT_X  |
+            ops::RLA_INDIRECT_X  |
+            ops::SRE_INDIRECT_X  |
+            ops::RRA_INDIRECT_X  |
+            ops::DCP_INDIRECT_X  |
+            ops::ISC_INDIRECT_X  |
+            ops::SLO_INDIRECT_Y  |
+            ops::RLA_INDIRECT_Y  |
+            ops::SRE_INDIRECT_Y  |
+            ops::RRA_INDIRECT_Y  |
+            ops::DCP_INDIRECT_Y  |
+            ops::ISC_INDIRECT_Y  => 8,
+
+            _ => 0,
+        }
+    }
+
+    // Read instructions using indexed addressing take one
+    // extra cycle when the effective address crosses a page.
+    pub fn has_page_penalty(opcode: u8) -> bool {
+        matches!(opcode,
+            ops::ADC_ABSOLUTE_X |
+            ops::AND_ABSOLUTE_X |
+            ops::CMP_ABSOLUTE_X |
+            ops::EOR_ABSOLUTE_X |
+            ops::LDA_ABSOLUTE_X |
+            ops::LDY_ABSOLUTE_X |
+            ops::ORA_ABSOLUTE_X |
+            ops::SBC_ABSOLUTE_X |
+            ops::ADC_ABSOLUTE_Y |
+            ops::AND_ABSOLUTE_Y |
+            ops::CMP_ABSOLUTE_Y |
+            ops::EOR_ABSOLUTE_Y |
+            ops::LDA_ABSOLUTE_Y |
+            ops::LDX_ABSOLUTE_Y |
+            ops::ORA_ABSOLUTE_Y |
+            ops::SBC_ABSOLUTE_Y |
+            ops::ADC_INDIRECT_Y |
+            ops::AND_INDIRECT_Y |
+            ops::CMP_INDIRECT_Y |
+            ops::EOR_INDIRECT_Y |
+            ops::LDA_INDIRECT_Y |
+            ops::ORA_INDIRECT_Y |
+            ops::SBC_INDIRECT_Y |
+            ops::LAX_ABSOLUTE_Y |
+            ops::LAX_INDIRECT_Y |
+            ops::LAS_ABSOLUTE_Y |
+            ops::NOP_ABSOLUTE_X_1C |
+            ops::NOP_ABSOLUTE_X_3C |
+            ops::NOP_ABSOLUTE_X_5C |
+            ops::NOP_ABSOLUTE_X_7C |
+            ops::NOP_ABSOLUTE_X_DC |
+            ops::NOP_ABSOLUTE_X_FC)
+    }
+}
+
+pub mod cmos {
+    use inst::mcs6502;
+    use inst::mcs6502::addr;
+    use inst::mcs6502::cycles;
+    use inst::mcs6502::ops;
+    use inst::mcs6502::ops::cmos;
+    use inst::mcs6502::AddressMode;
+
+    // Opcodes the 65C02 does not define are NOPs of various sizes.
+    fn nop_addr_mode(opcode: u8) -> Option<AddressMode> {
+        match opcode {
+            0x02 | 0x22 | 0x42 | 0x62 | 0x82 | 0xC2 | 0xE2 => {
+                Some(AddressMode::Immediate)
+            }
+
+            0x44               => Some(AddressMode::ZeroPage),
+
+            0x54 | 0xD4 | 0xF4 => Some(AddressMode::ZeroPageX),
+
+            0x5C | 0xDC | 0xFC => Some(AddressMode::Absolute),
+
+            // Remaining xxxxxx11 opcodes are single byte.
+            op if (op & 0x03) == 0x03 && op != cmos::WAI_IMPLIED &&
+                  op != cmos::STP_IMPLIED && (op & 0x0F) != 0x07 &&
+                  (op & 0x0F) != 0x0F => {
+                Some(AddressMode::Implied)
+            }
+
+            _                  => None
+        }
+    }
+
+    pub fn is_nop(opcode: u8) -> bool {
+        nop_addr_mode(opcode).is_some()
+    }
+
+    // Address mode of opcodes that differ from the NMOS 6502.
+    fn cmos_addr_mode(opcode: u8) -> Option<AddressMode> {
+        let mode = match opcode {
+            cmos::BIT_IMMEDIATE    => AddressMode::Immediate,
+
+            cmos::TSB_ZERO_PAGE    |
+            cmos::TRB_ZERO_PAGE    |
+            cmos::STZ_ZERO_PAGE    |
+            cmos::RMB0_ZERO_PAGE   |
+            cmos::RMB1_ZERO_PAGE   |
+            cmos::RMB2_ZERO_PAGE   |
+            cmos::RMB3_ZERO_PAGE   |
+            cmos::RMB4_ZERO_PAGE   |
+            cmos::RMB5_ZERO_PAGE   |
+            cmos::RMB6_ZERO_PAGE   |
+            cmos::RMB7_ZERO_PAGE   |
+            cmos::SMB0_ZERO_PAGE   |
+            cmos::SMB1_ZERO_PAGE   |
+            cmos::SMB2_ZERO_PAGE   |
+            cmos::SMB3_ZERO_PAGE   |
+            cmos::SMB4_ZERO_PAGE   |
+            cmos::SMB5_ZERO_PAGE   |
+            cmos::SMB6_ZERO_PAGE   |
+            cmos::SMB7_ZERO_PAGE   => AddressMode::ZeroPage,
+
+            cmos::BIT_ZERO_PAGE_X  |
+            cmos::STZ_ZERO_PAGE_X  => AddressMode::ZeroPageX,
+
+            cmos::TSB_ABSOLUTE     |
+            cmos::TRB_ABSOLUTE     |
+            cmos::STZ_ABSOLUTE     => AddressMode::Absolute,
+
+            cmos::BIT_ABSOLUTE_X   |
+            cmos::STZ_ABSOLUTE_X   => AddressMode::AbsoluteX,
+
+            cmos::ORA_ZERO_PAGE_INDIRECT |
+            cmos::AND_ZERO_PAGE_INDIRECT |
+            cmos::EOR_ZERO_PAGE_INDIRECT |
+            cmos::ADC_ZERO_PAGE_INDIRECT |
+            cmos::STA_ZERO_PAGE_INDIRECT |
+            cmos::LDA_ZERO_PAGE_INDIRECT |
+            cmos::CMP_ZERO_PAGE_INDIRECT |
+            cmos::SBC_ZERO_PAGE_INDIRECT => AddressMode::ZeroPageIndirect,
+
+            cmos::JMP_ABSOLUTE_INDIRECT_X => AddressMode::AbsoluteIndirectX,
+
+            cmos::BBR0_ZERO_PAGE_RELATIVE |
+            cmos::BBR1_ZERO_PAGE_RELATIVE |
+            cmos::BBR2_ZERO_PAGE_RELATIVE |
+            cmos::BBR3_ZERO_PAGE_RELATIVE |
+            cmos::BBR4_ZERO_PAGE_RELATIVE |
+            cmos::BBR5_ZERO_PAGE_RELATIVE |
+            cmos::BBR6_ZERO_PAGE_RELATIVE |
+            cmos::BBR7_ZERO_PAGE_RELATIVE |
+            cmos::BBS0_ZERO_PAGE_RELATIVE |
+            cmos::BBS1_ZERO_PAGE_RELATIVE |
+            cmos::BBS2_ZERO_PAGE_RELATIVE |
+            cmos::BBS3_ZERO_PAGE_RELATIVE |
+            cmos::BBS4_ZERO_PAGE_RELATIVE |
+            cmos::BBS5_ZERO_PAGE_RELATIVE |
+            cmos::BBS6_ZERO_PAGE_RELATIVE |
+            cmos::BBS7_ZERO_PAGE_RELATIVE => AddressMode::ZeroPageRelative,
+
+            cmos::BRA_RELATIVE     => AddressMode::Relative,
+
+            cmos::INC_ACCUMULATOR  |
+            cmos::DEC_ACCUMULATOR  => AddressMode::Accumulator,
+
+            cmos::PHX_IMPLIED      |
+            cmos::PHY_IMPLIED      |
+            cmos::PLX_IMPLIED      |
+            cmos::PLY_IMPLIED      |
+            cmos::WAI_IMPLIED      |
+            cmos::STP_IMPLIED      => AddressMode::Implied,
+
+            op                     => return nop_addr_mode(op)
+        };
+
+        Some(mode)
+    }
+
+    // True if the opcode means something else than on the NMOS 6502.
+    pub fn redefines(opcode: u8) -> bool {
+        cmos_addr_mode(opcode).is_some()
+    }
+
+    pub fn get_addr_mode(opcode: u8) -> AddressMode {
+        match cmos_addr_mode(opcode) {
+            Some(mode) => mode,
+            None       => addr::get_addr_mode(opcode)
+        }
+    }
+
+    pub fn base_count(opcode: u8) -> u8 {
+        match opcode {
+            cmos::BIT_IMMEDIATE    |
+            cmos::INC_ACCUMULATOR  |
+            cmos::DEC_ACCUMULATOR  |
+            cmos::BRA_RELATIVE     => 2,
+
+            cmos::PHX_IMPLIED      |
+            cmos::PHY_IMPLIED      |
+            cmos::STZ_ZERO_PAGE    |
+            cmos::WAI_IMPLIED      |
+            cmos::STP_IMPLIED      => 3,
+
+            cmos::PLX_IMPLIED      |
+            cmos::PLY_IMPLIED      |
+            cmos::STZ_ZERO_PAGE_X  |
+            cmos::STZ_ABSOLUTE     |
+            cmos::BIT_ZERO_PAGE_X  |
+            cmos::BIT_ABSOLUTE_X   => 4,
+
+            cmos::STZ_ABSOLUTE_X   |
+            cmos::TSB_ZERO_PAGE    |
+            cmos::TRB_ZERO_PAGE    |
+            cmos::ORA_ZERO_PAGE_INDIRECT |
+            cmos::AND_ZERO_PAGE_INDIRECT |
+            cmos::EOR_ZERO_PAGE_INDIRECT |
+            cmos::ADC_ZERO_PAGE_INDIRECT |
+            cmos::STA_ZERO_PAGE_INDIRECT |
+            cmos::LDA_ZERO_PAGE_INDIRECT |
+            cmos::CMP_ZERO_PAGE_INDIRECT |
+            cmos::SBC_ZERO_PAGE_INDIRECT |
+            cmos::RMB0_ZERO_PAGE          |
+            cmos::RMB1_ZERO_PAGE          |
+            cmos::RMB2_ZERO_PAGE          |
+            cmos::RMB3_ZERO_PAGE          |
+            cmos::RMB4_ZERO_PAGE          |
+            cmos::RMB5_ZERO_PAGE          |
+            cmos::RMB6_ZERO_PAGE          |
+            cmos::RMB7_ZERO_PAGE          |
+            cmos::SMB0_ZERO_PAGE          |
+            cmos::SMB1_ZERO_PAGE          |
+            cmos::SMB2_ZERO_PAGE          |
+            cmos::SMB3_ZERO_PAGE          |
+            cmos::SMB4_ZERO_PAGE          |
+            cmos::SMB5_ZERO_PAGE          |
+            cmos::SMB6_ZERO_PAGE          |
+            cmos::SMB7_ZERO_PAGE          |
+            cmos::BBR0_ZERO_PAGE_RELATIVE |
+            cmos::BBR1_ZERO_PAGE_RELATIVE |
+            cmos::BBR2_ZERO_PAGE_RELATIVE |
+            cmos::BBR3_ZERO_PAGE_RELATIVE |
+            cmos::BBR4_ZERO_PAGE_RELATIVE |
+            cmos::BBR5_ZERO_PAGE_RELATIVE |
+            cmos::BBR6_ZERO_PAGE_RELATIVE |
+            cmos::BBR7_ZERO_PAGE_RELATIVE |
+            cmos::BBS0_ZERO_PAGE_RELATIVE |
+            cmos::BBS1_ZERO_PAGE_RELATIVE |
+            cmos::BBS2_ZERO_PAGE_RELATIVE |
+            cmos::BBS3_ZERO_PAGE_RELATIVE |
+            cmos::BBS4_ZERO_PAGE_RELATIVE |
+            cmos::BBS5_ZERO_PAGE_RELATIVE |
+            cmos::BBS6_ZERO_PAGE_RELATIVE |
+            cmos::BBS7_ZERO_PAGE_RELATIVE => 5,
+
+            cmos::TSB_ABSOLUTE     |
+            cmos::TRB_ABSOLUTE     |
+            cmos::JMP_ABSOLUTE_INDIRECT_X |
+            ops::JMP_INDIRECT      => 6,
+
+            // Shifts with absolute X only take the extra cycle
+            // on page crossing.
+            ops::ASL_ABSOLUTE_X    |
+            ops::LSR_ABSOLUTE_X    |
+            ops::ROL_ABSOLUTE_X    |
+            ops::ROR_ABSOLUTE_X    => 6,
+
+            0x5C                   => 8,
+
+            op                     => {
+                match nop_addr_mode(op) {
+                    Some(AddressMode::Immediate) => 2,
+                    Some(AddressMode::ZeroPage)  => 3,
+                    Some(AddressMode::ZeroPageX) |
+                    Some(AddressMode::Absolute)  => 4,
+                    Some(_)                      => 1,
+                    None                         => cycles::base_count(op)
+                }
+            }
+        }
+    }
+
+    pub fn has_page_penalty(opcode: u8) -> bool {
+        match opcode {
+            cmos::BIT_ABSOLUTE_X   |
+            ops::ASL_ABSOLUTE_X    |
+            ops::LSR_ABSOLUTE_X    |
+            ops::ROL_ABSOLUTE_X    |
+            ops::ROR_ABSOLUTE_X    => true,
+
+            op if redefines(op)    => false,
+
+            op                     => cycles::has_page_penalty(op)
+        }
+    }
+
+    pub fn op_name(opcode: u8) -> String {
+        let str = match opcode {
+            op if !redefines(op)   => return mcs6502::op_name(op),
+
+            cmos::BRA_RELATIVE     => "BRA",
+
+            cmos::BIT_IMMEDIATE    |
+            cmos::BIT_ZERO_PAGE_X  |
+            cmos::BIT_ABSOLUTE_X   => "BIT",
+
+            cmos::INC_ACCUMULATOR  => "INC",
+
+            cmos::DEC_ACCUMULATOR  => "DEC",
+
+            cmos::JMP_ABSOLUTE_INDIRECT_X => "JMP",
+
+            cmos::PHX_IMPLIED      => "PHX",
+
+            cmos::PHY_IMPLIED      => "PHY",
+
+            cmos::PLX_IMPLIED      => "PLX",
+
+            cmos::PLY_IMPLIED      => "PLY",
+
+            cmos::STZ_ZERO_PAGE    |
+            cmos::STZ_ZERO_PAGE_X  |
+            cmos::STZ_ABSOLUTE     |
+            cmos::STZ_ABSOLUTE_X   => "STZ",
+
+            cmos::TRB_ZERO_PAGE    |
+            cmos::TRB_ABSOLUTE     => "TRB",
+
+            cmos::TSB_ZERO_PAGE    |
+            cmos::TSB_ABSOLUTE     => "TSB",
+
+            cmos::ORA_ZERO_PAGE_INDIRECT => "ORA",
+
+            cmos::AND_ZERO_PAGE_INDIRECT => "AND",
+
+            cmos::EOR_ZERO_PAGE_INDIRECT => "EOR",
+
+            cmos::ADC_ZERO_PAGE_INDIRECT => "ADC",
+
+            cmos::STA_ZERO_PAGE_INDIRECT => "STA",
+
+            cmos::LDA_ZERO_PAGE_INDIRECT => "LDA",
+
+            cmos::CMP_ZERO_PAGE_INDIRECT => "CMP",
+
+            cmos::SBC_ZERO_PAGE_INDIRECT => "SBC",
+
+            cmos::WAI_IMPLIED      => "WAI",
+
+            cmos::STP_IMPLIED      => "STP",
+
+            op if is_nop(op)       => "NOP",
+
+            // Bit instructions carry the bit index in their name.
+            op                     => {
+                let name = match (op & 0x0F, op & 0x80) {
+                    (0x07, 0x00) => "RMB",
+                    (0x07, _)    => "SMB",
+                    (_, 0x00)    => "BBR",
+                    (_, _)       => "BBS"
+                };
+
+                return format!("{}{}", name, bit_index(op));
+            }
+        };
+
+        String::from(str)
+    }
+
+    // Index of the bit used by RMB, SMB, BBR and BBS.
+    pub fn bit_index(opcode: u8) -> u8 {
+        (opcode >> 4) & 0x07
+    }
+
+    // Opcode of RMB, SMB, BBR and BBS, which carry the bit index
+    // in their name.
+    pub fn bit_op_to_opcode(op: &str) -> Option<u8> {
+        if op.len() != 4 {
+            return None;
+        }
+
+        let (name, bit) = op.split_at(3);
+        let bit = match bit.parse::<u8>() {
+            Ok(bit) if bit < 8 => bit << 4,
+            _                  => return None
+        };
+
+        let opcode = match name {
+            "RMB" => cmos::RMB0_ZERO_PAGE + bit,
+            "SMB" => cmos::SMB0_ZERO_PAGE + bit,
+            "BBR" => cmos::BBR0_ZERO_PAGE_RELATIVE + bit,
+            "BBS" => cmos::BBS0_ZERO_PAGE_RELATIVE + bit,
+            _     => return None
+        };
+
+        if redefines(opcode) {
+            Some(opcode)
+        } else {
+            None
+        }
+    }
+
+    pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> Result<u8, String> {
+        let opcode = match (op, mode) {
+            ("BRA", &AddressMode::Label)       |
+            ("BRA", &AddressMode::Relative)    => cmos::BRA_RELATIVE,
+
+            ("BIT", &AddressMode::Immediate)   => cmos::BIT_IMMEDIATE,
+            ("BIT", &AddressMode::ZeroPageX)   => cmos::BIT_ZERO_PAGE_X,
+            ("BIT", &AddressMode::LabelX)      |
+            ("BIT", &AddressMode::AbsoluteX)   => cmos::BIT_ABSOLUTE_X,
+
+            ("INC", &AddressMode::Accumulator) => cmos::INC_ACCUMULATOR,
+            ("DEC", &AddressMode::Accumulator) => cmos::DEC_ACCUMULATOR,
+
+            ("PHX", &AddressMode::Implied)     => cmos::PHX_IMPLIED,
+            ("PHY", &AddressMode::Implied)     => cmos::PHY_IMPLIED,
+            ("PLX", &AddressMode::Implied)     => cmos::PLX_IMPLIED,
+            ("PLY", &AddressMode::Implied)     => cmos::PLY_IMPLIED,
+
+            ("STZ", &AddressMode::ZeroPage)    => cmos::STZ_ZERO_PAGE,
+            ("STZ", &AddressMode::ZeroPageX)   => cmos::STZ_ZERO_PAGE_X,
+            ("STZ", &AddressMode::Label)       |
+            ("STZ", &AddressMode::Absolute)    => cmos::STZ_ABSOLUTE,
+            ("STZ", &AddressMode::LabelX)      |
+            ("STZ", &AddressMode::AbsoluteX)   => cmos::STZ_ABSOLUTE_X,
+
+            ("TRB", &AddressMode::ZeroPage)    => cmos::TRB_ZERO_PAGE,
+            ("TRB", &AddressMode::Label)       |
+            ("TRB", &AddressMode::Absolute)    => cmos::TRB_ABSOLUTE,
+
+            ("TSB", &AddressMode::ZeroPage)    => cmos::TSB_ZERO_PAGE,
+            ("TSB", &AddressMode::Label)       |
+            ("TSB", &AddressMode::Absolute)    => cmos::TSB_ABSOLUTE,
+
+            ("ORA", &AddressMode::ZeroPageIndirect) => cmos::ORA_ZERO_PAGE_INDIRECT,
+            ("AND", &AddressMode::ZeroPageIndirect) => cmos::AND_ZERO_PAGE_INDIRECT,
+            ("EOR", &AddressMode::ZeroPageIndirect) => cmos::EOR_ZERO_PAGE_INDIRECT,
+            ("ADC", &AddressMode::ZeroPageIndirect) => cmos::ADC_ZERO_PAGE_INDIRECT,
+            ("STA", &AddressMode::ZeroPageIndirect) => cmos::STA_ZERO_PAGE_INDIRECT,
+            ("LDA", &AddressMode::ZeroPageIndirect) => cmos::LDA_ZERO_PAGE_INDIRECT,
+            ("CMP", &AddressMode::ZeroPageIndirect) => cmos::CMP_ZERO_PAGE_INDIRECT,
+            ("SBC", &AddressMode::ZeroPageIndirect) => cmos::SBC_ZERO_PAGE_INDIRECT,
+
+            ("JMP", &AddressMode::ILabelX)     |
+            ("JMP", &AddressMode::AbsoluteIndirectX) => {
+                cmos::JMP_ABSOLUTE_INDIRECT_X
+            }
+
+            ("WAI", &AddressMode::Implied)     => cmos::WAI_IMPLIED,
+            ("STP", &AddressMode::Implied)     => cmos::STP_IMPLIED,
+
+            _                                  => {
+                return mcs6502::name_mode_to_opcode(op, mode);
+            }
+        };
+
+        Ok(opcode)
+    }
+
+    pub fn can_branch_to_label(op: u8) -> bool {
+        match cmos_addr_mode(op) {
+            Some(AddressMode::Relative)         |
+            Some(AddressMode::ZeroPageRelative) => true,
+            Some(_)                             => false,
+            None                                => mcs6502::can_branch_to_label(op)
+        }
+    }
+
+    pub fn can_jump_to_label(op: u8) -> bool {
+        op == cmos::JMP_ABSOLUTE_INDIRECT_X || mcs6502::can_jump_to_label(op)
+    }
+
+    pub fn can_use_variables(op: u8) -> bool {
+        match op {
+            cmos::STZ_ABSOLUTE     |
+            cmos::STZ_ABSOLUTE_X   |
+            cmos::TRB_ABSOLUTE     |
+            cmos::TSB_ABSOLUTE     |
+            cmos::BIT_ABSOLUTE_X   => true,
+
+            op if redefines(op)    => false,
+
+            op                     => mcs6502::can_use_variables(op)
         }
     }
 }
@@ -493,34 +1351,265 @@ pub mod ops {
     // Transfer index X to stack pointer.
     pub const TXS_IMPLIED:     u8 = 0x9A;
 
-    pub mod custom {
-        pub const VARIABLE:     u8 = 0xFB;
-        pub const TOS_ABSOLUTE: u8 = 0xFC;
-        pub const PRT_ABSOLUTE: u8 = 0xFF;
+    // Undocumented NMOS instructions.
+
+    // Shift left memory, then "OR" it with accumulator.
+    pub const SLO_ZERO_PAGE:   u8 = 0x07;
+    pub const SLO_ZERO_PAGE_X: u8 = 0x17;
+    pub const SLO_ABSOLUTE:    u8 = 0x0F;
+    pub const SLO_ABSOLUTE_X:  u8 = 0x1F;
+    pub const SLO_ABSOLUTE_Y:  u8 = 0x1B;
+    pub const SLO_INDIRECT_X:  u8 = 0x03;
+    pub const SLO_INDIRECT_Y:  u8 = 0x13;
+
+    // Rotate left memory, then "And" it with accumulator.
+    pub const RLA_ZERO_PAGE:   u8 = 0x27;
+    pub const RLA_ZERO_PAGE_X: u8 = 0x37;
+    pub const RLA_ABSOLUTE:    u8 = 0x2F;
+    pub const RLA_ABSOLUTE_X:  u8 = 0x3F;
+    pub const RLA_ABSOLUTE_Y:  u8 = 0x3B;
+    pub const RLA_INDIRECT_X:  u8 = 0x23;
+    pub const RLA_INDIRECT_Y:  u8 = 0x33;
+
+    // Shift right memory, then "Exclusive-Or" it with accumulator.
+    pub const SRE_ZERO_PAGE:   u8 = 0x47;
+    pub const SRE_ZERO_PAGE_X: u8 = 0x57;
+    pub const SRE_ABSOLUTE:    u8 = 0x4F;
+    pub const SRE_ABSOLUTE_X:  u8 = 0x5F;
+    pub const SRE_ABSOLUTE_Y:  u8 = 0x5B;
+    pub const SRE_INDIRECT_X:  u8 = 0x43;
+    pub const SRE_INDIRECT_Y:  u8 = 0x53;
+
+    // Rotate right memory, then add it to accumulator with carry.
+    pub const RRA_ZERO_PAGE:   u8 = 0x67;
+    pub const RRA_ZERO_PAGE_X: u8 = 0x77;
+    pub const RRA_ABSOLUTE:    u8 = 0x6F;
+    pub const RRA_ABSOLUTE_X:  u8 = 0x7F;
+    pub const RRA_ABSOLUTE_Y:  u8 = 0x7B;
+    pub const RRA_INDIRECT_X:  u8 = 0x63;
+    pub const RRA_INDIRECT_Y:  u8 = 0x73;
+
+    // Store accumulator "And" index X in memory.
+    pub const SAX_ZERO_PAGE:   u8 = 0x87;
+    pub const SAX_ZERO_PAGE_Y: u8 = 0x97;
+    pub const SAX_ABSOLUTE:    u8 = 0x8F;
+    pub const SAX_INDIRECT_X:  u8 = 0x83;
+
+    // Load accumulator and index X with memory.
+    // Immediate variant is unstable.
+    pub const LAX_IMMEDIATE:   u8 = 0xAB;
+    pub const LAX_ZERO_PAGE:   u8 = 0xA7;
+    pub const LAX_ZERO_PAGE_Y: u8 = 0xB7;
+    pub const LAX_ABSOLUTE:    u8 = 0xAF;
+    pub const LAX_ABSOLUTE_Y:  u8 = 0xBF;
+    pub const LAX_INDIRECT_X:  u8 = 0xA3;
+    pub const LAX_INDIRECT_Y:  u8 = 0xB3;
+
+    // Decrement memory by one, then compare it with accumulator.
+    pub const DCP_ZERO_PAGE:   u8 = 0xC7;
+    pub const DCP_ZERO_PAGE_X: u8 = 0xD7;
+    pub const DCP_ABSOLUTE:    u8 = 0xCF;
+    pub const DCP_ABSOLUTE_X:  u8 = 0xDF;
+    pub const DCP_ABSOLUTE_Y:  u8 = 0xDB;
+    pub const DCP_INDIRECT_X:  u8 = 0xC3;
+    pub const DCP_INDIRECT_Y:  u8 = 0xD3;
+
+    // Increment memory by one, then subtract it from accumulator.
+    pub const ISC_ZERO_PAGE:   u8 = 0xE7;
+    pub const ISC_ZERO_PAGE_X: u8 = 0xF7;
+    pub const ISC_ABSOLUTE:    u8 = 0xEF;
+    pub const ISC_ABSOLUTE_X:  u8 = 0xFF;
+    pub const ISC_ABSOLUTE_Y:  u8 = 0xFB;
+    pub const ISC_INDIRECT_X:  u8 = 0xE3;
+    pub const ISC_INDIRECT_Y:  u8 = 0xF3;
+
+    // "And" memory with accumulator, copy bit 7 to carry.
+    pub const ANC_IMMEDIATE_0B: u8 = 0x0B;
+    pub const ANC_IMMEDIATE_2B: u8 = 0x2B;
+
+    // "And" memory with accumulator, then shift right.
+    pub const ALR_IMMEDIATE:   u8 = 0x4B;
+
+    // "And" memory with accumulator, then rotate right.
+    pub const ARR_IMMEDIATE:   u8 = 0x6B;
+
+    // Subtract memory from accumulator "And" index X into index X.
+    pub const SBX_IMMEDIATE:   u8 = 0xCB;
+
+    // Same as SBC_IMMEDIATE.
+    pub const SBC_IMMEDIATE_EB: u8 = 0xEB;
+
+    // "And" index X and memory with accumulator (unstable).
+    pub const ANE_IMMEDIATE:   u8 = 0x8B;
+
+    // Store accumulator "And" index X "And" high byte
+    // of the address plus one (unstable).
+    pub const SHA_ABSOLUTE_Y:  u8 = 0x9F;
+    pub const SHA_INDIRECT_Y:  u8 = 0x93;
+
+    // Store index X "And" high byte of the address plus one (unstable).
+    pub const SHX_ABSOLUTE_Y:  u8 = 0x9E;
+
+    // Store index Y "And" high byte of the address plus one (unstable).
+    pub const SHY_ABSOLUTE_X:  u8 = 0x9C;
+
+    // Transfer accumulator "And" index X to stack pointer, then
+    // store it "And" high byte of the address plus one (unstable).
+    pub const TAS_ABSOLUTE_Y:  u8 = 0x9B;
+
+    // "And" memory with stack pointer into accumulator,
+    // index X and stack pointer.
+    pub const LAS_ABSOLUTE_Y:  u8 = 0xBB;
+
+    // No operation, reads its operand if it has one.
+    pub const NOP_IMPLIED_1A:     u8 = 0x1A;
+    pub const NOP_IMPLIED_3A:     u8 = 0x3A;
+    pub const NOP_IMPLIED_5A:     u8 = 0x5A;
+    pub const NOP_IMPLIED_7A:     u8 = 0x7A;
+    pub const NOP_IMPLIED_DA:     u8 = 0xDA;
+    pub const NOP_IMPLIED_FA:     u8 = 0xFA;
+    pub const NOP_IMMEDIATE_80:   u8 = 0x80;
+    pub const NOP_IMMEDIATE_82:   u8 = 0x82;
+    pub const NOP_IMMEDIATE_89:   u8 = 0x89;
+    pub const NOP_IMMEDIATE_C2:   u8 = 0xC2;
+    pub const NOP_IMMEDIATE_E2:   u8 = 0xE2;
+    pub const NOP_ZERO_PAGE_04:   u8 = 0x04;
+    pub const NOP_ZERO_PAGE_44:   u8 = 0x44;
+    pub const NOP_ZERO_PAGE_64:   u8 = 0x64;
+    pub const NOP_ZERO_PAGE_X_14: u8 = 0x14;
+    pub const NOP_ZERO_PAGE_X_34: u8 = 0x34;
+    pub const NOP_ZERO_PAGE_X_54: u8 = 0x54;
+    pub const NOP_ZERO_PAGE_X_74: u8 = 0x74;
+    pub const NOP_ZERO_PAGE_X_D4: u8 = 0xD4;
+    pub const NOP_ZERO_PAGE_X_F4: u8 = 0xF4;
+    pub const NOP_ABSOLUTE_0C:    u8 = 0x0C;
+    pub const NOP_ABSOLUTE_X_1C:  u8 = 0x1C;
+    pub const NOP_ABSOLUTE_X_3C:  u8 = 0x3C;
+    pub const NOP_ABSOLUTE_X_5C:  u8 = 0x5C;
+    pub const NOP_ABSOLUTE_X_7C:  u8 = 0x7C;
+    pub const NOP_ABSOLUTE_X_DC:  u8 = 0xDC;
+    pub const NOP_ABSOLUTE_X_FC:  u8 = 0xFC;
+
+    // Halt the cpu until reset (also known as KIL).
+    pub const JAM_02: u8 = 0x02;
+    pub const JAM_12: u8 = 0x12;
+    pub const JAM_22: u8 = 0x22;
+    pub const JAM_32: u8 = 0x32;
+    pub const JAM_42: u8 = 0x42;
+    pub const JAM_52: u8 = 0x52;
+    pub const JAM_62: u8 = 0x62;
+    pub const JAM_72: u8 = 0x72;
+    pub const JAM_92: u8 = 0x92;
+    pub const JAM_B2: u8 = 0xB2;
+    pub const JAM_D2: u8 = 0xD2;
+    pub const JAM_F2: u8 = 0xF2;
+
+    // Instructions added by the 65C02, most of them reuse
+    // opcodes of the undocumented NMOS instructions.
+    pub mod cmos {
+        // Branch always.
+        pub const BRA_RELATIVE:     u8 = 0x80;
+
+        // Test bits in memory with accumulator.
+        pub const BIT_IMMEDIATE:    u8 = 0x89;
+        pub const BIT_ZERO_PAGE_X:  u8 = 0x34;
+        pub const BIT_ABSOLUTE_X:   u8 = 0x3C;
+
+        // Increment or decrement accumulator by one.
+        pub const INC_ACCUMULATOR:  u8 = 0x1A;
+        pub const DEC_ACCUMULATOR:  u8 = 0x3A;
+
+        // Jump to new location indexed by X.
+        pub const JMP_ABSOLUTE_INDIRECT_X: u8 = 0x7C;
+
+        // Push or pull index registers.
+        pub const PHX_IMPLIED:      u8 = 0xDA;
+        pub const PHY_IMPLIED:      u8 = 0x5A;
+        pub const PLX_IMPLIED:      u8 = 0xFA;
+        pub const PLY_IMPLIED:      u8 = 0x7A;
+
+        // Store zero in memory.
+        pub const STZ_ZERO_PAGE:    u8 = 0x64;
+        pub const STZ_ZERO_PAGE_X:  u8 = 0x74;
+        pub const STZ_ABSOLUTE:     u8 = 0x9C;
+        pub const STZ_ABSOLUTE_X:   u8 = 0x9E;
+
+        // Test and reset memory bits with accumulator.
+        pub const TRB_ZERO_PAGE:    u8 = 0x14;
+        pub const TRB_ABSOLUTE:     u8 = 0x1C;
+
+        // Test and set memory bits with accumulator.
+        pub const TSB_ZERO_PAGE:    u8 = 0x04;
+        pub const TSB_ABSOLUTE:     u8 = 0x0C;
+
+        // Zero page indirect variants of the accumulator instructions.
+        pub const ORA_ZERO_PAGE_INDIRECT: u8 = 0x12;
+        pub const AND_ZERO_PAGE_INDIRECT: u8 = 0x32;
+        pub const EOR_ZERO_PAGE_INDIRECT: u8 = 0x52;
+        pub const ADC_ZERO_PAGE_INDIRECT: u8 = 0x72;
+        pub const STA_ZERO_PAGE_INDIRECT: u8 = 0x92;
+        pub const LDA_ZERO_PAGE_INDIRECT: u8 = 0xB2;
+        pub const CMP_ZERO_PAGE_INDIRECT: u8 = 0xD2;
+        pub const SBC_ZERO_PAGE_INDIRECT: u8 = 0xF2;
+
+        // Reset memory bit.
+        pub const RMB0_ZERO_PAGE:          u8 = 0x07;
+        pub const RMB1_ZERO_PAGE:          u8 = 0x17;
+        pub const RMB2_ZERO_PAGE:          u8 = 0x27;
+        pub const RMB3_ZERO_PAGE:          u8 = 0x37;
+        pub const RMB4_ZERO_PAGE:          u8 = 0x47;
+        pub const RMB5_ZERO_PAGE:          u8 = 0x57;
+        pub const RMB6_ZERO_PAGE:          u8 = 0x67;
+        pub const RMB7_ZERO_PAGE:          u8 = 0x77;
+
+        // Set memory bit.
+        pub const SMB0_ZERO_PAGE:          u8 = 0x87;
+        pub const SMB1_ZERO_PAGE:          u8 = 0x97;
+        pub const SMB2_ZERO_PAGE:          u8 = 0xA7;
+        pub const SMB3_ZERO_PAGE:          u8 = 0xB7;
+        pub const SMB4_ZERO_PAGE:          u8 = 0xC7;
+        pub const SMB5_ZERO_PAGE:          u8 = 0xD7;
+        pub const SMB6_ZERO_PAGE:          u8 = 0xE7;
+        pub const SMB7_ZERO_PAGE:          u8 = 0xF7;
+
+        // Branch on memory bit reset.
+        pub const BBR0_ZERO_PAGE_RELATIVE: u8 = 0x0F;
+        pub const BBR1_ZERO_PAGE_RELATIVE: u8 = 0x1F;
+        pub const BBR2_ZERO_PAGE_RELATIVE: u8 = 0x2F;
+        pub const BBR3_ZERO_PAGE_RELATIVE: u8 = 0x3F;
+        pub const BBR4_ZERO_PAGE_RELATIVE: u8 = 0x4F;
+        pub const BBR5_ZERO_PAGE_RELATIVE: u8 = 0x5F;
+        pub const BBR6_ZERO_PAGE_RELATIVE: u8 = 0x6F;
+        pub const BBR7_ZERO_PAGE_RELATIVE: u8 = 0x7F;
+
+        // Branch on memory bit set.
+        pub const BBS0_ZERO_PAGE_RELATIVE: u8 = 0x8F;
+        pub const BBS1_ZERO_PAGE_RELATIVE: u8 = 0x9F;
+        pub const BBS2_ZERO_PAGE_RELATIVE: u8 = 0xAF;
+        pub const BBS3_ZERO_PAGE_RELATIVE: u8 = 0xBF;
+        pub const BBS4_ZERO_PAGE_RELATIVE: u8 = 0xCF;
+        pub const BBS5_ZERO_PAGE_RELATIVE: u8 = 0xDF;
+        pub const BBS6_ZERO_PAGE_RELATIVE: u8 = 0xEF;
+        pub const BBS7_ZERO_PAGE_RELATIVE: u8 = 0xFF;
+
+        // Wait for interrupt.
+        pub const WAI_IMPLIED:      u8 = 0xCB;
+
+        // Stop the processor until reset.
+        pub const STP_IMPLIED:      u8 = 0xDB;
     }
 }
 
+// Evaluates a constant expression, leading # or * and
+// index registers are ignored, e.g. #<$1234 or *$10+2,X.
 pub fn extract_operand(chars: &[char]) -> Option<u16> {
-    let string: String = chars.into_iter().collect();
-
-    let mut lexer = Lexer::new(&string);
-    lexer.skip_while(&char::is_whitespace);
-    lexer.skip_while(&|c| c == '#' || c == '*');
-    lexer.skip_while(&char::is_whitespace);
-
-    let rule;
-    if lexer.test_eq('$') {
-        rule = UInt::new(16);
-        lexer.skip(1);
-    } else {
-        rule = UInt::new(10);
-    }
+    let string: String = chars.iter().collect();
+    let string = string.trim_start().trim_start_matches(['#', '*']);
+    let string = string.split(',').next().unwrap_or("");
 
-    let res = lexer.next(rule);
-    if let Some(Token::UInt(num)) = res {
-        Some(num as u16)
-    } else {
-        None
+    match Expr::parse(string).and_then(|expr| expr.eval_const()) {
+        Ok(value) => Some(value as u16),
+        Err(_)    => None
     }
 }
 
@@ -654,8 +1743,8 @@ pub fn parse_arguments(arguments: &str) -> (AddressMode, u16) {
     (addr_mode, operand)
 }
 
-pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
-    match op {
+pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> Result<u8, String> {
+    let opcode = match op {
         "ADC" => {
             match *mode {
                 AddressMode::Immediate => {
@@ -687,7 +1776,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::IndirectY => {
                     ops::ADC_INDIRECT_Y
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "AND" => {
@@ -721,7 +1810,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::IndirectY => {
                     ops::AND_INDIRECT_Y
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "ASL" => {
@@ -743,7 +1832,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::AbsoluteX   => {
                     ops::ASL_ABSOLUTE_X
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "BCC" => {
@@ -752,7 +1841,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Relative => {
                     ops::BCC_RELATIVE
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "BCS" => {
@@ -761,7 +1850,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Relative => {
                     ops::BCS_RELATIVE
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "BEQ" => {
@@ -770,7 +1859,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Relative => {
                     ops::BEQ_RELATIVE
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "BIT" => {
@@ -782,7 +1871,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Absolute => {
                     ops::BIT_ABSOLUTE
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "BMI" => {
@@ -791,7 +1880,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Relative => {
                     ops::BMI_RELATIVE
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "BNE" => {
@@ -800,7 +1889,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Relative => {
                     ops::BNE_RELATIVE
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "BPL" => {
@@ -809,7 +1898,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Relative => {
                     ops::BPL_RELATIVE
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "BRK" => {
@@ -817,7 +1906,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::BRK_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "BVC" => {
@@ -826,7 +1915,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Relative => {
                     ops::BVC_RELATIVE
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "BVS" => {
@@ -835,7 +1924,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Relative => {
                     ops::BVS_RELATIVE
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "CLC" => {
@@ -843,7 +1932,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::CLC_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "CLD" => {
@@ -851,7 +1940,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::CLD_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "CLI" => {
@@ -859,7 +1948,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::CLI_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "CLV" => {
@@ -867,7 +1956,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::CLV_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "CMP" => {
@@ -901,7 +1990,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::IndirectY => {
                     ops::CMP_INDIRECT_Y
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "CPX" => {
@@ -916,7 +2005,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Absolute  => {
                     ops::CPX_ABSOLUTE
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "CPY" => {
@@ -931,7 +2020,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Absolute  => {
                     ops::CPY_ABSOLUTE
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "DEC" => {
@@ -950,7 +2039,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::AbsoluteX => {
                     ops::DEC_ABSOLUTE_X
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "DEX" => {
@@ -958,7 +2047,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::DEX_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "DEY" => {
@@ -966,7 +2055,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::DEY_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "EOR" => {
@@ -1000,7 +2089,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::IndirectY => {
                     ops::EOR_INDIRECT_Y
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "INC" => {
@@ -1019,7 +2108,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::AbsoluteX => {
                     ops::INC_ABSOLUTE_X
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "INX" => {
@@ -1027,7 +2116,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::INX_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "INY" => {
@@ -1035,7 +2124,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::INY_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "JMP" => {
@@ -1048,7 +2137,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Indirect => {
                     ops::JMP_INDIRECT
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "JSR" => {
@@ -1057,7 +2146,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Absolute => {
                     ops::JSR_ABSOLUTE
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "LDA" => {
@@ -1091,7 +2180,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::IndirectY => {
                     ops::LDA_INDIRECT_Y
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "LDX" => {
@@ -1113,7 +2202,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::AbsoluteY => {
                     ops::LDX_ABSOLUTE_Y
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "LDY" => {
@@ -1135,7 +2224,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::AbsoluteX => {
                     ops::LDY_ABSOLUTE_X
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "LSR" => {
@@ -1157,7 +2246,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::AbsoluteX   => {
                     ops::LSR_ABSOLUTE_X
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "NOP" => {
@@ -1165,7 +2254,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::NOP_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "ORA" => {
@@ -1199,7 +2288,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::IndirectY => {
                     ops::ORA_INDIRECT_Y
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "PHA" => {
@@ -1207,7 +2296,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::PHA_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "PHP" => {
@@ -1215,7 +2304,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::PHP_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "PLA" => {
@@ -1223,7 +2312,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::PLA_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "PLP" => {
@@ -1231,7 +2320,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::PLP_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "ROL" => {
@@ -1253,7 +2342,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::AbsoluteX   => {
                     ops::ROL_ABSOLUTE_X
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "ROR" => {
@@ -1275,7 +2364,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::AbsoluteX   => {
                     ops::ROR_ABSOLUTE_X
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "RTI" => {
@@ -1283,7 +2372,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::RTI_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "RTS" => {
@@ -1291,7 +2380,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::RTS_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "SBC" => {
@@ -1325,7 +2414,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::IndirectY => {
                     ops::SBC_INDIRECT_Y
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "SEC" => {
@@ -1333,7 +2422,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::SEC_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "SED" => {
@@ -1341,7 +2430,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::SED_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "SEI" => {
@@ -1349,7 +2438,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::SEI_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "STA" => {
@@ -1380,7 +2469,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::IndirectY => {
                     ops::STA_INDIRECT_Y
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "STX" => {
@@ -1395,7 +2484,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Absolute  => {
                     ops::STX_ABSOLUTE
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "STY" => {
@@ -1410,7 +2499,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Absolute  => {
                     ops::STY_ABSOLUTE
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "TAX" => {
@@ -1418,7 +2507,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::TAX_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "TAY" => {
@@ -1426,7 +2515,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::TAY_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "TYA" => {
@@ -1434,15 +2523,15 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::TYA_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "TSX" => {
             match *mode {
                 AddressMode::Implied => {
-                    ops::TXS_IMPLIED
+                    ops::TSX_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "TXA" => {
@@ -1450,7 +2539,7 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::TXA_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
         "TXS" => {
@@ -1458,37 +2547,13 @@ pub fn name_mode_to_opcode(op: &str, mode: &AddressMode) -> u8 {
                 AddressMode::Implied => {
                     ops::TXS_IMPLIED
                 }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
-            }
-        }
-        "VAR" => {
-            match *mode {
-                AddressMode::Immediate => {
-                    ops::custom::VARIABLE
-                }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
-            }
-        }
-        "TOS" => {
-            match *mode {
-                AddressMode::Label    |
-                AddressMode::Absolute => {
-                    ops::custom::TOS_ABSOLUTE
-                }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
+                _ => return Err(format!("Invalid address mode for instruction {}: {:?}", op, mode))
             }
         }
-        "PRT" => {
-            match *mode {
-                AddressMode::Label    |
-                AddressMode::Absolute => {
-                    ops::custom::PRT_ABSOLUTE
-                }
-                _ => panic!("Unknown address mode for instruction {}: {:?}", op, mode)
-            }
-        }
-        &_    => panic!("Unknown instruction: {}", op)
-    }
+        &_    => return Err(format!("Unknown instruction: {}", op))
+    };
+
+    Ok(opcode)
 }
 
 pub fn is_valid_instruction(op: &str) -> bool {
@@ -1504,7 +2569,13 @@ pub fn is_valid_instruction(op: &str) -> bool {
         "ROR" | "RTI" | "RTS" | "SBC" | "SEC" |
         "SED" | "SEI" | "STA" | "STX" | "STY" |
         "TAX" | "TAY" | "TYA" | "TSX" | "TXA" |
-        "TXS" | "PRT" | "TOS" | "VAR" => true,
+        "TXS" => true,
+
+        // 65C02 additions.
+        "BRA" | "PHX" | "PHY" | "PLX" | "PLY" |
+        "STZ" | "TRB" | "TSB" | "WAI" | "STP" => true,
+        op if cmos::bit_op_to_opcode(op).is_some() => true,
+
         &_    => false
     }
 }
@@ -1596,9 +2667,6 @@ pub fn can_use_variables(op: u8) -> bool {
         ops::SBC_INDIRECT_Y |
         ops::STA_INDIRECT_Y => true,
 
-        ops::custom::TOS_ABSOLUTE |
-        ops::custom::PRT_ABSOLUTE => true,
-
         _                 => false
     }
 
@@ -1813,18 +2881,143 @@ pub fn op_name(opcode: u8) -> String {
 
         ops::TXS_IMPLIED          => "TXS",
 
-        ops::custom::VARIABLE     => "VAR",
-
-        ops::custom::TOS_ABSOLUTE => "TOS",
-
-        ops::custom::PRT_ABSOLUTE => "PRT",
-
-        _                         => "UNKNOWN"
+        ops::SLO_ZERO_PAGE        |
+        ops::SLO_ZERO_PAGE_X      |
+        ops::SLO_ABSOLUTE         |
+        ops::SLO_ABSOLUTE_X       |
+        ops::SLO_ABSOLUTE_Y       |
+        ops::SLO_INDIRECT_X       |
+        ops::SLO_INDIRECT_Y       => "SLO",
+
+        ops::RLA_ZERO_PAGE        |
+        ops::RLA_ZERO_PAGE_X      |
+        ops::RLA_ABSOLUTE         |
+        ops::RLA_ABSOLUTE_X       |
+        ops::RLA_ABSOLUTE_Y       |
+        ops::RLA_INDIRECT_X       |
+        ops::RLA_INDIRECT_Y       => "RLA",
+
+        ops::SRE_ZERO_PAGE        |
+        ops::SRE_ZERO_PAGE_X      |
+        ops::SRE_ABSOLUTE         |
+        ops::SRE_ABSOLUTE_X       |
+        ops::SRE_ABSOLUTE_Y       |
+        ops::SRE_INDIRECT_X       |
+        ops::SRE_INDIRECT_Y       => "SRE",
+
+        ops::RRA_ZERO_PAGE        |
+        ops::RRA_ZERO_PAGE_X      |
+        ops::RRA_ABSOLUTE         |
+        ops::RRA_ABSOLUTE_X       |
+        ops::RRA_ABSOLUTE_Y       |
+        ops::RRA_INDIRECT_X       |
+        ops::RRA_INDIRECT_Y       => "RRA",
+
+        ops::SAX_ZERO_PAGE        |
+        ops::SAX_ZERO_PAGE_Y      |
+        ops::SAX_ABSOLUTE         |
+        ops::SAX_INDIRECT_X       => "SAX",
+
+        ops::LAX_IMMEDIATE        |
+        ops::LAX_ZERO_PAGE        |
+        ops::LAX_ZERO_PAGE_Y      |
+        ops::LAX_ABSOLUTE         |
+        ops::LAX_ABSOLUTE_Y       |
+        ops::LAX_INDIRECT_X       |
+        ops::LAX_INDIRECT_Y       => "LAX",
+
+        ops::DCP_ZERO_PAGE        |
+        ops::DCP_ZERO_PAGE_X      |
+        ops::DCP_ABSOLUTE         |
+        ops::DCP_ABSOLUTE_X       |
+        ops::DCP_ABSOLUTE_Y       |
+        ops::DCP_INDIRECT_X       |
+        ops::DCP_INDIRECT_Y       => "DCP",
+
+        ops::ISC_ZERO_PAGE        |
+        ops::ISC_ZERO_PAGE_X      |
+        ops::ISC_ABSOLUTE         |
+        ops::ISC_ABSOLUTE_X       |
+        ops::ISC_ABSOLUTE_Y       |
+        ops::ISC_INDIRECT_X       |
+        ops::ISC_INDIRECT_Y       => "ISC",
+
+        ops::ANC_IMMEDIATE_0B     |
+        ops::ANC_IMMEDIATE_2B     => "ANC",
+
+        ops::ALR_IMMEDIATE        => "ALR",
+
+        ops::ARR_IMMEDIATE        => "ARR",
+
+        ops::SBX_IMMEDIATE        => "SBX",
+
+        ops::SBC_IMMEDIATE_EB     => "SBC",
+
+        ops::ANE_IMMEDIATE        => "ANE",
+
+        ops::SHA_ABSOLUTE_Y       |
+        ops::SHA_INDIRECT_Y       => "SHA",
+
+        ops::SHX_ABSOLUTE_Y       => "SHX",
+
+        ops::SHY_ABSOLUTE_X       => "SHY",
+
+        ops::TAS_ABSOLUTE_Y       => "TAS",
+
+        ops::LAS_ABSOLUTE_Y       => "LAS",
+
+        ops::NOP_IMPLIED_1A       |
+        ops::NOP_IMPLIED_3A       |
+        ops::NOP_IMPLIED_5A       |
+        ops::NOP_IMPLIED_7A       |
+        ops::NOP_IMPLIED_DA       |
+        ops::NOP_IMPLIED_FA       |
+        ops::NOP_IMMEDIATE_80     |
+        ops::NOP_IMMEDIATE_82     |
+        ops::NOP_IMMEDIATE_89     |
+        ops::NOP_IMMEDIATE_C2     |
+        ops::NOP_IMMEDIATE_E2     |
+        ops::NOP_ZERO_PAGE_04     |
+        ops::NOP_ZERO_PAGE_44     |
+        ops::NOP_ZERO_PAGE_64     |
+        ops::NOP_ZERO_PAGE_X_14   |
+        ops::NOP_ZERO_PAGE_X_34   |
+        ops::NOP_ZERO_PAGE_X_54   |
+        ops::NOP_ZERO_PAGE_X_74   |
+        ops::NOP_ZERO_PAGE_X_D4   |
+        ops::NOP_ZERO_PAGE_X_F4   |
+        ops::NOP_ABSOLUTE_0C      |
+        ops::NOP_ABSOLUTE_X_1C    |
+        ops::NOP_ABSOLUTE_X_3C    |
+        ops::NOP_ABSOLUTE_X_5C    |
+        ops::NOP_ABSOLUTE_X_7C    |
+        ops::NOP_ABSOLUTE_X_DC    |
+        ops::NOP_ABSOLUTE_X_FC    => "NOP",
+
+        ops::JAM_02               |
+        ops::JAM_12               |
+        ops::JAM_22               |
+        ops::JAM_32               |
+        ops::JAM_42               |
+        ops::JAM_52               |
+        ops::JAM_62               |
+        ops::JAM_72               |
+        ops::JAM_92               |
+        ops::JAM_B2               |
+        ops::JAM_D2               |
+        ops::JAM_F2               => "JAM"
     };
 
     String::from(str)
 }
 
+pub fn is_jam(opcode: u8) -> bool {
+    matches!(opcode,
+        ops::JAM_02 | ops::JAM_12 | ops::JAM_22 | ops::JAM_32 |
+        ops::JAM_42 | ops::JAM_52 | ops::JAM_62 | ops::JAM_72 |
+        ops::JAM_92 | ops::JAM_B2 | ops::JAM_D2 | ops::JAM_F2)
+}
+
 pub fn addr_mode_to_operand(mode: &AddressMode, op8: u8, op16: u16) -> String {
     match *mode {
         AddressMode::Immediate   => format!("#${:02X}", op8),
@@ -1839,27 +3032,43 @@ pub fn addr_mode_to_operand(mode: &AddressMode, op8: u8, op16: u16) -> String {
         AddressMode::IndirectY   => format!("(${:02X}), Y", op8),
         AddressMode::Relative    => format!("${:02X}", op8),
         AddressMode::Accumulator => String::from("A"),
+
+        AddressMode::ZeroPageIndirect  => format!("(${:02X})", op8),
+        AddressMode::AbsoluteIndirectX => format!("(${:04X}, X)", op16),
+        AddressMode::ZeroPageRelative  => {
+            format!("*${:02X}, ${:02X}", op8, (op16 >> 8) as u8)
+        }
+
         _                        => String::from("")
     }
 }
 
 pub fn op_to_str(cart: &Memory, idx: &mut usize) -> String {
-    let opcode = cart.read_u8(*idx);
+    op_to_str_variant(cart, idx, Variant::Nmos)
+}
+
+pub fn op_to_str_variant(cart: &dyn Memory, idx: &mut usize,
+                         variant: Variant) -> String {
+    // Peeks don't trigger watchpoints or I/O side effects.
+    let opcode = cart.peek(*idx);
     let operand_u8;
     let operand_u16;
 
     if *idx + 1 < cart.size() {
-        operand_u8 = cart.read_u8(*idx + 1);
+        operand_u8 = cart.peek(*idx + 1);
     } else {
         operand_u8 = 0;
     }
 
     if *idx + 2 < cart.size() {
-        operand_u16 = cart.read_u16(*idx + 1);
+        operand_u16 = (cart.peek(*idx + 2) as u16) << 8 | operand_u8 as u16;
     } else {
         operand_u16 = 0;
     }
-    let addr_mode = addr::get_addr_mode(opcode);
+    let addr_mode = match variant {
+        Variant::Nmos => addr::get_addr_mode(opcode),
+        Variant::Cmos => cmos::get_addr_mode(opcode)
+    };
 
     let mut off = addr::pc_offset(&addr_mode);
     if off == 0 {
@@ -1868,7 +3077,10 @@ pub fn op_to_str(cart: &Memory, idx: &mut usize) -> String {
     *idx = idx.wrapping_add(off);
 
     let arg = addr_mode_to_operand(&addr_mode, operand_u8, operand_u16);
-    let name = op_name(opcode);
+    let name = match variant {
+        Variant::Nmos => op_name(opcode),
+        Variant::Cmos => cmos::op_name(opcode)
+    };
 
     if arg.is_empty() {
         format!("{}", name)
@@ -1975,4 +3187,41 @@ pub mod tests {
         assert_eq!(addr_mode, AddressMode::AbsoluteY);
         assert_eq!(operand, 1017);
     }
+
+    #[test]
+    fn cmos_disassembly() {
+        use mems::rom::Rom8b;
+
+        let cart = Rom8b::from_vec(vec![
+            ops::cmos::LDA_ZERO_PAGE_INDIRECT, 0x20,
+            ops::cmos::BBR3_ZERO_PAGE_RELATIVE, 0x12, 0x05,
+            ops::cmos::STZ_ABSOLUTE, 0x00, 0x03,
+            ops::cmos::RMB7_ZERO_PAGE, 0x40,
+            0x03
+        ]);
+
+        let mut idx = 0;
+        let mut next = || op_to_str_variant(&cart, &mut idx, Variant::Cmos);
+        assert_eq!(next(), "LDA ($20)");
+        assert_eq!(next(), "BBR3 *$12, $05");
+        assert_eq!(next(), "STZ $0300");
+        assert_eq!(next(), "RMB7 *$40");
+        assert_eq!(next(), "NOP");
+    }
+
+    #[test]
+    fn cmos_name_mode_to_opcode() {
+        assert_eq!(cmos::name_mode_to_opcode("LDA", &AddressMode::ZeroPageIndirect),
+                   Ok(ops::cmos::LDA_ZERO_PAGE_INDIRECT));
+        assert_eq!(cmos::name_mode_to_opcode("STZ", &AddressMode::ZeroPage),
+                   Ok(ops::cmos::STZ_ZERO_PAGE));
+        assert_eq!(cmos::name_mode_to_opcode("LDA", &AddressMode::Immediate),
+                   Ok(ops::LDA_IMMEDIATE));
+        assert!(cmos::name_mode_to_opcode("STZ", &AddressMode::Immediate).is_err());
+        assert_eq!(cmos::bit_op_to_opcode("SMB5"), Some(ops::cmos::SMB5_ZERO_PAGE));
+        assert_eq!(cmos::bit_op_to_opcode("BBS7"), Some(0xFF));
+        assert_eq!(cmos::bit_op_to_opcode("SMB8"), None);
+        assert!(is_valid_instruction("BBR0"));
+        assert!(!is_valid_identifier("TSB", false));
+    }
 }
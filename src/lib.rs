@@ -1,6 +1,12 @@
+#[cfg(unix)]
+extern crate libc;
+
 pub mod asm;
 pub mod cpus;
+pub mod devs;
 pub mod inst;
 pub mod lang;
+pub mod machines;
 pub mod mems;
+pub mod monitor;
 pub mod util;
@@ -0,0 +1,152 @@
+use std::io::Write;
+use std::sync::mpsc::Receiver;
+
+use cpus::Cpu;
+use cpus::mcs6502::Mcs6502;
+use devs::pia::Pia;
+use machines::Machine;
+use mems::bus::Bus8b;
+use mems::ram::Ram8b;
+use mems::rom::Rom8b;
+
+// Keyboard and display, see devs::pia.
+pub const PIA_ADDRESS: usize = 0xD010;
+
+// Wozmon, which also contains the vectors.
+pub const ROM_ADDRESS: usize = 0xFF00;
+pub const ROM_SIZE:    usize = 0x0100;
+
+// The 8K configuration adds a second 4K bank here,
+// e.g. for Integer BASIC.
+pub const HIGH_RAM_ADDRESS: usize = 0xE000;
+
+pub const RAM_BANK_SIZE: usize = 0x1000;
+
+pub const CLOCK_HZ: u64 = 1_022_727;
+
+pub struct Apple1 {
+    cpu: Mcs6502<Bus8b>
+}
+
+impl Apple1 {
+    // Ram is in KiB, the Apple I came with 4K or 8K.
+    pub fn new(rom: &[u8], ram: usize, display: Box<dyn Write>,
+               keyboard: Receiver<u8>) -> Apple1 {
+        if rom.len() != ROM_SIZE {
+            panic!("Apple I rom must be {} bytes, got {}", ROM_SIZE, rom.len());
+        }
+
+        let mut bus = Bus8b::new(64 * 1024);
+        bus.attach(0x0000, Box::new(Ram8b::new(RAM_BANK_SIZE)));
+        match ram {
+            4 => (),
+            8 => bus.attach(HIGH_RAM_ADDRESS, Box::new(Ram8b::new(RAM_BANK_SIZE))),
+            _ => panic!("Apple I has 4K or 8K of RAM, not {}K", ram)
+        }
+        bus.attach(PIA_ADDRESS, Box::new(Pia::new(display, keyboard)));
+        bus.attach(ROM_ADDRESS, Box::new(Rom8b::from_vec(rom.to_vec())));
+
+        let mut cpu = Mcs6502::new(bus);
+        cpu.set_jam_halts(true);
+        cpu.restart();
+
+        Apple1 { cpu }
+    }
+}
+
+impl Machine for Apple1 {
+    fn cpu(&mut self) -> &mut Mcs6502<Bus8b> {
+        &mut self.cpu
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io;
+    use std::io::Write;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    use cpus::Cpu;
+    use cpus::StopReason;
+    use inst::mcs6502::ops;
+    use machines::Machine;
+    use machines::apple1;
+    use machines::apple1::Apple1;
+    use mems::Memory;
+
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Sets the PIA up like Wozmon and echoes keys until CR.
+    fn echo_rom() -> Vec<u8> {
+        let mut rom = vec![
+            ops::LDY_IMMEDIATE, 0x7F,
+            ops::STY_ABSOLUTE, 0x12, 0xD0,
+            ops::LDA_IMMEDIATE, 0xA7,
+            ops::STA_ABSOLUTE, 0x11, 0xD0,
+            ops::STA_ABSOLUTE, 0x13, 0xD0,
+            ops::LDA_ABSOLUTE, 0x11, 0xD0,
+            ops::BPL_RELATIVE, 0xFB,
+            ops::LDA_ABSOLUTE, 0x10, 0xD0,
+            ops::BIT_ABSOLUTE, 0x12, 0xD0,
+            ops::BMI_RELATIVE, 0xFB,
+            ops::STA_ABSOLUTE, 0x12, 0xD0,
+            ops::CMP_IMMEDIATE, 0x8D,
+            ops::BNE_RELATIVE, 0xEC,
+            ops::JAM_02
+        ];
+        rom.resize(apple1::ROM_SIZE, 0x00);
+
+        // Reset vector.
+        rom[0xFC] = 0x00;
+        rom[0xFD] = 0xFF;
+        rom
+    }
+
+    #[test]
+    fn echo() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let (sender, receiver) = mpsc::channel();
+        let mut apple = Apple1::new(&echo_rom(), 4, Box::new(Output(output.clone())), receiver);
+
+        for &key in b"hi there\n" {
+            sender.send(key).unwrap();
+        }
+
+        let cpu = apple.cpu();
+        assert_eq!(cpu.pc(), 0xFF00);
+        while cpu.running() {
+            cpu.execute().unwrap();
+        }
+
+        assert_eq!(cpu.stop_reason(), Some(StopReason::Jam(0xFF21)));
+        assert_eq!(*output.borrow(), b"HI THERE\n".to_vec());
+    }
+
+    #[test]
+    fn memory_map() {
+        let (_, receiver) = mpsc::channel();
+        let mut apple = Apple1::new(&echo_rom(), 8, Box::new(io::sink()), receiver);
+        let memory = apple.cpu().memory();
+
+        memory.write_u8(0x0FFF, 0x42);
+        memory.write_u8(0x1000, 0x42);
+        memory.write_u8(0xE000, 0x42);
+        memory.write_u8(0xFF00, 0x42);
+        assert_eq!(memory.read_u8(0x0FFF), 0x42);
+        assert_eq!(memory.read_u8(0x1000), 0x00);
+        assert_eq!(memory.read_u8(0xE000), 0x42);
+        assert_eq!(memory.read_u8(0xFF00), ops::LDY_IMMEDIATE);
+    }
+}
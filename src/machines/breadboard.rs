@@ -0,0 +1,275 @@
+use std::cell::Ref;
+use std::cell::RefCell;
+use std::cell::RefMut;
+use std::io::Write;
+use std::rc::Rc;
+use std::sync::mpsc::Receiver;
+
+use cpus::Cpu;
+use cpus::CpuError;
+use cpus::mcs6502::Mcs6502;
+use devs::acia::Acia;
+use devs::lcd::Lcd;
+use devs::via::Via;
+use inst::mcs6502::Variant;
+use machines::Machine;
+use mems::bus::Bus8b;
+use mems::ram::Ram8b;
+use mems::rom::Rom8b;
+
+// The RAM chip has 32K but the address decoding
+// only leaves the lower half of it.
+pub const RAM_SIZE: usize = 0x4000;
+
+// Serial port, mirrored over the whole 4K block.
+pub const ACIA_ADDRESS: usize = 0x5000;
+pub const ACIA_BLOCK:   usize = 0x1000;
+
+// The LCD is on port B, mirrored over the whole 8K block.
+pub const VIA_ADDRESS:  usize = 0x6000;
+pub const VIA_BLOCK:    usize = 0x2000;
+
+// The upper half of the address space, including the vectors.
+pub const ROM_ADDRESS:  usize = 0x8000;
+pub const ROM_SIZE:     usize = 0x8000;
+
+pub const CLOCK_HZ: u64 = 1_000_000;
+
+// Sources of the IRQ line.
+const IRQ_VIA:  u8 = 0;
+const IRQ_ACIA: u8 = 1;
+
+// Ben Eater's breadboard computer, a 65C02 with a 6522 VIA
+// driving an HD44780 LCD and a 6551 ACIA for the serial port.
+pub struct Breadboard {
+    cpu: Mcs6502<Bus8b>,
+    via: Rc<RefCell<Via>>,
+    acia: Rc<RefCell<Acia>>,
+    lcd: Rc<RefCell<Lcd>>,
+    lcd_output: Option<Box<dyn Write>>
+}
+
+impl Breadboard {
+    // The serial port is connected to output and input.
+    pub fn new(rom: &[u8], output: Box<dyn Write>, input: Receiver<u8>) -> Breadboard {
+        if rom.len() != ROM_SIZE {
+            panic!("breadboard rom must be {} bytes, got {}", ROM_SIZE, rom.len());
+        }
+
+        let lcd = Rc::new(RefCell::new(Lcd::new()));
+        let via = Rc::new(RefCell::new(Via::new()));
+        via.borrow_mut().connect_b(Box::new(lcd.clone()));
+        let acia = Rc::new(RefCell::new(Acia::new(output, input)));
+
+        let mut bus = Bus8b::new(64 * 1024);
+        bus.attach(0x0000, Box::new(Ram8b::new(RAM_SIZE)));
+        bus.attach_mirrored(ACIA_ADDRESS, ACIA_BLOCK, Box::new(acia.clone()));
+        bus.attach_mirrored(VIA_ADDRESS, VIA_BLOCK, Box::new(via.clone()));
+        bus.attach(ROM_ADDRESS, Box::new(Rom8b::from_vec(rom.to_vec())));
+
+        let mut cpu = Mcs6502::new(bus);
+        cpu.set_variant(Variant::Cmos);
+        cpu.restart();
+
+        Breadboard { cpu, via, acia, lcd, lcd_output: None }
+    }
+
+    // Shows the contents of the LCD whenever they change.
+    pub fn set_lcd_output(&mut self, output: Option<Box<dyn Write>>) {
+        self.lcd_output = output;
+    }
+
+    pub fn lcd(&self) -> Ref<'_, Lcd> {
+        self.lcd.borrow()
+    }
+
+    // Lets the host configure the serial port, e.g. its pacing.
+    pub fn acia(&self) -> RefMut<'_, Acia> {
+        self.acia.borrow_mut()
+    }
+}
+
+impl Machine for Breadboard {
+    fn cpu(&mut self) -> &mut Mcs6502<Bus8b> {
+        &mut self.cpu
+    }
+
+    fn step(&mut self) -> Result<(), CpuError> {
+        let start = self.cpu.cycles();
+        self.cpu.execute()?;
+
+        let cycles = self.cpu.cycles() - start;
+        self.via.borrow_mut().tick(cycles);
+        self.acia.borrow_mut().tick(cycles);
+
+        let via_irq = self.via.borrow().irq();
+        let acia_irq = self.acia.borrow().irq();
+        self.cpu.set_irq(IRQ_VIA, via_irq);
+        self.cpu.set_irq(IRQ_ACIA, acia_irq);
+
+        Ok(())
+    }
+
+    fn end_slice(&mut self) {
+        let output = match self.lcd_output {
+            Some(ref mut output) => output,
+            None                 => return
+        };
+
+        let mut lcd = self.lcd.borrow_mut();
+        if lcd.take_changed() {
+            let lines = lcd.lines();
+            let _ = writeln!(output, "LCD |{}|{}|", lines[0], lines[1]);
+            let _ = output.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io;
+    use std::io::Write;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    use cpus::Cpu;
+    use cpus::StopReason;
+    use cpus::mcs6502;
+    use devs::lcd;
+    use inst::mcs6502::ops;
+    use machines::Machine;
+    use machines::breadboard;
+    use machines::breadboard::Breadboard;
+
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Places the code at the start of the rom, the reset
+    // vector points to it.
+    fn rom(code: &[u8], irq: u16) -> Vec<u8> {
+        let mut rom = code.to_vec();
+        rom.resize(breadboard::ROM_SIZE, 0xEA);
+
+        rom[0x7FFC] = 0x00;
+        rom[0x7FFD] = 0x80;
+        rom[0x7FFE] = irq as u8;
+        rom[0x7FFF] = (irq >> 8) as u8;
+        rom
+    }
+
+    fn run(board: &mut Breadboard) {
+        while board.cpu().running() {
+            board.step().unwrap();
+        }
+    }
+
+    // Drives port B of the VIA.
+    fn port_b(code: &mut Vec<u8>, value: u8) {
+        code.extend_from_slice(&[ops::LDA_IMMEDIATE, value, ops::STA_ABSOLUTE, 0x00, 0x60]);
+    }
+
+    fn lcd_nibble(code: &mut Vec<u8>, nibble: u8, rs: u8) {
+        port_b(code, nibble | rs);
+        port_b(code, nibble | rs | lcd::PIN_E);
+        port_b(code, nibble | rs);
+    }
+
+    fn lcd_byte(code: &mut Vec<u8>, byte: u8, rs: u8) {
+        lcd_nibble(code, byte >> 4, rs);
+        lcd_nibble(code, byte & 0x0F, rs);
+    }
+
+    #[test]
+    fn lcd() {
+        let mut code = vec![ops::LDA_IMMEDIATE, 0xFF, ops::STA_ABSOLUTE, 0x02, 0x60];
+        lcd_nibble(&mut code, 0x02, 0);
+        for &byte in &[0x28, 0x0E, 0x06, 0x01] {
+            lcd_byte(&mut code, byte, 0);
+        }
+        for &ch in b"Hello, world!" {
+            lcd_byte(&mut code, ch, lcd::PIN_RS);
+        }
+        code.push(ops::cmos::STP_IMPLIED);
+
+        let (_, receiver) = mpsc::channel();
+        let mut board = Breadboard::new(&rom(&code, 0x8000), Box::new(io::sink()), receiver);
+        let output = Rc::new(RefCell::new(Vec::new()));
+        board.set_lcd_output(Some(Box::new(Output(output.clone()))));
+
+        run(&mut board);
+        assert_eq!(board.lcd().lines(), vec!["Hello, world!   ", "                "]);
+
+        board.end_slice();
+        board.end_slice();
+        assert_eq!(*output.borrow(), b"LCD |Hello, world!   |                |\n".to_vec());
+    }
+
+    #[test]
+    fn serial() {
+        // Echoes received bytes in the IRQ handler until CR.
+        let code = [
+            ops::LDA_IMMEDIATE, 0x09,
+            ops::STA_ABSOLUTE, 0x02, 0x50,
+            ops::CLI_IMPLIED,
+            ops::cmos::WAI_IMPLIED,
+            ops::cmos::BRA_RELATIVE, 0xFD,
+            ops::LDA_ABSOLUTE, 0x01, 0x50,
+            ops::LDA_ABSOLUTE, 0x00, 0x50,
+            ops::STA_ABSOLUTE, 0x00, 0x50,
+            ops::CMP_IMMEDIATE, 0x0D,
+            ops::BNE_RELATIVE, 0x01,
+            ops::cmos::STP_IMPLIED,
+            ops::RTI_IMPLIED
+        ];
+
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let (sender, receiver) = mpsc::channel();
+        let mut board = Breadboard::new(&rom(&code, 0x8009), Box::new(Output(output.clone())),
+                                        receiver);
+
+        for &byte in b"hi\r" {
+            sender.send(byte).unwrap();
+        }
+        run(&mut board);
+
+        assert_eq!(board.cpu().stop_reason(), Some(StopReason::Stop(0x8016)));
+        assert_eq!(*output.borrow(), b"hi\r".to_vec());
+    }
+
+    #[test]
+    fn timer() {
+        // Waits for a one shot of T1, the registers are mirrored.
+        let code = [
+            ops::LDA_IMMEDIATE, 0xC0,
+            ops::STA_ABSOLUTE, 0x1E, 0x70,
+            ops::LDA_IMMEDIATE, 0x00,
+            ops::STA_ABSOLUTE, 0x04, 0x60,
+            ops::LDA_IMMEDIATE, 0x01,
+            ops::STA_ABSOLUTE, 0x05, 0x60,
+            ops::CLI_IMPLIED,
+            ops::cmos::WAI_IMPLIED,
+            ops::cmos::BRA_RELATIVE, 0xFD,
+            ops::cmos::STP_IMPLIED
+        ];
+
+        let (_, receiver) = mpsc::channel();
+        let mut board = Breadboard::new(&rom(&code, 0x8013), Box::new(io::sink()), receiver);
+        run(&mut board);
+
+        let cpu = board.cpu();
+        assert_eq!(cpu.stop_reason(), Some(StopReason::Stop(0x8013)));
+        let cycles = cpu.cycles() - mcs6502::RESET_CYCLES;
+        assert!(cycles > 0x100);
+        assert!(cycles < 0x120);
+    }
+}
@@ -0,0 +1,233 @@
+use std::cell::Ref;
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use std::sync::mpsc::Receiver;
+
+use cpus::Cpu;
+use cpus::CpuError;
+use cpus::mcs6502::Mcs6502;
+use devs::keypad::Keypad;
+use devs::rriot::Rriot;
+use machines::Machine;
+use mems::bus::Bus8b;
+use mems::ram::Ram8b;
+use mems::rom::Rom8b;
+
+// Only the lowest 8K are decoded, the rest of the address
+// space mirrors them, e.g. the vectors at the end of the rom.
+pub const DECODED_SIZE: usize = 0x2000;
+
+pub const RAM_SIZE: usize = 0x0400;
+
+// I/O and timers of the 6530-003 and the 6530-002, which
+// also scans the keypad and the display.
+pub const RRIOT_003_ADDRESS: usize = 0x1700;
+pub const RRIOT_002_ADDRESS: usize = 0x1740;
+pub const RRIOT_BLOCK:       usize = 0x0040;
+
+// RAM of both 6530s.
+pub const RRIOT_RAM_ADDRESS: usize = 0x1780;
+pub const RRIOT_RAM_SIZE:    usize = 0x0080;
+
+// Roms of the 6530-003 and the 6530-002, the monitor.
+pub const ROM_ADDRESS: usize = 0x1800;
+pub const ROM_SIZE:    usize = 0x0800;
+
+// The SST switch raises the NMI after every instruction
+// fetched from outside of this part of the monitor.
+pub const MONITOR_ADDRESS: usize = 0x1C00;
+
+pub const CLOCK_HZ: u64 = 1_000_000;
+
+pub struct Kim1 {
+    cpu: Mcs6502<Bus8b>,
+    rriot_003: Rc<RefCell<Rriot>>,
+    rriot_002: Rc<RefCell<Rriot>>,
+    keypad: Rc<RefCell<Keypad>>,
+    display: Box<dyn Write>
+}
+
+impl Kim1 {
+    // Keys are mapped as described in devs::keypad.
+    pub fn new(rom: &[u8], display: Box<dyn Write>, keys: Receiver<u8>) -> Kim1 {
+        if rom.len() != ROM_SIZE {
+            panic!("KIM-1 rom must be {} bytes, got {}", ROM_SIZE, rom.len());
+        }
+
+        let keypad = Rc::new(RefCell::new(Keypad::new(keys)));
+        let (segments, select) = Keypad::ports(&keypad);
+
+        let rriot_003 = Rc::new(RefCell::new(Rriot::new()));
+        let rriot_002 = Rc::new(RefCell::new(Rriot::new()));
+        rriot_002.borrow_mut().connect_a(segments);
+        rriot_002.borrow_mut().connect_b(select);
+
+        let mut decoded = Bus8b::new(DECODED_SIZE);
+        decoded.attach(0x0000, Box::new(Ram8b::new(RAM_SIZE)));
+        decoded.attach_mirrored(RRIOT_003_ADDRESS, RRIOT_BLOCK, Box::new(rriot_003.clone()));
+        decoded.attach_mirrored(RRIOT_002_ADDRESS, RRIOT_BLOCK, Box::new(rriot_002.clone()));
+        decoded.attach(RRIOT_RAM_ADDRESS, Box::new(Ram8b::new(RRIOT_RAM_SIZE)));
+        decoded.attach(ROM_ADDRESS, Box::new(Rom8b::from_vec(rom.to_vec())));
+
+        let mut bus = Bus8b::new(64 * 1024);
+        bus.attach_mirrored(0x0000, 64 * 1024, Box::new(decoded));
+
+        let mut cpu = Mcs6502::new(bus);
+        cpu.set_jam_halts(true);
+        cpu.restart();
+
+        Kim1 { cpu, rriot_003, rriot_002, keypad, display }
+    }
+
+    pub fn keypad(&self) -> Ref<'_, Keypad> {
+        self.keypad.borrow()
+    }
+
+    // The RS key, RAM keeps its contents.
+    pub fn reset(&mut self) {
+        self.rriot_003.borrow_mut().reset();
+        self.rriot_002.borrow_mut().reset();
+        self.cpu.restart();
+    }
+}
+
+impl Machine for Kim1 {
+    fn cpu(&mut self) -> &mut Mcs6502<Bus8b> {
+        &mut self.cpu
+    }
+
+    // The timer interrupts are not connected, as on a KIM-1
+    // without the PB7 jumper.
+    fn step(&mut self) -> Result<(), CpuError> {
+        if self.keypad.borrow_mut().take_reset() {
+            self.reset();
+        }
+
+        // The NMI is taken after the instruction that follows
+        // the edge, which single steps the user program.
+        let user = self.cpu.pc() % DECODED_SIZE < MONITOR_ADDRESS;
+        let nmi = {
+            let keypad = self.keypad.borrow();
+            keypad.nmi() || (keypad.single_step() && user)
+        };
+        self.cpu.set_nmi(nmi);
+
+        let start = self.cpu.cycles();
+        self.cpu.execute()?;
+
+        let cycles = self.cpu.cycles() - start;
+        self.rriot_003.borrow_mut().tick(cycles);
+        self.rriot_002.borrow_mut().tick(cycles);
+        self.keypad.borrow_mut().tick(cycles);
+
+        Ok(())
+    }
+
+    fn end_slice(&mut self) {
+        let mut keypad = self.keypad.borrow_mut();
+        if keypad.take_changed() {
+            for line in keypad.lines() {
+                let _ = writeln!(self.display, "{}", line);
+            }
+            let _ = writeln!(self.display);
+            let _ = self.display.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::mpsc;
+
+    use cpus::Cpu;
+    use cpus::StopReason;
+    use devs::rriot;
+    use inst::mcs6502::ops;
+    use machines::Machine;
+    use machines::kim1;
+    use machines::kim1::Kim1;
+    use mems::Memory;
+
+    // Monitor at 0x1C00 and the NMI handler counting in 0xF0
+    // at 0x1C10.
+    fn rom(monitor: &[u8]) -> Vec<u8> {
+        let mut rom = vec![0xEA; kim1::ROM_SIZE];
+        rom[0x400..0x400 + monitor.len()].copy_from_slice(monitor);
+        rom[0x410..0x413].copy_from_slice(&[ops::INC_ZERO_PAGE, 0xF0, ops::RTI_IMPLIED]);
+
+        rom[0x7FA..].copy_from_slice(&[0x10, 0x1C, 0x00, 0x1C, 0x00, 0x1C]);
+        rom
+    }
+
+    #[test]
+    fn memory_map() {
+        let (_, receiver) = mpsc::channel();
+        let mut kim = Kim1::new(&rom(&[]), Box::new(io::sink()), receiver);
+        assert_eq!(kim.cpu().pc(), 0x1C00);
+
+        let memory = kim.cpu().memory();
+        memory.write_u8(0x03FF, 0x42);
+        memory.write_u8(0x0400, 0x42);
+        memory.write_u8(0x17FF, 0x42);
+        assert_eq!(memory.read_u8(0x03FF), 0x42);
+        assert_eq!(memory.read_u8(0x23FF), 0x42);
+        assert_eq!(memory.read_u8(0x0400), 0x00);
+        assert_eq!(memory.read_u8(0x17FF), 0x42);
+        assert_eq!(memory.read_u8(0xFFFA), 0x10);
+
+        // Digit 0 on the 6530-002.
+        memory.write_u8(0x1740 + rriot::PBDD, 0x1E);
+        memory.write_u8(0x1740 + rriot::PBD, 0x09);
+        memory.write_u8(0x1740 + rriot::PADD, 0x7F);
+        memory.write_u8(0x1740 + rriot::PAD, 0x06);
+        assert_eq!(kim.keypad().text(), "1      ");
+    }
+
+    #[test]
+    fn single_step() {
+        let (sender, receiver) = mpsc::channel();
+        let mut kim = Kim1::new(&rom(&[ops::JMP_ABSOLUTE, 0x00, 0x02]),
+                                Box::new(io::sink()), receiver);
+
+        let program = [ops::INX_IMPLIED, ops::INX_IMPLIED, ops::INX_IMPLIED, ops::JAM_02];
+        for (i, &byte) in program.iter().enumerate() {
+            kim.cpu().memory().write_u8(0x0200 + i, byte);
+        }
+
+        // SST on, every instruction of the program enters the
+        // NMI handler.
+        sender.send(b't').unwrap();
+        while kim.cpu().running() {
+            kim.step().unwrap();
+        }
+
+        let cpu = kim.cpu();
+        assert_eq!(cpu.stop_reason(), Some(StopReason::Jam(0x0203)));
+        assert_eq!(cpu.registers().idx_x, 3);
+        assert_eq!(cpu.memory().read_u8(0x00F0), 3);
+    }
+
+    #[test]
+    fn stop_and_reset() {
+        let monitor = [
+            ops::INC_ZERO_PAGE, 0xF1,
+            ops::JMP_ABSOLUTE, 0x02, 0x1C
+        ];
+
+        let (sender, receiver) = mpsc::channel();
+        let mut kim = Kim1::new(&rom(&monitor), Box::new(io::sink()), receiver);
+        sender.send(b's').unwrap();
+        sender.send(b'r').unwrap();
+
+        // ST is held for a while but triggers a single NMI.
+        for _ in 0..100_000 {
+            kim.step().unwrap();
+        }
+
+        let memory = kim.cpu().memory();
+        assert_eq!(memory.read_u8(0x00F0), 1);
+        assert_eq!(memory.read_u8(0x00F1), 2);
+    }
+}
@@ -0,0 +1,55 @@
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use cpus::Cpu;
+use cpus::CpuError;
+use cpus::mcs6502::Mcs6502;
+use mems::bus::Bus8b;
+
+pub mod apple1;
+pub mod breadboard;
+pub mod kim1;
+pub mod nes;
+
+// Slices the emulation is synchronised in.
+const SLICES_PER_SECOND: u64 = 100;
+
+pub trait Machine {
+    fn cpu(&mut self) -> &mut Mcs6502<Bus8b>;
+
+    // Executes an instruction and lets the devices catch up.
+    fn step(&mut self) -> Result<(), CpuError> {
+        self.cpu().execute()
+    }
+
+    // Called after every slice, e.g. to refresh a display.
+    fn end_slice(&mut self) {}
+}
+
+// Runs the machine at the given clock rate until the cpu
+// halts, sleeping whenever it gets ahead of real time.
+pub fn run_realtime<T: Machine>(machine: &mut T, clock_hz: u64) -> Result<(), CpuError> {
+    let slice = Duration::from_nanos(1_000_000_000 / SLICES_PER_SECOND);
+    let cycles = clock_hz / SLICES_PER_SECOND;
+
+    let mut deadline = Instant::now();
+    while machine.cpu().running() {
+        let start = machine.cpu().cycles();
+        while machine.cpu().running() && machine.cpu().cycles() - start < cycles {
+            machine.step()?;
+        }
+        machine.end_slice();
+
+        // Don't try to catch up after the host stalled.
+        deadline += slice;
+        let now = Instant::now();
+        if deadline > now {
+            thread::sleep(deadline - now);
+        } else {
+            deadline = now;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,535 @@
+use std::error::Error;
+use std::fmt;
+
+use mems::Memory;
+
+// CPU addresses of the cartridge, PRG RAM followed by the
+// 32K window of the PRG ROM.
+pub const CART_ADDRESS: usize = 0x6000;
+pub const CART_SIZE:    usize = 0xA000;
+
+const PRG_ROM_OFFSET: usize = 0x2000;
+
+pub const MAPPER_NROM: u16 = 0;
+pub const MAPPER_MMC1: u16 = 1;
+
+const MAGIC:        &[u8] = b"NES\x1A";
+const HEADER_SIZE:  usize = 16;
+const TRAINER_SIZE: usize = 512;
+
+// Units of the sizes in the header.
+const PRG_UNIT:     usize = 0x4000;
+const CHR_UNIT:     usize = 0x2000;
+const PRG_RAM_UNIT: usize = 0x2000;
+
+// Trainers are loaded to 0x7000.
+const TRAINER_OFFSET: usize = 0x1000;
+
+// Flags 6.
+const FLAG_VERTICAL:    u8 = 0x01;
+const FLAG_TRAINER:     u8 = 0x04;
+const FLAG_FOUR_SCREEN: u8 = 0x08;
+
+// Flags 7, bits 2 and 3 identify NES 2.0 headers.
+const FLAG_FORMAT: u8 = 0x0C;
+const FORMAT_NES2: u8 = 0x08;
+
+#[derive(Debug)]
+pub enum CartError {
+    BadMagic,
+    Truncated,
+    UnsupportedMapper(u16),
+
+    // Header field with a value that cannot be used.
+    Invalid(&'static str)
+}
+
+impl fmt::Display for CartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CartError::BadMagic                  => write!(f, "not an iNES image"),
+            CartError::Truncated                 => write!(f, "truncated iNES image"),
+            CartError::UnsupportedMapper(mapper) => write!(f, "unsupported mapper {}", mapper),
+            CartError::Invalid(field)            => write!(f, "invalid value of {}", field)
+        }
+    }
+}
+
+impl Error for CartError {}
+
+// Arrangement of the nametables in the 4K of PPU addresses
+// backed by the 2K of console VRAM.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+
+    // All four nametables show the same 1K.
+    SingleLow,
+    SingleHigh,
+
+    // The cartridge provides the other 2K.
+    FourScreen
+}
+
+// Bank switching of a cartridge board. Offsets wrap around
+// the sizes of the memories.
+pub trait Mapper {
+    // Offset in the PRG ROM of an address in the 32K window.
+    fn prg_offset(&self, addr: usize) -> usize;
+
+    // Offset in the CHR memory of a PPU address below 0x2000.
+    fn chr_offset(&self, addr: usize) -> usize;
+
+    // Write to the 32K window, e.g. to a bank register.
+    fn write(&mut self, _addr: usize, _data: u8) {}
+
+    fn mirroring(&self) -> Mirroring;
+
+    fn prg_ram_enabled(&self) -> bool {
+        true
+    }
+}
+
+// Mapper 0, 16K or 32K of PRG ROM and 8K of CHR without
+// any bank switching.
+struct Nrom {
+    mirroring: Mirroring
+}
+
+impl Mapper for Nrom {
+    fn prg_offset(&self, addr: usize) -> usize {
+        addr
+    }
+
+    fn chr_offset(&self, addr: usize) -> usize {
+        addr
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+// MMC1 control register.
+const MMC1_MIRRORING: u8 = 0x03;
+const MMC1_PRG_MODE:  u8 = 0x0C;
+const MMC1_CHR_4K:    u8 = 0x10;
+
+// Bit 7 of a write resets the shift register.
+const MMC1_RESET:   u8 = 0x80;
+const MMC1_RAM_OFF: u8 = 0x10;
+
+const PRG_BANK: usize = 0x4000;
+const CHR_BANK: usize = 0x1000;
+
+// Mapper 1, registers are written one bit at a time. The
+// PRG bank register of the 512K boards is not supported.
+struct Mmc1 {
+    shift: u8,
+    count: u8,
+    control: u8,
+    chr_banks: [u8; 2],
+    prg_bank: u8,
+
+    // 16K banks of the PRG ROM.
+    prg_banks: usize
+}
+
+impl Mmc1 {
+    // The last bank is fixed at 0xC000 after power up.
+    fn new(prg_banks: usize) -> Mmc1 {
+        Mmc1 {
+            shift: 0,
+            count: 0,
+            control: MMC1_PRG_MODE,
+            chr_banks: [0; 2],
+            prg_bank: 0,
+            prg_banks
+        }
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn prg_offset(&self, addr: usize) -> usize {
+        let selected = (self.prg_bank & 0x0F) as usize;
+        let high = addr >= PRG_BANK;
+
+        let bank = match (self.control & MMC1_PRG_MODE) >> 2 {
+            0 | 1 => (selected & !1) + high as usize,
+            2     => if high { selected } else { 0 },
+            _     => if high { self.prg_banks - 1 } else { selected }
+        };
+
+        bank * PRG_BANK + addr % PRG_BANK
+    }
+
+    fn chr_offset(&self, addr: usize) -> usize {
+        let high = addr >= CHR_BANK;
+
+        let bank = if self.control & MMC1_CHR_4K != 0 {
+            self.chr_banks[high as usize] as usize
+        } else {
+            (self.chr_banks[0] & !1) as usize + high as usize
+        };
+
+        bank * CHR_BANK + addr % CHR_BANK
+    }
+
+    // Bits 13 and 14 of the address of the fifth write select
+    // the register.
+    fn write(&mut self, addr: usize, data: u8) {
+        if data & MMC1_RESET != 0 {
+            self.shift = 0;
+            self.count = 0;
+            self.control |= MMC1_PRG_MODE;
+            return;
+        }
+
+        self.shift |= (data & 0x01) << self.count;
+        self.count += 1;
+        if self.count < 5 {
+            return;
+        }
+
+        let value = self.shift;
+        self.shift = 0;
+        self.count = 0;
+
+        match (addr >> 13) & 0x03 {
+            0 => self.control = value,
+            1 => self.chr_banks[0] = value,
+            2 => self.chr_banks[1] = value,
+            _ => self.prg_bank = value
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & MMC1_MIRRORING {
+            0 => Mirroring::SingleLow,
+            1 => Mirroring::SingleHigh,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & MMC1_RAM_OFF == 0
+    }
+}
+
+// Sizes in NES 2.0 headers are either a count of units or,
+// with the upper nibble all ones, 2^E * (M * 2 + 1).
+fn rom_size(lsb: u8, msb: u8, unit: usize) -> Result<usize, CartError> {
+    if msb == 0x0F {
+        let multiplier = (lsb & 0x03) as usize * 2 + 1;
+        1usize.checked_shl((lsb >> 2) as u32)
+            .and_then(|size| size.checked_mul(multiplier))
+            .ok_or(CartError::Invalid("rom size"))
+    } else {
+        Ok((((msb as usize) << 8) | lsb as usize) * unit)
+    }
+}
+
+// RAM sizes in NES 2.0 headers are shift counts.
+fn ram_size(shift: u8) -> usize {
+    if shift == 0 { 0 } else { 64 << shift }
+}
+
+// Game pak in the iNES or NES 2.0 format, attached to the cpu
+// at CART_ADDRESS. The PPU reads the pattern tables through
+// chr_read and chr_write.
+pub struct Cartridge {
+    mapper_id: u16,
+    mapper: Box<dyn Mapper>,
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool
+}
+
+impl Memory for Cartridge {
+    fn write_u8(&mut self, addr: usize, data: u8) {
+        if addr >= PRG_ROM_OFFSET {
+            self.mapper.write(addr - PRG_ROM_OFFSET, data);
+        } else if self.mapper.prg_ram_enabled() && !self.prg_ram.is_empty() {
+            let size = self.prg_ram.len();
+            self.prg_ram[addr % size] = data;
+        }
+    }
+
+    // Missing or disabled PRG RAM reads as 0.
+    fn read_u8(&self, addr: usize) -> u8 {
+        if addr >= PRG_ROM_OFFSET {
+            let offset = self.mapper.prg_offset(addr - PRG_ROM_OFFSET);
+            self.prg_rom[offset % self.prg_rom.len()]
+        } else if self.mapper.prg_ram_enabled() && !self.prg_ram.is_empty() {
+            self.prg_ram[addr % self.prg_ram.len()]
+        } else {
+            0
+        }
+    }
+
+    fn write_u16(&mut self, addr: usize, data: u16) {
+        self.write_u8(addr, (data & 0xFF) as u8);
+        self.write_u8(addr + 1, (data >> 8) as u8);
+    }
+
+    fn read_u16(&self, addr: usize) -> u16 {
+        let lo = self.read_u8(addr) as u16;
+        let hi = self.read_u8(addr + 1) as u16;
+
+        (hi << 8) | lo
+    }
+
+    fn size(&self) -> usize {
+        CART_SIZE
+    }
+}
+
+impl Cartridge {
+    pub fn load(data: &[u8]) -> Result<Cartridge, CartError> {
+        if !data.starts_with(MAGIC) {
+            return Err(CartError::BadMagic);
+        }
+
+        if data.len() < HEADER_SIZE {
+            return Err(CartError::Truncated);
+        }
+
+        let header = &data[..HEADER_SIZE];
+        let (flags6, flags7) = (header[6], header[7]);
+        let mut mapper_id = (flags6 >> 4) as u16;
+
+        let (prg_size, chr_size, prg_ram_size, chr_ram_size);
+        if flags7 & FLAG_FORMAT == FORMAT_NES2 {
+            mapper_id |= (flags7 & 0xF0) as u16 | ((header[8] & 0x0F) as u16) << 8;
+            prg_size = rom_size(header[4], header[9] & 0x0F, PRG_UNIT)?;
+            chr_size = rom_size(header[5], header[9] >> 4, CHR_UNIT)?;
+            prg_ram_size = ram_size(header[10] & 0x0F) + ram_size(header[10] >> 4);
+            chr_ram_size = ram_size(header[11] & 0x0F) + ram_size(header[11] >> 4);
+        } else {
+            // Old dumps have garbage such as "DiskDude!" at the
+            // end of the header instead of the upper nibble.
+            if header[12..].iter().all(|&byte| byte == 0) {
+                mapper_id |= (flags7 & 0xF0) as u16;
+            }
+
+            prg_size = header[4] as usize * PRG_UNIT;
+            chr_size = header[5] as usize * CHR_UNIT;
+            prg_ram_size = header[8].max(1) as usize * PRG_RAM_UNIT;
+            chr_ram_size = 0;
+        }
+
+        if prg_size == 0 {
+            return Err(CartError::Invalid("PRG ROM size"));
+        }
+
+        let mut offset = HEADER_SIZE;
+        let mut prg_ram = vec![0; prg_ram_size];
+        if flags6 & FLAG_TRAINER != 0 {
+            let trainer = data.get(offset..offset + TRAINER_SIZE).ok_or(CartError::Truncated)?;
+            if prg_ram.len() < TRAINER_OFFSET + TRAINER_SIZE {
+                return Err(CartError::Invalid("PRG RAM size"));
+            }
+
+            prg_ram[TRAINER_OFFSET..TRAINER_OFFSET + TRAINER_SIZE].copy_from_slice(trainer);
+            offset += TRAINER_SIZE;
+        }
+
+        let end = offset.checked_add(prg_size)
+            .and_then(|end| end.checked_add(chr_size))
+            .ok_or(CartError::Truncated)?;
+        if data.len() < end {
+            return Err(CartError::Truncated);
+        }
+
+        let mirroring = if flags6 & FLAG_FOUR_SCREEN != 0 {
+            Mirroring::FourScreen
+        } else if flags6 & FLAG_VERTICAL != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+
+        let mapper: Box<dyn Mapper> = match mapper_id {
+            MAPPER_NROM => Box::new(Nrom { mirroring }),
+            MAPPER_MMC1 => Box::new(Mmc1::new(prg_size / PRG_UNIT)),
+            _           => return Err(CartError::UnsupportedMapper(mapper_id))
+        };
+
+        // Boards without CHR ROM have 8K of RAM unless the
+        // header says otherwise.
+        let chr_ram = chr_size == 0;
+        let chr = if chr_ram {
+            vec![0; chr_ram_size.max(CHR_UNIT)]
+        } else {
+            data[offset + prg_size..end].to_vec()
+        };
+
+        Ok(Cartridge {
+            mapper_id,
+            mapper,
+            prg_rom: data[offset..offset + prg_size].to_vec(),
+            prg_ram,
+            chr,
+            chr_ram
+        })
+    }
+
+    pub fn mapper(&self) -> u16 {
+        self.mapper_id
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        self.mapper.mirroring()
+    }
+
+    pub fn chr_read(&self, addr: usize) -> u8 {
+        let offset = self.mapper.chr_offset(addr & 0x1FFF);
+        self.chr[offset % self.chr.len()]
+    }
+
+    // Writes to CHR ROM are ignored.
+    pub fn chr_write(&mut self, addr: usize, data: u8) {
+        if self.chr_ram {
+            let offset = self.mapper.chr_offset(addr & 0x1FFF);
+            let size = self.chr.len();
+            self.chr[offset % size] = data;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use machines::nes::cart;
+    use machines::nes::cart::CartError;
+    use machines::nes::cart::Cartridge;
+    use machines::nes::cart::Mirroring;
+    use mems::Memory;
+
+    // Every byte of a PRG bank holds its number, CHR banks
+    // are numbered from 0x80 in 4K units.
+    fn image(header: [u8; 12], prg_banks: usize, chr_banks: usize) -> Vec<u8> {
+        let mut image = b"NES\x1A".to_vec();
+        image.extend_from_slice(&header);
+        for bank in 0..prg_banks {
+            image.extend(vec![bank as u8; 0x4000]);
+        }
+        for bank in 0..chr_banks * 2 {
+            image.extend(vec![0x80 + bank as u8; 0x1000]);
+        }
+        image
+    }
+
+    // Writes the value to a register one bit at a time.
+    fn mmc1_write(cart: &mut Cartridge, addr: usize, value: u8) {
+        for bit in 0..5 {
+            cart.write_u8(addr - cart::CART_ADDRESS, value >> bit);
+        }
+    }
+
+    #[test]
+    fn load() {
+        let mut header = [0; 12];
+        header[0] = 1;
+        header[1] = 1;
+        header[2] = 0x01;
+        let nrom = Cartridge::load(&image(header, 1, 1)).unwrap();
+        assert_eq!(nrom.mapper(), cart::MAPPER_NROM);
+        assert_eq!(nrom.mirroring(), Mirroring::Vertical);
+
+        // Garbage at the end hides the upper nibble.
+        header[2] = 0x10;
+        header[3] = 0x40;
+        header[8..].copy_from_slice(b"Dude");
+        let dude = Cartridge::load(&image(header, 1, 1)).unwrap();
+        assert_eq!(dude.mapper(), cart::MAPPER_MMC1);
+
+        header[8..].copy_from_slice(&[0; 4]);
+        match Cartridge::load(&image(header, 1, 1)) {
+            Err(CartError::UnsupportedMapper(0x41)) => {}
+            _                                       => panic!("mapper 0x41 loaded")
+        }
+
+        // NES 2.0 with 2^15 * 1 bytes of PRG ROM.
+        header[3] = 0x08;
+        header[5] = 0x0F;
+        header[0] = 0x3C;
+        let nes2 = Cartridge::load(&image(header, 2, 1)).unwrap();
+        assert_eq!(nes2.mapper(), cart::MAPPER_MMC1);
+        assert_eq!(nes2.read_u8(0xFFFF - cart::CART_ADDRESS), 1);
+
+        let mut truncated = image(header, 2, 1);
+        truncated.pop();
+        assert!(Cartridge::load(&truncated).is_err());
+        assert!(Cartridge::load(b"NES").is_err());
+    }
+
+    #[test]
+    fn nrom() {
+        let mut header = [0; 12];
+        header[0] = 1;
+        header[2] = 0x04;
+        let mut image = image(header, 1, 0);
+        image.splice(0x10..0x10, vec![0xAA; 0x200]);
+
+        // The trainer is in PRG RAM and the 16K bank is mirrored.
+        let mut cart = Cartridge::load(&image).unwrap();
+        assert_eq!(cart.read_u8(0x7000 - cart::CART_ADDRESS), 0xAA);
+        assert_eq!(cart.read_u8(0xC000 - cart::CART_ADDRESS), 0x00);
+        cart.write_u8(0x6000 - cart::CART_ADDRESS, 0x42);
+        assert_eq!(cart.read_u8(0x6000 - cart::CART_ADDRESS), 0x42);
+
+        // No CHR ROM, so there is CHR RAM.
+        cart.chr_write(0x1FFF, 0x24);
+        assert_eq!(cart.chr_read(0x1FFF), 0x24);
+    }
+
+    #[test]
+    fn mmc1() {
+        let mut header = [0; 12];
+        header[0] = 8;
+        header[1] = 2;
+        header[2] = 0x10;
+        let mut cart = Cartridge::load(&image(header, 8, 2)).unwrap();
+        let read = |memory: &Cartridge, addr: usize| memory.read_u8(addr - cart::CART_ADDRESS);
+
+        // Last bank fixed at 0xC000 after power up.
+        mmc1_write(&mut cart, 0xE000, 0x03);
+        assert_eq!(read(&cart, 0x8000), 3);
+        assert_eq!(read(&cart, 0xC000), 7);
+
+        // First bank fixed at 0x8000, 4K CHR banks and vertical
+        // mirroring.
+        mmc1_write(&mut cart, 0x8000, 0x1A);
+        assert_eq!(read(&cart, 0x8000), 0);
+        assert_eq!(read(&cart, 0xC000), 3);
+        assert_eq!(cart.mirroring(), Mirroring::Vertical);
+
+        mmc1_write(&mut cart, 0xA000, 0x03);
+        mmc1_write(&mut cart, 0xC000, 0x01);
+        assert_eq!(cart.chr_read(0x0000), 0x83);
+        assert_eq!(cart.chr_read(0x1000), 0x81);
+
+        // 32K mode ignores the lowest bits of the banks.
+        mmc1_write(&mut cart, 0x8000, 0x00);
+        assert_eq!(read(&cart, 0x8000), 2);
+        assert_eq!(read(&cart, 0xC000), 3);
+        assert_eq!(cart.chr_read(0x0000), 0x82);
+        assert_eq!(cart.chr_read(0x1000), 0x83);
+        assert_eq!(cart.mirroring(), Mirroring::SingleLow);
+
+        // A reset in the middle of a write.
+        cart.write_u8(0xE000 - cart::CART_ADDRESS, 0x01);
+        cart.write_u8(0xE000 - cart::CART_ADDRESS, 0x80);
+        assert_eq!(read(&cart, 0xC000), 7);
+
+        // Bit 4 of the PRG register disables the RAM.
+        cart.write_u8(0x6000 - cart::CART_ADDRESS, 0x42);
+        mmc1_write(&mut cart, 0xE000, 0x10);
+        assert_eq!(read(&cart, 0x6000), 0);
+        mmc1_write(&mut cart, 0xE000, 0x00);
+        assert_eq!(read(&cart, 0x6000), 0x42);
+    }
+}
@@ -0,0 +1,164 @@
+use std::cell::Cell;
+
+use mems::Memory;
+
+// Registers relative to 0x4000. The sound registers are
+// stored but nothing is played.
+pub const OAM_DMA: usize = 0x14;
+pub const JOY1:    usize = 0x16;
+pub const JOY2:    usize = 0x17;
+
+pub const IO_SIZE: usize = 0x20;
+
+// Buttons of a standard controller in the order they are
+// shifted out.
+pub const BUTTON_A:      u8 = 1 << 0;
+pub const BUTTON_B:      u8 = 1 << 1;
+pub const BUTTON_SELECT: u8 = 1 << 2;
+pub const BUTTON_START:  u8 = 1 << 3;
+pub const BUTTON_UP:     u8 = 1 << 4;
+pub const BUTTON_DOWN:   u8 = 1 << 5;
+pub const BUTTON_LEFT:   u8 = 1 << 6;
+pub const BUTTON_RIGHT:  u8 = 1 << 7;
+
+// Upper bits of controller reads are left on the data bus
+// by the high byte of the address.
+const OPEN_BUS: u8 = 0x40;
+
+struct Controller {
+    buttons: u8,
+
+    // Ones are shifted in after the last button.
+    shift: Cell<u8>
+}
+
+impl Controller {
+    fn read(&self, strobe: bool) -> u8 {
+        if strobe {
+            return self.buttons & BUTTON_A;
+        }
+
+        let shift = self.shift.get();
+        self.shift.set((shift >> 1) | 0x80);
+        shift & 0x01
+    }
+}
+
+// APU and I/O registers of the 2A03, the controller ports
+// and the OAM DMA.
+pub struct Io {
+    registers: [u8; IO_SIZE],
+    controllers: [Controller; 2],
+
+    // Controllers reload their buttons while it's set.
+    strobe: bool,
+
+    // Page to copy to the OAM, the machine does the copy.
+    dma: Option<u8>
+}
+
+impl Memory for Io {
+    fn write_u8(&mut self, addr: usize, data: u8) {
+        match addr {
+            OAM_DMA => self.dma = Some(data),
+            JOY1    => {
+                self.strobe = data & 0x01 != 0;
+                if self.strobe {
+                    for controller in self.controllers.iter() {
+                        controller.shift.set(controller.buttons);
+                    }
+                }
+            }
+            _       => self.registers[addr] = data
+        }
+    }
+
+    // Other registers are write only and read as 0.
+    fn read_u8(&self, addr: usize) -> u8 {
+        match addr {
+            JOY1 => OPEN_BUS | self.controllers[0].read(self.strobe),
+            JOY2 => OPEN_BUS | self.controllers[1].read(self.strobe),
+            _    => 0
+        }
+    }
+
+    fn write_u16(&mut self, addr: usize, data: u16) {
+        self.write_u8(addr, (data & 0xFF) as u8);
+        self.write_u8(addr + 1, (data >> 8) as u8);
+    }
+
+    fn read_u16(&self, addr: usize) -> u16 {
+        let lo = self.read_u8(addr) as u16;
+        let hi = self.read_u8(addr + 1) as u16;
+
+        (hi << 8) | lo
+    }
+
+    fn size(&self) -> usize {
+        IO_SIZE
+    }
+}
+
+impl Default for Io {
+    fn default() -> Io {
+        Io::new()
+    }
+}
+
+impl Io {
+    pub fn new() -> Io {
+        Io {
+            registers: [0; IO_SIZE],
+            controllers: [
+                Controller { buttons: 0, shift: Cell::new(0) },
+                Controller { buttons: 0, shift: Cell::new(0) }
+            ],
+            strobe: false,
+            dma: None
+        }
+    }
+
+    // Buttons currently held on the controller of the
+    // player, 0 or 1.
+    pub fn set_buttons(&mut self, player: usize, buttons: u8) {
+        self.controllers[player].buttons = buttons;
+    }
+
+    // Returns the page written to OAM_DMA since the last call.
+    pub fn take_dma(&mut self) -> Option<u8> {
+        self.dma.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use machines::nes::io;
+    use machines::nes::io::Io;
+    use mems::Memory;
+
+    #[test]
+    fn controllers() {
+        let mut io = Io::new();
+        io.set_buttons(0, io::BUTTON_A | io::BUTTON_START | io::BUTTON_RIGHT);
+        io.set_buttons(1, io::BUTTON_B);
+
+        // A is read over and over while strobing.
+        io.write_u8(io::JOY1, 0x01);
+        assert_eq!(io.read_u8(io::JOY1), 0x41);
+        assert_eq!(io.read_u8(io::JOY1), 0x41);
+        io.write_u8(io::JOY1, 0x00);
+
+        let bits: Vec<u8> = (0..10).map(|_| io.read_u8(io::JOY1) & 0x01).collect();
+        assert_eq!(bits, vec![1, 0, 0, 1, 0, 0, 0, 1, 1, 1]);
+        assert_eq!(io.read_u8(io::JOY2), 0x40);
+        assert_eq!(io.read_u8(io::JOY2), 0x41);
+
+        // Writes to the frame counter at JOY2 don't strobe.
+        io.write_u8(io::JOY2, 0x01);
+        assert_eq!(io.read_u8(io::JOY1), 0x41);
+
+        io.write_u8(io::OAM_DMA, 0x02);
+        assert_eq!(io.take_dma(), Some(0x02));
+        assert_eq!(io.take_dma(), None);
+    }
+}
@@ -0,0 +1,225 @@
+use std::cell::Ref;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use cpus::Cpu;
+use cpus::CpuError;
+use cpus::mcs6502::DecimalMode;
+use cpus::mcs6502::Mcs6502;
+use machines::Machine;
+use mems::Memory;
+use mems::bus::Bus8b;
+use mems::ram::Ram8b;
+
+pub mod cart;
+pub mod io;
+pub mod ppu;
+
+use self::cart::Cartridge;
+use self::io::Io;
+use self::ppu::Ppu;
+
+// 2K of RAM mirrored over the first 8K.
+pub const RAM_SIZE:  usize = 0x0800;
+pub const RAM_BLOCK: usize = 0x2000;
+
+// PPU registers mirrored up to the I/O registers.
+pub const PPU_ADDRESS: usize = 0x2000;
+pub const PPU_BLOCK:   usize = 0x2000;
+
+pub const IO_ADDRESS: usize = 0x4000;
+
+// NTSC consoles.
+pub const CLOCK_HZ: u64 = 1_789_773;
+
+// Plus one when started on an odd cycle.
+const OAM_DMA_CYCLES: u64 = 513;
+
+// Nintendo Entertainment System, a Ricoh 2A03 with the PPU,
+// the controllers and a cartridge.
+pub struct Nes {
+    cpu: Mcs6502<Bus8b>,
+    ppu: Rc<RefCell<Ppu>>,
+    io: Rc<RefCell<Io>>
+}
+
+impl Nes {
+    pub fn new(cart: Cartridge) -> Nes {
+        let cart = Rc::new(RefCell::new(cart));
+        let ppu = Rc::new(RefCell::new(Ppu::new(cart.clone())));
+        let io = Rc::new(RefCell::new(Io::new()));
+
+        let mut bus = Bus8b::new(64 * 1024);
+        bus.attach_mirrored(0x0000, RAM_BLOCK, Box::new(Ram8b::new(RAM_SIZE)));
+        bus.attach_mirrored(PPU_ADDRESS, PPU_BLOCK, Box::new(ppu.clone()));
+        bus.attach(IO_ADDRESS, Box::new(io.clone()));
+        bus.attach(cart::CART_ADDRESS, Box::new(cart));
+
+        // The 2A03 has no decimal mode.
+        let mut cpu = Mcs6502::new(bus);
+        cpu.set_decimal_mode(DecimalMode::Disabled);
+        cpu.set_jam_halts(true);
+        cpu.restart();
+
+        Nes { cpu, ppu, io }
+    }
+
+    // Buttons held by the player, see io::BUTTON_A and others.
+    pub fn set_buttons(&mut self, player: usize, buttons: u8) {
+        self.io.borrow_mut().set_buttons(player, buttons);
+    }
+
+    pub fn ppu(&self) -> Ref<'_, Ppu> {
+        self.ppu.borrow()
+    }
+
+    // Runs until the PPU completes a frame or the cpu halts.
+    pub fn run_frame(&mut self) -> Result<(), CpuError> {
+        let frame = self.ppu.borrow().frames();
+        while self.cpu.running() && self.ppu.borrow().frames() == frame {
+            self.step()?;
+        }
+
+        Ok(())
+    }
+
+    // Copies the page to the OAM through OAMDATA while the cpu
+    // waits.
+    fn oam_dma(&mut self, page: u8) {
+        let start = (page as usize) << 8;
+        for i in 0..ppu::OAM_SIZE {
+            let data = self.cpu.memory().read_u8(start + i);
+            self.ppu.borrow_mut().write_u8(ppu::OAMDATA, data);
+        }
+
+        let odd = self.cpu.cycles() % 2;
+        self.cpu.stall(OAM_DMA_CYCLES + odd);
+    }
+}
+
+impl Machine for Nes {
+    fn cpu(&mut self) -> &mut Mcs6502<Bus8b> {
+        &mut self.cpu
+    }
+
+    fn step(&mut self) -> Result<(), CpuError> {
+        let start = self.cpu.cycles();
+        self.cpu.execute()?;
+
+        let dma = self.io.borrow_mut().take_dma();
+        if let Some(page) = dma {
+            self.oam_dma(page);
+        }
+
+        let cycles = self.cpu.cycles() - start;
+        self.ppu.borrow_mut().tick(cycles);
+
+        let nmi = self.ppu.borrow().nmi();
+        self.cpu.set_nmi(nmi);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cpus::Cpu;
+    use cpus::mcs6502;
+    use inst::mcs6502::ops;
+    use machines::Machine;
+    use machines::nes::Nes;
+    use machines::nes::cart::Cartridge;
+    use machines::nes::io;
+    use mems::Memory;
+
+    // NROM with 16K of PRG ROM at 0xC000 holding the code, the
+    // NMI handler at 0xC100 and no CHR ROM.
+    fn cart(code: &[u8], nmi: &[u8]) -> Cartridge {
+        let mut prg = vec![0xEA; 0x4000];
+        prg[..code.len()].copy_from_slice(code);
+        prg[0x100..0x100 + nmi.len()].copy_from_slice(nmi);
+        prg[0x3FFA..].copy_from_slice(&[0x00, 0xC1, 0x00, 0xC0, 0x00, 0xC0]);
+
+        let mut image = b"NES\x1A\x01\x00".to_vec();
+        image.resize(16, 0);
+        image.extend(prg);
+        Cartridge::load(&image).unwrap()
+    }
+
+    #[test]
+    fn memory_map() {
+        let mut nes = Nes::new(cart(&[], &[]));
+        assert_eq!(nes.cpu().pc(), 0xC000);
+
+        let memory = nes.cpu().memory();
+        memory.write_u8(0x07FF, 0x42);
+        assert_eq!(memory.read_u8(0x1FFF), 0x42);
+        assert_eq!(memory.read_u8(0x8000), 0xEA);
+        assert_eq!(memory.read_u8(0xFFFD), 0xC0);
+
+        // OAMADDR at its mirror.
+        memory.write_u8(0x3FFB, 0x10);
+        memory.write_u8(0x2004, 0x24);
+        assert_eq!(nes.ppu().oam()[0x10], 0x24);
+
+        nes.set_buttons(0, io::BUTTON_A);
+        let memory = nes.cpu().memory();
+        memory.write_u8(0x4016, 0x01);
+        memory.write_u8(0x4016, 0x00);
+        assert_eq!(memory.read_u8(0x4016), 0x41);
+        assert_eq!(memory.read_u8(0x4016), 0x40);
+    }
+
+    #[test]
+    fn decimal() {
+        let code = [
+            ops::SED_IMPLIED,
+            ops::LDA_IMMEDIATE, 0x09,
+            ops::CLC_IMPLIED,
+            ops::ADC_IMMEDIATE, 0x01
+        ];
+
+        let mut nes = Nes::new(cart(&code, &[]));
+        for _ in 0..4 {
+            nes.step().unwrap();
+        }
+        assert_eq!(nes.cpu().registers().accu, 0x0A);
+    }
+
+    #[test]
+    fn oam_dma() {
+        let code = [
+            ops::LDA_IMMEDIATE, 0x07,
+            ops::STA_ABSOLUTE, 0x14, 0x40
+        ];
+
+        let mut nes = Nes::new(cart(&code, &[]));
+        for i in 0..0x100 {
+            nes.cpu().memory().write_u8(0x0700 + i, i as u8);
+        }
+
+        // Starts on an odd cycle, the reset took 7.
+        nes.step().unwrap();
+        nes.step().unwrap();
+        assert_eq!(nes.cpu().cycles(), mcs6502::RESET_CYCLES + 2 + 4 + 514);
+        assert_eq!(nes.ppu().oam()[0xFF], 0xFF);
+    }
+
+    #[test]
+    fn vblank_nmi() {
+        // Enables the NMI and counts frames in 0x10.
+        let code = [
+            ops::LDA_IMMEDIATE, 0x80,
+            ops::STA_ABSOLUTE, 0x00, 0x20,
+            ops::JMP_ABSOLUTE, 0x05, 0xC0
+        ];
+        let nmi = [ops::INC_ZERO_PAGE, 0x10, ops::RTI_IMPLIED];
+
+        let mut nes = Nes::new(cart(&code, &nmi));
+        for _ in 0..3 {
+            nes.run_frame().unwrap();
+        }
+        assert_eq!(nes.ppu().frames(), 3);
+        assert_eq!(nes.cpu().memory().read_u8(0x0010), 3);
+    }
+}
@@ -0,0 +1,764 @@
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use machines::nes::cart::Cartridge;
+use machines::nes::cart::Mirroring;
+use mems::Memory;
+
+// Registers, mirrored every 8 bytes up to 0x3FFF.
+pub const PPUCTRL:   usize = 0x00;
+pub const PPUMASK:   usize = 0x01;
+pub const PPUSTATUS: usize = 0x02;
+pub const OAMADDR:   usize = 0x03;
+pub const OAMDATA:   usize = 0x04;
+pub const PPUSCROLL: usize = 0x05;
+pub const PPUADDR:   usize = 0x06;
+pub const PPUDATA:   usize = 0x07;
+
+pub const PPU_SIZE: usize = 0x08;
+
+pub const OAM_SIZE: usize = 0x100;
+
+pub const WIDTH:  usize = 256;
+pub const HEIGHT: usize = 240;
+
+const CTRL_NAMETABLE:   u8 = 0x03;
+const CTRL_INCREMENT:   u8 = 0x04;
+const CTRL_SPRITE_TABLE: u8 = 0x08;
+const CTRL_BG_TABLE:    u8 = 0x10;
+const CTRL_SPRITE_16:   u8 = 0x20;
+const CTRL_NMI:         u8 = 0x80;
+
+// Colour emphasis in the upper bits is not emulated.
+const MASK_GREYSCALE:   u8 = 0x01;
+const MASK_BG_LEFT:     u8 = 0x02;
+const MASK_SPRITE_LEFT: u8 = 0x04;
+const MASK_BG:          u8 = 0x08;
+const MASK_SPRITES:     u8 = 0x10;
+
+const STATUS_OVERFLOW: u8 = 0x20;
+const STATUS_HIT:      u8 = 0x40;
+const STATUS_VBLANK:   u8 = 0x80;
+
+// Sprite attributes.
+const SPRITE_PALETTE: u8 = 0x03;
+const SPRITE_BEHIND:  u8 = 0x20;
+const SPRITE_FLIP_H:  u8 = 0x40;
+const SPRITE_FLIP_V:  u8 = 0x80;
+
+const SPRITES_PER_LINE: usize = 8;
+
+// Three dots are drawn every cpu cycle.
+pub const DOTS_PER_CYCLE:  u64 = 3;
+pub const DOTS_PER_LINE:   u64 = 341;
+pub const LINES_PER_FRAME: u64 = 262;
+
+// VBlank starts at the second dot of this line and ends
+// at the second dot of the pre-render line.
+const VBLANK_LINE:     u64 = 241;
+const PRE_RENDER_LINE: u64 = 261;
+
+// Dots of a line where the scroll registers are updated.
+const DOT_INC_VERT:  u64 = 256;
+const DOT_COPY_HORI: u64 = 257;
+const DOT_COPY_VERT: u64 = 304;
+
+// Parts of the v and t registers, yyy NN YYYYY XXXXX.
+const ADDR_COARSE_X:  u16 = 0x001F;
+const ADDR_COARSE_Y:  u16 = 0x03E0;
+const ADDR_NT_X:      u16 = 0x0400;
+const ADDR_NT_Y:      u16 = 0x0800;
+const ADDR_FINE_Y:    u16 = 0x7000;
+const ADDR_HORIZONTAL: u16 = ADDR_COARSE_X | ADDR_NT_X;
+
+const NAMETABLE_ADDRESS: u16 = 0x2000;
+const ATTRIBUTE_OFFSET:  u16 = 0x03C0;
+const PALETTE_ADDRESS:   u16 = 0x3F00;
+
+// RGB of the 64 colours of the 2C02.
+const COLOURS: [[u8; 3]; 64] = [
+    [ 84,  84,  84], [  0,  30, 116], [  8,  16, 144], [ 48,   0, 136],
+    [ 68,   0, 100], [ 92,   0,  48], [ 84,   4,   0], [ 60,  24,   0],
+    [ 32,  42,   0], [  8,  58,   0], [  0,  64,   0], [  0,  60,   0],
+    [  0,  50,  60], [  0,   0,   0], [  0,   0,   0], [  0,   0,   0],
+    [152, 150, 152], [  8,  76, 196], [ 48,  50, 236], [ 92,  30, 228],
+    [136,  20, 176], [160,  20, 100], [152,  34,  32], [120,  60,   0],
+    [ 84,  90,   0], [ 40, 114,   0], [  8, 124,   0], [  0, 118,  40],
+    [  0, 102, 120], [  0,   0,   0], [  0,   0,   0], [  0,   0,   0],
+    [236, 238, 236], [ 76, 154, 236], [120, 124, 236], [176,  98, 236],
+    [228,  84, 236], [236,  88, 180], [236, 106, 100], [212, 136,  32],
+    [160, 170,   0], [116, 196,   0], [ 76, 208,  32], [ 56, 204, 108],
+    [ 56, 180, 204], [ 60,  60,  60], [  0,   0,   0], [  0,   0,   0],
+    [236, 238, 236], [168, 204, 236], [188, 188, 236], [212, 178, 236],
+    [236, 174, 236], [236, 174, 212], [236, 180, 176], [228, 196, 144],
+    [204, 210, 120], [180, 222, 120], [168, 226, 144], [152, 226, 180],
+    [160, 214, 228], [160, 162, 160], [  0,   0,   0], [  0,   0,   0]
+];
+
+// 2C02 picture processing unit. Each visible line is drawn
+// at once when its last dot is reached, so writes in the
+// middle of a line take effect on the next one.
+pub struct Ppu {
+    cart: Rc<RefCell<Cartridge>>,
+    ctrl: u8,
+    mask: u8,
+
+    // Reading the status clears VBlank and the write toggle.
+    status: Cell<u8>,
+    oam_addr: u8,
+    oam: [u8; OAM_SIZE],
+
+    // Nametables, 4K of them with four-screen mirroring.
+    vram: [u8; 0x1000],
+    palette: [u8; 32],
+
+    // Current and temporary VRAM address, fine X scroll and
+    // the toggle of the PPUSCROLL and PPUADDR writes.
+    v: Cell<u16>,
+    t: u16,
+    x: u8,
+    w: Cell<bool>,
+
+    // Reads of PPUDATA below the palettes are delayed by one.
+    buffer: Cell<u8>,
+
+    // Colour of each pixel, one of COLOURS.
+    frame: Vec<u8>,
+
+    // Position in the frame, the pre-render line is one dot
+    // shorter in odd frames.
+    line: u64,
+    dot: u64,
+    odd: bool,
+    frames: u64
+}
+
+impl Memory for Ppu {
+    fn write_u8(&mut self, addr: usize, data: u8) {
+        match addr {
+            PPUCTRL   => {
+                self.ctrl = data;
+                self.t = (self.t & !(ADDR_NT_X | ADDR_NT_Y))
+                    | (((data & CTRL_NAMETABLE) as u16) << 10);
+            }
+            PPUMASK   => self.mask = data,
+            OAMADDR   => self.oam_addr = data,
+            OAMDATA   => {
+                self.oam[self.oam_addr as usize] = data;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            }
+            PPUSCROLL => {
+                if !self.w.get() {
+                    self.t = (self.t & !ADDR_COARSE_X) | (data >> 3) as u16;
+                    self.x = data & 0x07;
+                } else {
+                    self.t = (self.t & !(ADDR_COARSE_Y | ADDR_FINE_Y))
+                        | (((data & 0xF8) as u16) << 2) | (((data & 0x07) as u16) << 12);
+                }
+                self.w.set(!self.w.get());
+            }
+            PPUADDR   => {
+                if !self.w.get() {
+                    self.t = (self.t & 0x00FF) | (((data & 0x3F) as u16) << 8);
+                } else {
+                    self.t = (self.t & 0xFF00) | data as u16;
+                    self.v.set(self.t);
+                }
+                self.w.set(!self.w.get());
+            }
+            PPUDATA   => {
+                let v = self.v.get();
+                self.write_vram(v, data);
+                self.increment();
+            }
+            _         => {}
+        }
+    }
+
+    // Write only registers read as 0.
+    fn read_u8(&self, addr: usize) -> u8 {
+        match addr {
+            PPUSTATUS => {
+                self.w.set(false);
+                self.status.replace(self.status.get() & !STATUS_VBLANK)
+            }
+            OAMDATA   => self.oam[self.oam_addr as usize],
+            PPUDATA   => {
+                let v = self.v.get() & 0x3FFF;
+                let data = if v >= PALETTE_ADDRESS {
+                    self.buffer.set(self.read_vram(v - 0x1000));
+                    self.read_vram(v)
+                } else {
+                    self.buffer.replace(self.read_vram(v))
+                };
+
+                self.increment();
+                data
+            }
+            _         => 0
+        }
+    }
+
+    fn write_u16(&mut self, addr: usize, data: u16) {
+        self.write_u8(addr, (data & 0xFF) as u8);
+        self.write_u8(addr + 1, (data >> 8) as u8);
+    }
+
+    fn read_u16(&self, addr: usize) -> u16 {
+        let lo = self.read_u8(addr) as u16;
+        let hi = self.read_u8(addr + 1) as u16;
+
+        (hi << 8) | lo
+    }
+
+    fn size(&self) -> usize {
+        PPU_SIZE
+    }
+}
+
+impl Ppu {
+    // The pattern tables and the mirroring come from the cartridge.
+    pub fn new(cart: Rc<RefCell<Cartridge>>) -> Ppu {
+        Ppu {
+            cart,
+            ctrl: 0,
+            mask: 0,
+            status: Cell::new(0),
+            oam_addr: 0,
+            oam: [0; OAM_SIZE],
+            vram: [0; 0x1000],
+            palette: [0; 32],
+            v: Cell::new(0),
+            t: 0,
+            x: 0,
+            w: Cell::new(false),
+            buffer: Cell::new(0),
+            frame: vec![0; WIDTH * HEIGHT],
+            line: 0,
+            dot: 0,
+            odd: false,
+            frames: 0
+        }
+    }
+
+    // Catches up with the cpu.
+    pub fn tick(&mut self, cycles: u64) {
+        for _ in 0..cycles * DOTS_PER_CYCLE {
+            self.step();
+        }
+    }
+
+    // State of the NMI line of the cpu.
+    pub fn nmi(&self) -> bool {
+        self.ctrl & CTRL_NMI != 0 && self.status.get() & STATUS_VBLANK != 0
+    }
+
+    // Frames completed since power up.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn oam(&self) -> &[u8] {
+        &self.oam
+    }
+
+    // Last picture, WIDTH * HEIGHT pixels as indices of the
+    // 64 colours.
+    pub fn frame(&self) -> &[u8] {
+        &self.frame
+    }
+
+    // Last picture as RGB, three bytes per pixel.
+    pub fn frame_rgb(&self) -> Vec<u8> {
+        self.frame.iter().flat_map(|&colour| COLOURS[colour as usize].iter().cloned()).collect()
+    }
+
+    fn rendering(&self) -> bool {
+        self.mask & (MASK_BG | MASK_SPRITES) != 0
+    }
+
+    fn step(&mut self) {
+        self.dot += 1;
+        let last = if self.line == PRE_RENDER_LINE && self.odd && self.rendering() {
+            DOTS_PER_LINE - 1
+        } else {
+            DOTS_PER_LINE
+        };
+
+        if self.dot == last {
+            self.dot = 0;
+            self.line += 1;
+            if self.line == LINES_PER_FRAME {
+                self.line = 0;
+                self.odd = !self.odd;
+                self.frames += 1;
+            }
+        }
+
+        let visible = (self.line as usize) < HEIGHT;
+        if self.dot == 1 && self.line == VBLANK_LINE {
+            self.status.set(self.status.get() | STATUS_VBLANK);
+        } else if self.dot == 1 && self.line == PRE_RENDER_LINE {
+            let cleared = STATUS_VBLANK | STATUS_HIT | STATUS_OVERFLOW;
+            self.status.set(self.status.get() & !cleared);
+        }
+
+        if !visible && self.line != PRE_RENDER_LINE {
+            return;
+        }
+
+        if visible && self.dot == DOT_INC_VERT {
+            self.render_line();
+        }
+
+        if !self.rendering() {
+            return;
+        }
+
+        match self.dot {
+            DOT_INC_VERT  => self.increment_y(),
+            DOT_COPY_HORI => {
+                let v = self.v.get();
+                self.v.set((v & !ADDR_HORIZONTAL) | (self.t & ADDR_HORIZONTAL));
+                self.oam_addr = 0;
+            }
+            DOT_COPY_VERT if self.line == PRE_RENDER_LINE => {
+                let v = self.v.get();
+                self.v.set((v & ADDR_HORIZONTAL) | (self.t & !ADDR_HORIZONTAL));
+            }
+            _             => {}
+        }
+    }
+
+    fn increment(&self) {
+        let step = if self.ctrl & CTRL_INCREMENT != 0 { 32 } else { 1 };
+        self.v.set(self.v.get().wrapping_add(step) & 0x7FFF);
+    }
+
+    // Moves v to the next tile, wrapping into the nametable
+    // on the right.
+    fn increment_x(v: u16) -> u16 {
+        if v & ADDR_COARSE_X == ADDR_COARSE_X {
+            (v & !ADDR_COARSE_X) ^ ADDR_NT_X
+        } else {
+            v + 1
+        }
+    }
+
+    // Moves v to the next line of pixels, the nametable below
+    // follows the 30th row of tiles.
+    fn increment_y(&mut self) {
+        let mut v = self.v.get();
+        if v & ADDR_FINE_Y != ADDR_FINE_Y {
+            v += 0x1000;
+        } else {
+            v &= !ADDR_FINE_Y;
+            let row = match (v & ADDR_COARSE_Y) >> 5 {
+                29  => {
+                    v ^= ADDR_NT_Y;
+                    0
+                }
+                31  => 0,
+                row => row + 1
+            };
+            v = (v & !ADDR_COARSE_Y) | (row << 5);
+        }
+        self.v.set(v);
+    }
+
+    // Offset in vram of a nametable address.
+    fn nametable(&self, addr: u16) -> usize {
+        let offset = (addr & 0x0FFF) as usize;
+        let table = match self.cart.borrow().mirroring() {
+            Mirroring::Horizontal => offset >> 11,
+            Mirroring::Vertical   => (offset >> 10) & 0x01,
+            Mirroring::SingleLow  => 0,
+            Mirroring::SingleHigh => 1,
+            Mirroring::FourScreen => offset >> 10
+        };
+
+        table * 0x400 + (offset & 0x3FF)
+    }
+
+    // $3F10, $3F14, $3F18 and $3F1C, the backdrops of the sprite
+    // palettes, mirror $3F00, $3F04, $3F08 and $3F0C.
+    fn palette_index(addr: u16) -> usize {
+        let index = (addr & 0x1F) as usize;
+        if index & 0x13 == 0x10 { index & 0x0F } else { index }
+    }
+
+    fn read_vram(&self, addr: u16) -> u8 {
+        let addr = addr & 0x3FFF;
+        if addr < NAMETABLE_ADDRESS {
+            self.cart.borrow().chr_read(addr as usize)
+        } else if addr < PALETTE_ADDRESS {
+            self.vram[self.nametable(addr)]
+        } else {
+            let colour = self.palette[Ppu::palette_index(addr)];
+            if self.mask & MASK_GREYSCALE != 0 { colour & 0x30 } else { colour }
+        }
+    }
+
+    fn write_vram(&mut self, addr: u16, data: u8) {
+        let addr = addr & 0x3FFF;
+        if addr < NAMETABLE_ADDRESS {
+            self.cart.borrow_mut().chr_write(addr as usize, data);
+        } else if addr < PALETTE_ADDRESS {
+            let offset = self.nametable(addr);
+            self.vram[offset] = data;
+        } else {
+            self.palette[Ppu::palette_index(addr)] = data & 0x3F;
+        }
+    }
+
+    // Bit of the pixel in both planes of a row of a tile.
+    fn pattern(&self, addr: u16, bit: u8) -> u8 {
+        let lo = self.read_vram(addr);
+        let hi = self.read_vram(addr + 8);
+        ((lo >> bit) & 0x01) | (((hi >> bit) & 0x01) << 1)
+    }
+
+    // Palette entries of the background, 0 where it's transparent.
+    fn background_line(&self) -> [u8; WIDTH] {
+        let mut pixels = [0; WIDTH];
+        if self.mask & MASK_BG == 0 {
+            return pixels;
+        }
+
+        let table = if self.ctrl & CTRL_BG_TABLE != 0 { 0x1000 } else { 0 };
+        let mut v = self.v.get();
+
+        // The fine X scroll makes the line span 33 tiles.
+        for tile in 0..WIDTH / 8 + 1 {
+            let id = self.read_vram(NAMETABLE_ADDRESS | (v & 0x0FFF)) as u16;
+            let attr_addr = NAMETABLE_ADDRESS | ATTRIBUTE_OFFSET | (v & (ADDR_NT_X | ADDR_NT_Y))
+                | ((v >> 4) & 0x38) | ((v >> 2) & 0x07);
+            let shift = ((v >> 4) & 0x04) | (v & 0x02);
+            let palette = (self.read_vram(attr_addr) >> shift) & 0x03;
+            let row = table + id * 16 + (v >> 12);
+
+            for bit in 0..8 {
+                let px = (tile * 8 + bit) as isize - self.x as isize;
+                if px < 0 || px >= WIDTH as isize {
+                    continue;
+                }
+
+                let pixel = self.pattern(row, 7 - bit as u8);
+                if pixel != 0 {
+                    pixels[px as usize] = palette * 4 + pixel;
+                }
+            }
+            v = Ppu::increment_x(v);
+        }
+
+        if self.mask & MASK_BG_LEFT == 0 {
+            pixels[..8].iter_mut().for_each(|pixel| *pixel = 0);
+        }
+
+        pixels
+    }
+
+    // Palette entries of the sprites, whether they are behind
+    // the background and whether they belong to sprite 0.
+    fn sprite_line(&mut self) -> [(u8, bool, bool); WIDTH] {
+        let mut pixels = [(0, false, false); WIDTH];
+        if self.mask & MASK_SPRITES == 0 {
+            return pixels;
+        }
+
+        let height = if self.ctrl & CTRL_SPRITE_16 != 0 { 16 } else { 8 };
+        let mut count = 0;
+
+        // Sprites are drawn a line below their Y coordinate.
+        for (index, sprite) in self.oam.chunks(4).enumerate() {
+            let row = self.line as isize - sprite[0] as isize - 1;
+            if row < 0 || row >= height {
+                continue;
+            }
+
+            if count == SPRITES_PER_LINE {
+                self.status.set(self.status.get() | STATUS_OVERFLOW);
+                break;
+            }
+            count += 1;
+
+            let (tile, attr, left) = (sprite[1] as u16, sprite[2], sprite[3] as usize);
+            let row = if attr & SPRITE_FLIP_V != 0 { height - 1 - row } else { row } as u16;
+            let addr = if height == 16 {
+                (tile & 0x01) * 0x1000 + (tile & 0xFE) * 16 + (row & 0x08) * 2 + (row & 0x07)
+            } else {
+                let table = if self.ctrl & CTRL_SPRITE_TABLE != 0 { 0x1000 } else { 0 };
+                table + tile * 16 + row
+            };
+
+            for bit in 0..8 {
+                let px = left + bit as usize;
+                if px >= WIDTH || pixels[px].0 != 0 {
+                    continue;
+                }
+
+                let bit = if attr & SPRITE_FLIP_H != 0 { bit } else { 7 - bit };
+                let pixel = self.pattern(addr, bit);
+                if pixel != 0 {
+                    let colour = 0x10 + (attr & SPRITE_PALETTE) * 4 + pixel;
+                    pixels[px] = (colour, attr & SPRITE_BEHIND != 0, index == 0);
+                }
+            }
+        }
+
+        if self.mask & MASK_SPRITE_LEFT == 0 {
+            pixels[..8].iter_mut().for_each(|pixel| pixel.0 = 0);
+        }
+
+        pixels
+    }
+
+    fn render_line(&mut self) {
+        let background = self.background_line();
+        let sprites = self.sprite_line();
+
+        let start = self.line as usize * WIDTH;
+        for px in 0..WIDTH {
+            let bg = background[px];
+            let (sprite, behind, zero) = sprites[px];
+
+            // Sprite 0 hits the background anywhere but the last
+            // pixel of a line.
+            if zero && sprite != 0 && bg != 0 && px != WIDTH - 1 {
+                self.status.set(self.status.get() | STATUS_HIT);
+            }
+
+            let entry = if sprite != 0 && (bg == 0 || !behind) { sprite } else { bg };
+            self.frame[start + px] = self.read_vram(PALETTE_ADDRESS + entry as u16);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use machines::nes::cart::Cartridge;
+    use machines::nes::ppu;
+    use machines::nes::ppu::Ppu;
+    use mems::Memory;
+
+    const FRAME_CYCLES: u64 = 262 * 341 / 3;
+
+    // NROM with CHR RAM and the given mirroring flags.
+    fn ppu(flags: u8) -> Ppu {
+        let mut image = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x00, flags];
+        image.resize(16 + 0x4000, 0);
+        let cart = Cartridge::load(&image).unwrap();
+        Ppu::new(Rc::new(RefCell::new(cart)))
+    }
+
+    fn set_addr(ppu: &mut Ppu, addr: u16) {
+        ppu.write_u8(ppu::PPUADDR, (addr >> 8) as u8);
+        ppu.write_u8(ppu::PPUADDR, addr as u8);
+    }
+
+    fn write(ppu: &mut Ppu, addr: u16, data: &[u8]) {
+        set_addr(ppu, addr);
+        for &byte in data {
+            ppu.write_u8(ppu::PPUDATA, byte);
+        }
+    }
+
+    // Tile 1 is solid in colour 1, tile 2 has its left column
+    // in colour 3. The backdrops of the sprite palettes mirror
+    // those of the background.
+    fn tiles(ppu: &mut Ppu) {
+        write(ppu, 0x0010, &[0xFF; 8]);
+        write(ppu, 0x0020, &[0x80; 16]);
+        write(ppu, 0x3F10, &[0x20, 0x21, 0x22, 0x23, 0x20, 0x24, 0x25, 0x26]);
+        write(ppu, 0x3F00, &[0x0F, 0x01, 0x02, 0x03, 0x0F, 0x11, 0x12, 0x13]);
+    }
+
+    fn pixel(ppu: &Ppu, x: usize, y: usize) -> u8 {
+        ppu.frame()[y * ppu::WIDTH + x]
+    }
+
+    #[test]
+    fn vblank() {
+        let mut ppu = ppu(0);
+        ppu.write_u8(ppu::PPUCTRL, 0x80);
+
+        // 241 lines of 341 dots, three dots per cycle.
+        ppu.tick(241 * 341 / 3);
+        assert!(!ppu.nmi());
+        ppu.tick(1);
+        assert!(ppu.nmi());
+
+        // Reading the status acknowledges it.
+        assert_eq!(ppu.read_u8(ppu::PPUSTATUS), 0x80);
+        assert_eq!(ppu.read_u8(ppu::PPUSTATUS), 0x00);
+        assert!(!ppu.nmi());
+
+        ppu.tick(20 * 341 / 3 + 1);
+        assert_eq!(ppu.read_u8(ppu::PPUSTATUS), 0x00);
+        ppu.tick(341);
+        assert_eq!(ppu.frames(), 1);
+
+        ppu.write_u8(ppu::OAMADDR, 0xFF);
+        ppu.write_u8(ppu::OAMDATA, 0x42);
+        ppu.write_u8(ppu::OAMDATA, 0x24);
+        assert_eq!(&ppu.oam()[..1], &[0x24]);
+        assert_eq!(ppu.oam()[0xFF], 0x42);
+    }
+
+    #[test]
+    fn vram() {
+        // Vertical mirroring.
+        let mut ppu = ppu(0x01);
+        write(&mut ppu, 0x2000, &[0x11]);
+        write(&mut ppu, 0x2C00, &[0x22]);
+        write(&mut ppu, 0x3F10, &[0x33]);
+
+        // Reads are buffered except for the palettes.
+        set_addr(&mut ppu, 0x2800);
+        ppu.read_u8(ppu::PPUDATA);
+        assert_eq!(ppu.read_u8(ppu::PPUDATA), 0x11);
+        set_addr(&mut ppu, 0x2400);
+        ppu.read_u8(ppu::PPUDATA);
+        assert_eq!(ppu.read_u8(ppu::PPUDATA), 0x22);
+        set_addr(&mut ppu, 0x3F00);
+        assert_eq!(ppu.read_u8(ppu::PPUDATA), 0x33);
+
+        // Increments by 32 go down a column.
+        ppu.write_u8(ppu::PPUCTRL, 0x04);
+        write(&mut ppu, 0x2001, &[0x44, 0x55]);
+        set_addr(&mut ppu, 0x2021);
+        ppu.read_u8(ppu::PPUDATA);
+        assert_eq!(ppu.read_u8(ppu::PPUDATA), 0x55);
+
+        // The status resets the write toggle.
+        ppu.write_u8(ppu::PPUADDR, 0x3F);
+        ppu.read_u8(ppu::PPUSTATUS);
+        write(&mut ppu, 0x0000, &[0x66]);
+        set_addr(&mut ppu, 0x0000);
+        ppu.read_u8(ppu::PPUDATA);
+        assert_eq!(ppu.read_u8(ppu::PPUDATA), 0x66);
+    }
+
+    #[test]
+    fn background() {
+        let mut ppu = ppu(0x01);
+        tiles(&mut ppu);
+
+        // Tile 1 at the top left and with palette 1 in the bottom
+        // right of the first 32x32 block, tiles 1 and 2 in the
+        // nametable on the right.
+        write(&mut ppu, 0x2000, &[0x01]);
+        write(&mut ppu, 0x2042, &[0x01]);
+        write(&mut ppu, 0x23C0, &[0x40]);
+        write(&mut ppu, 0x2400, &[0x02]);
+        write(&mut ppu, 0x2420, &[0x01]);
+
+        // The scroll is applied from the next frame, PPUADDR
+        // also selected another nametable.
+        ppu.write_u8(ppu::PPUCTRL, 0x00);
+        ppu.write_u8(ppu::PPUSCROLL, 0x00);
+        ppu.write_u8(ppu::PPUSCROLL, 0x00);
+        ppu.write_u8(ppu::PPUMASK, 0x0A);
+        ppu.tick(2 * FRAME_CYCLES);
+        assert_eq!(pixel(&ppu, 0, 0), 0x01);
+        assert_eq!(pixel(&ppu, 8, 0), 0x0F);
+        assert_eq!(pixel(&ppu, 16, 16), 0x11);
+
+        // Scrolled 4 pixels right and 8 down, the bottom right
+        // wraps to the nametables below, which mirror those
+        // above.
+        ppu.write_u8(ppu::PPUSCROLL, 0x04);
+        ppu.write_u8(ppu::PPUSCROLL, 0x08);
+        ppu.tick(2 * FRAME_CYCLES);
+        assert_eq!(pixel(&ppu, 3, 0), 0x0F);
+        assert_eq!(pixel(&ppu, 12, 8), 0x11);
+        assert_eq!(pixel(&ppu, 252, 0), 0x01);
+        assert_eq!(pixel(&ppu, 252, 232), 0x03);
+    }
+
+    #[test]
+    fn sprites() {
+        let mut ppu = ppu(0);
+        tiles(&mut ppu);
+
+        // Tile 1 without its left column at 0,0 and 8,8.
+        write(&mut ppu, 0x0010, &[0x7F; 8]);
+        write(&mut ppu, 0x2000, &[0x01]);
+        write(&mut ppu, 0x2021, &[0x01]);
+
+        // Sprite 0 off screen, sprite 1 behind the background
+        // but still hiding sprite 2, sprite 3 flipped.
+        let oam = [
+            0xFF, 0x01, 0x00, 0x00,
+            0x09, 0x02, 0x21, 0x09,
+            0x09, 0x01, 0x01, 0x08,
+            0x11, 0x02, 0x40, 0x04
+        ];
+        for &byte in oam.iter() {
+            ppu.write_u8(ppu::OAMDATA, byte);
+        }
+
+        ppu.write_u8(ppu::PPUSCROLL, 0x00);
+        ppu.write_u8(ppu::PPUSCROLL, 0x00);
+        ppu.write_u8(ppu::PPUMASK, 0x1E);
+        ppu.tick(2 * FRAME_CYCLES);
+        assert_eq!(pixel(&ppu, 0, 0), 0x0F);
+        assert_eq!(pixel(&ppu, 1, 0), 0x01);
+        assert_eq!(pixel(&ppu, 8, 10), 0x0F);
+        assert_eq!(pixel(&ppu, 9, 10), 0x01);
+        assert_eq!(pixel(&ppu, 10, 10), 0x24);
+        assert_eq!(pixel(&ppu, 4, 18), 0x0F);
+        assert_eq!(pixel(&ppu, 11, 18), 0x23);
+        assert_eq!(ppu.read_u8(ppu::PPUSTATUS) & 0x40, 0x00);
+
+        // Sprite 0 over the background.
+        ppu.write_u8(ppu::OAMADDR, 0x00);
+        ppu.write_u8(ppu::OAMDATA, 0x00);
+        ppu.tick(FRAME_CYCLES / 2);
+        assert_eq!(ppu.read_u8(ppu::PPUSTATUS) & 0x40, 0x40);
+        assert_eq!(pixel(&ppu, 1, 1), 0x21);
+    }
+
+    #[test]
+    fn sprite_zero_hit() {
+        let mut ppu = ppu(0);
+        tiles(&mut ppu);
+
+        // Tile 1 at the left and the right end of the first row.
+        write(&mut ppu, 0x2000, &[0x01]);
+        write(&mut ppu, 0x201F, &[0x01]);
+        ppu.write_u8(ppu::PPUSCROLL, 0x00);
+        ppu.write_u8(ppu::PPUSCROLL, 0x00);
+
+        // Sprite 0 as Y, tile, attributes and X, the mask and
+        // whether it hits the background.
+        let cases = [
+            ([0x00, 0x01, 0x00, 0x00], 0x1E, true),
+            ([0x00, 0x01, 0x20, 0x00], 0x1E, true),
+            ([0x00, 0x00, 0x00, 0x00], 0x1E, false),
+            ([0x00, 0x01, 0x00, 0x10], 0x1E, false),
+            ([0x00, 0x01, 0x00, 0xFF], 0x1E, false),
+            ([0x00, 0x01, 0x00, 0x00], 0x18, false),
+            ([0x00, 0x01, 0x00, 0x00], 0x0A, false)
+        ];
+
+        // Each frame is checked in its middle, the hit is cleared
+        // on the pre-render line.
+        ppu.tick(FRAME_CYCLES / 2);
+        for &(sprite, mask, hit) in cases.iter() {
+            ppu.write_u8(ppu::OAMADDR, 0x00);
+            for &byte in sprite.iter() {
+                ppu.write_u8(ppu::OAMDATA, byte);
+            }
+            ppu.write_u8(ppu::PPUMASK, mask);
+
+            ppu.tick(FRAME_CYCLES);
+            let status = ppu.read_u8(ppu::PPUSTATUS);
+            assert_eq!(status & ppu::STATUS_HIT != 0, hit, "{:?} {:02X}", sprite, mask);
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use mems::Memory;
+use mems::MemoryError;
+
+// Address range of the bus handled by a single device.
+struct Region {
+    start: usize,
+    end: usize,
+    device: Box<dyn Memory>
+}
+
+impl Region {
+    fn contains(&self, addr: usize) -> bool {
+        addr >= self.start && addr < self.end
+    }
+
+    // Regions larger than their device mirror it.
+    fn offset(&self, addr: usize) -> usize {
+        (addr - self.start) % self.device.size()
+    }
+}
+
+// Address bus dispatching accesses to the attached devices,
+// reads of unmapped addresses return 0 and writes to them
+// are ignored. Writes to read-only devices are dropped and
+// reported as a fault.
+pub struct Bus8b {
+    regions: Vec<Region>,
+    size: usize,
+    fault: Option<MemoryError>
+}
+
+impl Memory for Bus8b {
+    fn write_u8(&mut self, addr: usize, data: u8) {
+        let addr = addr % self.size;
+
+        // Keep the first fault.
+        if !self.store(addr, data) && self.fault.is_none() {
+            self.fault = Some(MemoryError::ReadOnly(addr));
+        }
+    }
+
+    fn read_u8(&self, addr: usize) -> u8 {
+        let addr = addr % self.size;
+
+        match self.region(addr) {
+            Some(region) => region.device.read_u8(region.offset(addr)),
+            None         => 0
+        }
+    }
+
+    fn peek(&self, addr: usize) -> u8 {
+        let addr = addr % self.size;
+
+        match self.region(addr) {
+            Some(region) => region.device.peek(region.offset(addr)),
+            None         => 0
+        }
+    }
+
+    fn poke(&mut self, addr: usize, data: u8) {
+        let addr = addr % self.size;
+
+        if let Some(region) = self.regions.iter_mut().find(|r| r.contains(addr)) {
+            let offset = region.offset(addr);
+            region.device.poke(offset, data);
+        }
+    }
+
+    fn write_u16(&mut self, addr: usize, data: u16) {
+        // Bytes can end up in different devices.
+        self.write_u8(addr, (data & 0xFF) as u8);
+        self.write_u8(addr + 1, (data >> 8) as u8);
+    }
+
+    fn read_u16(&self, addr: usize) -> u16 {
+        let lo = self.read_u8(addr) as u16;
+        let hi = self.read_u8(addr + 1) as u16;
+
+        (hi << 8) | lo
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    // Leaves read-only devices as they are without a fault.
+    fn map(&mut self, start: usize, src: &dyn Memory) {
+        for i in 0..src.size() {
+            let addr = (start + i) % self.size;
+            self.store(addr, src.read_u8(i));
+        }
+    }
+
+    fn fault(&mut self) -> Option<MemoryError> {
+        // Clear the faults of all devices.
+        let mut res = self.fault.take();
+        for region in self.regions.iter_mut() {
+            let fault = region.device.fault();
+            res = res.or(fault);
+        }
+
+        res
+    }
+
+    fn set_pc(&mut self, pc: usize) {
+        for region in self.regions.iter_mut() {
+            region.device.set_pc(pc);
+        }
+    }
+}
+
+impl Bus8b {
+    pub fn new(sz: usize) -> Bus8b {
+        Bus8b {
+            regions: Vec::new(),
+            size: sz,
+            fault: None
+        }
+    }
+
+    pub fn attach(&mut self, start: usize, device: Box<dyn Memory>) {
+        let size = device.size();
+        self.attach_mirrored(start, size, device);
+    }
+
+    // Attaches the device to size bytes starting at start,
+    // repeating its contents if it is smaller.
+    pub fn attach_mirrored(&mut self, start: usize, size: usize,
+                           device: Box<dyn Memory>) {
+        let end = start + size;
+
+        if device.size() == 0 || size == 0 {
+            panic!("Cannot attach empty device at 0x{:X}", start);
+        }
+
+        if end > self.size {
+            panic!("Device at 0x{:X}-0x{:X} exceeds the bus", start, end - 1);
+        }
+
+        if self.regions.iter().any(|r| start < r.end && r.start < end) {
+            panic!("Device at 0x{:X}-0x{:X} overlaps another device", start, end - 1);
+        }
+
+        self.regions.push(Region { start, end, device });
+    }
+
+    fn region(&self, addr: usize) -> Option<&Region> {
+        self.regions.iter().find(|r| r.contains(addr))
+    }
+
+    // False if the device at the address is read-only.
+    fn store(&mut self, addr: usize, data: u8) -> bool {
+        if let Some(region) = self.regions.iter_mut().find(|r| r.contains(addr)) {
+            if region.device.read_only() {
+                return false;
+            }
+
+            let offset = region.offset(addr);
+            region.device.write_u8(offset, data);
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use mems::Memory;
+    use mems::MemoryError;
+    use mems::bus::Bus8b;
+    use mems::ram::Ram8b;
+    use mems::rom::Rom8b;
+
+    // Register that counts writes and clears on read.
+    struct Counter {
+        value: Cell<u8>
+    }
+
+    impl Memory for Counter {
+        fn write_u8(&mut self, _: usize, data: u8) {
+            self.value.set(self.value.get().wrapping_add(data));
+        }
+
+        fn read_u8(&self, _: usize) -> u8 {
+            self.value.replace(0)
+        }
+
+        fn write_u16(&mut self, addr: usize, data: u16) {
+            self.write_u8(addr, data as u8);
+        }
+
+        fn read_u16(&self, addr: usize) -> u16 {
+            self.read_u8(addr) as u16
+        }
+
+        fn size(&self) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn dispatch() {
+        let mut bus = Bus8b::new(64 * 1024);
+        bus.attach(0x0000, Box::new(Ram8b::new(0x8000)));
+        bus.attach(0xFF00, Box::new(Rom8b::from_vec(vec![0xAB; 0x100])));
+
+        bus.write_u8(0x1234, 0x42);
+        assert_eq!(bus.read_u8(0x1234), 0x42);
+        assert_eq!(bus.read_u8(0xFF10), 0xAB);
+
+        // Unmapped.
+        bus.write_u8(0x9000, 0x42);
+        assert_eq!(bus.read_u8(0x9000), 0x00);
+
+        // Word spanning unmapped and mapped memory.
+        assert_eq!(bus.read_u16(0xFEFF), 0xAB00);
+    }
+
+    #[test]
+    fn read_only() {
+        let mut bus = Bus8b::new(64 * 1024);
+        bus.attach(0xF000, Box::new(Rom8b::from_vec(vec![0x12, 0x34])));
+
+        bus.write_u16(0xF000, 0xFFFF);
+        assert_eq!(bus.read_u16(0xF000), 0x3412);
+        assert_eq!(bus.fault(), Some(MemoryError::ReadOnly(0xF000)));
+        assert_eq!(bus.fault(), None);
+
+        let cart = Rom8b::from_vec(vec![0xFF]);
+        bus.map(0xF000, &cart);
+        assert_eq!(bus.read_u8(0xF000), 0x12);
+        assert_eq!(bus.fault(), None);
+    }
+
+    #[test]
+    fn mirroring() {
+        let mut bus = Bus8b::new(64 * 1024);
+        bus.attach_mirrored(0x0000, 0x2000, Box::new(Ram8b::new(0x0800)));
+
+        bus.write_u8(0x0001, 0x42);
+        assert_eq!(bus.read_u8(0x0801), 0x42);
+        assert_eq!(bus.read_u8(0x1801), 0x42);
+
+        bus.write_u8(0x1FFF, 0x24);
+        assert_eq!(bus.read_u8(0x07FF), 0x24);
+        assert_eq!(bus.read_u8(0x2000), 0x00);
+    }
+
+    #[test]
+    fn io_register() {
+        let counter = Rc::new(RefCell::new(Counter { value: Cell::new(0) }));
+
+        let mut bus = Bus8b::new(64 * 1024);
+        bus.attach(0xD000, Box::new(counter.clone()));
+
+        bus.write_u8(0xD000, 0x02);
+        bus.write_u8(0xD000, 0x03);
+        assert_eq!(counter.borrow().value.get(), 0x05);
+
+        assert_eq!(bus.read_u8(0xD000), 0x05);
+        assert_eq!(bus.read_u8(0xD000), 0x00);
+    }
+
+    #[test]
+    fn address_wrap() {
+        let mut bus = Bus8b::new(64 * 1024);
+        bus.attach(0x0000, Box::new(Ram8b::new(64 * 1024)));
+
+        bus.write_u16(0xFFFF, 0x1234);
+        assert_eq!(bus.read_u8(0xFFFF), 0x34);
+        assert_eq!(bus.read_u8(0x0000), 0x12);
+    }
+
+    #[test]
+    #[should_panic]
+    fn overlap() {
+        let mut bus = Bus8b::new(64 * 1024);
+        bus.attach(0x0000, Box::new(Ram8b::new(0x1000)));
+        bus.attach(0x0FFF, Box::new(Ram8b::new(0x1000)));
+    }
+}
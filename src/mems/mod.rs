@@ -1,5 +1,39 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+pub mod bus;
 pub mod rom;
 pub mod ram;
+pub mod watch;
+
+// Faults latched by memory instead of panicking, so that
+// the cpu can report them after the instruction.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MemoryError {
+    // Access outside of the memory.
+    BusError(usize),
+
+    // Write to read-only memory.
+    ReadOnly(usize)
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MemoryError::BusError(addr) => {
+                write!(f, "bus error at 0x{:04X}", addr)
+            }
+
+            MemoryError::ReadOnly(addr) => {
+                write!(f, "write to read-only memory at 0x{:04X}", addr)
+            }
+        }
+    }
+}
+
+impl Error for MemoryError {}
 
 pub trait Memory {
     fn write_u8(&mut self, usize, u8);
@@ -8,4 +42,77 @@ pub trait Memory {
     fn read_u16(&self, usize) -> u16;
     fn size(&self) -> usize;
     fn map(&mut self, usize, &Memory) {}
+
+    // Read by the emulator itself, e.g. to disassemble, which
+    // watchpoints don't see.
+    fn peek(&self, addr: usize) -> u8 {
+        self.read_u8(addr)
+    }
+
+    // Written by the emulator itself, e.g. to restore a saved
+    // state. Only RAM takes the value, devices and ROM keep
+    // their contents.
+    fn poke(&mut self, _addr: usize, _data: u8) {}
+
+    // Writes to read-only memory are dropped by the bus.
+    fn read_only(&self) -> bool {
+        false
+    }
+
+    // Returns and clears the first fault since the last call.
+    fn fault(&mut self) -> Option<MemoryError> {
+        None
+    }
+
+    // Address of the instruction about to be executed,
+    // lets watchpoints know who accessed the memory.
+    fn set_pc(&mut self, _pc: usize) {}
+}
+
+// Allows attaching a device to a bus while keeping
+// a handle to it, e.g. to inspect I/O registers.
+impl<M: Memory> Memory for Rc<RefCell<M>> {
+    fn write_u8(&mut self, addr: usize, data: u8) {
+        self.borrow_mut().write_u8(addr, data);
+    }
+
+    fn read_u8(&self, addr: usize) -> u8 {
+        self.borrow().read_u8(addr)
+    }
+
+    fn write_u16(&mut self, addr: usize, data: u16) {
+        self.borrow_mut().write_u16(addr, data);
+    }
+
+    fn read_u16(&self, addr: usize) -> u16 {
+        self.borrow().read_u16(addr)
+    }
+
+    fn size(&self) -> usize {
+        self.borrow().size()
+    }
+
+    fn map(&mut self, start: usize, src: &dyn Memory) {
+        self.borrow_mut().map(start, src);
+    }
+
+    fn peek(&self, addr: usize) -> u8 {
+        self.borrow().peek(addr)
+    }
+
+    fn poke(&mut self, addr: usize, data: u8) {
+        self.borrow_mut().poke(addr, data);
+    }
+
+    fn read_only(&self) -> bool {
+        self.borrow().read_only()
+    }
+
+    fn fault(&mut self) -> Option<MemoryError> {
+        self.borrow_mut().fault()
+    }
+
+    fn set_pc(&mut self, pc: usize) {
+        self.borrow_mut().set_pc(pc);
+    }
 }
@@ -1,29 +1,42 @@
+use std::cell::Cell;
+
 use mems::Memory;
+use mems::MemoryError;
 
 pub struct Ram8b {
     data: Vec<u8>,
-    size: usize
+    size: usize,
+    fault: Cell<Option<MemoryError>>
 }
 
 impl Memory for Ram8b {
     fn write_u8(&mut self, addr: usize, data: u8) {
-        self.data[addr] = data;
+        if addr < self.size {
+            self.data[addr] = data;
+        } else {
+            self.set_fault(MemoryError::BusError(addr));
+        }
     }
 
     fn read_u8(&self, addr: usize) -> u8 {
-        self.data[addr]
+        if addr < self.size {
+            self.data[addr]
+        } else {
+            self.set_fault(MemoryError::BusError(addr));
+            0
+        }
     }
 
     fn write_u16(&mut self, addr: usize, data: u16) {
         let lo = (data & 0xFF) as u8;
         let hi = (data >> 8) as u8;
-        self.data[addr] = lo;
-        self.data[addr + 1] = hi;
+        self.write_u8(addr, lo);
+        self.write_u8(addr + 1, hi);
     }
 
     fn read_u16(&self, addr: usize) -> u16 {
-        let lo = self.data[addr] as u16;
-        let hi = self.data[addr + 1] as u16;
+        let lo = self.read_u8(addr) as u16;
+        let hi = self.read_u8(addr + 1) as u16;
 
         ((hi << 8) | lo)
     }
@@ -32,18 +45,59 @@ impl Memory for Ram8b {
         self.size
     }
 
+    fn poke(&mut self, addr: usize, data: u8) {
+        if addr < self.size {
+            self.data[addr] = data;
+        }
+    }
+
     fn map(&mut self, start: usize, src: &Memory) {
         for i in 0..src.size() {
-            self.data[start + i] = src.read_u8(i);
+            self.write_u8(start + i, src.read_u8(i));
         }
     }
+
+    fn fault(&mut self) -> Option<MemoryError> {
+        self.fault.take()
+    }
 }
 
 impl Ram8b {
     pub fn new(sz: usize) -> Ram8b {
         Ram8b {
             data: vec![0; sz],
-            size: sz
+            size: sz,
+            fault: Cell::new(None)
+        }
+    }
+
+    fn set_fault(&self, error: MemoryError) {
+        // Keep the first fault.
+        if self.fault.get().is_none() {
+            self.fault.set(Some(error));
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use mems::Memory;
+    use mems::MemoryError;
+    use mems::ram::Ram8b;
+
+    #[test]
+    fn out_of_range() {
+        let mut ram = Ram8b::new(0x100);
+
+        ram.write_u8(0xFF, 0x42);
+        assert_eq!(ram.fault(), None);
+
+        assert_eq!(ram.read_u16(0xFF), 0x0042);
+        assert_eq!(ram.fault(), Some(MemoryError::BusError(0x100)));
+        assert_eq!(ram.fault(), None);
+
+        ram.write_u8(0x200, 0x42);
+        ram.write_u8(0x300, 0x42);
+        assert_eq!(ram.fault(), Some(MemoryError::BusError(0x200)));
+    }
+}
@@ -1,26 +1,35 @@
+use std::cell::Cell;
+
 use mems::Memory;
+use mems::MemoryError;
 
 pub struct Rom8b {
     data: Vec<u8>,
-    size: usize
+    size: usize,
+    fault: Cell<Option<MemoryError>>
 }
 
 impl Memory for Rom8b {
-    fn write_u8(&mut self, _: usize, _: u8) {
-        panic!("Trying to overwrite read-only memory.");
+    fn write_u8(&mut self, addr: usize, _: u8) {
+        self.set_fault(MemoryError::ReadOnly(addr));
     }
 
     fn read_u8(&self, addr: usize) -> u8 {
-        self.data[addr]
+        if addr < self.size {
+            self.data[addr]
+        } else {
+            self.set_fault(MemoryError::BusError(addr));
+            0
+        }
     }
 
-    fn write_u16(&mut self, _: usize, _: u16) {
-        panic!("Trying to overwrite read-only memory.");
+    fn write_u16(&mut self, addr: usize, _: u16) {
+        self.set_fault(MemoryError::ReadOnly(addr));
     }
 
     fn read_u16(&self, addr: usize) -> u16 {
-        let lo = self.data[addr] as u16;
-        let hi = self.data[addr + 1] as u16;
+        let lo = self.read_u8(addr) as u16;
+        let hi = self.read_u8(addr + 1) as u16;
 
         ((hi << 8) | lo)
     }
@@ -28,6 +37,14 @@ impl Memory for Rom8b {
     fn size(&self) -> usize {
         self.size
     }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn fault(&mut self) -> Option<MemoryError> {
+        self.fault.take()
+    }
 }
 
 impl Rom8b {
@@ -36,7 +53,15 @@ impl Rom8b {
 
         Rom8b {
             data: vec,
-            size: size
+            size: size,
+            fault: Cell::new(None)
+        }
+    }
+
+    fn set_fault(&self, error: MemoryError) {
+        // Keep the first fault.
+        if self.fault.get().is_none() {
+            self.fault.set(Some(error));
         }
     }
 }
@@ -0,0 +1,239 @@
+use std::cell::RefCell;
+use std::ops::Range;
+
+use mems::Memory;
+use mems::MemoryError;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AccessKind {
+    Read,
+    Write,
+
+    // Instruction fetch, reported before the instruction
+    // is executed.
+    Execute
+}
+
+// Memory access passed to watchpoint callbacks, pc is the
+// address of the instruction that made it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Access {
+    pub kind: AccessKind,
+    pub addr: usize,
+    pub value: u8,
+    pub pc: usize
+}
+
+struct Watchpoint {
+    id: usize,
+    range: Range<usize>,
+    kinds: Vec<AccessKind>,
+    callback: Box<dyn FnMut(Access)>
+}
+
+// Memory wrapper calling the callbacks of watchpoints
+// whose range contains the accessed address.
+pub struct Watched<M: Memory> {
+    memory: M,
+    pc: usize,
+    watchpoints: RefCell<Vec<Watchpoint>>,
+    next_id: usize
+}
+
+impl<M: Memory> Memory for Watched<M> {
+    fn write_u8(&mut self, addr: usize, data: u8) {
+        self.memory.write_u8(addr, data);
+        self.notify(AccessKind::Write, addr, data);
+    }
+
+    fn read_u8(&self, addr: usize) -> u8 {
+        let value = self.memory.read_u8(addr);
+        self.notify(AccessKind::Read, addr, value);
+
+        value
+    }
+
+    fn write_u16(&mut self, addr: usize, data: u16) {
+        // Watchpoints see each byte.
+        self.write_u8(addr, (data & 0xFF) as u8);
+        self.write_u8(addr + 1, (data >> 8) as u8);
+    }
+
+    fn read_u16(&self, addr: usize) -> u16 {
+        let lo = self.read_u8(addr) as u16;
+        let hi = self.read_u8(addr + 1) as u16;
+
+        (hi << 8) | lo
+    }
+
+    fn size(&self) -> usize {
+        self.memory.size()
+    }
+
+    fn map(&mut self, start: usize, src: &dyn Memory) {
+        // Loading is not traced.
+        self.memory.map(start, src);
+    }
+
+    fn peek(&self, addr: usize) -> u8 {
+        self.memory.peek(addr)
+    }
+
+    fn poke(&mut self, addr: usize, data: u8) {
+        self.memory.poke(addr, data);
+    }
+
+    fn read_only(&self) -> bool {
+        self.memory.read_only()
+    }
+
+    fn fault(&mut self) -> Option<MemoryError> {
+        self.memory.fault()
+    }
+
+    fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+        self.memory.set_pc(pc);
+
+        let opcode = self.memory.peek(pc);
+        self.notify(AccessKind::Execute, pc, opcode);
+    }
+}
+
+impl<M: Memory> Watched<M> {
+    pub fn new(memory: M) -> Watched<M> {
+        Watched {
+            memory,
+            pc: 0,
+            watchpoints: RefCell::new(Vec::new()),
+            next_id: 0
+        }
+    }
+
+    // Returns the id used to remove the watchpoint.
+    pub fn watch<F>(&mut self, range: Range<usize>, kinds: &[AccessKind],
+                    callback: F) -> usize
+        where F: FnMut(Access) + 'static {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.watchpoints.borrow_mut().push(Watchpoint {
+            id,
+            range,
+            kinds: kinds.to_vec(),
+            callback: Box::new(callback)
+        });
+
+        id
+    }
+
+    pub fn unwatch(&mut self, id: usize) -> bool {
+        let mut watchpoints = self.watchpoints.borrow_mut();
+        let count = watchpoints.len();
+        watchpoints.retain(|w| w.id != id);
+
+        watchpoints.len() != count
+    }
+
+    // Wrapped memory, accesses through it are not watched.
+    pub fn inner(&mut self) -> &mut M {
+        &mut self.memory
+    }
+
+    fn notify(&self, kind: AccessKind, addr: usize, value: u8) {
+        let access = Access { kind, addr, value, pc: self.pc };
+
+        for watchpoint in self.watchpoints.borrow_mut().iter_mut() {
+            if watchpoint.range.contains(&addr) && watchpoint.kinds.contains(&kind) {
+                (watchpoint.callback)(access);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io;
+    use std::rc::Rc;
+
+    use cpus::Cpu;
+    use cpus::mcs6502;
+    use cpus::mcs6502::Mcs6502;
+    use inst::mcs6502::ops;
+    use mems::Memory;
+    use mems::ram::Ram8b;
+    use mems::rom::Rom8b;
+    use mems::watch::{Access, AccessKind, Watched};
+
+    #[test]
+    fn callbacks() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut memory = Watched::new(Ram8b::new(0x100));
+
+        let sink = log.clone();
+        let id = memory.watch(0x10..0x12, &[AccessKind::Write], move |a| sink.borrow_mut().push(a));
+
+        memory.write_u8(0x0F, 0x01);
+        memory.write_u16(0x11, 0x1234);
+        assert_eq!(memory.read_u8(0x12), 0x12);
+        assert_eq!(*log.borrow(), vec![Access { kind: AccessKind::Write, addr: 0x11, value: 0x34, pc: 0 }]);
+
+        assert!(memory.unwatch(id));
+        assert!(!memory.unwatch(id));
+        memory.write_u8(0x10, 0x01);
+        assert_eq!(log.borrow().len(), 1);
+    }
+
+    #[test]
+    fn cpu_accesses() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+
+        let program = vec![
+            ops::LDA_IMMEDIATE, 0x42,
+            ops::STA_ZERO_PAGE, 0x80,
+            ops::LDX_ZERO_PAGE, 0x80
+        ];
+
+        let mut cpu = Mcs6502::new(Watched::new(Ram8b::new(64 * 1024)));
+        cpu.boot(&Rom8b::from_vec(program));
+
+        let sink = log.clone();
+        let kinds = [AccessKind::Read, AccessKind::Write, AccessKind::Execute];
+        cpu.memory().watch(0x80..0x81, &kinds, move |a| sink.borrow_mut().push(a));
+
+        let sink = log.clone();
+        cpu.memory().watch(0x04..0x05, &[AccessKind::Execute], move |a| sink.borrow_mut().push(a));
+
+        cpu.run(3).unwrap();
+        assert_eq!(*log.borrow(), vec![
+            Access { kind: AccessKind::Write,   addr: 0x80, value: 0x42, pc: 0x02 },
+            Access { kind: AccessKind::Execute, addr: 0x04, value: ops::LDX_ZERO_PAGE, pc: 0x04 },
+            Access { kind: AccessKind::Read,    addr: 0x80, value: 0x42, pc: 0x04 }
+        ]);
+    }
+
+    #[test]
+    fn emulator_reads() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+
+        let program = vec![
+            ops::LDY_IMMEDIATE, 0x01,
+            ops::LDA_INDIRECT_Y, 0x10
+        ];
+
+        let mut cpu = Mcs6502::new(Watched::new(Ram8b::new(64 * 1024)));
+        cpu.boot(&Rom8b::from_vec(program));
+        cpu.memory().write_u16(0x10, 0x20FF);
+        cpu.run(1).unwrap();
+
+        // Tracing and the page crossing check peek.
+        cpu.set_trace(Some(Box::new(io::sink())));
+        let sink = log.clone();
+        cpu.memory().watch(0x0000..0x10000, &[AccessKind::Read], move |a| sink.borrow_mut().push(a.addr));
+
+        cpu.run(1).unwrap();
+        assert_eq!(*log.borrow(), vec![0x02, 0x03, 0x10, 0x11, 0x2100]);
+        assert_eq!(cpu.cycles(), mcs6502::RESET_CYCLES + 2 + 6);
+    }
+}
@@ -0,0 +1,633 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+
+use cpus::Cpu;
+use cpus::mcs6502::Mcs6502;
+use cpus::mcs6502::STACK_BASE_ADDRESS;
+use cpus::state;
+use inst::mcs6502;
+use mems::Memory;
+use monitor;
+use monitor::Monitor;
+
+const HELP: &str = "\
+s [count]            step instructions
+g [addr]             continue (from addr) until a breakpoint
+b [addr]             set a breakpoint or list them
+del addr             delete a breakpoint
+r [reg=value ...]    show or modify registers (PC SP A X Y P)
+m [start [end]]      examine memory
+> addr byte ...      modify memory
+d [addr [count]]     disassemble (around the pc)
+st                   show the stack
+save file            save the machine state
+load file            restore a saved state
+trace file|-|off     trace instructions to a file or stdout
+q                    quit
+";
+
+// Bytes shown per line of memory examine.
+const DUMP_WIDTH: usize = 16;
+
+// Instructions shown before the pc by disassembly.
+const DIS_BEFORE: usize = 3;
+
+pub struct Monitor6502<M: Memory> {
+    cpu:         Mcs6502<M>,
+    breakpoints: BTreeSet<u16>,
+    last:        String,
+    mem_next:    u16,
+    dis_next:    Option<u16>
+}
+
+impl<M: Memory> Monitor6502<M> {
+    pub fn new(cpu: Mcs6502<M>) -> Monitor6502<M> {
+        Monitor6502 {
+            cpu,
+            breakpoints: BTreeSet::new(),
+            last:        String::new(),
+            mem_next:    0,
+            dis_next:    None
+        }
+    }
+
+    pub fn cpu(&mut self) -> &mut Mcs6502<M> {
+        &mut self.cpu
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn breakpoints(&self) -> Vec<u16> {
+        self.breakpoints.iter().cloned().collect()
+    }
+
+    // Executes instructions until a breakpoint, fault or halt,
+    // at least one instruction is executed.
+    pub fn resume(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        loop {
+            if !self.step_one(out)? {
+                return Ok(());
+            }
+
+            let pc = self.cpu.pc() as u16;
+            if self.breakpoints.contains(&pc) {
+                writeln!(out, "Breakpoint at ${:04X}", pc)?;
+                return self.show_state(out);
+            }
+        }
+    }
+
+    // Returns false if the cpu faulted or is halted.
+    fn step_one(&mut self, out: &mut dyn Write) -> io::Result<bool> {
+        if let Some(reason) = self.cpu.stop_reason() {
+            writeln!(out, "CPU halted: {}", reason)?;
+            return Ok(false);
+        }
+
+        if let Err(error) = self.cpu.execute() {
+            writeln!(out, "CPU FAULT: {}", error)?;
+            self.show_state(out)?;
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    fn step(&mut self, args: &[&str], out: &mut dyn Write) -> io::Result<()> {
+        let count = match args.first() {
+            Some(arg) => match arg.parse::<usize>() {
+                Ok(count) => count,
+                Err(_)    => return writeln!(out, "Invalid count: {}", arg)
+            },
+            None      => 1
+        };
+
+        for _ in 0..count {
+            if !self.step_one(out)? {
+                return Ok(());
+            }
+        }
+
+        self.show_state(out)
+    }
+
+    fn go(&mut self, args: &[&str], out: &mut dyn Write) -> io::Result<()> {
+        if let Some(arg) = args.first() {
+            match monitor::parse_hex(arg) {
+                Some(addr) => {
+                    let mut regs = self.cpu.registers();
+                    regs.pc = addr;
+                    self.cpu.set_registers(regs);
+                }
+                None       => return writeln!(out, "Invalid address: {}", arg)
+            }
+        }
+
+        self.resume(out)
+    }
+
+    fn breakpoint(&mut self, args: &[&str], out: &mut dyn Write) -> io::Result<()> {
+        match args.first() {
+            Some(arg) => match monitor::parse_hex(arg) {
+                Some(addr) => {
+                    self.add_breakpoint(addr);
+                    writeln!(out, "Breakpoint at ${:04X}", addr)
+                }
+                None       => writeln!(out, "Invalid address: {}", arg)
+            },
+            None      => {
+                if self.breakpoints.is_empty() {
+                    return writeln!(out, "No breakpoints");
+                }
+
+                for addr in self.breakpoints.iter() {
+                    writeln!(out, "${:04X}", addr)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn delete(&mut self, args: &[&str], out: &mut dyn Write) -> io::Result<()> {
+        match args.first().and_then(|arg| monitor::parse_hex(arg)) {
+            Some(addr) if self.remove_breakpoint(addr) => Ok(()),
+            Some(addr) => writeln!(out, "No breakpoint at ${:04X}", addr),
+            None       => writeln!(out, "Invalid address")
+        }
+    }
+
+    fn registers(&mut self, args: &[&str], out: &mut dyn Write) -> io::Result<()> {
+        let mut regs = self.cpu.registers();
+
+        for arg in args.iter() {
+            let parts: Vec<&str> = arg.splitn(2, '=').collect();
+            let value = match parts.get(1).and_then(|v| monitor::parse_hex(v)) {
+                Some(value) => value,
+                None        => return writeln!(out, "Invalid register assignment: {}", arg)
+            };
+
+            if parts[0].to_uppercase() != "PC" && value > 0xFF {
+                return writeln!(out, "Value too large: {}", arg);
+            }
+
+            match parts[0].to_uppercase().as_str() {
+                "PC" => regs.pc = value,
+                "SP" => regs.sp = value as u8,
+                "A"  => regs.accu = value as u8,
+                "X"  => regs.idx_x = value as u8,
+                "Y"  => regs.idx_y = value as u8,
+                "P"  => regs.status = value as u8,
+                _    => return writeln!(out, "Unknown register: {}", parts[0])
+            }
+        }
+
+        self.cpu.set_registers(regs);
+        self.show_registers(out)
+    }
+
+    fn examine(&mut self, args: &[&str], out: &mut dyn Write) -> io::Result<()> {
+        let addrs: Vec<Option<u16>> = args.iter().map(|arg| monitor::parse_hex(arg)).collect();
+        if addrs.contains(&None) {
+            return writeln!(out, "Invalid address");
+        }
+
+        let start = match addrs.first() {
+            Some(&Some(start)) => start as usize,
+            _                  => self.mem_next as usize
+        };
+        let end = match addrs.get(1) {
+            Some(&Some(end)) => end as usize,
+            _                => start + 4 * DUMP_WIDTH - 1
+        };
+
+        let mut addr = start;
+        while addr <= end {
+            let len = DUMP_WIDTH.min(end - addr + 1);
+            let bytes: Vec<u8> = (addr..addr + len)
+                .map(|a| self.cpu.memory().peek(a & 0xFFFF))
+                .collect();
+
+            let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            let text: String = bytes.iter()
+                .map(|&b| if (0x20..0x7F).contains(&b) { b as char } else { '.' })
+                .collect();
+
+            writeln!(out, "{:04X}  {:<47}  {}", addr & 0xFFFF, hex.join(" "), text)?;
+            addr += len;
+        }
+
+        self.mem_next = addr as u16;
+        Ok(())
+    }
+
+    fn modify(&mut self, args: &[&str], out: &mut dyn Write) -> io::Result<()> {
+        let addr = match args.first().and_then(|arg| monitor::parse_hex(arg)) {
+            Some(addr) => addr as usize,
+            None       => return writeln!(out, "Invalid address")
+        };
+
+        let mut bytes = Vec::new();
+        for arg in args[1..].iter() {
+            match monitor::parse_byte(arg) {
+                Some(byte) => bytes.push(byte),
+                None       => return writeln!(out, "Invalid byte: {}", arg)
+            }
+        }
+
+        for (i, byte) in bytes.iter().enumerate() {
+            self.cpu.memory().write_u8((addr + i) & 0xFFFF, *byte);
+        }
+        Ok(())
+    }
+
+    fn disassemble(&mut self, args: &[&str], out: &mut dyn Write) -> io::Result<()> {
+        let start = match args.first() {
+            Some(arg) => match monitor::parse_hex(arg) {
+                Some(addr) => addr,
+                None       => return writeln!(out, "Invalid address: {}", arg)
+            },
+            None      => match self.dis_next {
+                Some(next) => next,
+                None       => self.before_pc()
+            }
+        };
+
+        let count = match args.get(1) {
+            Some(arg) => match arg.parse::<usize>() {
+                Ok(count) => count,
+                Err(_)    => return writeln!(out, "Invalid count: {}", arg)
+            },
+            None      => 10
+        };
+
+        let mut addr = start;
+        for _ in 0..count {
+            let (line, next) = self.disassemble_line(addr);
+            writeln!(out, "{}", line)?;
+            addr = next;
+        }
+
+        self.dis_next = Some(addr);
+        Ok(())
+    }
+
+    // Start of the DIS_BEFORE instructions before the pc. Code
+    // can't be decoded backwards, so decoding starts as far back
+    // as they could be and moves closer until the instructions
+    // line up with the pc.
+    fn before_pc(&mut self) -> u16 {
+        let pc = self.cpu.pc() as u16;
+        let variant = self.cpu.variant();
+        let memory: &dyn Memory = self.cpu.memory();
+
+        for back in (1..=3 * DIS_BEFORE).rev() {
+            let mut offsets = Vec::new();
+            let mut offset = 0;
+            while offset < back {
+                offsets.push(offset);
+
+                let addr = pc.wrapping_sub((back - offset) as u16) as usize;
+                let mut idx = addr;
+                mcs6502::op_to_str_variant(memory, &mut idx, variant);
+                offset += idx - addr;
+            }
+
+            if offset == back {
+                let first = offsets[offsets.len().saturating_sub(DIS_BEFORE)];
+                return pc.wrapping_sub((back - first) as u16);
+            }
+        }
+
+        pc
+    }
+
+    // Address, bytes and instruction at addr and the address
+    // of the next instruction.
+    fn disassemble_line(&mut self, addr: u16) -> (String, u16) {
+        let variant = self.cpu.variant();
+        let pc = self.cpu.pc() as u16;
+        let memory: &dyn Memory = self.cpu.memory();
+
+        let mut idx = addr as usize;
+        let inst = mcs6502::op_to_str_variant(memory, &mut idx, variant);
+        let len = idx - addr as usize;
+
+        let bytes: Vec<String> = (0..len)
+            .map(|i| format!("{:02X}", memory.peek((addr as usize + i) & 0xFFFF)))
+            .collect();
+
+        let marker = if addr == pc { '>' } else { ' ' };
+        let line = format!("{}{:04X}  {:<9} {}", marker, addr, bytes.join(" "), inst);
+        (line, (idx & 0xFFFF) as u16)
+    }
+
+    fn stack(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        let sp = self.cpu.registers().sp as usize;
+        if sp == 0xFF {
+            return writeln!(out, "Stack empty");
+        }
+
+        for offset in (sp + 1..0x100).rev() {
+            let addr = STACK_BASE_ADDRESS + offset;
+            writeln!(out, "{:04X}  {:02X}", addr, self.cpu.memory().peek(addr))?;
+        }
+        Ok(())
+    }
+
+    fn save(&mut self, args: &[&str], out: &mut dyn Write) -> io::Result<()> {
+        let name = match args.first() {
+            Some(name) => name,
+            None       => return writeln!(out, "Usage: save file")
+        };
+
+        match state::write_file(name, &self.cpu.save_state()) {
+            Ok(_)      => writeln!(out, "State saved to {}", name),
+            Err(error) => writeln!(out, "Cannot save state: {}", error)
+        }
+    }
+
+    fn load(&mut self, args: &[&str], out: &mut dyn Write) -> io::Result<()> {
+        let name = match args.first() {
+            Some(name) => name,
+            None       => return writeln!(out, "Usage: load file")
+        };
+
+        match state::read_file(name).and_then(|data| self.cpu.load_state(&data)) {
+            Ok(_)      => self.show_state(out),
+            Err(error) => writeln!(out, "Cannot load state: {}", error)
+        }
+    }
+
+    fn trace(&mut self, args: &[&str], out: &mut dyn Write) -> io::Result<()> {
+        match args.first() {
+            Some(&"off") => {
+                // Dropping the writer flushes it.
+                self.cpu.set_trace(None);
+                writeln!(out, "Trace off")
+            }
+            Some(&"-")   => {
+                self.cpu.set_trace(Some(Box::new(io::stdout())));
+                Ok(())
+            }
+            Some(name)   => match File::create(name) {
+                Ok(file)   => {
+                    self.cpu.set_trace(Some(Box::new(BufWriter::new(file))));
+                    writeln!(out, "Tracing to {}", name)
+                }
+                Err(error) => writeln!(out, "Cannot create trace file: {}", error)
+            },
+            None         => writeln!(out, "Usage: trace file|-|off")
+        }
+    }
+
+    fn show_registers(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        let regs = self.cpu.registers();
+
+        let flags: String = "NV-BDIZC".chars().enumerate()
+            .map(|(i, c)| if regs.status & (0x80 >> i) != 0 { c } else { '.' })
+            .collect();
+
+        writeln!(out, "PC={:04X} A={:02X} X={:02X} Y={:02X} SP={:02X} P={:02X} {} CYC={}",
+                 regs.pc, regs.accu, regs.idx_x, regs.idx_y, regs.sp,
+                 regs.status, flags, self.cpu.cycles())
+    }
+
+    // Registers and the next instruction.
+    fn show_state(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        self.show_registers(out)?;
+
+        let pc = self.cpu.pc() as u16;
+        let (line, _) = self.disassemble_line(pc);
+        self.dis_next = None;
+        writeln!(out, "{}", line)
+    }
+}
+
+impl<M: Memory> Monitor for Monitor6502<M> {
+    fn prompt(&self) -> String {
+        format!("(${:04X}) ", self.cpu.pc())
+    }
+
+    fn command(&mut self, line: &str, out: &mut dyn Write) -> io::Result<bool> {
+        // Empty line repeats the last command, e.g. step.
+        let line = if line.is_empty() {
+            self.last.clone()
+        } else {
+            String::from(line)
+        };
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (name, args) = match tokens.split_first() {
+            Some((name, args)) => (name.to_lowercase(), args),
+            None               => return Ok(true)
+        };
+
+        match name.as_str() {
+            "s" | "step"  => self.step(args, out)?,
+            "g" | "go"    => self.go(args, out)?,
+            "b" | "break" => self.breakpoint(args, out)?,
+            "del"         => self.delete(args, out)?,
+            "r"           => self.registers(args, out)?,
+            "m"           => self.examine(args, out)?,
+            ">"           => self.modify(args, out)?,
+            "d"           => self.disassemble(args, out)?,
+            "st"          => self.stack(out)?,
+            "save"        => self.save(args, out)?,
+            "load"        => self.load(args, out)?,
+            "trace"       => self.trace(args, out)?,
+            "h" | "?"     => write!(out, "{}", HELP)?,
+            "q" | "quit"  => return Ok(false),
+            _             => writeln!(out, "Unknown command: {} (h for help)", name)?
+        }
+
+        // Only repeat commands that continue where they stopped.
+        self.last = match name.as_str() {
+            "s" | "step" | "m" | "d" => String::from(name.as_str()),
+            _                        => String::new()
+        };
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::cell::Cell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    use cpus::Cpu;
+    use cpus::mcs6502::Mcs6502;
+    use inst::mcs6502::ops;
+    use mems::ram::Ram8b;
+    use mems::rom::Rom8b;
+    use mems::watch::{AccessKind, Watched};
+    use monitor::Monitor;
+    use monitor::mcs6502::Monitor6502;
+
+    fn monitor(program: Vec<u8>) -> Monitor6502<Ram8b> {
+        let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+        cpu.boot(&Rom8b::from_vec(program));
+        cpu.set_brk_trap(true);
+
+        Monitor6502::new(cpu)
+    }
+
+    fn command(monitor: &mut Monitor6502<Ram8b>, line: &str) -> String {
+        let mut out = Vec::new();
+        assert!(monitor.command(line, &mut out).unwrap());
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn step_and_registers() {
+        let mut monitor = monitor(vec![ops::LDA_IMMEDIATE, 0x42, ops::INX_IMPLIED, ops::INX_IMPLIED]);
+
+        let out = command(&mut monitor, "s");
+        assert!(out.starts_with("PC=0002 A=42 X=00"));
+        assert!(out.ends_with(">0002  E8        INX\n"));
+
+        // Repeats the step.
+        command(&mut monitor, "");
+        assert_eq!(monitor.cpu().registers().idx_x, 0x01);
+
+        let out = command(&mut monitor, "r X=10 pc=0002");
+        assert!(out.starts_with("PC=0002 A=42 X=10"));
+
+        let out = command(&mut monitor, "r Q=1");
+        assert_eq!(out, "Unknown register: Q\n");
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut monitor = monitor(vec![ops::INX_IMPLIED; 10]);
+
+        command(&mut monitor, "b 0005");
+        command(&mut monitor, "b $0007");
+        assert_eq!(command(&mut monitor, "b"), "$0005\n$0007\n");
+
+        let out = command(&mut monitor, "g");
+        assert!(out.starts_with("Breakpoint at $0005"));
+        assert_eq!(monitor.cpu().registers().idx_x, 0x05);
+
+        command(&mut monitor, "del 7");
+        let out = command(&mut monitor, "g");
+        assert!(out.starts_with("CPU halted: break at 0x000A"));
+        assert_eq!(monitor.breakpoints(), vec![0x0005]);
+    }
+
+    #[test]
+    fn memory() {
+        let mut monitor = monitor(vec![ops::NOP_IMPLIED]);
+
+        command(&mut monitor, "> 2000 48 49 0");
+        let out = command(&mut monitor, "m 2000 2002");
+        assert_eq!(out, format!("2000  48 49 00{}  HI.\n", " ".repeat(39)));
+
+        // Continues after the last line.
+        command(&mut monitor, "");
+        assert_eq!(monitor.mem_next, 0x2043);
+    }
+
+    #[test]
+    fn disassembly_and_stack() {
+        let mut monitor = monitor(vec![ops::LDA_IMMEDIATE, 0x01, ops::PHA_IMPLIED,
+                                       ops::JMP_ABSOLUTE, 0x00, 0x00]);
+
+        let out = command(&mut monitor, "d 0 3");
+        assert_eq!(out, ">0000  A9 01     LDA #$01\n 0002  48        PHA\n 0003  4C 00 00  JMP $0000\n");
+
+        command(&mut monitor, "s 2");
+        let out = command(&mut monitor, "st");
+        assert_eq!(out, "01FF  00\n01FE  00\n01FD  01\n");
+
+        let mut out = Vec::new();
+        assert!(!monitor.command("q", &mut out).unwrap());
+    }
+
+    #[test]
+    fn disassembly_around_pc() {
+        let mut monitor = monitor(vec![ops::INX_IMPLIED, ops::LDA_IMMEDIATE, 0xE8,
+                                       ops::STA_ABSOLUTE, 0x00, 0x02, ops::INX_IMPLIED,
+                                       ops::INX_IMPLIED, ops::INX_IMPLIED]);
+
+        // The operand of LDA is not taken for an INX.
+        command(&mut monitor, "s 4");
+        let out = command(&mut monitor, "d");
+        assert_eq!(out, " 0001  A9 E8     LDA #$E8\n 0003  8D 00 02  STA $0200\n 0006  E8        INX\n\
+                         >0007  E8        INX\n 0008  E8        INX\n 0009  00        BRK\n\
+                         \x20000A  00        BRK\n 000B  00        BRK\n 000C  00        BRK\n\
+                         \x20000D  00        BRK\n");
+
+        // Wraps around the address space.
+        monitor.cpu().restart();
+        command(&mut monitor, "s");
+        let out = command(&mut monitor, "d");
+        assert!(out.starts_with(" FFFE  00        BRK\n FFFF  00        BRK\n 0000  E8        INX\n\
+                                 >0001  A9 E8     LDA #$E8\n"));
+    }
+
+    #[test]
+    fn unwatched() {
+        let mut cpu = Mcs6502::new(Watched::new(Ram8b::new(64 * 1024)));
+        cpu.boot(&Rom8b::from_vec(vec![ops::LDA_IMMEDIATE, 0x01, ops::PHA_IMPLIED]));
+        cpu.run(2).unwrap();
+
+        let hits = Rc::new(Cell::new(0));
+        let counter = hits.clone();
+        cpu.memory().watch(0x0000..0x10000, &[AccessKind::Read], move |_| counter.set(counter.get() + 1));
+
+        // Only the program's reads are watched.
+        let mut monitor = Monitor6502::new(cpu);
+        let mut out = Vec::new();
+        for line in ["m 0 2", "st", "d 0 2"].iter() {
+            assert!(monitor.command(line, &mut out).unwrap());
+        }
+        assert_eq!(hits.get(), 0);
+    }
+
+    #[test]
+    fn trace() {
+        let mut monitor = monitor(vec![ops::LDA_IMMEDIATE, 0x01, ops::INX_IMPLIED, ops::INX_IMPLIED]);
+        let path = env::temp_dir().join("dzemu_monitor_trace.log");
+        let name = path.to_str().unwrap();
+
+        command(&mut monitor, &format!("trace {}", name));
+        command(&mut monitor, "s 2");
+        assert_eq!(command(&mut monitor, "trace off"), "Trace off\n");
+        command(&mut monitor, "s");
+
+        let trace = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("0000  A9 01     LDA #$01"));
+        assert!(lines[1].ends_with("A:01 X:00 Y:00 P:24 SP:FD CYC:9"));
+
+        assert_eq!(command(&mut monitor, "trace"), "Usage: trace file|-|off\n");
+    }
+
+    #[test]
+    fn run_session() {
+        let mut monitor = monitor(vec![ops::INX_IMPLIED; 4]);
+
+        let mut input = Cursor::new("s 2\nbogus\n");
+        let mut out = Vec::new();
+        monitor.run(&mut input, &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("($0000) PC=0002"));
+        assert!(out.contains("Unknown command: bogus"));
+    }
+}
@@ -0,0 +1,38 @@
+use std::io;
+use std::io::{BufRead, Write};
+
+pub mod mcs6502;
+
+// Line based machine monitor, commands are read until
+// command returns false or the input ends.
+pub trait Monitor {
+    fn prompt(&self) -> String;
+    fn command(&mut self, line: &str, out: &mut dyn Write) -> io::Result<bool>;
+
+    fn run(&mut self, input: &mut dyn BufRead, out: &mut dyn Write) -> io::Result<()> {
+        loop {
+            write!(out, "{}", self.prompt())?;
+            out.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+
+            if !self.command(line.trim(), out)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Addresses and values are hexadecimal, the $ is optional.
+pub fn parse_hex(arg: &str) -> Option<u16> {
+    let arg = arg.trim_start_matches('$');
+    u16::from_str_radix(arg, 16).ok()
+}
+
+pub fn parse_byte(arg: &str) -> Option<u8> {
+    let arg = arg.trim_start_matches('$');
+    u8::from_str_radix(arg, 16).ok()
+}
@@ -3,7 +3,6 @@ use std::io::Read;
 
 use mems::Memory;
 use inst::mcs6502;
-use inst::mcs6502::ops;
 
 pub fn read_rom(fname: &str) -> Vec<u8> {
     let rom_file = match File::open(fname) {
@@ -18,17 +17,8 @@ pub fn dump_rom(rom: &Memory) {
     println!("Rom contents:");
     let mut idx = 0;
     while idx < rom.size() {
-        let opcode = rom.read_u8(idx);
-        let mut offset = 0u8;
-
-        if opcode == ops::custom::VARIABLE {
-            offset = rom.read_u8(idx + 1);
-        }
-
         let i = idx;
         println!("0x{:04X}: {}", i, mcs6502::op_to_str(rom, &mut idx));
-
-        idx = idx + offset as usize;
     }
     println!("-------------");
 }
@@ -82,7 +72,7 @@ pub fn is_valid_identifier(label: &str, decl: bool) -> bool {
         return false;
     }
 
-    for i in 1..len - 2 {
+    for i in 1..len.saturating_sub(1) {
         if !is_valid_identifier_char(chars[i]) {
             return false;
         }
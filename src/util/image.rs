@@ -0,0 +1,149 @@
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// Largest block of uncompressed deflate data.
+const STORED_BLOCK: usize = 0xFFFF;
+
+// Binary PPM of the RGB pixels, three bytes each.
+pub fn write_ppm<W: Write>(out: &mut W, width: usize, height: usize,
+                           rgb: &[u8]) -> io::Result<()> {
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    out.write_all(rgb)
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+fn png_chunk<W: Write>(out: &mut W, kind: &[u8], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+
+    let mut chunk = kind.to_vec();
+    chunk.extend_from_slice(data);
+    out.write_all(&chunk)?;
+    out.write_all(&crc32(&chunk).to_be_bytes())
+}
+
+// PNG of the RGB pixels. The image data is stored without
+// compression, which needs no dependencies and is still
+// read by every viewer.
+pub fn write_png<W: Write>(out: &mut W, width: usize, height: usize,
+                           rgb: &[u8]) -> io::Result<()> {
+    out.write_all(PNG_SIGNATURE)?;
+
+    // 8 bits per channel, RGB, no interlacing.
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    png_chunk(out, b"IHDR", &header)?;
+
+    // Every line starts with filter type 0.
+    let mut raw = Vec::with_capacity((width * 3 + 1) * height);
+    for line in rgb.chunks(width * 3) {
+        raw.push(0);
+        raw.extend_from_slice(line);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let blocks = raw.chunks(STORED_BLOCK).count();
+    for (i, block) in raw.chunks(STORED_BLOCK).enumerate() {
+        let len = block.len() as u16;
+        zlib.push((i + 1 == blocks) as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+    png_chunk(out, b"IDAT", &zlib)?;
+
+    png_chunk(out, b"IEND", &[])
+}
+
+// Writes a PNG or, for names ending with .ppm, a PPM file.
+pub fn save(path: &str, width: usize, height: usize, rgb: &[u8]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    if path.ends_with(".ppm") {
+        write_ppm(&mut out, width, height, rgb)?;
+    } else {
+        write_png(&mut out, width, height, rgb)?;
+    }
+
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use util::image;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(image::crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(image::adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn ppm() {
+        let mut out = Vec::new();
+        image::write_ppm(&mut out, 2, 1, &[1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(out, b"P6\n2 1\n255\n\x01\x02\x03\x04\x05\x06".to_vec());
+    }
+
+    #[test]
+    fn png() {
+        // Large enough for two stored blocks.
+        let (width, height) = (256, 240);
+        let rgb: Vec<u8> = (0..width * height * 3).map(|i| i as u8).collect();
+
+        let mut out = Vec::new();
+        image::write_png(&mut out, width, height, &rgb).unwrap();
+        assert_eq!(&out[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&out[12..16], b"IHDR");
+        assert_eq!(&out[16..29], &[0, 0, 1, 0, 0, 0, 0, 240, 8, 2, 0, 0, 0]);
+        assert_eq!(&out[out.len() - 12..], b"\0\0\0\0IEND\xAE\x42\x60\x82");
+
+        // Inflate the stored blocks back.
+        let len = u32::from_be_bytes([out[33], out[34], out[35], out[36]]) as usize;
+        assert_eq!(&out[37..41], b"IDAT");
+        let zlib = &out[41..41 + len];
+        let (mut pos, mut raw) = (2, Vec::new());
+        loop {
+            let last = zlib[pos] == 1;
+            let size = zlib[pos + 1] as usize | (zlib[pos + 2] as usize) << 8;
+            assert_eq!(!(size as u16), zlib[pos + 3] as u16 | (zlib[pos + 4] as u16) << 8);
+            raw.extend_from_slice(&zlib[pos + 5..pos + 5 + size]);
+            pos += 5 + size;
+            if last {
+                break;
+            }
+        }
+
+        assert_eq!(raw.len(), (width * 3 + 1) * height);
+        assert_eq!(&raw[..4], &[0, 0, 1, 2]);
+        assert_eq!(&raw[width * 3 + 1..width * 3 + 3], &[0, (width * 3) as u8]);
+        assert_eq!(&zlib[pos..], &image::adler32(&raw).to_be_bytes());
+    }
+}
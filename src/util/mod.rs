@@ -1,2 +1,3 @@
 mod general;
+pub mod image;
 pub use self::general::*;
@@ -0,0 +1,42 @@
+use std::env;
+use std::fs;
+use std::process::Command;
+use std::process::Stdio;
+
+// Runs dzemu_asm on the source and returns whether it succeeded
+// and the output.
+fn assemble(name: &str, source: &str, args: &[&str]) -> (bool, Vec<u8>) {
+    let input = env::temp_dir().join(format!("{}.a65", name));
+    let output = env::temp_dir().join(format!("{}.out", name));
+    fs::write(&input, source).unwrap();
+    let _ = fs::remove_file(&output);
+
+    let status = Command::new(env!("CARGO_BIN_EXE_dzemu_asm"))
+        .args(args)
+        .arg("-o").arg(&output)
+        .arg(&input)
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+
+    (status.success(), fs::read(&output).unwrap_or_default())
+}
+
+#[test]
+fn cpu_option() {
+    let source = ".ORG $0400\nSTART:\n  STZ *$10\n  BRA START\n";
+
+    // 65C02 instructions are not known by default.
+    let (success, _) = assemble("dzemu_asm_nmos", source, &[]);
+    assert!(!success);
+
+    let (success, _) = assemble("dzemu_asm_6502", source, &["--cpu", "6502"]);
+    assert!(!success);
+
+    let (success, data) = assemble("dzemu_asm_65c02", source, &["--cpu", "65c02"]);
+    assert!(success);
+    assert_eq!(data, vec![0x64, 0x10, 0x80, 0xFC]);
+
+    let (success, _) = assemble("dzemu_asm_z80", source, &["--cpu", "z80"]);
+    assert!(!success);
+}
@@ -0,0 +1,95 @@
+extern crate dzemu;
+
+use std::path::Path;
+
+use dzemu::asm;
+use dzemu::asm::Assembler;
+use dzemu::asm::mcs6502::Assembler6502;
+use dzemu::cpus::harness;
+use dzemu::cpus::mcs6502::Mcs6502;
+use dzemu::inst::mcs6502::Variant;
+use dzemu::mems::ram::Ram8b;
+use dzemu::util;
+
+// Test programs are assembled at this address.
+const LOAD_ADDRESS: u16 = 0x0400;
+
+// Enough for the decimal test, which runs about 3M instructions.
+const LIMIT: u64 = 10_000_000;
+
+fn source(name: &str) -> String {
+    format!("{}/asm/tests/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn assemble(name: &str) -> Assembler6502 {
+    let mut asm = Assembler6502::new();
+    let diags = asm.assemble(&source(name));
+    assert!(!asm::has_errors(&diags), "{:?}", diags);
+    let diags = asm.link();
+    assert!(!asm::has_errors(&diags), "{:?}", diags);
+
+    asm
+}
+
+fn run(cpu: &mut Mcs6502<Ram8b>, name: &str, success: u16) {
+    match harness::run_until_trap(cpu, LOAD_ADDRESS, LIMIT) {
+        Ok(trap)   => assert_eq!(trap.pc, success, "{}: {}", name, trap),
+        Err(error) => panic!("{}: {}", name, error)
+    }
+}
+
+// Assembles the program, runs it from LOAD_ADDRESS and checks
+// that it trapped at SUCCESS.
+fn run_program(name: &str, variant: Variant) {
+    let asm = assemble(name);
+
+    let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+    cpu.set_variant(variant);
+    harness::load_flat(&mut cpu, asm.data(), LOAD_ADDRESS as usize);
+
+    run(&mut cpu, name, asm.label("SUCCESS").unwrap());
+}
+
+// Flat 64K images of the programs, i.e. the output of dzemu_asm
+// at LOAD_ADDRESS. An image that doesn't match its source fails
+// the test and has to be assembled again.
+fn run_image(name: &str) {
+    let asm = assemble(&format!("{}.a65", name));
+    let path = source(&format!("{}.bin", name));
+    assert!(Path::new(&path).exists(), "{} not found", path);
+
+    let image = util::read_rom(&path);
+    let code = LOAD_ADDRESS as usize..LOAD_ADDRESS as usize + asm.data().len();
+    assert_eq!(image.len(), 64 * 1024, "{}", path);
+    assert!(image[code] == *asm.data(), "{} is out of date", path);
+
+    let mut cpu = Mcs6502::new(Ram8b::new(64 * 1024));
+    harness::load_flat(&mut cpu, &image, 0x0000);
+
+    run(&mut cpu, name, asm.label("SUCCESS").unwrap());
+}
+
+#[test]
+fn functional() {
+    run_program("functional.a65", Variant::Nmos);
+}
+
+#[test]
+fn decimal_nmos() {
+    run_program("decimal.a65", Variant::Nmos);
+}
+
+#[test]
+fn decimal_cmos() {
+    run_program("decimal.a65", Variant::Cmos);
+}
+
+#[test]
+fn functional_image() {
+    run_image("functional");
+}
+
+#[test]
+fn decimal_image() {
+    run_image("decimal");
+}
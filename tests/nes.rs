@@ -0,0 +1,75 @@
+extern crate dzemu;
+
+use std::fs;
+use std::path::Path;
+
+use dzemu::asm;
+use dzemu::asm::Assembler;
+use dzemu::asm::mcs6502::Assembler6502;
+use dzemu::cpus::Cpu;
+use dzemu::cpus::mcs6502::Registers;
+use dzemu::machines::Machine;
+use dzemu::machines::nes::Nes;
+use dzemu::machines::nes::cart::Cartridge;
+use dzemu::mems::Memory;
+
+fn source(name: &str) -> String {
+    format!("{}/asm/tests/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+// PC, instruction bytes, registers and cycles of a trace or log
+// line. The log annotates the operands in the disassembly, e.g.
+// LDA $00 = 00, marks unofficial opcodes with a * right after
+// the bytes and has a PPU column which the trace doesn't.
+fn columns(line: &str) -> String {
+    let regs = &line[line.find("A:").unwrap()..];
+    let regs = match (regs.find(" PPU:"), regs.find(" CYC:")) {
+        (Some(ppu), Some(cyc)) => format!("{}{}", &regs[..ppu], &regs[cyc..]),
+        _                      => String::from(regs)
+    };
+
+    format!("{}  {}", &line[..14], regs)
+}
+
+// CPU test in the style of the nestest rom by kevtris, and the
+// trace of a run in the format of the log of Nintendulator
+// running nestest in automation mode. The rom has to match the
+// assembled source.
+#[test]
+fn nestest() {
+    let (rom, log) = (source("nes_cpu.nes"), source("nes_cpu.log"));
+    assert!(Path::new(&rom).exists(), "{} not found", rom);
+    assert!(Path::new(&log).exists(), "{} not found", log);
+
+    let mut asm = Assembler6502::new();
+    assert!(!asm::has_errors(&asm.assemble(&source("nes_cpu.a65"))));
+    assert!(!asm::has_errors(&asm.link()));
+
+    let rom = fs::read(&rom).unwrap();
+    assert!(rom[16..16 + asm.data().len()] == *asm.data(), "nes_cpu.nes is out of date");
+
+    let mut nes = Nes::new(Cartridge::load(&rom).unwrap());
+
+    // Automation mode starts at 0xC000 with the state after
+    // the reset sequence, which took 7 cycles.
+    nes.cpu().set_registers(Registers {
+        pc: 0xC000,
+        sp: 0xFD,
+        accu: 0,
+        idx_x: 0,
+        idx_y: 0,
+        status: 0x24
+    });
+
+    let log = fs::read_to_string(&log).unwrap();
+    for (number, line) in log.lines().enumerate() {
+        let trace = nes.cpu().trace_line();
+        assert_eq!(columns(&trace), columns(line), "line {}", number + 1);
+
+        nes.step().unwrap();
+    }
+
+    // Failed checks of the official and the unofficial opcodes.
+    let memory = nes.cpu().memory();
+    assert_eq!((memory.read_u8(0x0002), memory.read_u8(0x0003)), (0x00, 0x00));
+}